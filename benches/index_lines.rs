@@ -0,0 +1,53 @@
+//! `cargo bench`: how fast the line index is built for a 512 MB log, next
+//! to the byte-by-byte loop it replaced.
+
+// Its unit tests are compiled in too, but only run by `cargo test`
+#[allow(dead_code, unused_imports)]
+#[path = "../src/lines.rs"]
+mod lines;
+
+use std::sync::atomic::AtomicU64;
+use std::time::Instant;
+
+const SIZE: usize = 512 << 20;
+const RUNS: u32 = 5;
+
+fn bytewise(data: &[u8]) -> Vec<usize> {
+    let mut offsets = vec![0];
+    for (i, &byte) in data.iter().enumerate() {
+        if byte == b'\n' && i + 1 < data.len() {
+            offsets.push(i + 1);
+        }
+    }
+    offsets
+}
+
+/// Best of `RUNS` timings of `f`, in GB/s of `data`.
+fn throughput(data: &[u8], f: impl Fn(&[u8]) -> Vec<usize>) -> (f64, usize) {
+    let mut best = f64::MAX;
+    let mut lines = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        lines = std::hint::black_box(f(data)).len();
+        best = best.min(start.elapsed().as_secs_f64());
+    }
+    (data.len() as f64 / best / 1e9, lines)
+}
+
+fn main() {
+    let mut data = Vec::with_capacity(SIZE + 128);
+    let mut n = 0u64;
+    while data.len() < SIZE {
+        // Log-like lines of varying length
+        let line = format!("2024-01-01T00:00:{:02} INFO request {} took {}ms\n", n % 60, n, n * 7 % 1000);
+        data.extend_from_slice(line.as_bytes());
+        n += 1;
+    }
+
+    let (old, old_lines) = throughput(&data, bytewise);
+    let (new, new_lines) = throughput(&data, |d| lines::index(d, &AtomicU64::new(0)));
+    assert_eq!(old_lines, new_lines);
+    println!("index_lines  {} MB, {} lines", data.len() >> 20, new_lines);
+    println!("  bytewise   {:6.2} GB/s", old);
+    println!("  lines      {:6.2} GB/s  ({:.1}x)", new, new / old);
+}
@@ -1,10 +1,36 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::buffer::Buffer;
-use crate::config::Config;
-use crate::keymap::KeyMap;
-use crate::search::{SearchBatch, SearchState};
-use crate::syntax::SyntaxHighlighter;
+use crate::blame::{self, Blame};
+use crate::buffer::{Buffer, Derived, Limits};
+use crate::config::{Config, Palette};
+use crate::config_check;
+use crate::git::{self, GitDiff, GitLog, RepoStatus};
+use crate::{cols, jq, sort, strings, tabs};
+use crossterm::event::KeyEvent;
+use ratatui::layout::{Position, Rect};
+use crate::exec::{self, Exec};
+use crate::export::{ExportFormat, YankFormat};
+use crate::filter::{CaseMode, FilterBatch, FilterJob, LineFilter, ViewRow};
+use crate::keymap::{Key, KeyMap};
+use crate::options::{self, Opt, SetArg};
+use crate::paths::Completion;
+use crate::range::{Address, Base};
+use crate::picker::{self, FuzzyMatch};
+use crate::search::{self, MatchStep, SearchBatch, SearchState};
+use crate::session::{Session, SessionBuffer, SessionFilter, SessionNote, SessionSearch};
+use crate::syntax::{Engine, SyntaxHighlighter};
+
+/// Status messages `:messages` remembers.
+const MESSAGE_HISTORY: usize = 100;
+
+/// How long the git state shown (branch, gutter changes) may be out of date.
+const GIT_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long an alert flashes the status bar.
+const ALERT_FLASH: Duration = Duration::from_secs(1);
 
 /// The current interaction mode.
 #[derive(Debug, Clone, PartialEq)]
@@ -25,12 +51,199 @@ pub enum Mode {
     /// User is typing a filter pattern
     FilterInput {
         input: String,
+        case: CaseMode,
+    },
+    /// Buffer picker overlay: fuzzy-filtering the open buffers
+    BufferPicker {
+        query: String,
+        /// Index into the filtered list
+        selected: usize,
     },
+    /// Marks list overlay (`:marks`)
+    MarksList {
+        /// Index into the sorted marks
+        selected: usize,
+    },
+    /// List of the lines matching the search (`:g/regex`)
+    MatchList {
+        /// Index into the matching lines
+        selected: usize,
+    },
+    /// Key binding help overlay (`h`, F1)
+    Help {
+        /// First row shown
+        scroll: usize,
+    },
+    /// File details overlay (`:info`)
+    Info,
+    /// Recent status messages overlay (`:messages`)
+    Messages,
     /// Visual line-selection mode
     Visual {
         anchor: usize,
         cursor: usize,
     },
+    /// Typing a shell command to pipe the selected lines (inclusive range) through
+    PipeInput {
+        input: String,
+        range: (usize, usize),
+    },
+    /// Character-wise visual mode: a (line, char column) cursor, and the
+    /// selection anchor once `v` has been pressed to start selecting
+    VisualChar {
+        anchor: Option<(usize, usize)>,
+        cursor: (usize, usize),
+    },
+    /// The active file changed on disk: asking whether to reload it
+    ReloadPrompt,
+}
+
+/// The last visual selection, line- or character-wise; its lines are `'<,'>`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LastVisual {
+    Lines { anchor: usize, cursor: usize },
+    /// (line, char column) ends of a character-wise selection
+    Chars { anchor: (usize, usize), cursor: (usize, usize) },
+}
+
+impl LastVisual {
+    /// First and last line of the selection.
+    pub fn lines(self) -> (usize, usize) {
+        let (a, c) = match self {
+            LastVisual::Lines { anchor, cursor } => (anchor, cursor),
+            LastVisual::Chars { anchor, cursor } => (anchor.0, cursor.0),
+        };
+        (a.min(c), a.max(c))
+    }
+}
+
+/// `path` made absolute where possible, so sessions match files however they were opened.
+/// A file that no longer exists is resolved through its directory.
+fn canonical(path: &std::path::Path) -> std::path::PathBuf {
+    path.canonicalize().unwrap_or_else(|_| {
+        let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+        match (dir.canonicalize(), path.file_name()) {
+            (Ok(dir), Some(name)) => dir.join(name),
+            _ => path.to_path_buf(),
+        }
+    })
+}
+
+/// `parts` shown one after another in a single scratch buffer, highlighted
+/// as they are when they all agree (`--cat`, `:join`).
+pub fn joined(parts: &[&Buffer], highlighter: &SyntaxHighlighter, limits: Limits) -> anyhow::Result<Buffer> {
+    let mut buf = Buffer::concat(&format!("[cat {} files]", parts.len()), parts, limits)?;
+    buf.syntax = highlighter.common_syntax(parts);
+    Ok(buf)
+}
+
+/// The directory part of a glob pattern: its components before the first
+/// one with a wildcard.
+fn glob_dir(pattern: &std::path::Path) -> PathBuf {
+    pattern
+        .components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect()
+}
+
+/// Positions visited before large jumps, walked with Ctrl-O / Ctrl-I.
+#[derive(Debug, Default)]
+pub struct JumpList {
+    /// Top lines, oldest first
+    entries: Vec<usize>,
+    /// Index of the entry being shown; `entries.len()` when not walking the list
+    pos: usize,
+}
+
+impl JumpList {
+    const MAX: usize = 100;
+
+    /// Record `line` as a position to come back to. Drops any newer entries
+    /// left from walking back, like vim.
+    pub fn push(&mut self, line: usize) {
+        self.entries.truncate(self.pos);
+        if self.entries.last() != Some(&line) {
+            self.entries.push(line);
+        }
+        if self.entries.len() > Self::MAX {
+            self.entries.remove(0);
+        }
+        self.pos = self.entries.len();
+    }
+
+    /// Step back from `current`, returning the line to show.
+    pub fn back(&mut self, current: usize) -> Option<usize> {
+        if self.pos == self.entries.len() {
+            // Remember where we left off so Ctrl-I can return here
+            self.push(current);
+            self.pos = self.entries.len() - 1;
+        }
+        if self.pos == 0 {
+            return None;
+        }
+        self.pos -= 1;
+        Some(self.entries[self.pos])
+    }
+
+    /// Drop the entries on the oldest `count` lines, which are gone, and
+    /// move the rest up.
+    pub fn lines_dropped(&mut self, count: usize) {
+        let gone = self.entries.iter().filter(|&&line| line < count).count();
+        self.entries.retain(|&line| line >= count);
+        self.entries.iter_mut().for_each(|line| *line -= count);
+        self.pos = self.pos.saturating_sub(gone);
+    }
+
+    /// Step forward again after `back`.
+    pub fn forward(&mut self) -> Option<usize> {
+        if self.pos + 1 >= self.entries.len() {
+            return None;
+        }
+        self.pos += 1;
+        Some(self.entries[self.pos])
+    }
+}
+
+/// Per-buffer view state, saved while the buffer is in the background and
+/// restored when switching back to it.
+#[derive(Default)]
+pub struct ViewState {
+    pub top_line: usize,
+    pub left_col: usize,
+    pub filter: Option<LineFilter>,
+    pub top_filter_idx: usize,
+    pub search: SearchState,
+    pub marks: HashMap<char, usize>,
+    pub notes: BTreeMap<usize, String>,
+    pub jumps: JumpList,
+    pub last_visual: Option<LastVisual>,
+    /// Display settings, None until the buffer is first shown
+    pub settings: Option<LocalSettings>,
+}
+
+/// Display settings local to a buffer. They start from `[general]` and the
+/// buffer's `[filetype.*]` section, and change with toggles and `:set`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalSettings {
+    pub line_numbers: bool,
+    pub wrap: bool,
+    pub tab_width: u8,
+    pub theme: String,
+}
+
+/// An entry of the `:marks` list: a named mark or an annotated line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Bookmark {
+    Mark(char, usize),
+    Note(usize, String),
+}
+
+impl Bookmark {
+    pub fn line(&self) -> usize {
+        match self {
+            Bookmark::Mark(_, line) | Bookmark::Note(line, _) => *line,
+        }
+    }
 }
 
 /// Central application state.
@@ -49,6 +262,8 @@ pub struct App {
     pub content_height: usize,
     /// Terminal width
     pub content_width: usize,
+    /// Screen area of the gutter and text from the last render, for mouse hit-testing
+    pub content_area: Rect,
     /// Search state
     pub search: SearchState,
     /// Syntax highlighter
@@ -59,36 +274,120 @@ pub struct App {
     pub show_line_numbers: bool,
     /// Whether to wrap long lines
     pub wrap_lines: bool,
+    /// Display width of a tab
+    pub tab_width: u8,
+    /// Colors from `[colors]`
+    pub palette: Palette,
     /// Status message (transient, shown in status bar)
     pub status_message: Option<String>,
+    /// Status messages shown so far, oldest first, with when they
+    /// appeared (`:messages`)
+    pub messages: VecDeque<(SystemTime, String)>,
+    /// The status message as of the last tick, and since when
+    message_shown: Option<(String, Instant)>,
     /// Should the app quit?
     pub quit: bool,
+    /// Set while follow mode is paused to look back: the line count then,
+    /// to tell how many lines have come in since
+    pub follow_paused: Option<usize>,
+    /// Set when a search or filter prompt was opened from follow mode, to go
+    /// back to following once it is closed
+    pub follow_after_input: bool,
+    /// Set when something on screen may have changed; the event loop only
+    /// redraws then
+    pub dirty: bool,
+    /// The `[alerts]` patterns combined, checked against lines that come in
+    /// while following
+    pub alert_regex: Option<regex::bytes::Regex>,
+    /// Set by an alert for the event loop to ring the terminal bell
+    pub bell: bool,
+    /// When an alert started flashing the status bar
+    pub alert_flash: Option<Instant>,
     /// Named marks: char → top_line at time of mark
     pub marks: HashMap<char, usize>,
+    /// Notes attached to lines with `:note` (line → text)
+    pub notes: BTreeMap<usize, String>,
+    /// Jumplist of the active buffer (Ctrl-O / Ctrl-I)
+    pub jumps: JumpList,
+    /// The last visual selection; its lines are addressed as `'<,'>`
+    pub last_visual: Option<LastVisual>,
     /// Pending first key of a two-key sequence (e.g. 'm', '\'')
     pub pending_key: Option<char>,
-    /// Active line filter: (query_string, matching line indices)
-    pub filter: Option<(String, Vec<usize>)>,
+    /// Keys typed so far of a multi-key binding (e.g. the first `z` of `zz`)
+    pub pending_keys: Vec<Key>,
+    /// Recorded keyboard macros by register
+    pub macros: HashMap<char, Vec<KeyEvent>>,
+    /// Register being recorded into and the keys so far (`Q<r>` … `Q`)
+    pub recording: Option<(char, Vec<KeyEvent>)>,
+    /// Length of the recording when the key binding being typed began, so
+    /// the keys that stop a recording are left out of it
+    pub chord_start: usize,
+    /// Register of the last replayed macro, for `@@`
+    pub last_macro: Option<char>,
+    /// True while a macro is being replayed
+    pub replaying: bool,
+    /// Active line filter
+    pub filter: Option<LineFilter>,
     /// Scroll position within filtered lines
     pub top_filter_idx: usize,
+    /// Filter currently being computed in the background
+    pub filter_job: Option<FilterJob>,
+    /// Whether to show the `git blame` column
+    pub show_blame: bool,
+    /// `git blame` running in the background for the file at this path
+    blame_job: Option<(PathBuf, Receiver<anyhow::Result<Blame>>)>,
+    /// Branch state of the active file's repository, for the status bar
+    pub repo_status: Option<RepoStatus>,
+    /// File `repo_status` was last asked for, and when
+    repo_checked: Option<(PathBuf, Instant)>,
+    /// `git status` running in the background
+    repo_job: Option<Receiver<Option<RepoStatus>>>,
+    /// `git diff` running in the background for the file at this path
+    changes_job: Option<(PathBuf, Receiver<anyhow::Result<GitDiff>>)>,
+    /// File whose gutter changes were last asked for, and when
+    changes_checked: Option<(PathBuf, Instant)>,
     /// File-change event receiver (for follow mode)
     pub watcher_rx: Option<std::sync::mpsc::Receiver<notify::Result<notify::Event>>>,
     /// File watcher (kept alive as long as App is alive)
     watcher: Option<notify::RecommendedWatcher>,
+    /// `--watch-glob` patterns, made absolute, with the directory watched
+    /// for each
+    watch_globs: Vec<(glob::Pattern, PathBuf)>,
+    /// Watcher on the config directory, for live reload of config.toml
+    config_watcher: Option<(notify::RecommendedWatcher, std::sync::mpsc::Receiver<notify::Result<notify::Event>>)>,
+    /// Command-line flags, re-applied over the config file when it is reloaded
+    pub cli: Option<crate::cli::Cli>,
+    /// Draw with colors; off for `--color=never` and `NO_COLOR`
+    pub color: bool,
     /// Key → Action dispatch table
     pub key_map: KeyMap,
+    /// Path completion in progress in command mode (cleared by any non-Tab key)
+    pub completion: Option<Completion>,
+    /// Saved view state per buffer, parallel to `buffers`. The entry for the
+    /// active buffer is unused: its state lives in the fields above.
+    pub views: Vec<ViewState>,
+    /// Buffer indices in most-recently-used order, current buffer first
+    pub mru: Vec<usize>,
+    /// File and 1-based line to open in $EDITOR; the event loop suspends
+    /// the TUI to run it
+    pub edit_request: Option<(std::path::PathBuf, usize)>,
+    /// Session saved automatically on exit (`--session` / `:mksession`)
+    pub session_name: Option<String>,
+    /// Line to go to (`+N`) once the active buffer has loaded in the
+    /// background
+    pub pending_goto: Option<usize>,
 }
 
 impl App {
-    pub fn new(mut buffers: Vec<Buffer>, config: Config, highlighter: SyntaxHighlighter) -> Self {
-        // Load git change indicators for all file-backed buffers
-        for buf in &mut buffers {
-            if buf.path.is_some() && !buf.is_diff {
-                buf.load_git_changes();
-            }
-        }
+    pub fn new(buffers: Vec<Buffer>, config: Config, highlighter: SyntaxHighlighter) -> Self {
         let key_map = KeyMap::build(&config.keys);
-        Self {
+        let views = buffers.iter().map(|_| ViewState::default()).collect();
+        let mut search = SearchState::new();
+        search.wrap_around = config.general.wrap_search;
+        search.fancy = config.general.fancy_regex;
+        search.max_matches = config.general.max_matches;
+        let alert_regex = config.alerts.regex();
+        let mut app = Self {
             buffers,
             active_buffer: 0,
             mode: Mode::Normal,
@@ -96,21 +395,63 @@ impl App {
             left_col: 0,
             content_height: 24,
             content_width: 80,
-            search: SearchState::new(),
+            content_area: Rect::default(),
+            search,
             highlighter,
             show_line_numbers: config.general.line_numbers,
             wrap_lines: config.general.wrap,
+            tab_width: config.general.tab_width,
+            palette: config.colors.palette(),
             config,
             status_message: None,
+            messages: VecDeque::new(),
+            message_shown: None,
             quit: false,
+            follow_paused: None,
+            follow_after_input: false,
+            dirty: true,
+            alert_regex,
+            bell: false,
+            alert_flash: None,
             marks: HashMap::new(),
+            notes: BTreeMap::new(),
+            jumps: JumpList::default(),
+            last_visual: None,
             pending_key: None,
+            pending_keys: Vec::new(),
+            macros: HashMap::new(),
+            recording: None,
+            chord_start: 0,
+            last_macro: None,
+            replaying: false,
             filter: None,
             top_filter_idx: 0,
+            filter_job: None,
+            show_blame: false,
+            blame_job: None,
+            repo_status: None,
+            repo_checked: None,
+            repo_job: None,
+            changes_job: None,
+            changes_checked: None,
             watcher_rx: None,
             watcher: None,
+            watch_globs: Vec::new(),
+            config_watcher: None,
+            cli: None,
+            color: true,
             key_map,
+            completion: None,
+            views,
+            mru: vec![0],
+            edit_request: None,
+            pending_goto: None,
+            session_name: None,
+        };
+        if !app.buffers.is_empty() {
+            app.apply_settings(app.initial_settings(0));
         }
+        app
     }
 
     /// Get a reference to the active buffer.
@@ -125,7 +466,73 @@ impl App {
 
     /// The maximum value for top_line (so the last line is visible).
     pub fn max_top_line(&self) -> usize {
-        self.total_lines().saturating_sub(self.content_height)
+        let total = self.total_lines();
+        total.saturating_sub(self.rows_at_bottom((0..total).map(ViewRow::Line)))
+    }
+
+    /// The maximum value for top_filter_idx (so the last row is visible).
+    fn max_top_filter_idx(&self) -> usize {
+        self.filter.as_ref().map_or(0, |filter| {
+            filter.rows.len().saturating_sub(self.rows_at_bottom(filter.rows.iter().copied()))
+        })
+    }
+
+    /// How many of the last of `rows` fill the screen: a screenful, or
+    /// fewer when long lines wrap, but at least one.
+    fn rows_at_bottom(&self, rows: impl DoubleEndedIterator<Item = ViewRow> + ExactSizeIterator) -> usize {
+        if !self.wrap_lines {
+            return rows.len().min(self.content_height);
+        }
+        let mut used = 0;
+        let fit = rows
+            .rev()
+            .take_while(|&row| {
+                used += self.row_height(row);
+                used <= self.content_height
+            })
+            .count();
+        fit.max(1)
+    }
+
+    /// Scroll the least that brings `line` on screen: all of it, or as much
+    /// as fits when it wraps to more rows than the screen has.
+    pub fn keep_in_view(&mut self, line: usize) {
+        if line < self.top_line {
+            self.top_line = line;
+        } else {
+            let fit = self.rows_at_bottom((self.top_line..line + 1).map(ViewRow::Line));
+            self.top_line = line + 1 - fit;
+        }
+    }
+
+    /// Screen rows `row` takes: one, or with `wrap` on as many as it wraps
+    /// to in the content area.
+    pub fn row_height(&self, row: ViewRow) -> usize {
+        if !self.wrap_lines {
+            return 1;
+        }
+        let buf = self.buffer();
+        let rows = |text: &str| tabs::wrapped_rows(text, self.content_width.max(1), self.tab_width as usize);
+        match row {
+            ViewRow::Line(i) if buf.is_binary() => rows(&buf.hex_line(i)),
+            ViewRow::Line(i) => rows(buf.line_prefix(i, self.config.general.max_line_len).unwrap_or("")),
+            ViewRow::Deleted { at, index } => rows(buf.git_deleted.get(&at).and_then(|d| d.get(index)).map_or("", String::as_str)),
+            ViewRow::Hidden { .. } => 1,
+        }
+    }
+
+    /// The row drawn at screen row `y` of the content area, and how many
+    /// screen rows down into it `y` is when it wraps.
+    pub fn row_at(&self, y: usize) -> Option<(ViewRow, usize)> {
+        let mut top = 0;
+        for row in self.active_rows() {
+            let height = self.row_height(row);
+            if y < top + height {
+                return Some((row, y - top));
+            }
+            top += height;
+        }
+        None
     }
 
     /// Returns true when a tab bar should be shown.
@@ -133,27 +540,62 @@ impl App {
         self.buffers.len() > 1
     }
 
-    /// The ordered list of line indices to display in the viewport.
-    pub fn active_lines(&self) -> Vec<usize> {
-        if let Some((_, ref indices)) = self.filter {
+    /// Length in bytes of the longest line on screen that is cut off at
+    /// `general.max_line_len`, if any.
+    pub fn truncated_line_len(&self) -> Option<usize> {
+        let buf = self.buffer();
+        if buf.is_binary() {
+            return None;
+        }
+        self.active_rows()
+            .iter()
+            .filter_map(|row| match *row {
+                ViewRow::Line(i) => buf.line_bytes(i).map(<[u8]>::len),
+                _ => None,
+            })
+            .filter(|&len| len > self.config.general.max_line_len)
+            .max()
+    }
+
+    /// The ordered list of rows to display in the viewport.
+    pub fn active_rows(&self) -> Vec<ViewRow> {
+        let rows = if let Some(LineFilter { ref rows, .. }) = self.filter {
             let start = self.top_filter_idx;
-            let end = (start + self.content_height).min(indices.len());
-            if start >= indices.len() {
+            let end = (start + self.content_height).min(rows.len());
+            if start >= rows.len() {
                 vec![]
             } else {
-                indices[start..end].to_vec()
+                rows[start..end].to_vec()
             }
         } else {
             let start = self.top_line;
             let end = (start + self.content_height).min(self.total_lines());
-            (start..end).collect()
+            (start..end).map(ViewRow::Line).collect()
+        };
+        let buf = self.buffer();
+        if buf.shown_deletions.is_empty() {
+            return rows;
+        }
+        // Deleted text shown with `zd` goes under its marker, pushing the
+        // lines below it down
+        let mut out = Vec::with_capacity(rows.len());
+        for row in rows {
+            out.push(row);
+            if let ViewRow::Line(at) = row {
+                if buf.shown_deletions.contains(&at) {
+                    let count = buf.git_deleted.get(&at).map_or(0, Vec::len);
+                    out.extend((0..count).map(|index| ViewRow::Deleted { at, index }));
+                }
+            }
         }
+        out.truncate(self.content_height);
+        out
     }
 
     /// Scroll down by N lines, clamped. Operates on the filtered list when active.
     pub fn scroll_down(&mut self, n: usize) {
-        if let Some((_, ref indices)) = self.filter {
-            let max = indices.len().saturating_sub(self.content_height);
+        if self.filter.is_some() {
+            let max = self.max_top_filter_idx();
             self.top_filter_idx = (self.top_filter_idx + n).min(max);
         } else {
             self.top_line = std::cmp::min(self.top_line + n, self.max_top_line());
@@ -169,262 +611,1840 @@ impl App {
         }
     }
 
-    /// Jump to a specific line, centering it in the viewport.
+    /// Jump to a specific line, centering it in the viewport. With a filter
+    /// active the line is centered within the filtered rows.
     pub fn goto_line(&mut self, line: usize) {
+        if let Some(filter) = &self.filter {
+            let row = filter.row_of_line(line);
+            let max = self.max_top_filter_idx();
+            self.top_filter_idx = row.saturating_sub(self.content_height / 2).min(max);
+            return;
+        }
         let target = line.saturating_sub(self.content_height / 2);
         self.top_line = std::cmp::min(target, self.max_top_line());
     }
 
-    /// Go to the top of the file.
-    pub fn goto_top(&mut self) {
-        self.top_line = 0;
-        self.top_filter_idx = 0;
+    /// Ask the event loop to open the active file in $EDITOR at the top line.
+    pub fn request_edit(&mut self) {
+        let buf = self.buffer();
+        match &buf.path {
+            Some(path) if !buf.is_diff => {
+                self.edit_request = Some((path.clone(), self.top_view_line() + 1));
+            }
+            _ => self.status_message = Some("No file to edit".to_string()),
+        }
     }
 
-    /// Go to the bottom of the file.
-    pub fn goto_bottom(&mut self) {
-        self.top_line = self.max_top_line();
-        if let Some((_, ref indices)) = self.filter {
-            self.top_filter_idx = indices.len().saturating_sub(self.content_height);
-        }
+    /// Jump to the line containing byte `offset` (`:byte N`).
+    pub fn goto_byte(&mut self, offset: usize) {
+        let line = self.buffer().row_of_byte(offset);
+        self.push_jump();
+        self.goto_line(line);
+        self.status_message = Some(format!("Byte {}: line {}", offset, line + 1));
     }
 
-    /// Switch to the next buffer (wraps around).
-    pub fn next_buffer(&mut self) {
-        if self.buffers.len() > 1 {
-            self.active_buffer = (self.active_buffer + 1) % self.buffers.len();
-            self.top_line = 0;
-            self.left_col = 0;
-            self.status_message = Some(format!(
-                "Buffer {}/{}: {}",
-                self.active_buffer + 1,
-                self.buffers.len(),
-                self.buffer().name
-            ));
+    /// Jump `percent` of the way into the file, by bytes like less's `p`
+    /// (`:50%`).
+    pub fn goto_percent(&mut self, percent: f64) {
+        let len = self.buffer().byte_len();
+        let offset = (len as f64 * percent.clamp(0.0, 100.0) / 100.0) as usize;
+        let line = self.buffer().row_of_byte(offset);
+        self.push_jump();
+        self.goto_line(line);
+        self.status_message = Some(format!("{}%: line {}", percent, line + 1));
+    }
+
+    /// Scroll so `line` is the top row, without centering (used by the jumplist).
+    fn scroll_to_line(&mut self, line: usize) {
+        if let Some(filter) = &self.filter {
+            let max = self.max_top_filter_idx();
+            self.top_filter_idx = filter.row_of_line(line).min(max);
+        } else {
+            self.top_line = line.min(self.max_top_line());
         }
     }
 
-    /// Switch to the previous buffer (wraps around).
-    pub fn prev_buffer(&mut self) {
-        if self.buffers.len() > 1 {
-            self.active_buffer = if self.active_buffer == 0 {
-                self.buffers.len() - 1
-            } else {
-                self.active_buffer - 1
-            };
-            self.top_line = 0;
-            self.left_col = 0;
-            self.status_message = Some(format!(
-                "Buffer {}/{}: {}",
-                self.active_buffer + 1,
-                self.buffers.len(),
-                self.buffer().name
-            ));
+    /// Marks and notes of the active buffer in line order (marks first on a shared line).
+    pub fn bookmarks(&self) -> Vec<Bookmark> {
+        let mut marks: Vec<(usize, char)> = self.marks.iter().map(|(&c, &l)| (l, c)).collect();
+        marks.sort_unstable();
+        let mut list: Vec<Bookmark> = marks.into_iter().map(|(l, c)| Bookmark::Mark(c, l)).collect();
+        for (&line, text) in &self.notes {
+            let at = list.partition_point(|b| b.line() <= line);
+            list.insert(at, Bookmark::Note(line, text.clone()));
         }
+        list
     }
 
-    /// Percentage through the file based on top_line.
-    pub fn scroll_percentage(&self) -> u16 {
-        if let Some((_, ref indices)) = self.filter {
-            if indices.is_empty() {
-                return 100;
+    /// Remove a mark or note listed by `bookmarks()`.
+    pub fn delete_bookmark(&mut self, bookmark: &Bookmark) {
+        match bookmark {
+            Bookmark::Mark(c, _) => {
+                self.marks.remove(c);
+                self.status_message = Some(format!("Mark '{}' deleted", c));
             }
-            let bottom = self.top_filter_idx + self.content_height;
-            let effective = bottom.min(indices.len());
-            ((effective as f64 / indices.len() as f64) * 100.0) as u16
-        } else {
-            if self.total_lines() == 0 {
-                return 100;
+            Bookmark::Note(line, _) => {
+                self.notes.remove(line);
+                self.status_message = Some(format!("Note on line {} deleted", line + 1));
             }
-            let bottom = self.top_line + self.content_height;
-            let effective = std::cmp::min(bottom, self.total_lines());
-            ((effective as f64 / self.total_lines() as f64) * 100.0) as u16
         }
     }
 
-    /// Width of the line number gutter (digits + 1 space).
-    pub fn gutter_width(&self) -> usize {
-        if !self.show_line_numbers {
-            return 0;
-        }
-        let max_line = self.total_lines();
-        let digits = if max_line == 0 {
-            1
+    /// Attach `text` to the top line in view (`:note`); empty text removes the note there.
+    pub fn set_note(&mut self, text: &str) {
+        let line = self.top_view_line();
+        if text.is_empty() {
+            self.status_message = Some(match self.notes.remove(&line) {
+                Some(_) => format!("Note on line {} removed", line + 1),
+                None => "Usage: :note <text>".to_string(),
+            });
         } else {
-            (max_line as f64).log10() as usize + 1
-        };
-        digits + 2 // e.g. " 42 " — padding on each side
+            self.notes.insert(line, text.to_string());
+            self.status_message = Some(format!("Note added on line {}", line + 1));
+        }
     }
 
-    /// Execute a search asynchronously, updating `search.matches` via a background thread.
-    pub fn execute_search(&mut self) {
-        let smart_case = self.config.general.smart_case;
-        let query = self.search.query_string.clone();
-        if self.search.set_pattern(&query, smart_case).is_err() {
-            self.status_message = Some(format!("Invalid regex: {}", query));
+    /// Export the active buffer's notes (`:wnotes`).
+    pub fn write_notes(&mut self, path: &str) {
+        if path.is_empty() {
+            self.status_message = Some("Usage: :wnotes <path>".to_string());
             return;
         }
-        let pattern = match self.search.pattern.clone() {
-            Some(p) => p,
-            None => {
-                self.status_message = Some("Empty pattern".to_string());
-                return;
-            }
-        };
+        if self.notes.is_empty() {
+            self.status_message = Some("No notes to write".to_string());
+            return;
+        }
+        let target = crate::paths::expand_path(path);
+        let buf = &self.buffers[self.active_buffer];
+        self.status_message = Some(match crate::export::write_notes(buf, &self.notes, &target) {
+            Ok(n) => format!("Wrote {} notes to {}", n, target.display()),
+            Err(e) => format!("Write failed: {:#}", e),
+        });
+    }
 
-        self.search.matches.clear();
-        self.search.preview_matches.clear();
-        self.search.is_searching = true;
+    /// Record the current position in the jumplist before a large jump.
+    pub fn push_jump(&mut self) {
+        let line = self.top_view_line();
+        self.jumps.push(line);
+    }
 
-        let snapshot = self.buffers[self.active_buffer].text_snapshot();
-        let (tx, rx) = std::sync::mpsc::channel();
-        self.search.search_rx = Some(rx);
+    /// Go back to the previous position in the jumplist (Ctrl-O).
+    pub fn jump_back(&mut self) {
+        let current = self.top_view_line();
+        match self.jumps.back(current) {
+            Some(line) => self.scroll_to_line(line),
+            None => self.status_message = Some("Already at oldest position in jump list".to_string()),
+        }
+    }
 
-        std::thread::spawn(move || {
-            let mut batch = Vec::new();
-            for (line_idx, text) in snapshot.iter().enumerate() {
-                for mat in pattern.find_iter(text) {
-                    batch.push((line_idx, mat.start()..mat.end()));
-                }
-                if line_idx % 10_000 == 9_999 {
-                    let _ = tx.send(SearchBatch::Progress {
-                        matches: std::mem::take(&mut batch),
-                        lines_scanned: line_idx + 1,
-                    });
-                }
-            }
-            let _ = tx.send(SearchBatch::Done { matches: batch });
-        });
+    /// Go forward again in the jumplist (Ctrl-I / Tab).
+    pub fn jump_forward(&mut self) {
+        match self.jumps.forward() {
+            Some(line) => self.scroll_to_line(line),
+            None => self.status_message = Some("Already at newest position in jump list".to_string()),
+        }
+    }
 
-        self.status_message = Some(format!("Searching /{} \u{2026}", self.search.query_string));
+    /// Buffer line shown in the top row of the viewport (the first hidden line
+    /// when the top row is a placeholder).
+    pub fn top_view_line(&self) -> usize {
+        match &self.filter {
+            Some(filter) => match filter.rows.get(self.top_filter_idx) {
+                Some(ViewRow::Line(i)) => *i,
+                Some(ViewRow::Hidden { start, .. }) => *start,
+                Some(ViewRow::Deleted { at, .. }) => *at,
+                None => 0,
+            },
+            None => self.top_line,
+        }
     }
 
-    /// Apply a filter: keep only lines matching the regex.
-    pub fn apply_filter(&mut self, query: &str) {
-        if query.is_empty() {
-            self.clear_filter();
+    /// Mouse click at screen cell (x, y): the gutter toggles a bookmark on the
+    /// clicked line; the text makes it the current line, or moves the visual cursor.
+    pub fn click(&mut self, x: u16, y: u16) {
+        let area = self.content_area;
+        if !area.contains(Position { x, y }) {
             return;
         }
-        match regex::RegexBuilder::new(query)
-            .case_insensitive(true)
-            .build()
-        {
-            Ok(re) => {
-                let total = self.buffers[self.active_buffer].line_count();
-                let indices: Vec<usize> = (0..total)
-                    .filter(|&i| {
-                        self.buffers[self.active_buffer]
-                            .get_line(i)
-                            .map(|l| re.is_match(l))
-                            .unwrap_or(false)
-                    })
-                    .collect();
-                let count = indices.len();
-                self.filter = Some((query.to_string(), indices));
-                self.top_filter_idx = 0;
-                self.status_message = Some(format!("Filter: {} ({} lines)", query, count));
+        let Some((ViewRow::Line(line), down)) = self.row_at((y - area.y) as usize) else { return };
+        let gutter = (self.blame_width() + self.gutter_width()) as u16;
+        if x < area.x + gutter {
+            self.toggle_bookmark(line);
+            return;
+        }
+        match self.mode {
+            // A click in a `:log` buffer selects that commit
+            Mode::Normal => match self.buffers[self.active_buffer].log.as_mut() {
+                Some(log) => log.selected = line,
+                None => self.scroll_to_line(line),
+            },
+            Mode::Visual { anchor, .. } => self.mode = Mode::Visual { anchor, cursor: line },
+            Mode::VisualChar { anchor, .. } => {
+                // The click is in screen columns, the cursor a character index
+                let text = self.buffer().get_line(line).unwrap_or("");
+                let (x, tab_width) = ((x - area.x - gutter) as usize, self.tab_width as usize);
+                let index = match self.wrap_lines {
+                    true => tabs::char_at_wrapped(text, down, x, self.content_width.max(1), tab_width),
+                    false => tabs::char_at(text, x + self.left_col, tab_width),
+                };
+                let last = text.chars().count().saturating_sub(1);
+                self.mode = Mode::VisualChar { anchor, cursor: (line, index.min(last)) };
             }
-            Err(e) => {
-                self.status_message = Some(format!("Invalid filter regex: {}", e));
+            _ => {}
+        }
+    }
+
+    /// Remove the marks on `line`, or set the first free mark `a`–`z` on it.
+    pub fn toggle_bookmark(&mut self, line: usize) {
+        let before = self.marks.len();
+        self.marks.retain(|_, &mut l| l != line);
+        if self.marks.len() < before {
+            self.status_message = Some(format!("Removed mark from line {}", line + 1));
+            return;
+        }
+        match ('a'..='z').find(|c| !self.marks.contains_key(c)) {
+            Some(c) => {
+                self.marks.insert(c, line);
+                self.status_message = Some(format!("Mark '{}' set on line {}", c, line + 1));
             }
+            None => self.status_message = Some("All marks a-z are in use".to_string()),
         }
     }
 
-    /// Clear the active filter.
-    pub fn clear_filter(&mut self) {
-        self.filter = None;
+    /// Center the current search match in the view, or the top line when
+    /// there is no search (`zz`).
+    pub fn center_view(&mut self) {
+        let line = self.search.current_match_line().unwrap_or_else(|| self.top_view_line());
+        self.goto_line(line);
+    }
+
+    /// Go to the top of the file.
+    pub fn goto_top(&mut self) {
+        self.top_line = 0;
         self.top_filter_idx = 0;
     }
 
-    /// Yank the visual selection to the clipboard and return to Normal mode.
-    pub fn yank_selection(&mut self) {
-        let (anchor, cursor) = match &self.mode {
-            Mode::Visual { anchor, cursor } => (*anchor, *cursor),
-            _ => return,
+    /// Go to the bottom of the file.
+    pub fn goto_bottom(&mut self) {
+        self.top_line = self.max_top_line();
+        if self.filter.is_some() {
+            self.top_filter_idx = self.max_top_filter_idx();
+        }
+    }
+
+    /// True in follow mode unless it is paused: new lines are scrolled to.
+    pub fn tailing(&self) -> bool {
+        self.mode == Mode::Follow && self.follow_paused.is_none()
+    }
+
+    /// After scrolling in follow mode: pause it when the view has left the
+    /// bottom, and resume once it is back there.
+    pub fn update_follow_pause(&mut self) {
+        let at_bottom = match &self.filter {
+            Some(_) => self.top_filter_idx >= self.max_top_filter_idx(),
+            None => self.top_line >= self.max_top_line(),
         };
-        let start = anchor.min(cursor);
-        let end = anchor.max(cursor);
-        let buf = &self.buffers[self.active_buffer];
-        let text: String = (start..=end)
-            .filter_map(|i| buf.get_line(i))
-            .collect::<Vec<_>>()
-            .join("\n");
-        let line_count = end - start + 1;
-        match arboard::Clipboard::new() {
-            Ok(mut clipboard) => match clipboard.set_text(text) {
-                Ok(_) => {
-                    self.status_message = Some(format!("Yanked {} lines", line_count));
-                }
-                Err(e) => {
-                    self.status_message = Some(format!("Clipboard error: {}", e));
-                }
-            },
-            Err(e) => {
-                self.status_message = Some(format!("Clipboard unavailable: {}", e));
-            }
+        if at_bottom {
+            self.follow_paused = None;
+        } else if self.follow_paused.is_none() {
+            self.follow_paused = Some(self.buffer().line_count());
         }
-        self.mode = Mode::Normal;
     }
 
-    /// Start watching all buffer paths for changes (follow mode).
-    pub fn start_watching(&mut self) {
-        use notify::{RecursiveMode, Watcher};
+    /// Go back to showing new lines as they come in.
+    pub fn resume_follow(&mut self) {
+        self.follow_paused = None;
+        self.tail();
+    }
 
-        let paths: Vec<_> = self.buffers.iter().filter_map(|b| b.path.clone()).collect();
-        if paths.is_empty() {
-            return;
+    /// The mode to go back to when a search or filter prompt is closed.
+    pub fn mode_after_input(&mut self) -> Mode {
+        if std::mem::take(&mut self.follow_after_input) {
+            Mode::Follow
+        } else {
+            Mode::Normal
         }
-        let (tx, rx) = std::sync::mpsc::channel();
-        match notify::RecommendedWatcher::new(
-            move |res: notify::Result<notify::Event>| {
-                let _ = tx.send(res);
-            },
-            notify::Config::default(),
-        ) {
-            Ok(mut watcher) => {
-                for path in &paths {
-                    let _ = watcher.watch(path.as_path(), RecursiveMode::NonRecursive);
-                }
-                self.watcher_rx = Some(rx);
-                self.watcher = Some(watcher);
+    }
+
+    /// Show the newest lines, and make the newest search match the current one.
+    fn tail(&mut self) {
+        self.goto_bottom();
+        self.search.current = self.search.matches.len().saturating_sub(1);
+    }
+
+    /// Lines that have come in since follow mode was paused.
+    pub fn follow_new_lines(&self) -> Option<usize> {
+        self.follow_paused.map(|count| self.buffer().line_count().saturating_sub(count))
+    }
+
+    /// Make buffer `idx` the active one, saving the current buffer's viewport,
+    /// filter, search and marks and restoring those of `idx`.
+    pub fn switch_buffer(&mut self, idx: usize) {
+        if idx != self.active_buffer {
+            // Background jobs scan the buffer being left; drop them
+            self.filter_job = None;
+            self.search.search_rx = None;
+            self.search.is_searching = false;
+            let saved = self.take_view();
+            let restored = std::mem::take(&mut self.views[idx]);
+            self.views[self.active_buffer] = saved;
+            self.active_buffer = idx;
+            self.load_view(restored);
+            self.mru.retain(|&i| i != idx);
+            self.mru.insert(0, idx);
+            // Background tabs are kept up to date while following
+            if self.mode == Mode::Follow {
+                self.resume_follow();
             }
-            Err(e) => {
-                eprintln!("Warning: failed to start file watcher: {}", e);
+            // Search and filter again what changed in the background
+            if self.buffers[idx].new_data {
+                self.contents_changed(false);
             }
         }
+        self.buffers[idx].new_data = false;
+        self.status_message = Some(format!(
+            "Buffer {}/{}: {}",
+            self.active_buffer + 1,
+            self.buffers.len(),
+            self.buffer().name
+        ));
     }
 
-    /// Drain pending async search result batches. Called each event loop tick.
-    pub fn drain_search_results(&mut self) {
-        while let Some(rx) = &self.search.search_rx {
-            match rx.try_recv() {
-                Ok(SearchBatch::Progress { matches, lines_scanned }) => {
-                    self.search.matches.extend(matches);
-                    self.status_message = Some(format!(
-                        "Searching\u{2026} ({} matches, {}k lines)",
-                        self.search.match_count(),
-                        lines_scanned / 1000
-                    ));
-                }
-                Ok(SearchBatch::Done { matches }) => {
-                    self.search.matches.extend(matches);
-                    self.search.is_searching = false;
-                    self.search.search_rx = None;
-                    self.search.jump_to_line(self.top_line);
-                    if let Some(line) = self.search.current_match_line() {
-                        self.goto_line(line);
-                    }
-                    if self.search.match_count() > 0 {
-                        self.status_message = Some(format!(
-                            "{}{} ({} matches)",
-                            if self.search.forward { "/" } else { "?" },
-                            self.search.query_string,
-                            self.search.match_count()
-                        ));
+    /// Move the active buffer's view state out of the App fields.
+    fn take_view(&mut self) -> ViewState {
+        ViewState {
+            top_line: self.top_line,
+            left_col: self.left_col,
+            filter: self.filter.take(),
+            top_filter_idx: self.top_filter_idx,
+            search: std::mem::take(&mut self.search),
+            marks: std::mem::take(&mut self.marks),
+            notes: std::mem::take(&mut self.notes),
+            jumps: std::mem::take(&mut self.jumps),
+            last_visual: self.last_visual.take(),
+            settings: Some(LocalSettings {
+                line_numbers: self.show_line_numbers,
+                wrap: self.wrap_lines,
+                tab_width: self.tab_width,
+                theme: self.highlighter.theme_name().to_string(),
+            }),
+        }
+    }
+
+    /// Install `view` as the view of the active buffer, keeping the current
+    /// search settings.
+    fn load_view(&mut self, view: ViewState) {
+        let settings = view.settings.unwrap_or_else(|| self.initial_settings(self.active_buffer));
+        self.apply_settings(settings);
+        self.top_line = view.top_line;
+        self.left_col = view.left_col;
+        self.filter = view.filter;
+        self.top_filter_idx = view.top_filter_idx;
+        let settings = std::mem::replace(&mut self.search, view.search);
+        self.search.inherit_settings(&settings);
+        self.marks = view.marks;
+        self.notes = view.notes;
+        self.jumps = view.jumps;
+        self.last_visual = view.last_visual;
+    }
+
+    /// Syntax forced for a file at `path`: `--language`, else the `syntax`
+    /// of its `[filetype.*]` section. None leaves it to detection.
+    fn initial_syntax(&self, path: Option<&std::path::Path>) -> Option<String> {
+        let language = self.cli.as_ref().and_then(|cli| cli.language.clone());
+        language.or_else(|| self.config.filetype(path, &self.highlighter).and_then(|ft| ft.syntax.clone()))
+    }
+
+    /// Highlighting backend for a file at `path`: the `highlighter` of its
+    /// `[filetype.*]` section, else syntect.
+    fn initial_engine(&self, path: Option<&std::path::Path>) -> Engine {
+        self.config.filetype(path, &self.highlighter).and_then(|ft| ft.highlighter).unwrap_or_default()
+    }
+
+    /// Settings buffer `idx` starts with: `[general]`, overridden by its
+    /// `[filetype.*]` section if it has one, and the tab width by its
+    /// modeline.
+    fn initial_settings(&self, idx: usize) -> LocalSettings {
+        let general = &self.config.general;
+        let ft = self.config.filetype(self.buffers[idx].path.as_deref(), &self.highlighter);
+        let modeline = crate::modeline::find(&self.buffers[idx]).unwrap_or_default();
+        LocalSettings {
+            line_numbers: ft.and_then(|f| f.line_numbers).unwrap_or(general.line_numbers),
+            wrap: ft.and_then(|f| f.wrap).unwrap_or(general.wrap),
+            tab_width: modeline.tab_width.or(ft.and_then(|f| f.tab_width)).unwrap_or(general.tab_width),
+            theme: ft.and_then(|f| f.theme.clone()).unwrap_or_else(|| general.theme.clone()),
+        }
+    }
+
+    fn apply_settings(&mut self, settings: LocalSettings) {
+        self.show_line_numbers = settings.line_numbers;
+        self.wrap_lines = settings.wrap;
+        self.tab_width = settings.tab_width;
+        if self.highlighter.theme_name() != settings.theme {
+            self.highlighter.set_theme(&settings.theme);
+        }
+    }
+
+    /// Snapshot the open files and their view state for `:mksession`.
+    /// Buffers without a file (stdin, diffs) are skipped.
+    pub fn session(&self) -> Session {
+        let mut session = Session::default();
+        for (i, buf) in self.buffers.iter().enumerate() {
+            let Some(path) = buf.path.as_ref().filter(|_| !buf.is_diff) else { continue };
+            if i == self.active_buffer {
+                session.active = session.buffers.len();
+            }
+            let view = &self.views[i];
+            let (top_line, left_col, filter, top_filter_idx, search, marks, notes) = if i == self.active_buffer {
+                (self.top_line, self.left_col, &self.filter, self.top_filter_idx, &self.search, &self.marks, &self.notes)
+            } else {
+                (view.top_line, view.left_col, &view.filter, view.top_filter_idx, &view.search, &view.marks, &view.notes)
+            };
+            session.buffers.push(SessionBuffer {
+                path: canonical(path),
+                top_line,
+                left_col,
+                top_filter_idx,
+                filter: filter.as_ref().map(|f| SessionFilter {
+                    query: f.query.clone(),
+                    invert: f.invert,
+                    case: f.case,
+                    name: f.name.clone(),
+                }),
+                search: search.pattern.as_ref().map(|_| SessionSearch {
+                    query: search.query_string.clone(),
+                    forward: search.forward,
+                    current: search.current,
+                }),
+                marks: marks.iter().map(|(c, line)| (c.to_string(), *line)).collect(),
+                notes: notes
+                    .iter()
+                    .map(|(&line, text)| SessionNote { line, text: text.clone() })
+                    .collect(),
+            });
+        }
+        session
+    }
+
+    /// Restore view state from `session` onto the buffers opened from it,
+    /// matched by path. Filters and searches are recomputed synchronously so
+    /// every buffer is ready before the first draw.
+    pub fn restore_session(&mut self, session: &Session) {
+        let smart_case = self.config.general.smart_case;
+        for saved in &session.buffers {
+            let Some(idx) = self.buffers.iter().position(|b| {
+                b.path.as_ref().is_some_and(|p| canonical(p) == saved.path)
+            }) else {
+                continue;
+            };
+            let buf = &self.buffers[idx];
+            let mut view = ViewState {
+                top_line: saved.top_line.min(buf.line_count().saturating_sub(1)),
+                left_col: saved.left_col,
+                top_filter_idx: saved.top_filter_idx,
+                ..ViewState::default()
+            };
+            view.search.inherit_settings(&self.search);
+            if let Some(s) = &saved.search {
+                if view.search.set_pattern(&s.query, smart_case).is_ok() {
+                    view.search.forward = s.forward;
+                    view.search.search_buffer(buf);
+                    view.search.current = s.current.min(view.search.match_count().saturating_sub(1));
+                }
+            }
+            if let Some(f) = &saved.filter {
+                if let Ok(re) = self.filter_regex(&f.query, f.case) {
+                    let mut filter = LineFilter::scan(f.query.clone(), f.invert, &re, &buf.snapshot());
+                    filter.name = f.name.clone();
+                    filter.case = f.case;
+                    view.top_filter_idx = view.top_filter_idx.min(filter.rows.len().saturating_sub(1));
+                    view.filter = Some(filter);
+                }
+            }
+            view.marks = saved
+                .marks
+                .iter()
+                .filter_map(|(c, line)| Some((c.chars().next()?, *line)))
+                .collect();
+            view.notes = saved.notes.iter().map(|n| (n.line, n.text.clone())).collect();
+            if idx == self.active_buffer {
+                self.load_view(view);
+            } else {
+                self.views[idx] = view;
+            }
+        }
+        let active = session.buffers.get(session.active).and_then(|saved| {
+            self.buffers.iter().position(|b| {
+                b.path.as_ref().is_some_and(|p| canonical(p) == saved.path)
+            })
+        });
+        if let Some(idx) = active {
+            self.switch_buffer(idx);
+        }
+    }
+
+    /// Save the current session to `name` (or the session given with
+    /// `--session`), adopting it as the session saved on exit.
+    pub fn save_session(&mut self, name: &str) {
+        let name = if name.is_empty() { self.session_name.clone() } else { Some(name.to_string()) };
+        let Some(name) = name else {
+            self.status_message = Some("Usage: :mksession <name>".to_string());
+            return;
+        };
+        match self.session().save(&name) {
+            Ok(path) => {
+                self.status_message = Some(format!("Session saved to {}", path.display()));
+                self.session_name = Some(name);
+            }
+            Err(e) => self.status_message = Some(format!("{:#}", e)),
+        }
+    }
+
+    /// Switch to the next buffer (wraps around).
+    pub fn next_buffer(&mut self) {
+        if self.buffers.len() > 1 {
+            self.switch_buffer((self.active_buffer + 1) % self.buffers.len());
+        }
+    }
+
+    /// Switch to the previous buffer (wraps around).
+    pub fn prev_buffer(&mut self) {
+        if self.buffers.len() > 1 {
+            let idx = if self.active_buffer == 0 {
+                self.buffers.len() - 1
+            } else {
+                self.active_buffer - 1
+            };
+            self.switch_buffer(idx);
+        }
+    }
+
+    /// Move the active buffer to position `to` (0-based, clamped) in the tab bar.
+    pub fn move_buffer(&mut self, to: usize) {
+        let from = self.active_buffer;
+        let to = to.min(self.buffers.len() - 1);
+        if from != to {
+            let buf = self.buffers.remove(from);
+            self.buffers.insert(to, buf);
+            let view = self.views.remove(from);
+            self.views.insert(to, view);
+            self.active_buffer = to;
+            // Buffers between the two positions shift by one
+            for i in &mut self.mru {
+                *i = if *i == from {
+                    to
+                } else if from < to && (from + 1..=to).contains(i) {
+                    *i - 1
+                } else if to < from && (to..from).contains(i) {
+                    *i + 1
+                } else {
+                    *i
+                };
+            }
+        }
+        self.status_message = Some(format!(
+            "Moved {} to position {}/{}",
+            self.buffer().name,
+            to + 1,
+            self.buffers.len()
+        ));
+    }
+
+    /// Switch to the previously active buffer (Ctrl-^ / `:b#`).
+    pub fn alternate_buffer(&mut self) {
+        match self.mru.get(1) {
+            Some(&idx) => self.switch_buffer(idx),
+            None => self.status_message = Some("No alternate buffer".to_string()),
+        }
+    }
+
+    /// Move the active buffer one tab to the right (or left), stopping at the ends.
+    pub fn shift_buffer(&mut self, right: bool) {
+        let idx = self.active_buffer;
+        if right && idx + 1 < self.buffers.len() {
+            self.move_buffer(idx + 1);
+        } else if !right && idx > 0 {
+            self.move_buffer(idx - 1);
+        }
+    }
+
+    /// Open buffers matching the picker query, best first: (buffer index,
+    /// label, match). Buffers are labelled by path, or by name when unnamed.
+    pub fn picker_entries(&self, query: &str) -> Vec<(usize, String, FuzzyMatch)> {
+        let labels: Vec<String> = self
+            .buffers
+            .iter()
+            .map(|b| match &b.path {
+                Some(path) => path.display().to_string(),
+                None => b.name.clone(),
+            })
+            .collect();
+        picker::rank(query, labels.iter().map(String::as_str))
+            .into_iter()
+            .map(|(i, m)| (i, labels[i].clone(), m))
+            .collect()
+    }
+
+    /// Open `path` in a new buffer (`:e`) and switch to it. A file that is
+    /// already open is switched to instead of being loaded twice.
+    pub fn open_file(&mut self, path: &str) {
+        if path.is_empty() {
+            self.status_message = Some("Usage: :e <path>".to_string());
+            return;
+        }
+        let path = crate::paths::expand_path(path);
+        if let Some(idx) = self.buffer_of_path(&path) {
+            self.switch_buffer(idx);
+            return;
+        }
+        match self.open_tab(&path) {
+            Ok(idx) => self.switch_buffer(idx),
+            Err(e) => self.status_message = Some(format!("{:#}", e)),
+        }
+    }
+
+    /// Open `path` in a new tab without switching to it, watched like the
+    /// files given on the command line. Returns the index of the tab.
+    fn open_tab(&mut self, path: &std::path::Path) -> anyhow::Result<usize> {
+        let general = &self.config.general;
+        let mut buf = Buffer::open(path, general.limits(), general.preprocessor.as_deref())?;
+        buf.syntax = self.initial_syntax(Some(path));
+        buf.engine = self.initial_engine(Some(path));
+        self.buffers.push(buf);
+        self.views.push(ViewState::default());
+        self.watch_path(path);
+        Ok(self.buffers.len() - 1)
+    }
+
+    /// Watch for files matching `patterns` (`--watch-glob`) to appear, to
+    /// open each in a new tab. Relative patterns are taken from the current
+    /// directory, and the directory before the first wildcard is watched
+    /// recursively.
+    pub fn watch_globs(&mut self, patterns: &[String]) -> anyhow::Result<()> {
+        use notify::{RecursiveMode, Watcher};
+
+        let cwd = std::env::current_dir()?;
+        for pattern in patterns {
+            let absolute = cwd.join(pattern);
+            let glob = glob::Pattern::new(&absolute.to_string_lossy())
+                .map_err(|e| anyhow::anyhow!("Invalid --watch-glob pattern '{}': {}", pattern, e))?;
+            let dir = glob_dir(&absolute);
+            if !dir.is_dir() {
+                anyhow::bail!("--watch-glob '{}': no such directory '{}'", pattern, dir.display());
+            }
+            if let Some(watcher) = self.watcher.as_mut() {
+                watcher
+                    .watch(&dir, RecursiveMode::Recursive)
+                    .map_err(|e| anyhow::anyhow!("Cannot watch '{}': {}", dir.display(), e))?;
+            }
+            self.watch_globs.push((glob, dir));
+        }
+        if self.watcher.is_none() {
+            self.start_watching();
+        }
+        Ok(())
+    }
+
+    /// Open the files among `paths` that match a `--watch-glob` pattern and
+    /// are not open yet, each in a new tab marked as having new data. The
+    /// view moves to the first one only if the current tab is empty.
+    fn open_new_matches(&mut self, paths: &[PathBuf]) {
+        let options = glob::MatchOptions { require_literal_separator: true, ..Default::default() };
+        for path in paths {
+            let matches = self.watch_globs.iter().any(|(glob, _)| glob.matches_path_with(path, options));
+            if !matches || !path.is_file() || self.buffer_of_path(path).is_some() {
+                continue;
+            }
+            match self.open_tab(path) {
+                Ok(idx) => {
+                    self.buffers[idx].new_data = true;
+                    self.status_message = Some(format!("New file: {}", path.display()));
+                    if self.buffer().line_count() == 0 && self.buffer().stream.is_none() {
+                        self.switch_buffer(idx);
+                    }
+                }
+                Err(e) => self.status_message = Some(format!("{:#}", e)),
+            }
+        }
+    }
+
+    /// The tab showing the file at `path`, if any.
+    fn buffer_of_path(&self, path: &std::path::Path) -> Option<usize> {
+        let canonical = path.canonicalize().ok()?;
+        self.buffers
+            .iter()
+            .position(|b| b.path.as_ref().and_then(|p| p.canonicalize().ok()).as_ref() == Some(&canonical))
+    }
+
+    /// Add an in-memory buffer (e.g. command output) as a new tab and switch to it.
+    pub fn open_scratch(&mut self, buf: Buffer) {
+        self.buffers.push(buf);
+        self.views.push(ViewState::default());
+        self.switch_buffer(self.buffers.len() - 1);
+    }
+
+    /// Open a tab showing every open buffer one after another, in tab
+    /// order (`:join`). It is a copy: it does not follow the files.
+    pub fn join_buffers(&mut self) {
+        if self.buffers.len() < 2 {
+            self.status_message = Some("Join needs more than one buffer".to_string());
+            return;
+        }
+        if let Some(buf) = self.buffers.iter().find(|b| b.loading.is_some()) {
+            self.status_message = Some(format!("{} is still loading", buf.name));
+            return;
+        }
+        let parts: Vec<&Buffer> = self.buffers.iter().collect();
+        match joined(&parts, &self.highlighter, self.config.general.limits()) {
+            Ok(buf) => self.open_scratch(buf),
+            Err(e) => self.status_message = Some(format!("{:#}", e)),
+        }
+    }
+
+    /// Open a tab showing the fields at the comma-separated paths `spec`
+    /// (`.level,.msg`) of each JSON line of the active buffer (`:jq`). It
+    /// has a line for every line of the buffer, so it opens at the same
+    /// place.
+    pub fn jq(&mut self, spec: &str) {
+        if spec.is_empty() {
+            self.status_message = Some("Usage: :jq .field[,.field...]".to_string());
+            return;
+        }
+        let paths = match jq::parse(spec) {
+            Ok(paths) => paths,
+            Err(e) => {
+                self.status_message = Some(format!("jq: {}", e));
+                return;
+            }
+        };
+        let output = jq::apply(&self.buffer().snapshot(), &paths);
+        let lines = (0..self.buffer().line_count()).collect();
+        self.open_derived(Buffer::from_bytes(&format!("[jq {}]", spec), output), lines, self.top_line);
+    }
+
+    /// Open a tab showing only the fields `args` chooses (`1,3-5`, with an
+    /// optional `-d DELIM`) of each line of the active buffer (`:cols`).
+    pub fn cols(&mut self, args: &str) {
+        if args.is_empty() {
+            self.status_message = Some("Usage: :cols [-d DELIM] N[,N-M,N-...]".to_string());
+            return;
+        }
+        let columns = match cols::parse(args) {
+            Ok(columns) => columns,
+            Err(e) => {
+                self.status_message = Some(format!("cols: {}", e));
+                return;
+            }
+        };
+        let output = columns.apply(&self.buffer().snapshot());
+        let lines = (0..self.buffer().line_count()).collect();
+        self.open_derived(Buffer::from_bytes(&format!("[cols {}]", args), output), lines, self.top_line);
+    }
+
+    /// Open a tab showing the active buffer's lines sorted, as `sort` would
+    /// with the flags in `args` (`:sort -n -r`), highlighted as they are
+    /// in the buffer.
+    pub fn sort(&mut self, args: &str) {
+        let options = match sort::parse(args) {
+            Ok(options) => options,
+            Err(e) => {
+                self.status_message = Some(format!("sort: {}", e));
+                return;
+            }
+        };
+        let buf = self.buffer();
+        let snapshot = buf.snapshot();
+        let lines = sort::order(&snapshot, options);
+        let name = match args.trim() {
+            "" => format!("[sort {}]", buf.name),
+            flags => format!("[sort {} {}]", flags, buf.name),
+        };
+        let mut sorted = Buffer::from_bytes(&name, snapshot.select_lines(&lines));
+        sorted.syntax = Some(self.highlighter.buffer_syntax(buf).name.clone());
+        self.open_derived(sorted, lines, 0);
+    }
+
+    /// Open a tab showing the active buffer with each run of identical
+    /// adjacent lines as one line marked `×N` (`:uniq`).
+    pub fn uniq(&mut self) {
+        let buf = self.buffer();
+        let snapshot = buf.snapshot();
+        let runs = sort::runs(&snapshot);
+        let mut text = Vec::new();
+        for &(first, count) in &runs {
+            text.extend_from_slice(snapshot.line_bytes(first).unwrap_or_default());
+            if count > 1 {
+                text.extend_from_slice(format!(" \u{d7}{}", count).as_bytes());
+            }
+            text.push(b'\n');
+        }
+        let collapsed = snapshot.line_count() - runs.len();
+        let mut unique = Buffer::from_bytes(&format!("[uniq {}]", buf.name), text);
+        unique.syntax = Some(self.highlighter.buffer_syntax(buf).name.clone());
+        // Open at the run the top line is in
+        let top_line = self.top_view_line();
+        let top = runs.partition_point(|&(first, _)| first <= top_line).saturating_sub(1);
+        let lines = runs.into_iter().map(|(first, _)| first).collect();
+        self.open_derived(unique, lines, top);
+        self.status_message = Some(format!("{} repeated line{} collapsed", collapsed, if collapsed == 1 { "" } else { "s" }));
+    }
+
+    /// Open a tab listing the printable runs in the active buffer with the
+    /// offset of each, like `strings -t x` (`:strings [MIN]`). MIN defaults
+    /// to `general.strings_min_len`. Enter goes back to the run's hex row.
+    pub fn strings(&mut self, args: &str) {
+        let min_len = match args.trim() {
+            "" => self.config.general.strings_min_len,
+            n => match n.parse::<usize>() {
+                Ok(n) if n > 0 => n,
+                _ => {
+                    self.status_message = Some(format!("strings: '{}' is not a length", n));
+                    return;
+                }
+            },
+        };
+        let buf = self.buffer();
+        let found = strings::find(buf.snapshot().as_bytes(), min_len);
+        let mut text = Vec::new();
+        for (offset, run) in &found {
+            text.extend_from_slice(format!("{:08x}  {}\n", offset, run).as_bytes());
+        }
+        let lines = found.iter().map(|&(offset, _)| buf.row_of_byte(offset)).collect();
+        let list = Buffer::from_bytes(&format!("[strings {}]", buf.name), text);
+        let count = found.len();
+        self.open_derived(list, lines, 0);
+        self.status_message = Some(format!("{} string{} of {}+ characters", count, if count == 1 { "" } else { "s" }, min_len));
+    }
+
+    /// Open `buf`, whose line `i` was made from line `lines[i]` of the
+    /// active buffer, in a new tab with `top` as its top line.
+    fn open_derived(&mut self, mut buf: Buffer, lines: Vec<usize>, top: usize) {
+        let source = self.buffer();
+        buf.derived = Some(Derived { source_path: source.path.clone(), source_name: source.name.clone(), lines });
+        self.open_scratch(buf);
+        self.top_line = top.min(self.max_top_line());
+    }
+
+    /// Go back from a derived buffer (`:sort`, `:uniq`, `:jq`, `:cols`) to
+    /// the one it was made from, at the line its top line came from (Enter).
+    pub fn goto_source(&mut self) {
+        let Some(derived) = &self.buffer().derived else { return };
+        let Some(&line) = derived.lines.get(self.top_view_line()) else { return };
+        let source = self.buffers.iter().position(|b| b.name == derived.source_name && b.path == derived.source_path);
+        match source {
+            Some(idx) => {
+                self.switch_buffer(idx);
+                self.goto_line(line);
+            }
+            None => self.status_message = Some(format!("{} is no longer open", derived.source_name)),
+        }
+    }
+
+    /// Pipe lines `start..=end` through a shell command in the background
+    /// and show its output in a new scratch buffer as it comes (`|` in
+    /// Visual mode).
+    pub fn pipe_lines(&mut self, (start, end): (usize, usize), cmd: &str) {
+        let cmd = cmd.trim();
+        if cmd.is_empty() {
+            return;
+        }
+        let input = self.range_input((start, end));
+        self.start_command(&format!("[| {}]", cmd), cmd, Some(input), false);
+    }
+
+    /// List the available themes in a new tab (`:themes`).
+    pub fn list_themes(&mut self) {
+        let mut text = Vec::new();
+        let _ = crate::print::write_theme_list(&mut text, &self.highlighter, false);
+        self.open_scratch(Buffer::from_bytes("[themes]", text));
+    }
+
+    /// `:!cmd` — run a shell command in the background and show its output
+    /// (stdout and stderr) in a new scratch buffer as it comes. With
+    /// `range`, lines `start..=end` of the current buffer are its stdin
+    /// (`:%!cmd`, `:'<,'>!cmd`).
+    pub fn shell_command(&mut self, range: Option<(usize, usize)>, cmd: &str) {
+        let cmd = cmd.trim();
+        if cmd.is_empty() {
+            self.status_message = Some("Usage: :!<command>".to_string());
+            return;
+        }
+        let input = range.map(|range| self.range_input(range));
+        self.start_command(&format!("[! {}]", cmd), cmd, input, true);
+    }
+
+    /// Lines `start..=end` of the active buffer, newline-terminated, as the
+    /// stdin of a `:!` or `|` command.
+    fn range_input(&self, (start, end): (usize, usize)) -> Vec<u8> {
+        let buf = self.buffer();
+        let mut input = Vec::new();
+        for i in start..=end {
+            if let Some(line) = buf.get_line(i) {
+                input.extend_from_slice(line.as_bytes());
+                input.push(b'\n');
+            }
+        }
+        input
+    }
+
+    /// Start `cmd` in the background and open a tab named `name` that its
+    /// output streams into. `drain_streams` reports how it ended; Esc in
+    /// the tab stops it (`stop_command`).
+    fn start_command(&mut self, name: &str, cmd: &str, input: Option<Vec<u8>>, merge_stderr: bool) {
+        match crate::shell::start(cmd, input, merge_stderr) {
+            Ok((running, stdout)) => {
+                let mut scratch = Buffer::streaming(name, self.config.general.limits(), move || Ok(stdout));
+                scratch.command = Some(running);
+                self.open_scratch(scratch);
+                self.status_message = Some(format!("Running '{}' (Esc stops it)", cmd));
+            }
+            Err(e) => self.status_message = Some(format!("{:#}", e)),
+        }
+    }
+
+    /// Stop the command the active tab shows the output of (Esc).
+    pub fn stop_command(&mut self) {
+        if let Some(running) = self.buffers[self.active_buffer].command.as_mut() {
+            running.kill();
+        }
+    }
+
+    /// Report how the command behind tab `idx` ended, once its output has
+    /// and it has exited; until then it is left running.
+    fn finish_command(&mut self, idx: usize) {
+        let buf = &mut self.buffers[idx];
+        let Some((status, stderr)) = buf.command.as_mut().and_then(|running| running.try_finish()) else { return };
+        let Some(running) = buf.command.take() else { return };
+        let lines = buf.line_count();
+        let killed = running.is_killed();
+        let cmd = running.cmd;
+        self.dirty = true;
+        self.status_message = Some(match status {
+            _ if killed => format!("Stopped '{}' after {} lines", cmd, lines),
+            Some(status) if lines == 0 && !status.success() => match stderr.as_str() {
+                "" => format!("'{}' failed ({})", cmd, status),
+                msg => format!("'{}' failed: {}", cmd, msg),
+            },
+            _ if lines == 0 => format!("'{}' produced no output", cmd),
+            Some(status) if !status.success() => format!("{} lines from '{}' ({})", lines, cmd, status),
+            _ => format!("{} lines from '{}'", lines, cmd),
+        });
+    }
+
+    /// Percentage through the file based on top_line.
+    pub fn scroll_percentage(&self) -> u16 {
+        if let Some(LineFilter { ref rows, .. }) = self.filter {
+            if rows.is_empty() {
+                return 100;
+            }
+            let bottom = self.top_filter_idx + self.content_height;
+            let effective = bottom.min(rows.len());
+            ((effective as f64 / rows.len() as f64) * 100.0) as u16
+        } else {
+            if self.total_lines() == 0 {
+                return 100;
+            }
+            let bottom = self.top_line + self.content_height;
+            let effective = std::cmp::min(bottom, self.total_lines());
+            ((effective as f64 / self.total_lines() as f64) * 100.0) as u16
+        }
+    }
+
+    /// Width of the line number gutter (digits + 1 space).
+    pub fn gutter_width(&self) -> usize {
+        if !self.show_line_numbers {
+            return 0;
+        }
+        let max_line = self.total_lines();
+        let digits = if max_line == 0 {
+            1
+        } else {
+            (max_line as f64).log10() as usize + 1
+        };
+        digits + 2 // e.g. " 42 " — padding on each side
+    }
+
+    /// Execute a search asynchronously, updating `search.matches` via a background thread.
+    /// The thread scans a shared snapshot of the buffer rather than a copy of its text.
+    pub fn execute_search(&mut self) {
+        let smart_case = self.config.general.smart_case;
+        let query = self.search.query_string.clone();
+        if self.search.set_pattern(&query, smart_case).is_err() {
+            self.status_message = Some(format!("Invalid regex: {}", query));
+            return;
+        }
+        let pattern = match self.search.pattern.clone() {
+            Some(p) => p,
+            None => {
+                self.status_message = Some("Empty pattern".to_string());
+                return;
+            }
+        };
+
+        self.search.matches.clear();
+        self.search.preview_matches.clear();
+        self.search.is_searching = true;
+        self.search.truncated = false;
+        self.search.started = Some(Instant::now());
+        let max_matches = self.search.max_matches;
+
+        let snapshot = self.buffers[self.active_buffer].snapshot();
+        self.search.searched = snapshot.line_count();
+        let (first, last) = self.search.line_bounds(snapshot.line_count());
+        self.search.progress = (0, last - first);
+        self.search.search_rx = Some(search::spawn_search(snapshot, pattern, (first, last), max_matches));
+
+        self.status_message = Some(match self.search.range {
+            Some((lo, hi)) => format!(
+                "Searching /{} in lines {}-{} \u{2026}",
+                self.search.query_string,
+                lo + 1,
+                hi + 1
+            ),
+            None => format!("Searching /{} \u{2026}", self.search.query_string),
+        });
+    }
+
+    /// `:g/regex` — search for `query` (within `range`, if given) and list
+    /// the matching lines in a panel, leaving the view unfiltered so n/N and
+    /// the context around each match stay available. An empty query reopens
+    /// the list for the current search.
+    pub fn global_search(&mut self, query: &str, range: Option<(usize, usize)>) {
+        if !query.is_empty() {
+            self.search.query_string = query.to_string();
+            self.search.forward = true;
+            self.search.range = range;
+            self.execute_search();
+        } else if !self.search.has_pattern() {
+            self.status_message = Some("No previous search pattern".to_string());
+            return;
+        }
+        if self.search.has_pattern() {
+            self.mode = Mode::MatchList { selected: 0 };
+        }
+    }
+
+    /// Distinct lines with a search match, in order.
+    pub fn match_lines(&self) -> Vec<usize> {
+        let mut lines: Vec<usize> = self.search.matches.iter().map(|(line, _)| *line).collect();
+        lines.dedup();
+        lines
+    }
+
+    /// Step to the next match further down (`down = true`) or up the buffer,
+    /// reporting wrap-around or end-of-buffer in the status message.
+    pub fn step_match(&mut self, down: bool) {
+        if !self.search.has_pattern() {
+            return;
+        }
+        let origin = self.search.current;
+        let step = if down { self.search.next_match() } else { self.search.prev_match() };
+        let Some(step) = self.skip_hidden_matches(down, step) else {
+            self.search.current = origin;
+            self.status_message = Some(format!(
+                "No match for {} in the filtered lines",
+                self.search.query_string
+            ));
+            return;
+        };
+        let (edge, other) = if down { ("BOTTOM", "TOP") } else { ("TOP", "BOTTOM") };
+        match step {
+            MatchStep::NoMatches => {
+                self.status_message = Some(format!("Pattern not found: {}", self.search.query_string));
+                return;
+            }
+            MatchStep::HitEnd => {
+                self.status_message = Some(format!(
+                    "search hit {} without match for: {}",
+                    edge, self.search.query_string
+                ));
+                return;
+            }
+            MatchStep::Wrapped => {
+                self.status_message = Some(format!("search hit {}, continuing at {}", edge, other));
+            }
+            MatchStep::Moved => {
+                self.status_message = Some(format!(
+                    "Match {}/{}",
+                    self.search.current + 1,
+                    self.search.match_count()
+                ));
+            }
+        }
+        if let Some(line) = self.search.current_match_line() {
+            self.push_jump();
+            self.goto_line(line);
+        }
+    }
+
+    /// With a filter active, keep stepping past matches on hidden lines.
+    /// Returns the overall step (Wrapped if any step wrapped), or None when
+    /// no visible match is reachable in this direction.
+    fn skip_hidden_matches(&mut self, down: bool, mut step: MatchStep) -> Option<MatchStep> {
+        let Some(filter) = &self.filter else { return Some(step) };
+        let mut wrapped = step == MatchStep::Wrapped;
+        let mut remaining = self.search.match_count();
+        while matches!(step, MatchStep::Moved | MatchStep::Wrapped) {
+            match self.search.current_match_line() {
+                Some(line) if !filter.shows_line(line) => {}
+                _ => break,
+            }
+            remaining -= 1;
+            if remaining == 0 {
+                return None;
+            }
+            step = if down { self.search.next_match() } else { self.search.prev_match() };
+            wrapped |= step == MatchStep::Wrapped;
+        }
+        match step {
+            MatchStep::HitEnd => None,
+            MatchStep::Moved if wrapped => Some(MatchStep::Wrapped),
+            _ => Some(step),
+        }
+    }
+
+    /// Toggle whether n/N wrap around at the ends of the buffer.
+    pub fn toggle_search_wrap(&mut self) {
+        self.search.wrap_around = !self.search.wrap_around;
+        self.status_message = Some(if self.search.wrap_around {
+            "Search wrap-around on".to_string()
+        } else {
+            "Search wrap-around off".to_string()
+        });
+    }
+
+    /// `:syntax NAME` — highlight the current buffer as NAME (a syntax name
+    /// or file extension). `:syntax on`/`off` force highlighting on or off,
+    /// whatever the file's size. With no argument, show the syntax in use.
+    pub fn set_syntax(&mut self, name: &str) {
+        if let Some(on) = match name {
+            "on" => Some(true),
+            "off" => Some(false),
+            _ => None,
+        } {
+            self.buffers[self.active_buffer].highlight = Some(on);
+            self.status_message = Some(format!("Highlighting {}", name));
+            return;
+        }
+        if !name.is_empty() {
+            let Some(syntax) = self.highlighter.find_syntax(name) else {
+                self.status_message = Some(format!("Unknown syntax: {}", name));
+                return;
+            };
+            self.buffers[self.active_buffer].syntax = Some(syntax.name.clone());
+        }
+        self.status_message = Some(format!("syntax={}", self.highlighter.backend_name(self.buffer())));
+    }
+
+    /// `:set` — change runtime options, vim style (`wrap`, `nonumber`,
+    /// `ws!`, `tabwidth=8`, `theme?`). With no argument, show them all.
+    pub fn set_options(&mut self, arg: &str) {
+        let args = options::parse(arg);
+        if args.is_empty() {
+            let all: Vec<String> = Opt::ALL.iter().map(|&opt| self.option_string(opt)).collect();
+            self.status_message = Some(all.join("  "));
+            return;
+        }
+        let mut shown = Vec::new();
+        for set in args {
+            let (SetArg::On(name) | SetArg::Off(name) | SetArg::Toggle(name) | SetArg::Show(name) | SetArg::Assign(name, _)) = set;
+            let Some(opt) = Opt::from_name(name) else {
+                self.status_message = Some(format!("Unknown option: {}", name));
+                return;
+            };
+            let result = match set {
+                // As in vim, naming a value option shows it
+                SetArg::Show(_) => Ok(()),
+                SetArg::On(_) if !opt.is_bool() => Ok(()),
+                SetArg::Off(_) | SetArg::Toggle(_) if !opt.is_bool() => {
+                    Err(format!("Option '{}' needs a value: {}=…", opt.name(), opt.name()))
+                }
+                SetArg::On(_) => self.set_bool_option(opt, |_| true),
+                SetArg::Off(_) => self.set_bool_option(opt, |_| false),
+                SetArg::Toggle(_) => self.set_bool_option(opt, |on| !on),
+                SetArg::Assign(_, value) => self.assign_option(opt, value),
+            };
+            if let Err(e) = result {
+                self.status_message = Some(e);
+                return;
+            }
+            shown.push(self.option_string(opt));
+        }
+        self.status_message = Some(shown.join("  "));
+    }
+
+    /// Current value of `opt` as `:set` would accept it, e.g. "nowrap" or "tabwidth=4".
+    fn option_string(&self, opt: Opt) -> String {
+        let on = match opt {
+            Opt::Number => self.show_line_numbers,
+            Opt::Wrap => self.wrap_lines,
+            Opt::WrapScan => self.search.wrap_around,
+            Opt::SmartCase => self.config.general.smart_case,
+            Opt::FancyRegex => self.search.fancy,
+            Opt::TabWidth => return format!("tabwidth={}", self.tab_width),
+            Opt::MaxMatches => return format!("maxmatches={}", self.search.max_matches),
+            Opt::Theme => return format!("theme={}", self.highlighter.theme_name()),
+        };
+        format!("{}{}", if on { "" } else { "no" }, opt.name())
+    }
+
+    fn set_bool_option(&mut self, opt: Opt, f: impl Fn(bool) -> bool) -> Result<(), String> {
+        let field = match opt {
+            Opt::Number => &mut self.show_line_numbers,
+            Opt::Wrap => &mut self.wrap_lines,
+            Opt::WrapScan => &mut self.search.wrap_around,
+            Opt::SmartCase => &mut self.config.general.smart_case,
+            Opt::FancyRegex => &mut self.search.fancy,
+            _ => unreachable!("not a boolean option"),
+        };
+        *field = f(*field);
+        Ok(())
+    }
+
+    fn assign_option(&mut self, opt: Opt, value: &str) -> Result<(), String> {
+        match opt {
+            Opt::TabWidth => match value.parse::<u8>() {
+                Ok(n) if n > 0 => self.tab_width = n,
+                _ => return Err(format!("Invalid tabwidth: {}", value)),
+            },
+            Opt::MaxMatches => match value.parse::<usize>() {
+                Ok(n) => self.search.max_matches = n,
+                Err(_) => return Err(format!("Invalid maxmatches: {}", value)),
+            },
+            Opt::Theme => {
+                if !self.highlighter.set_theme(value) {
+                    return Err(format!("Unknown theme: {}", value));
+                }
+            }
+            _ => match value {
+                "on" | "true" | "1" => return self.set_bool_option(opt, |_| true),
+                "off" | "false" | "0" => return self.set_bool_option(opt, |_| false),
+                _ => return Err(format!("Invalid value for {}: {}", opt.name(), value)),
+            },
+        }
+        Ok(())
+    }
+
+    /// Apply a filter: keep only lines matching the regex, or with `invert`
+    /// hide the matching lines and keep the rest. `case` decides letter-case
+    /// handling (smart case by default). The scan runs on a background
+    /// thread; `drain_filter_results()` installs the filter when it completes.
+    pub fn apply_filter(&mut self, query: &str, invert: bool, case: CaseMode) {
+        if query.is_empty() {
+            self.cancel_filter();
+            self.clear_filter();
+            return;
+        }
+        match self.filter_regex(query, case) {
+            Ok(re) => {
+                let snapshot = self.buffers[self.active_buffer].snapshot();
+                let mut job = FilterJob::spawn(query.to_string(), invert, re, snapshot);
+                job.case = case;
+                self.status_message = Some(format!("Filtering {} \u{2026} (Esc to cancel)", job.label()));
+                self.filter_job = Some(job);
+            }
+            Err(e) => {
+                self.status_message = Some(format!("Invalid filter regex: {}", e));
+            }
+        }
+    }
+
+    /// Compile a filter pattern with the given case handling.
+    fn filter_regex(&self, query: &str, case: CaseMode) -> Result<regex::bytes::Regex, regex::Error> {
+        let ignore_case = case.ignore_case(query, self.config.general.smart_case);
+        regex::bytes::RegexBuilder::new(query)
+            .case_insensitive(ignore_case)
+            .build()
+    }
+
+    /// Canned filter showing lines at `level` or more severe, using the
+    /// configured `[log_levels]` regexes. `level` is e/w/i/d/t.
+    pub fn apply_log_level_filter(&mut self, level: char) {
+        let Some((name, pattern)) = self.config.log_levels.at_least(level) else {
+            self.status_message = Some(format!("Unknown log level '{}' (use e/w/i/d/t)", level));
+            return;
+        };
+        self.apply_filter(&pattern, false, CaseMode::Insensitive);
+        if let Some(job) = self.filter_job.as_mut() {
+            job.name = Some(format!("level>={}", name));
+        }
+    }
+
+    /// True while a filter is being computed in the background.
+    pub fn is_filtering(&self) -> bool {
+        self.filter_job.is_some()
+    }
+
+    /// True while the current buffer is being highlighted in the background.
+    pub fn is_highlighting(&self) -> bool {
+        self.buffer().highlight_cache.borrow().is_running()
+    }
+
+    /// `:blame` — show or hide the `git blame` column.
+    pub fn toggle_blame(&mut self) {
+        self.show_blame = !self.show_blame;
+        if !self.show_blame {
+            self.status_message = Some("Blame off".to_string());
+        } else if self.buffer().path.is_none() || self.buffer().is_diff {
+            self.show_blame = false;
+            self.status_message = Some("Blame needs a file".to_string());
+        } else {
+            self.start_blame();
+        }
+    }
+
+    /// Run `git blame` for the active buffer unless it has been or is
+    /// running. Buffers without a file (stdin, scratch tabs) get no column.
+    fn start_blame(&mut self) {
+        let buf = self.buffer();
+        if buf.blame.is_some() || buf.is_diff {
+            return;
+        }
+        let Some(path) = buf.path.clone() else { return };
+        if self.blame_job.as_ref().is_some_and(|(job, _)| *job == path) {
+            return;
+        }
+        self.status_message = Some("Running git blame\u{2026}".to_string());
+        self.blame_job = Some((path.clone(), blame::spawn(&path)));
+    }
+
+    /// True while `git blame` runs in the background.
+    pub fn is_blaming(&self) -> bool {
+        self.blame_job.is_some()
+    }
+
+    /// Take the result of a finished `git blame`, and start one for the
+    /// active buffer if the column is on and it has none (after switching
+    /// files or a reload). Called each event loop tick.
+    pub fn drain_blame_results(&mut self) {
+        if let Some((path, rx)) = &self.blame_job {
+            match rx.try_recv() {
+                Ok(Ok(blame)) => {
+                    let path = path.clone();
+                    let mut blame = Some(blame);
+                    for buf in self.buffers.iter_mut().filter(|b| b.path.as_ref() == Some(&path) && !b.is_diff) {
+                        buf.blame = blame.take();
+                    }
+                    self.blame_job = None;
+                    self.status_message = None;
+                }
+                Ok(Err(e)) => {
+                    self.blame_job = None;
+                    self.show_blame = false;
+                    self.status_message = Some(format!("git blame: {}", e));
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => {}
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => self.blame_job = None,
+            }
+        }
+        if self.show_blame && self.blame_job.is_none() {
+            self.start_blame();
+        }
+    }
+
+    /// Keep `repo_status` up to date for the active buffer: ask git again
+    /// after switching to a file elsewhere, or when the answer is older
+    /// than `GIT_REFRESH_INTERVAL`. Called each event loop tick.
+    pub fn refresh_repo_status(&mut self) {
+        if let Some(rx) = &self.repo_job {
+            match rx.try_recv() {
+                Ok(status) => {
+                    self.repo_status = status;
+                    self.repo_job = None;
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => return,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => self.repo_job = None,
+            }
+        }
+        let Some(path) = self.buffer().path.clone().filter(|_| !self.buffer().is_diff) else {
+            self.repo_status = None;
+            self.repo_checked = None;
+            return;
+        };
+        let moved = self.repo_checked.as_ref().is_none_or(|(last, _)| last.parent() != path.parent());
+        if moved {
+            self.repo_status = None;
+        }
+        if moved || self.repo_checked.as_ref().is_some_and(|(_, at)| at.elapsed() >= GIT_REFRESH_INTERVAL) {
+            self.repo_job = Some(git::spawn_status(&path));
+            self.repo_checked = Some((path, Instant::now()));
+        }
+    }
+
+    /// Show the lines deleted at the first `▾` marker in view whose
+    /// deleted lines are hidden, or hide them all again when every marker
+    /// in view is open (`zd`).
+    pub fn toggle_deleted(&mut self) {
+        let rows = self.active_rows();
+        let buf = &mut self.buffers[self.active_buffer];
+        let closed = rows.iter().find_map(|row| match *row {
+            ViewRow::Line(at) if buf.git_deleted.contains_key(&at) && !buf.shown_deletions.contains(&at) => Some(at),
+            _ => None,
+        });
+        match closed {
+            Some(at) => {
+                buf.shown_deletions.insert(at);
+                let count = buf.git_deleted[&at].len();
+                self.status_message = Some(format!("{} deleted line{} after line {}", count, if count == 1 { "" } else { "s" }, at + 1));
+            }
+            None if !buf.shown_deletions.is_empty() => {
+                buf.shown_deletions.clear();
+                self.status_message = Some("Deleted lines hidden".to_string());
+            }
+            None => self.status_message = Some("No deleted lines in view".to_string()),
+        }
+    }
+
+    /// Ask git for the active file's changes again on the next tick, e.g.
+    /// after it changed on disk.
+    pub fn refresh_git_changes(&mut self) {
+        self.changes_checked = None;
+    }
+
+    /// Remap the buffers whose mapped file was truncated by another process
+    /// before anything reads past its new end. Called each event loop tick,
+    /// before drawing.
+    pub fn check_truncated(&mut self) {
+        for idx in 0..self.buffers.len() {
+            if !self.buffers[idx].is_truncated() {
+                continue;
+            }
+            if idx == self.active_buffer {
+                self.reload_active_buffer();
+                self.top_line = self.top_line.min(self.max_top_line());
+            } else {
+                let general = &self.config.general;
+                if let Err(e) = self.buffers[idx].reload(general.limits(), general.preprocessor.as_deref()) {
+                    self.status_message = Some(format!("Reload failed: {}", e));
+                    continue;
+                }
+            }
+            self.status_message = Some(format!("{} was truncated; reloaded", self.buffers[idx].name));
+            self.dirty = true;
+        }
+    }
+
+    /// Fill in the buffers whose background load has finished. Called each
+    /// event loop tick.
+    pub fn drain_loads(&mut self) {
+        for idx in 0..self.buffers.len() {
+            match self.buffers[idx].poll_loading() {
+                None => continue,
+                Some(Err(e)) => {
+                    self.status_message = Some(format!("{}: {:#}", self.buffers[idx].name, e));
+                    continue;
+                }
+                Some(Ok(())) => {}
+            }
+            if idx != self.active_buffer {
+                continue;
+            }
+            if let Some(line) = self.pending_goto.take() {
+                self.goto_line(line);
+            }
+            if self.search.has_pattern() {
+                self.search.search_buffer(&self.buffers[idx]);
+            }
+            if self.tailing() {
+                self.goto_bottom();
+            }
+        }
+    }
+
+    /// Add what pipes have produced to their buffers. The active buffer is
+    /// searched again and, in follow mode, kept at the bottom. Called each
+    /// event loop tick.
+    pub fn drain_streams(&mut self) {
+        for idx in 0..self.buffers.len() {
+            let (before, dropped) = (self.buffers[idx].complete_lines(), self.buffers[idx].dropped);
+            let polled = self.buffers[idx].poll_stream();
+            let dropped = self.buffers[idx].dropped - dropped;
+            if dropped > 0 {
+                self.lines_dropped(idx, dropped);
+            }
+            if matches!(polled, Ok(true)) {
+                self.check_alerts(idx, before.saturating_sub(dropped));
+            }
+            if self.buffers[idx].stream.is_none() && self.buffers[idx].command.is_some() {
+                self.finish_command(idx);
+            }
+            match polled {
+                Ok(false) => continue,
+                Ok(true) => {}
+                Err(e) => self.status_message = Some(format!("{:#}", e)),
+            }
+            self.dirty = true;
+            if idx != self.active_buffer {
+                self.buffers[idx].new_data = true;
+                continue;
+            }
+            self.contents_changed(dropped == 0);
+        }
+    }
+
+    /// Keep the line positions of buffer `idx` on the same text after its
+    /// oldest `count` lines were dropped. Marks and notes on those lines go.
+    fn lines_dropped(&mut self, idx: usize, count: usize) {
+        let (top_line, marks, notes, jumps) = if idx == self.active_buffer {
+            if let Some(lines) = self.follow_paused.as_mut() {
+                *lines = lines.saturating_sub(count);
+            }
+            (&mut self.top_line, &mut self.marks, &mut self.notes, &mut self.jumps)
+        } else {
+            let view = &mut self.views[idx];
+            (&mut view.top_line, &mut view.marks, &mut view.notes, &mut view.jumps)
+        };
+        *top_line = top_line.saturating_sub(count);
+        marks.retain(|_, line| *line >= count);
+        marks.values_mut().for_each(|line| *line -= count);
+        *notes = std::mem::take(notes).into_iter().filter_map(|(line, note)| Some((line.checked_sub(count)?, note))).collect();
+        jumps.lines_dropped(count);
+    }
+
+    /// Show the latest output of `--exec` commands, keeping what changed
+    /// since the run before for the viewer to highlight. Called each event
+    /// loop tick.
+    pub fn drain_exec(&mut self) {
+        for idx in 0..self.buffers.len() {
+            let buf = &mut self.buffers[idx];
+            let Some(run) = buf.exec.as_mut().and_then(Exec::poll) else { continue };
+            self.dirty = true;
+            let (output, status) = match run {
+                Ok(run) => run,
+                Err(e) => {
+                    self.status_message = Some(format!("{:#}", e));
+                    continue;
+                }
+            };
+            let old: Vec<String> = (0..buf.line_count()).map(|i| buf.get_line(i).unwrap_or("").to_string()).collect();
+            buf.set_contents(output);
+            let old: Vec<&str> = old.iter().map(String::as_str).collect();
+            let new: Vec<&str> = (0..buf.line_count()).map(|i| buf.get_line(i).unwrap_or("")).collect();
+            let changes = exec::changes(&old, &new);
+            if let Some(exec) = buf.exec.as_mut() {
+                exec.changes = changes;
+                exec.status = Some(status);
+            }
+            if idx != self.active_buffer {
+                buf.new_data = true;
+                continue;
+            }
+            self.top_line = self.top_line.min(self.max_top_line());
+            self.contents_changed(false);
+        }
+    }
+
+    /// Bring the search, the filter and, when tailing, the view up to date
+    /// with new contents of the active buffer. `appended` when lines were
+    /// only added at the end, so that only those need scanning.
+    fn contents_changed(&mut self, appended: bool) {
+        let buffer = &self.buffers[self.active_buffer];
+        if appended {
+            self.search.search_appended(buffer);
+        } else if self.search.has_pattern() {
+            // A search still running is over the old contents
+            self.search.search_rx = None;
+            self.search.is_searching = false;
+            self.search.started = None;
+            self.search.search_buffer(buffer);
+        }
+        self.refresh_filter(appended);
+        if self.tailing() {
+            self.tail();
+        }
+    }
+
+    /// Keep the filter in step with the active buffer: match just the
+    /// appended lines, or scan the whole buffer again in the background.
+    fn refresh_filter(&mut self, appended: bool) {
+        let snapshot = self.buffers[self.active_buffer].snapshot();
+        if appended {
+            // A filter job still running catches up when it is done
+            let Some(filter) = &self.filter else { return };
+            let Ok(re) = self.filter_regex(&filter.query, filter.case) else { return };
+            if self.filter.as_mut().is_some_and(|f| f.extend(&re, &snapshot)) {
+                return;
+            }
+        }
+        let (query, invert, name, case) = match (&self.filter_job, &self.filter) {
+            (Some(job), _) => (job.query.clone(), job.invert, job.name.clone(), job.case),
+            (None, Some(f)) => (f.query.clone(), f.invert, f.name.clone(), f.case),
+            (None, None) => return,
+        };
+        let Ok(re) = self.filter_regex(&query, case) else { return };
+        let mut job = FilterJob::spawn(query, invert, re, snapshot);
+        job.name = name;
+        job.case = case;
+        self.filter_job = Some(job);
+    }
+
+    /// True while more may still come from a pipe into any buffer.
+    pub fn is_streaming(&self) -> bool {
+        self.buffers.iter().any(|b| b.stream.is_some())
+    }
+
+    /// True while any buffer is still loading in the background.
+    pub fn is_loading(&self) -> bool {
+        self.buffers.iter().any(|b| b.loading.is_some())
+    }
+
+    /// True while an alert is flashing the status bar.
+    pub fn alert_flashing(&self) -> bool {
+        self.alert_flash.is_some()
+    }
+
+    /// True while anything running in the background may still change what
+    /// is shown: loading, highlighting, searching, filtering or git.
+    pub fn is_busy(&self) -> bool {
+        self.is_loading()
+            || self.is_highlighting()
+            || self.search.is_searching
+            || self.is_filtering()
+            || self.blame_job.is_some()
+            || self.repo_job.is_some()
+            || self.changes_job.is_some()
+    }
+
+    /// Keep the gutter's git changes up to date: store finished `git diff`
+    /// results, and start one for the active file when it has not been
+    /// asked for yet or the answer is older than `GIT_REFRESH_INTERVAL`.
+    /// Called each event loop tick.
+    pub fn drain_git_changes(&mut self) {
+        if let Some((path, rx)) = &self.changes_job {
+            match rx.try_recv() {
+                Ok(result) => {
+                    if let Ok(diff) = result {
+                        for buf in self.buffers.iter_mut().filter(|b| b.path.as_ref() == Some(path) && !b.is_diff) {
+                            buf.git_changes = diff.changes.clone();
+                            buf.git_deleted = diff.deleted.clone();
+                            let deleted = &buf.git_deleted;
+                            buf.shown_deletions.retain(|at| deleted.contains_key(at));
+                        }
+                    }
+                    self.changes_job = None;
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => return,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => self.changes_job = None,
+            }
+        }
+        let Some(path) = self.buffer().path.clone().filter(|_| !self.buffer().is_diff) else { return };
+        let stale = self
+            .changes_checked
+            .as_ref()
+            .is_none_or(|(last, at)| *last != path || at.elapsed() >= GIT_REFRESH_INTERVAL);
+        if stale {
+            self.changes_job = Some((path.clone(), git::spawn_changes(&path)));
+            self.changes_checked = Some((path, Instant::now()));
+        }
+    }
+
+    /// Width of the blame column: hash, author, age and separator.
+    pub fn blame_width(&self) -> usize {
+        match self.buffer().blame.as_ref().filter(|_| self.show_blame) {
+            Some(blame) => 7 + 1 + blame.author_width() + 1 + 4 + 2,
+            None => 0,
+        }
+    }
+
+    /// Open the commit that last changed the top line, with its full
+    /// message, in a scratch tab.
+    pub fn show_blame_commit(&mut self) {
+        let line = self.top_view_line();
+        let buf = self.buffer();
+        let (Some(path), Some(blame)) = (buf.path.clone(), buf.blame.as_ref()) else {
+            self.status_message = Some("No blame for this file (turn it on with :blame)".to_string());
+            return;
+        };
+        let Some(commit) = blame.line(line) else { return };
+        if !commit.is_committed() {
+            self.status_message = Some(format!("Line {} is not committed yet", line + 1));
+            return;
+        }
+        let name = format!("[commit {}]", commit.short_hash());
+        match blame::show_commit(&path, &commit.hash) {
+            Ok(text) => self.open_scratch(Buffer::from_bytes(&name, text)),
+            Err(e) => self.status_message = Some(format!("git show: {}", e)),
+        }
+    }
+
+    /// Keep the message history and expire the status message: a new
+    /// message is added to `messages`, and one that has been up for
+    /// `message_timeout` seconds is cleared. The progress reports of a
+    /// running search or filter are left alone. Called each event loop
+    /// tick.
+    pub fn update_messages(&mut self) {
+        if self.alert_flash.is_some_and(|since| since.elapsed() >= ALERT_FLASH) {
+            self.alert_flash = None;
+            self.dirty = true;
+        }
+        if self.search.is_searching || self.is_filtering() {
+            return;
+        }
+        let Some(message) = &self.status_message else {
+            self.message_shown = None;
+            return;
+        };
+        match &self.message_shown {
+            Some((shown, since)) if shown == message => {
+                let timeout = self.config.general.message_timeout;
+                if timeout > 0 && since.elapsed() >= Duration::from_secs(timeout) {
+                    self.status_message = None;
+                    self.message_shown = None;
+                    self.dirty = true;
+                }
+            }
+            _ => {
+                self.messages.push_back((SystemTime::now(), message.clone()));
+                if self.messages.len() > MESSAGE_HISTORY {
+                    self.messages.pop_front();
+                }
+                self.message_shown = Some((message.clone(), Instant::now()));
+            }
+        }
+    }
+
+    /// Rows of the `:info` popup: label and value.
+    pub fn info_rows(&self) -> Vec<(&'static str, String)> {
+        let buf = self.buffer();
+        let info = &buf.info;
+        let loaded = buf.byte_len() as u64;
+        let now = SystemTime::now();
+        let mut rows = vec![("Name", buf.name.clone())];
+        if let Some(path) = &buf.path {
+            rows.push(("Path", path.display().to_string()));
+        }
+        if let Some(size) = info.size {
+            rows.push(("Size", match size {
+                0..1024 => format!("{} bytes", size),
+                _ => format!("{} ({} bytes)", crate::info::human_size(size), size),
+            }));
+        }
+        if info.size.is_some_and(|size| size != loaded) {
+            rows.push(("Loaded", format!("{} (decompressed or preprocessed)", crate::info::human_size(loaded))));
+        }
+        if let Some(modified) = info.modified {
+            let age = match blame::age(now.duration_since(modified).map_or(0, |d| d.as_secs() as i64)) {
+                age if age == "now" => "just now".to_string(),
+                age => format!("{} ago", age),
+            };
+            rows.push(("Modified", format!("{} ({})", crate::info::format_time(modified), age)));
+        }
+        rows.push(("Encoding", info.encoding.to_string()));
+        rows.push(("Line endings", info.line_endings.to_string()));
+        rows.push(("Lines", buf.line_count().to_string()));
+        rows.push(("Syntax", self.highlighter.backend_name(buf)));
+        rows
+    }
+
+    /// List the commits that touched the current file in a new buffer
+    /// (`:log`).
+    pub fn open_log(&mut self) {
+        let Some(path) = self.buffer().path.clone() else {
+            self.status_message = Some("Log needs a file".to_string());
+            return;
+        };
+        match git::log(&path) {
+            Ok(text) if text.is_empty() => {
+                self.status_message = Some(format!("No commits touch {}", self.buffer().name));
+            }
+            Ok(text) => {
+                let name = format!("[log {}]", self.buffer().name);
+                let mut buf = Buffer::from_bytes(&name, text);
+                buf.log = Some(GitLog { path, selected: 0 });
+                self.open_scratch(buf);
+            }
+            Err(e) => self.status_message = Some(format!("git log: {}", e)),
+        }
+    }
+
+    /// Move the selection of a `:log` buffer by `delta` commits, scrolling
+    /// to keep it in view. False if this is not a log buffer.
+    pub fn step_log(&mut self, delta: isize) -> bool {
+        let last = self.buffer().line_count().saturating_sub(1);
+        let Some(log) = self.buffers[self.active_buffer].log.as_mut() else { return false };
+        log.selected = log.selected.saturating_add_signed(delta).min(last);
+        let selected = log.selected;
+        self.keep_in_view(selected);
+        true
+    }
+
+    /// Open the file as of the selected commit of a `:log` buffer, or with
+    /// `diff` the changes from it to the working copy.
+    pub fn open_log_revision(&mut self, diff: bool) {
+        let buf = self.buffer();
+        let Some(log) = buf.log.clone() else { return };
+        let Some(hash) = buf.get_line(log.selected).and_then(|l| l.split(' ').next().map(str::to_string)) else {
+            return;
+        };
+        let file_name = log.path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let old = match git::show_file(&log.path, &hash) {
+            Ok(old) => old,
+            Err(e) => {
+                self.status_message = Some(format!("git show: {}", e));
+                return;
+            }
+        };
+        let name = format!("{}@{}", file_name, hash);
+        if diff {
+            let new = match std::fs::read(&log.path) {
+                Ok(new) => new,
+                Err(e) => {
+                    self.status_message = Some(format!("Cannot read '{}': {}", log.path.display(), e));
+                    return;
+                }
+            };
+            let (old, new) = (String::from_utf8_lossy(&old), String::from_utf8_lossy(&new));
+            let display = log.path.display().to_string();
+            let labels = (format!("{}@{}", display, hash), display);
+            let diff_name = format!("{} \u{2192} {}", name, file_name);
+            self.open_scratch(Buffer::from_diff_text(&old, &new, (&labels.0, &labels.1), &diff_name));
+            return;
+        }
+        let mut revision = Buffer::from_bytes(&name, old);
+        revision.syntax = self.initial_syntax(Some(&log.path)).or_else(|| {
+            let syntax = self.highlighter.detect_syntax(Some(&log.path));
+            (syntax.name != "Plain Text").then(|| syntax.name.clone())
+        });
+        revision.engine = self.initial_engine(Some(&log.path));
+        self.open_scratch(revision);
+    }
+
+    /// Abandon a running filter job; the previous view stays as it was.
+    pub fn cancel_filter(&mut self) {
+        if self.filter_job.take().is_some() {
+            self.status_message = Some("Filter cancelled".to_string());
+        }
+    }
+
+    /// Drain pending async filter batches. Called each event loop tick.
+    pub fn drain_filter_results(&mut self) {
+        while let Some(job) = &mut self.filter_job {
+            match job.rx.try_recv() {
+                Ok(FilterBatch::Progress { indices, lines_scanned, total_lines }) => {
+                    job.indices.extend(indices);
+                    job.progress = (lines_scanned, total_lines);
+                    self.status_message = Some(format!(
+                        "Filtering\u{2026} {}% ({} lines kept, Esc to cancel)",
+                        job.percent(),
+                        job.indices.len()
+                    ));
+                }
+                Ok(FilterBatch::Done { indices }) => {
+                    let Some(mut job) = self.filter_job.take() else { break };
+                    job.indices.extend(indices);
+                    let total = job.progress.1;
+                    let count = job.indices.len();
+                    let mut filter = LineFilter::new(job.query, job.invert, job.indices, total);
+                    filter.name = job.name;
+                    filter.case = job.case;
+                    self.status_message = Some(format!("Filter: {} ({} lines)", filter.label(), count));
+                    self.filter = Some(filter);
+                    // Take in what was appended while the job ran
+                    self.refresh_filter(true);
+                    if self.tailing() {
+                        self.goto_bottom();
                     } else {
-                        self.status_message = Some(format!(
-                            "Pattern not found: {}", self.search.query_string
-                        ));
+                        self.top_filter_idx = 0;
                     }
                     break;
                 }
@@ -433,23 +2453,568 @@ impl App {
         }
     }
 
-    /// Reload the active buffer from disk and refresh search results.
-    pub fn reload_active_buffer(&mut self) {
-        let mmap_threshold = self.config.general.mmap_threshold;
-        if let Err(e) = self.buffers[self.active_buffer].reload(mmap_threshold) {
-            self.status_message = Some(format!("Reload failed: {}", e));
+    /// Expand the "lines hidden" placeholder at the top of the view, if there is one.
+    pub fn expand_hidden(&mut self) {
+        let Some(filter) = self.filter.as_mut() else { return };
+        if let Some(&ViewRow::Hidden { start, count }) = filter.rows.get(self.top_filter_idx) {
+            filter.expand_gap(start);
+            self.status_message = Some(format!("Showing {} hidden lines", count));
+        }
+    }
+
+    /// Collapse all expanded hidden regions, keeping the top line in view.
+    pub fn collapse_hidden(&mut self) {
+        let top = self.top_view_line();
+        let Some(filter) = self.filter.as_mut() else { return };
+        if filter.collapse_all() {
+            self.top_filter_idx = filter.row_of_line(top);
+            self.status_message = Some("Hidden lines collapsed".to_string());
+        }
+    }
+
+    /// Clear the active filter.
+    pub fn clear_filter(&mut self) {
+        self.filter = None;
+        self.top_filter_idx = 0;
+    }
+
+    /// Write the lines currently in view — the filtered lines, or the whole
+    /// buffer when no filter is active — to `path`.
+    pub fn write_view(&mut self, path: &str) {
+        if path.is_empty() {
+            self.status_message = Some("Usage: :wfilter <path>".to_string());
             return;
         }
-        if self.search.has_pattern() {
-            let buffer = &self.buffers[self.active_buffer];
-            self.search.search_buffer(buffer);
+        let target = crate::paths::expand_path(path);
+        let buf = &self.buffers[self.active_buffer];
+        let result = match &self.filter {
+            Some(filter) => crate::export::write_lines(buf, filter.indices.iter().copied(), &target),
+            None => crate::export::write_lines(buf, 0..buf.line_count(), &target),
+        };
+        self.status_message = Some(match result {
+            Ok(n) => format!("Wrote {} lines to {}", n, target.display()),
+            Err(e) => format!("Write failed: {:#}", e),
+        });
+    }
+
+    /// `:export html|ansi <path>` — write the lines in view (the filtered
+    /// lines, `range` if given, or the whole buffer) with their highlighting.
+    pub fn export(&mut self, range: Option<(usize, usize)>, arg: &str) {
+        let (format, path) = arg.split_once(char::is_whitespace).unwrap_or((arg, ""));
+        let path = path.trim();
+        let Some(format) = ExportFormat::from_name(format).filter(|_| !path.is_empty() && path != "-") else {
+            self.status_message = Some("Usage: :export html|ansi <path>".to_string());
+            return;
+        };
+        let target = crate::paths::expand_path(path);
+        let buf = &self.buffers[self.active_buffer];
+        let lines: Vec<usize> = match (range, &self.filter) {
+            (Some((start, end)), _) => (start..=end).collect(),
+            (None, Some(filter)) => filter.indices.clone(),
+            (None, None) => (0..buf.line_count()).collect(),
+        };
+        let opts = crate::export::StyledExport {
+            highlighter: &self.highlighter,
+            line_numbers: self.show_line_numbers,
+            tab_width: self.tab_width,
+            search: self.search.pattern.as_ref(),
+            notes: Some(&self.notes),
+        };
+        self.status_message = Some(match crate::export::write_styled(buf, lines, &opts, format, &target) {
+            Ok(n) => format!("Exported {} lines to {}", n, target.display()),
+            Err(e) => format!("Export failed: {:#}", e),
+        });
+    }
+
+    /// Write lines `start..=end` to a file (`w` in Visual mode, `:'<,'>w`).
+    pub fn write_range(&mut self, (start, end): (usize, usize), path: &str) {
+        if path.is_empty() {
+            self.status_message = Some("Usage: :<range>w <path>".to_string());
+            return;
+        }
+        let target = crate::paths::expand_path(path);
+        let buf = &self.buffers[self.active_buffer];
+        self.status_message = Some(match crate::export::write_lines(buf, start..=end, &target) {
+            Ok(n) => format!("Wrote {} lines to {}", n, target.display()),
+            Err(e) => format!("Write failed: {:#}", e),
+        });
+    }
+
+    /// Resolve the addresses of an ex range to ordered 0-based lines,
+    /// clamped to the buffer.
+    pub fn resolve_range(&self, (start, end): (Address, Address)) -> Result<(usize, usize), String> {
+        let start = self.resolve_address(start)?;
+        let end = self.resolve_address(end)?;
+        Ok((start.min(end), start.max(end)))
+    }
+
+    fn resolve_address(&self, addr: Address) -> Result<usize, String> {
+        let last = self.buffer().line_count().saturating_sub(1);
+        let base = match addr.base {
+            Base::Line(n) => n.saturating_sub(1),
+            Base::Current => self.top_view_line(),
+            Base::Last => last,
+            Base::Mark('<') | Base::Mark('>') => {
+                let (s, e) = self.last_visual.ok_or("No visual selection")?.lines();
+                if addr.base == Base::Mark('<') { s } else { e }
+            }
+            Base::Mark(c) => *self.marks.get(&c).ok_or_else(|| format!("Mark '{}' not set", c))?,
+        };
+        Ok(base.saturating_add_signed(addr.offset).min(last))
+    }
+
+    /// Ordered (start, end) of the character-wise selection, both inclusive.
+    pub fn char_selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        match self.mode {
+            Mode::VisualChar { anchor: Some(anchor), cursor } => Some((anchor.min(cursor), anchor.max(cursor))),
+            _ => None,
+        }
+    }
+
+    /// Index of the first character of `line` scrolled into view, where a
+    /// character-wise selection starts.
+    pub fn first_visible_char(&self, line: usize) -> usize {
+        let text = self.buffer().get_line(line).unwrap_or("");
+        let col = if self.wrap_lines { 0 } else { self.left_col };
+        let tab_width = self.tab_width as usize;
+        // Not a tab or wide character cut by the left edge
+        let mut index = tabs::char_at(text, col, tab_width);
+        if tabs::column(text, index, tab_width) < col {
+            index += 1;
+        }
+        index.min(text.chars().count().saturating_sub(1))
+    }
+
+    /// Scroll so the character-wise visual cursor stays in view.
+    pub fn reveal_cursor(&mut self, (line, col): (usize, usize)) {
+        self.keep_in_view(line);
+        // The cursor is a character index, `left_col` a screen column
+        let tab_width = self.tab_width as usize;
+        let col = self.buffer().get_line(line).map_or(col, |text| tabs::column(text, col, tab_width));
+        if col < self.left_col {
+            self.left_col = col;
+        } else if col >= self.left_col + self.content_width {
+            self.left_col = col + 1 - self.content_width;
+        }
+    }
+
+    /// Yank the visual selection to the clipboard and return to Normal mode.
+    /// Line selections are laid out according to `format`; character-wise
+    /// selections are always copied as they are.
+    pub fn yank_selection(&mut self, format: YankFormat) {
+        let buf = &self.buffers[self.active_buffer];
+        let (text, what) = match self.mode {
+            Mode::Visual { anchor, cursor } => {
+                let start = anchor.min(cursor);
+                let end = anchor.max(cursor);
+                let text = crate::export::format_lines(buf, (start, end), format);
+                (text, format!("{} lines", end - start + 1))
+            }
+            Mode::VisualChar { .. } => {
+                let Some(((l0, c0), (l1, c1))) = self.char_selection() else {
+                    self.status_message = Some("Press v to start selecting".to_string());
+                    return;
+                };
+                let mut parts = Vec::new();
+                for i in l0..=l1 {
+                    let line = buf.get_line(i).unwrap_or("");
+                    let from = if i == l0 { crate::motion::byte_offset(line, c0) } else { 0 };
+                    let to = if i == l1 { crate::motion::byte_offset(line, c1 + 1) } else { line.len() };
+                    parts.push(&line[from..to.max(from)]);
+                }
+                let text = parts.join("\n");
+                let what = format!("{} characters", text.chars().count());
+                (text, what)
+            }
+            _ => return,
+        };
+        self.copy_to_clipboard(text, &what);
+        self.mode = Mode::Normal;
+    }
+
+    /// Copy the top line of the view (`yy`).
+    pub fn yank_line(&mut self) {
+        let line = self.top_view_line();
+        let text = self.buffer().get_line(line).unwrap_or("").to_string();
+        self.copy_to_clipboard(text, &format!("line {}", line + 1));
+    }
+
+    /// Copy the whole buffer, or only the filtered lines while a filter is
+    /// active (`yG`, `:y%`).
+    pub fn yank_all(&mut self) {
+        let buf = &self.buffers[self.active_buffer];
+        let lines: Vec<&str> = match &self.filter {
+            Some(filter) => filter.indices.iter().filter_map(|&i| buf.get_line(i)).collect(),
+            None => (0..buf.line_count()).filter_map(|i| buf.get_line(i)).collect(),
+        };
+        let what = match self.filter {
+            Some(_) => format!("{} filtered lines", lines.len()),
+            None => format!("{} lines", lines.len()),
+        };
+        let text = lines.join("\n");
+        self.copy_to_clipboard(text, &what);
+    }
+
+    /// Copy lines `start..=end` (`:10,50y`).
+    pub fn yank_range(&mut self, (start, end): (usize, usize)) {
+        let buf = &self.buffers[self.active_buffer];
+        let text = crate::export::format_lines(buf, (start, end), YankFormat::Plain);
+        self.copy_to_clipboard(text, &format!("{} lines", end - start + 1));
+    }
+
+    /// Put `text` on the system clipboard, reporting "Yanked <what>".
+    fn copy_to_clipboard(&mut self, text: String, what: &str) {
+        match arboard::Clipboard::new() {
+            Ok(mut clipboard) => match clipboard.set_text(text) {
+                Ok(_) => {
+                    self.status_message = Some(format!("Yanked {}", what));
+                }
+                Err(e) => {
+                    self.status_message = Some(format!("Clipboard error: {}", e));
+                }
+            },
+            Err(e) => {
+                self.status_message = Some(format!("Clipboard unavailable: {}", e));
+            }
+        }
+    }
+
+    /// Start watching all buffer paths for changes (follow mode), and the
+    /// `--watch-glob` directories for new files.
+    pub fn start_watching(&mut self) {
+        use notify::{RecursiveMode, Watcher};
+
+        let paths: Vec<_> = self.buffers.iter().filter_map(|b| b.path.clone()).collect();
+        if paths.is_empty() && self.watch_globs.is_empty() {
+            return;
+        }
+        let (tx, rx) = std::sync::mpsc::channel();
+        match notify::RecommendedWatcher::new(
+            move |res: notify::Result<notify::Event>| {
+                let _ = tx.send(res);
+            },
+            notify::Config::default(),
+        ) {
+            Ok(mut watcher) => {
+                for path in &paths {
+                    let _ = watcher.watch(path.as_path(), RecursiveMode::NonRecursive);
+                }
+                for (_, dir) in &self.watch_globs {
+                    let _ = watcher.watch(dir, RecursiveMode::Recursive);
+                }
+                self.watcher_rx = Some(rx);
+                self.watcher = Some(watcher);
+            }
+            // Runs while the viewer is up, where stderr would draw over it
+            Err(e) => self.status_message = Some(format!("Cannot watch files for changes: {}", e)),
+        }
+    }
+
+    /// Watch config.toml so edits to it are applied without a restart. The
+    /// directory is watched because editors often save by replacing the file.
+    pub fn watch_config(&mut self) {
+        use notify::{RecursiveMode, Watcher};
+
+        let Some(dir) = Config::config_path().and_then(|p| p.parent().map(|d| d.to_path_buf())) else { return };
+        let (tx, rx) = std::sync::mpsc::channel();
+        let watcher = notify::RecommendedWatcher::new(
+            move |res: notify::Result<notify::Event>| {
+                let _ = tx.send(res);
+            },
+            notify::Config::default(),
+        );
+        if let Ok(mut watcher) = watcher {
+            if watcher.watch(&dir, RecursiveMode::NonRecursive).is_ok() {
+                self.config_watcher = Some((watcher, rx));
+            }
+        }
+    }
+
+    /// Reload the config if config.toml changed since the last call. Called
+    /// each event loop tick.
+    pub fn check_config_changed(&mut self) {
+        use notify::EventKind;
+
+        let Some((_, rx)) = &self.config_watcher else { return };
+        let Some(path) = Config::config_path() else { return };
+        let mut changed = false;
+        while let Ok(ev) = rx.try_recv() {
+            if let Ok(ev) = ev {
+                if matches!(ev.kind, EventKind::Modify(_) | EventKind::Create(_)) && ev.paths.contains(&path) {
+                    changed = true;
+                }
+            }
+        }
+        if changed && path.exists() {
+            self.reload_config();
+        }
+    }
+
+    /// Re-read config.toml and apply it as at startup (command-line flags
+    /// still win): theme, key bindings, colors and the display and search
+    /// settings. Problems are shown in the status bar as on launch.
+    pub fn reload_config(&mut self) {
+        self.dirty = true;
+        let (mut config, mut issues) = Config::load();
+        if let Some(cli) = &self.cli {
+            config.merge_cli(cli);
+        }
+        issues.extend(config_check::check_theme(&config.general.theme, &self.highlighter));
+        self.key_map = KeyMap::build(&config.keys);
+        self.palette = config.colors.palette();
+        self.highlighter.set_limits(config.general.highlight_max_size, config.general.highlight_max_line_len);
+        self.search.wrap_around = config.general.wrap_search;
+        self.search.fancy = config.general.fancy_regex;
+        self.search.max_matches = config.general.max_matches;
+        self.alert_regex = config.alerts.regex();
+        self.config = config;
+        // Every buffer starts over from the new file
+        for idx in 0..self.buffers.len() {
+            self.buffers[idx].syntax = self.initial_syntax(self.buffers[idx].path.as_deref());
+            self.buffers[idx].engine = self.initial_engine(self.buffers[idx].path.as_deref());
+        }
+        for view in &mut self.views {
+            view.settings = None;
+        }
+        self.apply_settings(self.initial_settings(self.active_buffer));
+        self.status_message = Some(match config_check::summary(&issues) {
+            Some(summary) => format!("Config reloaded: {}", summary),
+            None => "Config reloaded".to_string(),
+        });
+    }
+
+    /// Add a newly opened file to the watcher, starting one if needed.
+    fn watch_path(&mut self, path: &std::path::Path) {
+        use notify::{RecursiveMode, Watcher};
+
+        match self.watcher.as_mut() {
+            Some(watcher) => {
+                let _ = watcher.watch(path, RecursiveMode::NonRecursive);
+            }
+            None => self.start_watching(),
+        }
+    }
+
+    /// Drain pending async search result batches. Called each event loop tick.
+    pub fn drain_search_results(&mut self) {
+        while let Some(rx) = &self.search.search_rx {
+            match rx.try_recv() {
+                Ok(SearchBatch::Progress { matches, lines_scanned, total_lines }) => {
+                    self.search.matches.extend(matches);
+                    self.search.progress = (lines_scanned, total_lines);
+                    let eta = match self.search.eta_secs() {
+                        Some(secs) => format!(", ETA {}s", secs),
+                        None => String::new(),
+                    };
+                    self.status_message = Some(format!(
+                        "Searching\u{2026} {}% ({} matches{})",
+                        (self.search.progress_ratio() * 100.0) as u16,
+                        self.search.match_count(),
+                        eta
+                    ));
+                }
+                Ok(SearchBatch::Done { matches, truncated }) => {
+                    self.search.matches.extend(matches);
+                    self.search.truncated = truncated;
+                    self.search.is_searching = false;
+                    self.search.started = None;
+                    self.search.search_rx = None;
+                    self.search.search_appended(&self.buffers[self.active_buffer]);
+                    if self.tailing() {
+                        self.tail();
+                        self.status_message = Some(self.search_summary());
+                        break;
+                    }
+                    self.search.jump_to_line(self.top_view_line());
+                    let hidden = match (&self.filter, self.search.current_match_line()) {
+                        (Some(filter), Some(line)) => !filter.shows_line(line),
+                        _ => false,
+                    };
+                    if hidden {
+                        // Start from the first match the filter leaves visible
+                        let current = self.search.current;
+                        let step = self.search.next_match();
+                        if self.skip_hidden_matches(true, step).is_none() {
+                            self.search.current = current;
+                        }
+                    }
+                    if let Some(line) = self.search.current_match_line() {
+                        self.push_jump();
+                        self.goto_line(line);
+                    }
+                    self.status_message = Some(self.search_summary());
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Status message for a finished search.
+    fn search_summary(&self) -> String {
+        if self.search.truncated {
+            format!("Too many matches for {}: stopped after {}", self.search.query_string, self.search.match_count())
+        } else if self.search.match_count() > 0 {
+            format!(
+                "{}{} ({} matches)",
+                if self.search.forward { "/" } else { "?" },
+                self.search.query_string,
+                self.search.match_count()
+            )
+        } else {
+            format!("Pattern not found: {}", self.search.query_string)
+        }
+    }
+
+    /// Act on the watcher reporting that `paths` changed. In follow mode
+    /// every buffer of those files is reloaded, and a tab other than the
+    /// active one that grew is pointed out in the tab bar; otherwise the
+    /// active file's git changes are refreshed, and with `reload_prompt` on
+    /// the user is asked whether to reload it. New files matching a
+    /// `--watch-glob` pattern are opened, and files that were deleted or
+    /// moved away are noted, in either case.
+    pub fn files_changed(&mut self, paths: &[PathBuf]) {
+        self.open_new_matches(paths);
+        // Events name files as the watcher saw them, which need not be as
+        // they were given on the command line
+        let paths: Vec<PathBuf> = paths.iter().map(|p| canonical(p)).collect();
+        let changed = |buf: &Buffer| buf.path.as_ref().is_some_and(|path| paths.contains(&canonical(path)));
+        self.check_deleted(&paths);
+        if self.mode != Mode::Follow {
+            let buf = self.buffer();
+            if self.mode == Mode::Normal
+                && self.config.general.reload_prompt
+                && buf.stream.is_none()
+                && changed(buf)
+                && buf.changed_on_disk()
+            {
+                self.mode = Mode::ReloadPrompt;
+                self.dirty = true;
+            }
+            self.refresh_git_changes();
+            return;
+        }
+        for idx in 0..self.buffers.len() {
+            if !changed(&self.buffers[idx]) {
+                continue;
+            }
+            let before = self.buffers[idx].complete_lines();
+            if idx == self.active_buffer {
+                self.reload_active_buffer();
+                self.check_alerts(idx, before);
+                continue;
+            }
+            let buf = &mut self.buffers[idx];
+            let size = buf.byte_len();
+            let general = &self.config.general;
+            match buf.reload(general.limits(), general.preprocessor.as_deref()) {
+                Ok(_) => buf.new_data |= buf.byte_len() != size,
+                Err(e) => self.status_message = Some(format!("Reload failed: {}", e)),
+            }
+            self.check_alerts(idx, before);
+        }
+    }
+
+    /// Mark the buffers of `paths` whose file is gone as deleted, keeping
+    /// what they show, and watch the file's directory so that a file created
+    /// there again under the same name (a rotated log) is noticed. Such a
+    /// file is watched in place of the old one and read as any change would
+    /// be.
+    fn check_deleted(&mut self, paths: &[PathBuf]) {
+        use notify::{RecursiveMode, Watcher};
+
+        for buf in &mut self.buffers {
+            let Some(path) = buf.path.clone() else { continue };
+            if buf.stream.is_some() || buf.loading.is_some() || !paths.contains(&canonical(&path)) {
+                continue;
+            }
+            let watch = match (buf.deleted, path.exists()) {
+                (false, false) => {
+                    buf.mark_deleted();
+                    self.status_message = Some(format!("{} was deleted or moved (showing its last contents)", buf.name));
+                    canonical(&path).parent().map(|dir| dir.to_path_buf())
+                }
+                (true, true) => {
+                    // Read by a reload, which clears `deleted`
+                    let hint = if self.mode == Mode::Follow { "" } else { " (R reloads it)" };
+                    self.status_message = Some(format!("{} is back on disk{}", buf.name, hint));
+                    Some(path)
+                }
+                _ => None,
+            };
+            if let (Some(watcher), Some(watch)) = (self.watcher.as_mut(), watch) {
+                let _ = watcher.watch(&watch, RecursiveMode::NonRecursive);
+            }
+        }
+    }
+
+    /// Alert if a line that came into buffer `idx` in follow mode, from line
+    /// `from` on, matches one of the `[alerts]` patterns: show the first such
+    /// line, then ring the bell, flash the status bar and run the command as
+    /// configured. A line is only checked once it is complete.
+    fn check_alerts(&mut self, idx: usize, from: usize) {
+        if self.mode != Mode::Follow {
+            return;
+        }
+        let Some(re) = &self.alert_regex else { return };
+        let buf = &self.buffers[idx];
+        let mut found = (from..buf.complete_lines()).filter(|&i| buf.line_bytes(i).is_some_and(|l| re.is_match(l)));
+        let Some(line) = found.next() else { return };
+        let more = found.count();
+        let text = buf.get_line(line).unwrap_or_default().to_string();
+        let mut message = format!("Alert: {}:{}: {}", buf.name, line + 1, text);
+        if more > 0 {
+            message.push_str(&format!(" (+{} more)", more));
         }
-        if self.mode == Mode::Follow {
-            self.goto_bottom();
+        let alerts = &self.config.alerts;
+        self.bell |= alerts.bell;
+        if alerts.flash {
+            self.alert_flash = Some(Instant::now());
+        }
+        if let Some(cmd) = &alerts.command {
+            let number = (line + 1).to_string();
+            let env = [("SOME_ALERT_FILE", buf.name.as_str()), ("SOME_ALERT_LINE", text.as_str()), ("SOME_ALERT_LINE_NUMBER", number.as_str())];
+            if let Err(e) = crate::shell::spawn(cmd, &env) {
+                message = format!("{:#}", e);
+            }
+        }
+        self.status_message = Some(message);
+        self.dirty = true;
+    }
+
+    /// Read the active file again on demand (`R`), keeping the view where
+    /// it was as far as the new contents allow.
+    pub fn reload(&mut self) {
+        let buf = self.buffer();
+        if buf.path.is_none() || buf.stream.is_some() || buf.loading.is_some() {
+            self.status_message = Some("Not a file that can be reloaded".to_string());
+            return;
+        }
+        if buf.deleted && !buf.path.as_ref().is_some_and(|p| p.exists()) {
+            self.status_message = Some(format!("{} was deleted or moved (showing its last contents)", buf.name));
+            return;
+        }
+        self.status_message = Some(format!("Reloaded {}", buf.name));
+        self.reload_active_buffer();
+        self.top_line = self.top_line.min(self.max_top_line());
+        self.dirty = true;
+    }
+
+    /// Reload the active buffer from disk and refresh search results.
+    pub fn reload_active_buffer(&mut self) {
+        let general = &self.config.general;
+        match self.buffers[self.active_buffer].reload(general.limits(), general.preprocessor.as_deref()) {
+            Ok(grew) => self.contents_changed(grew),
+            Err(e) => {
+                self.status_message = Some(format!("Reload failed: {}", e));
+                return;
+            }
         }
         let buf = &mut self.buffers[self.active_buffer];
         if buf.path.is_some() && !buf.is_diff {
-            buf.load_git_changes();
+            // Blamed again on the next tick if the column is on
+            buf.blame = None;
+            self.refresh_git_changes();
         }
     }
 }
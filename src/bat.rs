@@ -0,0 +1,65 @@
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use syntect::dumps;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxSet, SyntaxSetBuilder};
+
+/// bat's config directory: `$BAT_CONFIG_DIR`, else `bat` in the user's
+/// config directory. Custom themes live in `themes/`, syntaxes in `syntaxes/`.
+pub fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("BAT_CONFIG_DIR")
+        .map(PathBuf::from)
+        .or_else(|| dirs::config_dir().map(|d| d.join("bat")))
+}
+
+/// bat's cache directory, where `bat cache --build` writes `themes.bin`:
+/// `$BAT_CACHE_PATH`, else `bat` in the user's cache directory.
+pub fn cache_dir() -> Option<PathBuf> {
+    std::env::var_os("BAT_CACHE_PATH")
+        .map(PathBuf::from)
+        .or_else(|| dirs::cache_dir().map(|d| d.join("bat")))
+}
+
+/// bat's themes: those in its `themes.bin` cache, then the `.tmTheme`
+/// files in its config directory, which win if the cache is out of date.
+pub fn themes() -> Vec<(String, Theme)> {
+    let mut themes = cached_themes();
+    let dir = config_dir().map(|d| d.join("themes")).filter(|d| d.is_dir());
+    if let Some(set) = dir.and_then(|d| ThemeSet::load_from_folder(d).ok()) {
+        themes.extend(set.themes);
+    }
+    themes
+}
+
+/// `syntaxes` plus the `.sublime-syntax` files in bat's config directory.
+/// bat's own `syntaxes.bin` is not read: it is built for lines that keep
+/// their newline, which `some` strips.
+pub fn add_syntaxes(syntaxes: SyntaxSet) -> SyntaxSet {
+    let Some(dir) = config_dir().map(|d| d.join("syntaxes")).filter(|d| d.is_dir()) else {
+        return syntaxes;
+    };
+    let mut builder: SyntaxSetBuilder = syntaxes.into_builder();
+    match builder.add_from_folder(&dir, false) {
+        Ok(()) => builder.build(),
+        // A syntax that does not parse: keep the defaults
+        Err(_) => SyntaxSet::load_defaults_nonewlines(),
+    }
+}
+
+/// Themes from `themes.bin`, which maps each theme name to that theme's
+/// own serialized dump.
+fn cached_themes() -> Vec<(String, Theme)> {
+    let Some(data) = cache_dir().and_then(|d| std::fs::read(d.join("themes.bin")).ok()) else {
+        return Vec::new();
+    };
+    let Some(dumps) = decode::<BTreeMap<String, Vec<u8>>>(&data) else {
+        return Vec::new();
+    };
+    dumps.into_iter().filter_map(|(name, dump)| Some((name, decode(&dump)?))).collect()
+}
+
+/// bat writes its assets with bincode, some of them zlib-compressed.
+fn decode<T: DeserializeOwned>(data: &[u8]) -> Option<T> {
+    dumps::from_reader(data).or_else(|_| dumps::from_uncompressed_data(data)).ok()
+}
@@ -0,0 +1,204 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::mpsc;
+use unicode_width::UnicodeWidthChar;
+
+use crate::git;
+
+/// Hash `git blame` gives lines that are not committed yet.
+const UNCOMMITTED: &str = "0000000000000000000000000000000000000000";
+
+/// Widest author name shown in the blame column.
+const MAX_AUTHOR_WIDTH: usize = 16;
+
+/// A commit as `git blame --porcelain` describes it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Commit {
+    pub hash: String,
+    pub author: String,
+    /// Author time, in seconds since the epoch
+    pub time: i64,
+}
+
+impl Commit {
+    pub fn is_committed(&self) -> bool {
+        self.hash != UNCOMMITTED
+    }
+
+    pub fn short_hash(&self) -> &str {
+        &self.hash[..self.hash.len().min(7)]
+    }
+}
+
+/// Who last changed each line of a file.
+#[derive(Debug, Default)]
+pub struct Blame {
+    commits: Vec<Commit>,
+    /// Index into `commits` for each line
+    lines: Vec<usize>,
+}
+
+impl Blame {
+    /// The commit that last changed line `i` (0-based).
+    pub fn line(&self, i: usize) -> Option<&Commit> {
+        self.commits.get(*self.lines.get(i)?)
+    }
+
+    /// Width of the author column: the longest name, up to a limit.
+    pub fn author_width(&self) -> usize {
+        self.commits.iter().map(|c| text_width(&c.author)).max().unwrap_or(0).min(MAX_AUTHOR_WIDTH)
+    }
+
+    /// Blame column text for line `i`: short hash, author and age, padded
+    /// to `author_width`.
+    pub fn column(&self, i: usize, now: i64) -> Option<String> {
+        let commit = self.line(i)?;
+        let width = self.author_width();
+        if !commit.is_committed() {
+            return Some(format!("{:7} {:width$} {:>4}", "", fit("Not committed", width), ""));
+        }
+        Some(format!("{} {} {:>4}", commit.short_hash(), fit(&commit.author, width), age(now - commit.time)))
+    }
+}
+
+/// Parse the output of `git blame --porcelain`. Each line starts with a
+/// header (`<hash> <orig line> <final line> [<group size>]`), followed
+/// the first time a commit appears by `author`, `author-time` and other
+/// details, and ends with the line's text after a tab.
+pub fn parse(porcelain: &str) -> Blame {
+    let mut blame = Blame::default();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut current: Option<usize> = None;
+    for line in porcelain.lines() {
+        if line.starts_with('\t') {
+            current = None;
+            continue;
+        }
+        let Some(idx) = current else {
+            // A header
+            let mut fields = line.split(' ');
+            let (Some(hash), Some(_), Some(Ok(final_line))) = (fields.next(), fields.next(), fields.next().map(str::parse::<usize>)) else {
+                continue;
+            };
+            let idx = *index.entry(hash.to_string()).or_insert_with(|| {
+                blame.commits.push(Commit { hash: hash.to_string(), ..Default::default() });
+                blame.commits.len() - 1
+            });
+            if final_line >= 1 {
+                if blame.lines.len() < final_line {
+                    blame.lines.resize(final_line, idx);
+                }
+                blame.lines[final_line - 1] = idx;
+            }
+            current = Some(idx);
+            continue;
+        };
+        let commit = &mut blame.commits[idx];
+        match line.split_once(' ') {
+            Some(("author", name)) => commit.author = name.to_string(),
+            Some(("author-time", time)) => commit.time = time.parse().unwrap_or(0),
+            _ => {}
+        }
+    }
+    blame
+}
+
+/// Run `git blame` on `path` on a background thread.
+pub fn spawn(path: &Path) -> mpsc::Receiver<Result<Blame>> {
+    let (tx, rx) = mpsc::channel();
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        let _ = tx.send(run(&path));
+    });
+    rx
+}
+
+fn run(path: &Path) -> Result<Blame> {
+    let output = git::run(path, &["blame", "--porcelain", "--"], Some(path))?;
+    Ok(parse(&String::from_utf8_lossy(&output)))
+}
+
+/// The commit `hash` with its full message, as `git show` prints it
+/// without the patch.
+pub fn show_commit(path: &Path, hash: &str) -> Result<Vec<u8>> {
+    git::run(path, &["show", "--no-patch", "--no-color", "--format=medium", hash], None)
+}
+
+/// How long ago something `secs` old happened, in at most four columns:
+/// `now`, `5m`, `3h`, `2d`, `6w`, `4mo`, `2y`.
+pub fn age(secs: i64) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    match secs.max(0) {
+        s if s < MINUTE => "now".to_string(),
+        s if s < HOUR => format!("{}m", s / MINUTE),
+        s if s < DAY => format!("{}h", s / HOUR),
+        s if s < 14 * DAY => format!("{}d", s / DAY),
+        s if s < 60 * DAY => format!("{}w", s / (7 * DAY)),
+        s if s < 365 * DAY => format!("{}mo", s / (30 * DAY)),
+        s => format!("{}y", s / (365 * DAY)),
+    }
+}
+
+fn text_width(s: &str) -> usize {
+    s.chars().map(|c| c.width().unwrap_or(0)).sum()
+}
+
+/// `s` cut or padded to exactly `width` columns.
+fn fit(s: &str, width: usize) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > width {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push_str(&" ".repeat(width - used));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let porcelain = "\
+aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa 1 1 2
+author Ada Lovelace
+author-mail <ada@example.com>
+author-time 1000
+summary First
+filename f.txt
+\tone
+aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa 2 2
+\ttwo
+0000000000000000000000000000000000000000 3 3 1
+author Not Committed Yet
+author-time 2000
+filename f.txt
+\tthree
+";
+        let blame = parse(porcelain);
+        assert_eq!(blame.line(0).map(|c| c.author.as_str()), Some("Ada Lovelace"));
+        assert_eq!(blame.line(1).map(|c| c.time), Some(1000));
+        assert_eq!(blame.line(1).map(Commit::short_hash), Some("aaaaaaa"));
+        assert_eq!(blame.line(2).map(Commit::is_committed), Some(false));
+        assert!(blame.line(3).is_none());
+        assert_eq!(blame.column(0, 1000 + 3 * 86400).as_deref(), Some("aaaaaaa Ada Lovelace       3d"));
+    }
+
+    #[test]
+    fn test_age() {
+        assert_eq!(age(5), "now");
+        assert_eq!(age(3 * 3600), "3h");
+        assert_eq!(age(20 * 86400), "2w");
+        assert_eq!(age(100 * 86400), "3mo");
+        assert_eq!(age(800 * 86400), "2y");
+    }
+}
@@ -1,9 +1,22 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use memmap2::Mmap;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use crate::blame::Blame;
+use crate::exec::Exec;
+use crate::git::GitLog;
+use crate::info::{self, FileInfo};
+use crate::shell::Running;
+use crate::syntax::{Engine, HighlightCache};
+use crate::{lines, overstrike, preprocess, sigbus};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum GitChange {
@@ -12,189 +25,493 @@ pub enum GitChange {
     Deleted,
 }
 
+/// The git change of each changed line, by 0-based line index.
+pub type GitChanges = HashMap<usize, GitChange>;
+
+/// Files at least this large on disk are read on a background thread by
+/// `Buffer::open`, so the viewer comes up before they are loaded.
+const BACKGROUND_LOAD_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Sizes past which a buffer stops keeping all of its contents in memory,
+/// from `[general]`.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Files at least this large are mapped instead of read
+    pub mmap_threshold: u64,
+    /// Decompressed contents past this size go to a temporary file
+    pub spill_threshold: u64,
+    /// Streamed contents drop their oldest lines past this many (0 = no limit)
+    pub scrollback_lines: usize,
+    /// Streamed contents drop their oldest lines past this size (0 = no limit)
+    pub scrollback_bytes: u64,
+}
+
+/// How much of a file is read (or indexed, when mapped) between progress
+/// updates.
+const READ_CHUNK: usize = 1 << 20;
+
 /// Holds file contents and provides efficient random line access.
 pub struct Buffer {
-    source: BufferSource,
+    source: Arc<BufferSource>,
     /// Byte offset of the start of each line
-    line_offsets: Vec<usize>,
+    line_offsets: Arc<Vec<usize>>,
     /// Original file path (None for stdin)
     pub path: Option<PathBuf>,
     /// Display name for the status bar
     pub name: String,
     /// Git change indicators per line (0-indexed)
-    pub git_changes: HashMap<usize, GitChange>,
+    pub git_changes: GitChanges,
+    /// Text deleted at each `GitChange::Deleted` marker
+    pub git_deleted: HashMap<usize, Vec<String>>,
+    /// Markers whose deleted lines are shown inline (`zd`)
+    pub shown_deletions: HashSet<usize>,
+    /// `git blame` of the file, once loaded for the blame column
+    pub blame: Option<Blame>,
+    /// Set on `:log` buffers: the file whose history they list
+    pub log: Option<GitLog>,
     /// True when this buffer is a synthetic unified diff
     pub is_diff: bool,
+    /// Syntax to highlight with, overriding detection (`[filetype.*] syntax`)
+    pub syntax: Option<String>,
+    /// `:syntax on`/`off`; None highlights unless the file is too large
+    pub highlight: Option<bool>,
+    /// Backend to highlight with (`[filetype.*] highlighter`)
+    pub engine: Engine,
+    /// Bold/underlined runs per line when backspace overstrikes (man pages)
+    /// were interpreted; None for ordinary text
+    overstrike: Option<HashMap<usize, Vec<overstrike::Run>>>,
+    /// Lines highlighted by the background job, kept across reloads that
+    /// only append
+    pub highlight_cache: RefCell<HighlightCache>,
+    /// Size, encoding and line endings, for `:info`
+    pub info: FileInfo,
+    /// Set while a background thread reads the file (`Buffer::open`)
+    pub loading: Option<Loading>,
+    /// Set while more is still to come from a pipe (`Buffer::streaming`)
+    pub stream: Option<Stream>,
+    /// Set on the `--exec` buffer: the command whose output it shows
+    pub exec: Option<Exec>,
+    /// Set when a tab other than the active one gets new content, for the
+    /// tab bar to point out until it is switched to
+    pub new_data: bool,
+    /// Oldest lines dropped so far to keep a stream within its scrollback
+    /// limits
+    pub dropped: usize,
+    /// Set when the file is no longer at `path` (deleted or renamed); the
+    /// contents last read are kept in memory
+    pub deleted: bool,
+    /// Set on a buffer made from another one line by line (`:sort`,
+    /// `:uniq`, `:jq`, `:cols`): where each of its lines came from
+    pub derived: Option<Derived>,
+    /// Set on a `:!` or `|` tab while the command whose output it streams
+    /// in is running
+    pub command: Option<Running>,
+}
+
+/// The buffer a derived buffer was made from, and the line of it behind
+/// each of the derived buffer's lines.
+#[derive(Debug, Clone)]
+pub struct Derived {
+    pub source_path: Option<PathBuf>,
+    pub source_name: String,
+    pub lines: Vec<usize>,
+}
+
+/// A file being read on a background thread. The buffer stays empty until
+/// `Buffer::poll_loading` swaps in what it read.
+pub struct Loading {
+    /// Bytes of the file read so far (compressed bytes for .gz/.zst/.bz2)
+    read: Arc<AtomicU64>,
+    /// Size of the file on disk
+    size: u64,
+    started: Instant,
+    rx: mpsc::Receiver<Result<Buffer>>,
+}
+
+impl Loading {
+    /// Spinner frame for the time since loading started.
+    pub fn spinner(&self) -> char {
+        const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+        FRAMES[(self.started.elapsed().as_millis() / 100) as usize % FRAMES.len()]
+    }
+
+    pub fn percent(&self) -> u64 {
+        (self.read.load(Ordering::Relaxed) * 100 / self.size.max(1)).min(100)
+    }
+
+    /// Bytes read of the whole file, e.g. `1.2 GiB / 2.0 GiB`.
+    pub fn progress(&self) -> String {
+        let read = self.read.load(Ordering::Relaxed).min(self.size);
+        format!("{} / {}", info::human_size(read), info::human_size(self.size))
+    }
+}
+
+/// A pipe read on a background thread. What it produces is added to the
+/// buffer by `Buffer::poll_stream` as it arrives.
+pub struct Stream {
+    rx: mpsc::Receiver<io::Result<Vec<u8>>>,
+    limits: Limits,
+}
+
+/// Reader over byte slices one after another (`Buffer::concat`).
+struct Pieces<'a> {
+    rest: std::vec::IntoIter<Cow<'a, [u8]>>,
+    current: io::Cursor<Cow<'a, [u8]>>,
+}
+
+impl Read for Pieces<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.current.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            match self.rest.next() {
+                Some(piece) => self.current = io::Cursor::new(piece),
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+/// Reader that adds the bytes it reads to a shared count.
+struct Counted<'a, R> {
+    inner: R,
+    count: &'a AtomicU64,
+}
+
+impl<R: Read> Read for Counted<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
 }
 
 enum BufferSource {
     Mmap(Mmap),
+    /// Map of a temporary file holding decompressed contents
+    Spilled(Mmap),
     Memory(Vec<u8>),
 }
 
+impl Drop for BufferSource {
+    fn drop(&mut self) {
+        if let BufferSource::Mmap(map) = self {
+            sigbus::unregister(map);
+        }
+    }
+}
+
 impl BufferSource {
     fn as_bytes(&self) -> &[u8] {
         match self {
-            BufferSource::Mmap(m) => m.as_ref(),
+            BufferSource::Mmap(m) | BufferSource::Spilled(m) => m.as_ref(),
             BufferSource::Memory(v) => v.as_slice(),
         }
     }
 }
 
+/// Cheap, thread-safe handle to a buffer's bytes and line index.
+///
+/// Cloning shares the underlying storage, so background work (e.g. async search)
+/// can scan the whole buffer without copying it. A later `reload()` swaps in new
+/// storage and leaves existing snapshots pointing at the old contents.
+#[derive(Clone)]
+pub struct TextSnapshot {
+    source: Arc<BufferSource>,
+    line_offsets: Arc<Vec<usize>>,
+}
+
+impl TextSnapshot {
+    pub fn line_count(&self) -> usize {
+        self.line_offsets.len()
+    }
+
+    /// The whole contents.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.source.as_bytes()
+    }
+
+    /// Raw bytes of line `n` without the trailing newline / CRLF.
+    pub fn line_bytes(&self, n: usize) -> Option<&[u8]> {
+        line_slice(self.source.as_bytes(), &self.line_offsets, n)
+    }
+
+    /// Lines `lines` in that order, as text of their own (`:sort`).
+    pub fn select_lines(&self, lines: &[usize]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for &i in lines {
+            out.extend_from_slice(self.line_bytes(i).unwrap_or_default());
+            out.push(b'\n');
+        }
+        out
+    }
+
+    /// Every line passed through `f` on all cores, joined back into text
+    /// with a line for each line (`:jq`, `:cols`).
+    pub fn map_lines(&self, f: impl Fn(&[u8]) -> Vec<u8> + Sync) -> Vec<u8> {
+        use rayon::prelude::*;
+
+        let lines: Vec<Vec<u8>> = (0..self.line_count())
+            .into_par_iter()
+            .map(|i| f(self.line_bytes(i).unwrap_or_default()))
+            .collect();
+        let mut out = Vec::with_capacity(lines.iter().map(|l| l.len() + 1).sum());
+        for line in lines {
+            out.extend_from_slice(&line);
+            out.push(b'\n');
+        }
+        out
+    }
+}
+
+/// Slice line `n` out of `data`, stripping the trailing `\n` and `\r`.
+fn line_slice<'a>(data: &'a [u8], line_offsets: &[usize], n: usize) -> Option<&'a [u8]> {
+    if n >= line_offsets.len() {
+        return None;
+    }
+    let start = line_offsets[n];
+    let end = if n + 1 < line_offsets.len() {
+        line_offsets[n + 1]
+    } else {
+        data.len()
+    };
+
+    let mut slice = &data[start..end];
+    if slice.last() == Some(&b'\n') {
+        slice = &slice[..slice.len() - 1];
+    }
+    if slice.last() == Some(&b'\r') {
+        slice = &slice[..slice.len() - 1];
+    }
+    Some(slice)
+}
+
 // ── Decompression helpers ───────────────────────────────────────────────────
 
-fn decompress_gz(path: &Path) -> Result<Vec<u8>> {
+fn decompress_gz(path: &Path, spill_threshold: u64, read: &AtomicU64) -> Result<BufferSource> {
     let file = File::open(path).with_context(|| format!("Cannot open '{}'", path.display()))?;
-    let mut decoder = flate2::read::GzDecoder::new(file);
-    let mut out = Vec::new();
-    decoder.read_to_end(&mut out).with_context(|| format!("Failed to decompress '{}'", path.display()))?;
-    Ok(out)
+    let decoder = flate2::read::GzDecoder::new(Counted { inner: file, count: read });
+    decompress(decoder, spill_threshold).with_context(|| format!("Failed to decompress '{}'", path.display()))
 }
 
-fn decompress_zst(path: &Path) -> Result<Vec<u8>> {
+fn decompress_zst(path: &Path, spill_threshold: u64, read: &AtomicU64) -> Result<BufferSource> {
     let file = File::open(path).with_context(|| format!("Cannot open '{}'", path.display()))?;
-    let mut decoder = zstd::stream::read::Decoder::new(file)
+    let decoder = zstd::stream::read::Decoder::new(Counted { inner: file, count: read })
         .with_context(|| format!("Failed to init zstd decoder for '{}'", path.display()))?;
-    let mut out = Vec::new();
-    decoder.read_to_end(&mut out).with_context(|| format!("Failed to decompress '{}'", path.display()))?;
-    Ok(out)
+    decompress(decoder, spill_threshold).with_context(|| format!("Failed to decompress '{}'", path.display()))
 }
 
-fn decompress_bz2(path: &Path) -> Result<Vec<u8>> {
+fn decompress_bz2(path: &Path, spill_threshold: u64, read: &AtomicU64) -> Result<BufferSource> {
     let file = File::open(path).with_context(|| format!("Cannot open '{}'", path.display()))?;
-    let mut decoder = bzip2::read::BzDecoder::new(file);
-    let mut out = Vec::new();
-    decoder.read_to_end(&mut out).with_context(|| format!("Failed to decompress '{}'", path.display()))?;
-    Ok(out)
+    let decoder = bzip2::read::BzDecoder::new(Counted { inner: file, count: read });
+    decompress(decoder, spill_threshold).with_context(|| format!("Failed to decompress '{}'", path.display()))
 }
 
-fn decompress_if_needed(path: &Path) -> Result<Option<Vec<u8>>> {
+/// Read `decoder` to the end: into memory up to `spill_threshold` bytes,
+/// and past that into a temporary file that is then mapped, so a file that
+/// decompresses to more than fits in memory can still be viewed. Also
+/// used for the copy `Buffer::concat` makes.
+fn decompress(mut decoder: impl Read, spill_threshold: u64) -> io::Result<BufferSource> {
+    let mut data = Vec::new();
+    (&mut decoder).take(spill_threshold).read_to_end(&mut data)?;
+    if (data.len() as u64) < spill_threshold {
+        return Ok(BufferSource::Memory(data));
+    }
+    let file = spill_file()?;
+    let mut out = BufWriter::with_capacity(READ_CHUNK, &file);
+    out.write_all(&data)?;
+    drop(data);
+    io::copy(&mut decoder, &mut out)?;
+    out.flush()?;
+    drop(out);
+    let mmap = unsafe { Mmap::map(&file) }?;
+    Ok(BufferSource::Spilled(mmap))
+}
+
+/// A new temporary file, already unlinked so its space is freed when the
+/// last handle or mapping of it goes.
+fn spill_file() -> io::Result<File> {
+    static COUNT: AtomicU64 = AtomicU64::new(0);
+    let n = COUNT.fetch_add(1, Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!("some-{}-{}.tmp", std::process::id(), n));
+    let file = std::fs::OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+    let _ = std::fs::remove_file(&path);
+    Ok(file)
+}
+
+/// The contents of `path` when the preprocessor produced any, or when it
+/// is compressed.
+fn preprocess_or_decompress(path: &Path, preprocessor: Option<&str>, spill_threshold: u64, read: &AtomicU64) -> Result<Option<BufferSource>> {
+    if let Some(spec) = preprocessor {
+        if let Some(data) = preprocess::run(spec, path)? {
+            return Ok(Some(BufferSource::Memory(data)));
+        }
+    }
+    decompress_if_needed(path, spill_threshold, read)
+}
+
+fn decompress_if_needed(path: &Path, spill_threshold: u64, read: &AtomicU64) -> Result<Option<BufferSource>> {
     match path.extension().and_then(|e| e.to_str()) {
-        Some("gz")              => Ok(Some(decompress_gz(path)?)),
-        Some("zst") | Some("zstd") => Ok(Some(decompress_zst(path)?)),
-        Some("bz2")             => Ok(Some(decompress_bz2(path)?)),
+        Some("gz")              => Ok(Some(decompress_gz(path, spill_threshold, read)?)),
+        Some("zst") | Some("zstd") => Ok(Some(decompress_zst(path, spill_threshold, read)?)),
+        Some("bz2")             => Ok(Some(decompress_bz2(path, spill_threshold, read)?)),
         _                       => Ok(None),
     }
 }
 
-// ── Git diff parsing ────────────────────────────────────────────────────────
-
-fn parse_git_changes(stdout: &[u8]) -> HashMap<usize, GitChange> {
-    let mut changes: HashMap<usize, GitChange> = HashMap::new();
-    let text = match std::str::from_utf8(stdout) {
-        Ok(t) => t,
-        Err(_) => return changes,
-    };
+/// The contents of `path`: preprocessed or decompressed (see `decompress`),
+/// mapped if it is at least `limits.mmap_threshold` bytes, else read. Bytes read
+/// from disk are added to `read` as they come in.
+fn read_source(path: &Path, limits: Limits, preprocessor: Option<&str>, read: &AtomicU64) -> Result<BufferSource> {
+    if let Some(source) = preprocess_or_decompress(path, preprocessor, limits.spill_threshold, read)? {
+        return Ok(source);
+    }
 
-    for line in text.lines() {
-        if !line.starts_with("@@") {
-            continue;
-        }
-        // Parse @@ -old[,count] +new[,count] @@
-        // Example: @@ -10,5 +10,7 @@
-        let rest = &line[2..];
-        let end = rest.find("@@").unwrap_or(rest.len());
-        let hunk_header = rest[..end].trim();
-
-        // Split into old/new parts
-        let parts: Vec<&str> = hunk_header.split_whitespace().collect();
-        if parts.len() < 2 {
-            continue;
-        }
-        let new_part = parts.iter().find(|p| p.starts_with('+'));
-        if let Some(new_range) = new_part {
-            let range_str = &new_range[1..]; // strip leading '+'
-            let (start, count) = parse_range(range_str);
-            if count == 0 {
-                // Deletion at line `start`
-                if start > 0 {
-                    changes.entry(start - 1).or_insert(GitChange::Deleted);
-                }
-            } else {
-                let old_part = parts.iter().find(|p| p.starts_with('-'));
-                let (_, old_count) = old_part
-                    .map(|p| parse_range(&p[1..]))
-                    .unwrap_or((0, 0));
-
-                let tag = if old_count == 0 {
-                    GitChange::Added
-                } else {
-                    GitChange::Modified
-                };
-                for line_idx in start..(start + count) {
-                    if line_idx > 0 {
-                        changes.entry(line_idx - 1).or_insert(tag);
-                    }
-                }
-            }
-        }
+    let metadata = std::fs::metadata(path)
+        .with_context(|| format!("Cannot stat '{}'", path.display()))?;
+    let file_size = metadata.len();
+    let mut file = File::open(path)
+        .with_context(|| format!("Cannot open '{}'", path.display()))?;
+    if file_size >= limits.mmap_threshold {
+        let mmap = unsafe { Mmap::map(&file) }
+            .with_context(|| format!("Cannot mmap '{}'", path.display()))?;
+        // Another process may truncate the file under the map
+        sigbus::register(&mmap);
+        return Ok(BufferSource::Mmap(mmap));
     }
-    changes
+    let mut contents = Vec::with_capacity(file_size as usize);
+    while (&mut file).take(READ_CHUNK as u64).read_to_end(&mut contents)? > 0 {
+        read.store(contents.len() as u64, Ordering::Relaxed);
+    }
+    Ok(BufferSource::Memory(contents))
 }
 
-fn parse_range(s: &str) -> (usize, usize) {
-    if let Some(comma) = s.find(',') {
-        let start = s[..comma].parse().unwrap_or(1);
-        let count = s[comma + 1..].parse().unwrap_or(1);
-        (start, count)
-    } else {
-        let start = s.parse().unwrap_or(1);
-        (start, 1)
+/// True for FIFOs and character devices, which are read as they are
+/// written to rather than up to an end that may never come.
+fn is_stream(metadata: &std::fs::Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        let file_type = metadata.file_type();
+        file_type.is_fifo() || file_type.is_char_device()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        false
     }
 }
 
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
 // ── Buffer impl ─────────────────────────────────────────────────────────────
 
 impl Buffer {
     /// Load a file into a buffer. Uses mmap for files above the threshold.
-    /// Transparently decompresses .gz/.zst/.bz2 files.
-    pub fn from_file(path: &Path, mmap_threshold: u64) -> Result<Self> {
-        let name = path
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| path.display().to_string());
-
-        // Attempt transparent decompression
-        if let Some(data) = decompress_if_needed(path)? {
-            let line_offsets = Self::index_lines(&data);
-            return Ok(Self {
-                source: BufferSource::Memory(data),
-                line_offsets,
-                path: Some(path.to_path_buf()),
-                name,
-                git_changes: HashMap::new(),
-                is_diff: false,
-            });
-        }
+    /// Transparently decompresses .gz/.zst/.bz2 files, after giving the
+    /// input `preprocessor` (LESSOPEN) a chance to replace the contents.
+    pub fn from_file(path: &Path, limits: Limits, preprocessor: Option<&str>) -> Result<Self> {
+        Self::load(path, limits, preprocessor, &AtomicU64::new(0))
+    }
 
+    /// Like `from_file`, but a file of at least `BACKGROUND_LOAD_SIZE` bytes
+    /// is read on a background thread: the buffer starts out empty with
+    /// `loading` set, and `poll_loading` fills it in.
+    /// FIFOs and character devices are streamed (see `streaming`) instead.
+    pub fn open(path: &Path, limits: Limits, preprocessor: Option<&str>) -> Result<Self> {
         let metadata = std::fs::metadata(path)
             .with_context(|| format!("Cannot stat '{}'", path.display()))?;
-        let file_size = metadata.len();
-
-        let source = if file_size >= mmap_threshold {
-            let file = File::open(path)
-                .with_context(|| format!("Cannot open '{}'", path.display()))?;
-            let mmap = unsafe { Mmap::map(&file) }
-                .with_context(|| format!("Cannot mmap '{}'", path.display()))?;
-            BufferSource::Mmap(mmap)
-        } else {
-            let mut file = File::open(path)
-                .with_context(|| format!("Cannot open '{}'", path.display()))?;
-            let mut contents = Vec::with_capacity(file_size as usize);
-            file.read_to_end(&mut contents)?;
-            BufferSource::Memory(contents)
-        };
-
-        let line_offsets = Self::index_lines(source.as_bytes());
+        if is_stream(&metadata) {
+            let owned = path.to_path_buf();
+            let mut buf = Self::streaming(&file_name(path), limits, move || File::open(owned));
+            buf.path = Some(path.to_path_buf());
+            return Ok(buf);
+        }
+        let size = metadata.len();
+        if size < BACKGROUND_LOAD_SIZE {
+            return Self::from_file(path, limits, preprocessor);
+        }
+        let read = Arc::new(AtomicU64::new(0));
+        let (tx, rx) = mpsc::channel();
+        let (owned, count, preprocessor) = (path.to_path_buf(), Arc::clone(&read), preprocessor.map(str::to_string));
+        std::thread::spawn(move || {
+            let _ = tx.send(Self::load(&owned, limits, preprocessor.as_deref(), &count));
+        });
+        let mut buf = Self::from_bytes(&file_name(path), Vec::new());
+        buf.path = Some(path.to_path_buf());
+        buf.loading = Some(Loading { read, size, started: Instant::now(), rx });
+        Ok(buf)
+    }
 
-        Ok(Self {
-            source,
-            line_offsets,
+    /// `from_file`, adding the bytes read from disk to `read` as it goes.
+    /// A mapped file counts as read once its lines are indexed.
+    fn load(path: &Path, limits: Limits, preprocessor: Option<&str>, read: &AtomicU64) -> Result<Self> {
+        let source = read_source(path, limits, preprocessor, read)?;
+        let line_offsets = match &source {
+            BufferSource::Mmap(mmap) => lines::index(mmap, read),
+            BufferSource::Spilled(data) => Self::index_lines(data),
+            BufferSource::Memory(data) => Self::index_lines(data),
+        };
+        let mut buf = Self {
+            source: Arc::new(source),
+            line_offsets: Arc::new(line_offsets),
             path: Some(path.to_path_buf()),
-            name,
+            name: file_name(path),
             git_changes: HashMap::new(),
+            git_deleted: HashMap::new(),
+            shown_deletions: HashSet::new(),
+            blame: None,
+            log: None,
             is_diff: false,
-        })
+            syntax: None,
+            highlight: None,
+            engine: Engine::default(),
+            overstrike: None,
+            highlight_cache: Default::default(),
+            info: FileInfo::default(),
+            loading: None,
+            stream: None,
+            exec: None,
+            new_data: false,
+            dropped: 0,
+            deleted: false,
+            derived: None,
+            command: None,
+        };
+        buf.interpret_overstrike(false);
+        buf.detect_info();
+        Ok(buf)
+    }
+
+    /// Swap in the contents read by the background thread once it is done.
+    /// None while it is still reading (or the buffer was not loading), else
+    /// whether the file could be read.
+    pub fn poll_loading(&mut self) -> Option<Result<()>> {
+        let loaded = match self.loading.as_ref()?.rx.try_recv() {
+            Ok(loaded) => loaded,
+            Err(mpsc::TryRecvError::Empty) => return None,
+            Err(mpsc::TryRecvError::Disconnected) => Err(anyhow!("Loading '{}' failed", self.name)),
+        };
+        self.loading = None;
+        let loaded = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => return Some(Err(e)),
+        };
+        // --man set on the empty buffer still applies
+        let man = self.is_man();
+        self.highlight_cache.get_mut().truncate(0);
+        self.source = loaded.source;
+        self.line_offsets = loaded.line_offsets;
+        self.overstrike = loaded.overstrike;
+        self.info = loaded.info;
+        self.interpret_overstrike(man);
+        Some(Ok(()))
     }
 
     /// Load from stdin into an in-memory buffer.
@@ -203,15 +520,208 @@ impl Buffer {
         std::io::stdin()
             .read_to_end(&mut contents)
             .context("Failed to read from stdin")?;
-        let line_offsets = Self::index_lines(&contents);
-        Ok(Self {
-            source: BufferSource::Memory(contents),
-            line_offsets,
+        Ok(Self::from_bytes("[stdin]", contents))
+    }
+
+    /// An empty buffer called `name` that the reader returned by `open` (a
+    /// pipe, say) is read into on a background thread; `poll_stream` adds
+    /// what it has produced so far, so output that never ends
+    /// (`journalctl -f`) can be paged, keeping within the scrollback
+    /// `limits`. `open` runs on that thread too, as opening a FIFO waits for
+    /// a writer.
+    pub fn streaming<R: Read>(name: &str, limits: Limits, open: impl FnOnce() -> io::Result<R> + Send + 'static) -> Self {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut reader = match open() {
+                Ok(reader) => reader,
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    return;
+                }
+            };
+            let mut chunk = vec![0; READ_CHUNK];
+            loop {
+                let read = match reader.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(n) => Ok(chunk[..n].to_vec()),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => Err(e),
+                };
+                let failed = read.is_err();
+                if tx.send(read).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        let mut buf = Self::from_bytes(name, Vec::new());
+        buf.stream = Some(Stream { rx, limits });
+        buf
+    }
+
+    /// Add what the stream has produced since the last call. True when the
+    /// buffer changed or the stream ended; a read error ends it too.
+    pub fn poll_stream(&mut self) -> Result<bool> {
+        let Some(stream) = &self.stream else { return Ok(false) };
+        let mut data = Vec::new();
+        let mut end = None;
+        loop {
+            match stream.rx.try_recv() {
+                Ok(Ok(chunk)) => data.extend_from_slice(&chunk),
+                Ok(Err(e)) => {
+                    end = Some(Err(e));
+                    break;
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    end = Some(Ok(()));
+                    break;
+                }
+            }
+        }
+        let limits = stream.limits;
+        self.append(&data);
+        self.drop_oldest(limits);
+        let Some(end) = end else { return Ok(!data.is_empty()) };
+        self.stream = None;
+        // Overstrikes are only interpreted once the whole text is in
+        let man = self.is_man();
+        self.overstrike = None;
+        self.interpret_overstrike(man);
+        if self.is_man() {
+            self.highlight_cache.get_mut().truncate(0);
+        }
+        end.with_context(|| format!("Failed to read {}", self.name))?;
+        Ok(true)
+    }
+
+    /// Wait up to `timeout` for the stream to end, adding what it produces.
+    /// True if it ended.
+    pub fn wait_stream(&mut self, timeout: Duration) -> Result<bool> {
+        let started = Instant::now();
+        while self.stream.is_some() && started.elapsed() < timeout {
+            self.poll_stream()?;
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        self.poll_stream()?;
+        Ok(self.stream.is_none())
+    }
+
+    /// Add `bytes` to the end of the buffer, indexing only the new lines.
+    /// The lines already highlighted are kept until highlighted again.
+    fn append(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        let from = self.byte_len();
+        let mut data = if let Some(BufferSource::Memory(data)) = Arc::get_mut(&mut self.source) {
+            std::mem::take(data)
+        } else {
+            // Shared with a snapshot that is being searched
+            self.source.as_bytes().to_vec()
+        };
+        data.extend_from_slice(bytes);
+        lines::extend(Arc::make_mut(&mut self.line_offsets), &data, from);
+        self.source = Arc::new(BufferSource::Memory(data));
+        let kept = self.line_count();
+        self.highlight_cache.get_mut().truncate(kept);
+        self.detect_info();
+    }
+
+    /// Drop the oldest lines once the buffer is past the scrollback `limits`:
+    /// down to nine tenths of them, so that the rest is moved now and then
+    /// rather than on every append. The last line is always kept.
+    fn drop_oldest(&mut self, limits: Limits) {
+        let count = self.line_count();
+        let len = self.byte_len() as u64;
+        let mut keep_from = 0;
+        // Nine tenths, rounded down, and at least one less than the limit
+        let nine_tenths = |n: usize| n - n.div_ceil(10);
+        if limits.scrollback_lines > 0 && count > limits.scrollback_lines {
+            keep_from = count - nine_tenths(limits.scrollback_lines);
+        }
+        if limits.scrollback_bytes > 0 && len > limits.scrollback_bytes {
+            let cut = (len - nine_tenths(limits.scrollback_bytes as usize) as u64) as usize;
+            keep_from = keep_from.max(self.line_offsets.partition_point(|&start| start < cut));
+        }
+        let keep_from = keep_from.min(count.saturating_sub(1));
+        if keep_from == 0 {
+            return;
+        }
+        let cut = self.line_offsets[keep_from];
+        let mut data = if let Some(BufferSource::Memory(data)) = Arc::get_mut(&mut self.source) {
+            std::mem::take(data)
+        } else {
+            self.source.as_bytes().to_vec()
+        };
+        data.drain(..cut);
+        let offsets = Arc::make_mut(&mut self.line_offsets);
+        offsets.drain(..keep_from);
+        offsets.iter_mut().for_each(|start| *start -= cut);
+        self.source = Arc::new(BufferSource::Memory(data));
+        self.highlight_cache.get_mut().truncate(0);
+        self.dropped += keep_from;
+        self.detect_info();
+    }
+
+    /// Create an in-memory scratch buffer with no backing file.
+    /// Interprets backspace overstrikes, so `:!man ls` reads as a man page.
+    pub fn from_bytes(name: &str, data: Vec<u8>) -> Self {
+        Self::from_source(name, BufferSource::Memory(data))
+    }
+
+    /// Create a scratch buffer over contents read already.
+    fn from_source(name: &str, source: BufferSource) -> Self {
+        let line_offsets = Self::index_lines(source.as_bytes());
+        let mut buf = Self {
+            source: Arc::new(source),
+            line_offsets: Arc::new(line_offsets),
             path: None,
-            name: "[stdin]".to_string(),
+            name: name.to_string(),
             git_changes: HashMap::new(),
+            git_deleted: HashMap::new(),
+            shown_deletions: HashSet::new(),
+            blame: None,
+            log: None,
             is_diff: false,
-        })
+            syntax: None,
+            highlight: None,
+            engine: Engine::default(),
+            overstrike: None,
+            highlight_cache: Default::default(),
+            info: FileInfo::default(),
+            loading: None,
+            stream: None,
+            exec: None,
+            new_data: false,
+            dropped: 0,
+            deleted: false,
+            derived: None,
+            command: None,
+        };
+        buf.interpret_overstrike(false);
+        buf.detect_info();
+        buf
+    }
+
+    /// Create a scratch buffer showing `parts` one after another, each
+    /// headed by a `==> name <==` line as `head` and `tail` do for several
+    /// files (`--cat`, `:join`). Like decompressed contents, past
+    /// `limits.spill_threshold` bytes the copy goes to a mapped temporary
+    /// file rather than memory, so joining large logs stays cheap on RAM.
+    pub fn concat(name: &str, parts: &[&Buffer], limits: Limits) -> Result<Self> {
+        let mut pieces: Vec<Cow<[u8]>> = Vec::with_capacity(parts.len() * 3);
+        for (i, part) in parts.iter().enumerate() {
+            let newline = if i > 0 { "\n" } else { "" };
+            pieces.push(Cow::Owned(format!("{}==> {} <==\n", newline, part.name).into_bytes()));
+            let bytes = part.source.as_bytes();
+            pieces.push(Cow::Borrowed(bytes));
+            if !bytes.is_empty() && !bytes.ends_with(b"\n") {
+                pieces.push(Cow::Borrowed(b"\n"));
+            }
+        }
+        let reader = Pieces { rest: pieces.into_iter(), current: io::Cursor::new(Cow::Borrowed(b"")) };
+        let source = decompress(reader, limits.spill_threshold).context("Cannot join the files")?;
+        Ok(Self::from_source(name, source))
     }
 
     /// Create a synthetic unified diff buffer comparing two files.
@@ -220,10 +730,21 @@ impl Buffer {
             .with_context(|| format!("Cannot read '{}'", file_a.display()))?;
         let text_b = std::fs::read_to_string(file_b)
             .with_context(|| format!("Cannot read '{}'", file_b.display()))?;
+        let name = format!(
+            "{} → {}",
+            file_a.file_name().unwrap_or_default().to_string_lossy(),
+            file_b.file_name().unwrap_or_default().to_string_lossy()
+        );
+        let labels = (file_a.display().to_string(), file_b.display().to_string());
+        Ok(Self::from_diff_text(&text_a, &text_b, (&labels.0, &labels.1), &name))
+    }
 
-        let diff = similar::TextDiff::from_lines(&text_a, &text_b);
+    /// Create a unified diff buffer called `name` from `text_a` to
+    /// `text_b`, with `labels` in its `---`/`+++` header.
+    pub fn from_diff_text(text_a: &str, text_b: &str, labels: (&str, &str), name: &str) -> Self {
+        let diff = similar::TextDiff::from_lines(text_a, text_b);
 
-        let mut out = format!("--- {}\n+++ {}\n", file_a.display(), file_b.display());
+        let mut out = format!("--- {}\n+++ {}\n", labels.0, labels.1);
         for group in diff.grouped_ops(3) {
             // Emit @@ header
             let first_op = &group[0];
@@ -249,34 +770,40 @@ impl Buffer {
 
         let data = out.into_bytes();
         let line_offsets = Self::index_lines(&data);
-        let name = format!(
-            "{} → {}",
-            file_a.file_name().unwrap_or_default().to_string_lossy(),
-            file_b.file_name().unwrap_or_default().to_string_lossy()
-        );
 
-        Ok(Self {
-            source: BufferSource::Memory(data),
-            line_offsets,
+        let mut buf = Self {
+            source: Arc::new(BufferSource::Memory(data)),
+            line_offsets: Arc::new(line_offsets),
             path: None,
-            name,
+            name: name.to_string(),
             git_changes: HashMap::new(),
+            git_deleted: HashMap::new(),
+            shown_deletions: HashSet::new(),
+            blame: None,
+            log: None,
             is_diff: true,
-        })
+            syntax: None,
+            highlight: None,
+            engine: Engine::default(),
+            overstrike: None,
+            highlight_cache: Default::default(),
+            info: FileInfo::default(),
+            loading: None,
+            stream: None,
+            exec: None,
+            new_data: false,
+            dropped: 0,
+            deleted: false,
+            derived: None,
+            command: None,
+        };
+        buf.detect_info();
+        buf
     }
 
     /// Build an index of byte offsets for the start of each line.
     fn index_lines(data: &[u8]) -> Vec<usize> {
-        if data.is_empty() {
-            return vec![];
-        }
-        let mut offsets = vec![0usize];
-        for (i, &byte) in data.iter().enumerate() {
-            if byte == b'\n' && i + 1 < data.len() {
-                offsets.push(i + 1);
-            }
-        }
-        offsets
+        lines::index(data, &AtomicU64::new(0))
     }
 
     /// Total number of lines in the buffer.
@@ -284,6 +811,17 @@ impl Buffer {
         self.line_offsets.len()
     }
 
+    /// Lines that end in a newline: all of them but a last line still being
+    /// written.
+    pub fn complete_lines(&self) -> usize {
+        let count = self.line_count();
+        if self.source.as_bytes().ends_with(b"\n") {
+            count
+        } else {
+            count.saturating_sub(1)
+        }
+    }
+
     /// Number of hex dump lines (16 bytes per row).
     pub fn hex_line_count(&self) -> usize {
         let len = self.source.as_bytes().len();
@@ -299,6 +837,32 @@ impl Buffer {
         }
     }
 
+    /// Size of the contents in bytes.
+    pub fn byte_len(&self) -> usize {
+        self.source.as_bytes().len()
+    }
+
+    /// Byte offset where display row `row` starts: its line, or its hex
+    /// dump row for binary files. Rows past the end give the length.
+    pub fn byte_of_row(&self, row: usize) -> usize {
+        let offset = if self.is_binary() {
+            row.saturating_mul(16)
+        } else {
+            self.line_offsets.get(row).copied().unwrap_or(usize::MAX)
+        };
+        offset.min(self.byte_len())
+    }
+
+    /// Display row containing byte `offset`: the line it falls on, or the
+    /// hex dump row for binary files. Offsets past the end give the last row.
+    pub fn row_of_byte(&self, offset: usize) -> usize {
+        let last = self.display_line_count().saturating_sub(1);
+        if self.is_binary() {
+            return (offset / 16).min(last);
+        }
+        self.line_offsets.partition_point(|&start| start <= offset).saturating_sub(1).min(last)
+    }
+
     /// Render row `n` of a hex dump (16 bytes per row).
     pub fn hex_line(&self, n: usize) -> String {
         let data = self.source.as_bytes();
@@ -329,68 +893,144 @@ impl Buffer {
 
     /// Get the text content of line `n` (0-indexed), without trailing newline.
     pub fn get_line(&self, n: usize) -> Option<&str> {
-        if n >= self.line_offsets.len() {
-            return None;
+        let slice = line_slice(self.source.as_bytes(), &self.line_offsets, n)?;
+        std::str::from_utf8(slice).ok()
+    }
+
+    /// The first `max` bytes of line `n`, cut back to a character boundary,
+    /// and short of the first byte that is not UTF-8. Byte offsets into the
+    /// line, such as search matches, are offsets into this text as far as it
+    /// goes. Only those bytes are checked, so this is cheap on a line of any
+    /// length.
+    pub fn line_prefix(&self, n: usize, max: usize) -> Option<&str> {
+        let slice = line_slice(self.source.as_bytes(), &self.line_offsets, n)?;
+        let prefix = &slice[..slice.len().min(max)];
+        match std::str::from_utf8(prefix) {
+            Ok(text) => Some(text),
+            Err(e) => std::str::from_utf8(&prefix[..e.valid_up_to()]).ok(),
         }
-        let data = self.source.as_bytes();
-        let start = self.line_offsets[n];
-        let end = if n + 1 < self.line_offsets.len() {
-            self.line_offsets[n + 1]
-        } else {
-            data.len()
-        };
+    }
+
+    /// Raw bytes of line `n` (0-indexed), without trailing newline. Unlike
+    /// `get_line`, this also returns lines that are not valid UTF-8.
+    pub fn line_bytes(&self, n: usize) -> Option<&[u8]> {
+        line_slice(self.source.as_bytes(), &self.line_offsets, n)
+    }
+
+    /// Share the buffer contents with a background thread without copying them.
+    pub fn snapshot(&self) -> TextSnapshot {
+        TextSnapshot {
+            source: Arc::clone(&self.source),
+            line_offsets: Arc::clone(&self.line_offsets),
+        }
+    }
 
-        let mut slice = &data[start..end];
-        if slice.last() == Some(&b'\n') {
-            slice = &slice[..slice.len() - 1];
+    /// Reload the buffer from disk (no-op for stdin and pipes, and while the
+    /// file is still loading). Re-runs the preprocessor and re-decompresses if needed.
+    /// Returns false when the contents changed other than by growing.
+    pub fn reload(&mut self, limits: Limits, preprocessor: Option<&str>) -> anyhow::Result<bool> {
+        let path = match &self.path {
+            Some(p) if self.loading.is_none() && self.stream.is_none() => p.clone(),
+            _ => return Ok(true),
+        };
+        // Nothing to read until a file appears at the path again
+        if self.deleted && !path.exists() {
+            return Ok(true);
         }
-        if slice.last() == Some(&b'\r') {
-            slice = &slice[..slice.len() - 1];
+        self.deleted = false;
+        // A pipe cannot be read again
+        if std::fs::metadata(&path).is_ok_and(|m| is_stream(&m)) {
+            return Ok(true);
         }
 
-        std::str::from_utf8(slice).ok()
+        let man = self.is_man();
+        let source = read_source(&path, limits, preprocessor, &AtomicU64::new(0))?;
+        Ok(self.set_source(source, man))
     }
 
-    /// Clone all lines into owned strings (for async search snapshot).
-    pub fn text_snapshot(&self) -> Vec<String> {
-        (0..self.line_count())
-            .filter_map(|i| self.get_line(i).map(str::to_string))
-            .collect()
+    /// Replace the contents with `data`, as a reload would.
+    pub fn set_contents(&mut self, data: Vec<u8>) {
+        let man = self.is_man();
+        self.set_source(BufferSource::Memory(data), man);
     }
 
-    /// Reload the buffer from disk (no-op for stdin). Re-decompresses if needed.
-    pub fn reload(&mut self, mmap_threshold: u64) -> anyhow::Result<()> {
-        let path = match &self.path {
-            Some(p) => p.clone(),
-            None => return Ok(()),
+    /// Swap in reloaded contents. If the file only grew, the highlighted
+    /// lines that were already there are kept until highlighted again.
+    /// Returns whether it only grew.
+    fn set_source(&mut self, source: BufferSource, man: bool) -> bool {
+        let grew = match &*self.source {
+            BufferSource::Memory(old) => source.as_bytes().starts_with(old),
+            BufferSource::Spilled(old) => source.as_bytes().starts_with(old),
+            // The old map already shows the file as it is now
+            BufferSource::Mmap(old) => source.as_bytes().len() >= old.len(),
         };
+        let kept = if grew { self.line_count() } else { 0 };
+        self.highlight_cache.get_mut().truncate(kept);
+        self.line_offsets = Arc::new(Self::index_lines(source.as_bytes()));
+        self.source = Arc::new(source);
+        self.overstrike = None;
+        self.interpret_overstrike(man);
+        self.detect_info();
+        grew
+    }
 
-        // Re-decompress if this is a compressed file
-        if let Some(data) = decompress_if_needed(&path)? {
-            self.line_offsets = Self::index_lines(&data);
-            self.source = BufferSource::Memory(data);
-            return Ok(());
+    /// Note that the file is gone from its path. A map would still show the
+    /// old file, but nothing stops another process that has it open from
+    /// truncating it, so its contents are copied into memory.
+    pub fn mark_deleted(&mut self) {
+        if let BufferSource::Mmap(map) = &*self.source {
+            self.source = Arc::new(BufferSource::Memory(map.to_vec()));
         }
+        self.deleted = true;
+    }
 
-        let metadata = std::fs::metadata(&path)
-            .with_context(|| format!("Cannot stat '{}'", path.display()))?;
-        let file_size = metadata.len();
-        let source = if file_size >= mmap_threshold {
-            let file = File::open(&path)
-                .with_context(|| format!("Cannot open '{}'", path.display()))?;
-            let mmap = unsafe { Mmap::map(&file) }
-                .with_context(|| format!("Cannot mmap '{}'", path.display()))?;
-            BufferSource::Mmap(mmap)
-        } else {
-            let mut file = File::open(&path)
-                .with_context(|| format!("Cannot open '{}'", path.display()))?;
-            let mut contents = Vec::with_capacity(file_size as usize);
-            file.read_to_end(&mut contents)?;
-            BufferSource::Memory(contents)
-        };
-        self.line_offsets = Self::index_lines(source.as_bytes());
-        self.source = source;
-        Ok(())
+    /// True when the file's size or modification time is no longer what it
+    /// was when the buffer last read it.
+    pub fn changed_on_disk(&self) -> bool {
+        let Some(path) = &self.path else { return false };
+        let Ok(metadata) = std::fs::metadata(path) else { return false };
+        Some(metadata.len()) != self.info.size || metadata.modified().ok() != self.info.modified
+    }
+
+    /// True when the file under a mapped buffer is now shorter than the
+    /// map: the pages past its new end read as zeros (see `sigbus`), so the
+    /// buffer must be reloaded before its lines are shown.
+    pub fn is_truncated(&self) -> bool {
+        match (&*self.source, &self.path) {
+            (BufferSource::Mmap(map), Some(path)) => {
+                std::fs::metadata(path).is_ok_and(|m| m.len() < map.len() as u64)
+            }
+            _ => false,
+        }
+    }
+
+    fn detect_info(&mut self) {
+        self.info = FileInfo::detect(self.path.as_deref(), self.source.as_bytes(), &self.line_offsets);
+    }
+
+    /// Interpret backspace overstrikes, as `man` writes them, as bold and
+    /// underline: the sequences are removed from the text and kept as
+    /// styles. Unless `force` (`--man`), only done when the start of the
+    /// buffer has any.
+    pub fn interpret_overstrike(&mut self, force: bool) {
+        let data = self.source.as_bytes();
+        if self.is_man() || !force && !overstrike::detect(data) {
+            return;
+        }
+        let (text, runs) = overstrike::strip(data);
+        self.line_offsets = Arc::new(Self::index_lines(&text));
+        self.source = Arc::new(BufferSource::Memory(text));
+        self.overstrike = Some(runs);
+    }
+
+    /// Bold/underlined runs of line `n` if this buffer is a man page.
+    pub fn overstrike_runs(&self, n: usize) -> Option<&[overstrike::Run]> {
+        let runs = self.overstrike.as_ref()?;
+        Some(runs.get(&n).map(Vec::as_slice).unwrap_or_default())
+    }
+
+    pub fn is_man(&self) -> bool {
+        self.overstrike.is_some()
     }
 
     /// Check if the file appears to be binary.
@@ -399,26 +1039,6 @@ impl Buffer {
         let check_len = std::cmp::min(data.len(), 8192);
         data[..check_len].contains(&0)
     }
-
-    /// Shell out to `git diff HEAD` and populate `git_changes`.
-    pub fn load_git_changes(&mut self) {
-        let path = match &self.path {
-            Some(p) => p.clone(),
-            None => return,
-        };
-        let parent = path.parent().unwrap_or(Path::new("."));
-        let result = std::process::Command::new("git")
-            .args(["diff", "HEAD", "--unified=0", "--"])
-            .arg(&path)
-            .current_dir(parent)
-            .output();
-
-        if let Ok(output) = result {
-            if output.status.success() || !output.stdout.is_empty() {
-                self.git_changes = parse_git_changes(&output.stdout);
-            }
-        }
-    }
 }
 
 #[cfg(test)]
@@ -429,12 +1049,30 @@ mod tests {
         let data = data.to_vec();
         let offsets = Buffer::index_lines(&data);
         Buffer {
-            source: BufferSource::Memory(data),
-            line_offsets: offsets,
+            source: Arc::new(BufferSource::Memory(data)),
+            line_offsets: Arc::new(offsets),
             path: None,
             name: "test".to_string(),
             git_changes: HashMap::new(),
+            git_deleted: HashMap::new(),
+            shown_deletions: HashSet::new(),
+            blame: None,
+            log: None,
             is_diff: false,
+            syntax: None,
+            highlight: None,
+            engine: Engine::default(),
+            overstrike: None,
+            highlight_cache: Default::default(),
+            info: FileInfo::default(),
+            loading: None,
+            stream: None,
+            exec: None,
+            new_data: false,
+            dropped: 0,
+            deleted: false,
+            derived: None,
+            command: None,
         }
     }
 
@@ -445,6 +1083,25 @@ mod tests {
         assert_eq!(buf.get_line(0), None);
     }
 
+    #[test]
+    fn test_from_bytes() {
+        let buf = Buffer::from_bytes("[scratch]", b"one\ntwo\n".to_vec());
+        assert_eq!(buf.line_count(), 2);
+        assert_eq!(buf.get_line(1), Some("two"));
+        assert_eq!(buf.name, "[scratch]");
+        assert!(buf.path.is_none());
+    }
+
+    #[test]
+    fn test_row_of_byte() {
+        let buf = make_buffer(b"one\ntwo\nthree\n");
+        assert_eq!(buf.row_of_byte(0), 0);
+        assert_eq!(buf.row_of_byte(3), 0);
+        assert_eq!(buf.row_of_byte(4), 1);
+        assert_eq!(buf.row_of_byte(9), 2);
+        assert_eq!(buf.row_of_byte(1000), 2);
+    }
+
     #[test]
     fn test_single_line() {
         let buf = make_buffer(b"hello world");
@@ -474,6 +1131,17 @@ mod tests {
         assert!(buf.is_binary());
     }
 
+    #[test]
+    fn test_line_prefix() {
+        let buf = Buffer::from_bytes("t", "abcdé\n".as_bytes().to_vec());
+        assert_eq!(buf.line_prefix(0, 3), Some("abc"));
+        assert_eq!(buf.line_prefix(0, 5), Some("abcd"));
+        assert_eq!(buf.line_prefix(0, 100), Some("abcdé"));
+        assert_eq!(buf.line_prefix(1, 3), None);
+        let buf = Buffer::from_bytes("t", b"ab\xffcd\n".to_vec());
+        assert_eq!(buf.line_prefix(0, 100), Some("ab"));
+    }
+
     #[test]
     fn test_hex_line() {
         let buf = make_buffer(b"ABCDEFGHIJKLMNOP");
@@ -483,6 +1151,76 @@ mod tests {
         assert!(line.contains("|ABCDEFGHIJKLMNOP|"));
     }
 
+    #[test]
+    fn test_concat() {
+        let (a, b) = (make_buffer(b"one\ntwo"), make_buffer(b"three\n"));
+        let limits = Limits { mmap_threshold: 0, spill_threshold: 1 << 20, scrollback_lines: 0, scrollback_bytes: 0 };
+        let joined = Buffer::concat("j", &[&a, &b], limits).unwrap();
+        assert!(matches!(*joined.source, BufferSource::Memory(_)));
+        assert_eq!(joined.source.as_bytes(), b"==> test <==\none\ntwo\n\n==> test <==\nthree\n");
+        // Past the spill threshold the copy is a mapped temporary file
+        let spilled = Buffer::concat("j", &[&a, &b], Limits { spill_threshold: 16, ..limits }).unwrap();
+        assert!(matches!(*spilled.source, BufferSource::Spilled(_)));
+        assert_eq!(spilled.source.as_bytes(), joined.source.as_bytes());
+        assert_eq!(spilled.line_count(), 6);
+    }
+
+    #[test]
+    fn test_truncated_map_reads_zeros() {
+        sigbus::install();
+        let path = std::env::temp_dir().join(format!("some_truncated_{}.txt", std::process::id()));
+        std::fs::write(&path, "x".repeat(3 * 4096) + "\nend\n").unwrap();
+        let limits = Limits { mmap_threshold: 0, spill_threshold: u64::MAX, scrollback_lines: 0, scrollback_bytes: 0 };
+        let buf = Buffer::from_file(&path, limits, None).unwrap();
+        let snap = buf.snapshot();
+        std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(10).unwrap();
+        assert!(buf.is_truncated());
+        // Past the new end: zeros, not SIGBUS
+        assert!(snap.line_bytes(1).unwrap().iter().all(|&b| b == 0));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_shares_lines() {
+        let buf = make_buffer(b"caf\xc3\xa9\n\xff\xfe\r\nlast");
+        let snap = buf.snapshot();
+        assert_eq!(snap.line_count(), 3);
+        assert_eq!(snap.line_bytes(0), Some("caf\u{e9}".as_bytes()));
+        // Invalid UTF-8 lines keep their index in the snapshot
+        assert_eq!(snap.line_bytes(1), Some(&b"\xff\xfe"[..]));
+        assert_eq!(snap.line_bytes(2), Some(&b"last"[..]));
+        assert_eq!(snap.line_bytes(3), None);
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let data: String = (1..=20).map(|i| format!("{}\n", i)).collect();
+        let mut buf = make_buffer(data.as_bytes());
+        let limits = Limits { mmap_threshold: 0, spill_threshold: 0, scrollback_lines: 10, scrollback_bytes: 0 };
+        buf.drop_oldest(limits);
+        assert_eq!((buf.line_count(), buf.dropped), (9, 11));
+        assert_eq!(buf.get_line(0), Some("12"));
+        buf.drop_oldest(limits);
+        assert_eq!(buf.dropped, 11);
+        // Bytes: "12\n".."20\n" is 27 bytes, down to 18 keeps 6 lines
+        buf.drop_oldest(Limits { scrollback_lines: 0, scrollback_bytes: 20, ..limits });
+        assert_eq!(buf.get_line(0), Some("15"));
+        assert_eq!(buf.dropped, 14);
+    }
+
+    #[test]
+    fn test_drop_oldest_small_limit() {
+        let data: String = (1..=20).map(|i| format!("{}\n", i)).collect();
+        let mut buf = make_buffer(data.as_bytes());
+        let limits = Limits { mmap_threshold: 0, spill_threshold: 0, scrollback_lines: 5, scrollback_bytes: 0 };
+        buf.drop_oldest(limits);
+        assert_eq!(buf.get_line(0), Some("17"));
+        assert_eq!(buf.line_count(), 4);
+        // "17\n".."20\n" is 12 bytes, down to 7 keeps 2 lines
+        buf.drop_oldest(Limits { scrollback_lines: 0, scrollback_bytes: 8, ..limits });
+        assert_eq!(buf.get_line(0), Some("19"));
+    }
+
     #[test]
     fn test_hex_line_count() {
         let buf = make_buffer(&[0u8; 32]);
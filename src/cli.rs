@@ -1,12 +1,13 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use std::ffi::OsString;
 use std::path::PathBuf;
 
 /// some — A jazzed-up terminal file viewer.
 /// Like 'less', but with syntax highlighting, line numbers, and more.
 #[derive(Parser, Debug, Clone)]
-#[command(name = "some", version, about, long_about = None)]
+#[command(name = "some", version, about, long_about = None, args_override_self = true)]
 pub struct Cli {
-    /// Files to view (reads stdin if none provided)
+    /// Files to view (reads stdin if none provided; '-' reads stdin too)
     #[arg(value_name = "FILE")]
     pub files: Vec<PathBuf>,
 
@@ -34,6 +35,11 @@ pub struct Cli {
     #[arg(short = 't', long = "theme", default_value = "base16-ocean.dark")]
     pub theme: String,
 
+    /// Highlight every file as LANG, a syntax name or file extension (for
+    /// files without an extension and stdin)
+    #[arg(short = 'l', long = "language", value_name = "LANG")]
+    pub language: Option<String>,
+
     /// Disable syntax highlighting
     #[arg(long = "no-syntax")]
     pub no_syntax: bool,
@@ -42,11 +48,173 @@ pub struct Cli {
     #[arg(long = "plain")]
     pub plain: bool,
 
+    /// When to use colors: auto (on a terminal, unless NO_COLOR is set),
+    /// always or never
+    #[arg(long = "color", value_enum, value_name = "WHEN", default_value_t = ColorWhen::Auto)]
+    pub color: ColorWhen,
+
     /// Tab width for display
     #[arg(long = "tab-width", default_value = "4")]
     pub tab_width: u8,
 
+    /// Treat the input as a man page: show backspace overstrikes as bold and
+    /// underline even where they are not detected (for MANPAGER)
+    #[arg(long = "man")]
+    pub man: bool,
+
+    /// Ignore the input preprocessor (LESSOPEN or general.preprocessor)
+    #[arg(short = 'L', long = "no-preprocessor")]
+    pub no_preprocessor: bool,
+
+    /// Restore the named session (open files, positions, filters, searches,
+    /// marks) and save it again on exit
+    #[arg(long = "session", value_name = "NAME")]
+    pub session: Option<String>,
+
+    /// Print the content and exit instead of paging it if it fits on one screen
+    /// (same as --paging=auto)
+    #[arg(short = 'F', long = "quit-if-one-screen")]
+    pub quit_if_one_screen: bool,
+
+    /// Write highlighted output to stdout instead of opening the viewer
+    /// (same as --paging=never)
+    #[arg(long = "print")]
+    pub print: bool,
+
+    /// When to open the viewer: always, never (print instead), or auto
+    /// (print if the content fits on one screen)
+    #[arg(long = "paging", value_enum, value_name = "WHEN", default_value_t = Paging::Always)]
+    pub paging: Paging,
+
+    /// Write the first FILE as highlighted HTML to OUT ("-" for stdout) and
+    /// exit (uses -n and -p)
+    #[arg(long = "export-html", value_name = "OUT")]
+    pub export_html: Option<PathBuf>,
+
+    /// Write the first FILE as ANSI-colored text to OUT ("-" for stdout) and
+    /// exit (uses -n and -p)
+    #[arg(long = "export-ansi", value_name = "OUT", conflicts_with = "export_html")]
+    pub export_ansi: Option<PathBuf>,
+
+    /// Write a commented default config.toml (and create the themes
+    /// directory) in the config directory, print its path and exit
+    #[arg(long = "init-config")]
+    pub init_config: bool,
+
+    /// List the available themes (with a preview on a terminal) and exit
+    #[arg(long = "list-themes")]
+    pub list_themes: bool,
+
+    /// Check config.toml for unknown keys, bad key specs, colors and
+    /// themes, print what is wrong and exit (non-zero if anything is)
+    #[arg(long = "check-config")]
+    pub check_config: bool,
+
     /// Compare FILE (first positional) against FILE2 in unified diff view
     #[arg(long = "diff", value_name = "FILE2")]
     pub diff: Option<std::path::PathBuf>,
+
+    /// Run CMD every --interval seconds and show its latest output, with
+    /// the changes from the run before highlighted (like watch -d)
+    #[arg(long = "exec", value_name = "CMD", conflicts_with = "diff")]
+    pub exec: Option<String>,
+
+    /// Seconds between --exec runs
+    #[arg(long = "interval", value_name = "SECS", default_value = "2", requires = "exec")]
+    pub interval: f64,
+
+    /// Open the files matching PATTERN (e.g. "logs/*.log") and, as they
+    /// appear, new ones in new tabs; may be given more than once
+    #[arg(long = "watch-glob", value_name = "PATTERN")]
+    pub watch_glob: Vec<String>,
+
+    /// Show the FILEs one after another as a single buffer, each headed by
+    /// a "==> FILE <==" line (e.g. rotated logs, oldest first)
+    #[arg(long = "cat", conflicts_with_all = ["diff", "exec", "watch_glob"])]
+    pub cat: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Paging {
+    Always,
+    Never,
+    Auto,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum ColorWhen {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Cli {
+    /// Parse the command line with the default options from `$SOME_OPTS`
+    /// in front of it, so that options given on the command line win.
+    pub fn parse_with_env() -> Self {
+        let mut args: Vec<OsString> = std::env::args_os().collect();
+        if let Ok(opts) = std::env::var("SOME_OPTS") {
+            let at = args.len().min(1);
+            args.splice(at..at, split_words(&opts).into_iter().map(OsString::from));
+        }
+        Self::parse_from(args)
+    }
+
+    /// Whether to emit any colors or styling, given whether the output is a
+    /// terminal. `--color=auto` follows the NO_COLOR convention: any
+    /// non-empty value turns colors off.
+    pub fn use_color(&self, tty: bool) -> bool {
+        match self.color {
+            ColorWhen::Always => true,
+            ColorWhen::Never => false,
+            ColorWhen::Auto => tty && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+        }
+    }
+
+    /// The paging behavior after applying the `--print` and `-F` shorthands.
+    pub fn paging(&self) -> Paging {
+        if self.print {
+            Paging::Never
+        } else if self.quit_if_one_screen {
+            Paging::Auto
+        } else {
+            self.paging
+        }
+    }
+}
+
+/// Split `s` into words like a shell: whitespace separates words, quotes
+/// group them and a backslash escapes the next character.
+fn split_words(s: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+            (_, '\\') => word.get_or_insert_with(String::new).extend(chars.next()),
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(split_words("  -n --theme Nord "), vec!["-n", "--theme", "Nord"]);
+        assert_eq!(split_words(r#"-t "Solarized (dark)" -p 'a b' x\ y """#), vec!["-t", "Solarized (dark)", "-p", "a b", "x y", ""]);
+    }
 }
@@ -0,0 +1,113 @@
+use crate::buffer::TextSnapshot;
+
+/// What `:cols` shows: which fields, split on what.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Columns {
+    /// 1-based inclusive field ranges; `usize::MAX` ends an open range
+    pub ranges: Vec<(usize, usize)>,
+    /// None splits on runs of whitespace, as awk does
+    pub delimiter: Option<String>,
+}
+
+/// Parse `:cols` arguments: an optional `-d DELIM` (`-d,`, `-d '|'`,
+/// `-d '\t'`) and a list of fields as `cut` takes them: `1,3,5`, `2-4`,
+/// `3-`, `-2`.
+pub fn parse(args: &str) -> Result<Columns, String> {
+    let mut delimiter = None;
+    let mut list = args.trim();
+    if let Some(rest) = list.strip_prefix("-d") {
+        let rest = rest.trim_start();
+        let (delim, after) = match rest.chars().next() {
+            Some(quote @ ('\'' | '"')) => {
+                let end = rest[1..].find(quote).ok_or("unclosed quote after -d")?;
+                (&rest[1..end + 1], &rest[end + 2..])
+            }
+            Some(_) if rest.starts_with("\\t") => ("\\t", &rest[2..]),
+            Some(c) => (&rest[..c.len_utf8()], &rest[c.len_utf8()..]),
+            None => return Err("-d needs a delimiter".to_string()),
+        };
+        let delim = delim.replace("\\t", "\t");
+        if delim.is_empty() {
+            return Err("-d needs a delimiter".to_string());
+        }
+        delimiter = Some(delim);
+        list = after.trim_start();
+    }
+    if list.is_empty() {
+        return Err("no fields given".to_string());
+    }
+    let ranges = list.split(',').map(|field| parse_range(field.trim())).collect::<Result<_, _>>()?;
+    Ok(Columns { ranges, delimiter })
+}
+
+fn parse_range(s: &str) -> Result<(usize, usize), String> {
+    let number = |n: &str, default: usize| match n {
+        "" => Ok(default),
+        n => n.parse::<usize>().ok().filter(|&n| n > 0).ok_or_else(|| format!("'{}' is not a field number", s)),
+    };
+    let (lo, hi) = match s.split_once('-') {
+        Some((lo, hi)) => (number(lo, 1)?, number(hi, usize::MAX)?),
+        None if s.is_empty() => return Err("empty field".to_string()),
+        None => {
+            let n = number(s, 0)?;
+            (n, n)
+        }
+    };
+    if lo > hi {
+        return Err(format!("'{}' is a decreasing range", s));
+    }
+    Ok((lo, hi))
+}
+
+impl Columns {
+    /// The chosen fields of `line`, in the order the ranges name them,
+    /// joined by the delimiter (a space when splitting on whitespace).
+    /// Fields past the end of the line are left out.
+    pub fn extract(&self, line: &str) -> String {
+        let fields: Vec<&str> = match &self.delimiter {
+            Some(delim) => line.split(delim.as_str()).collect(),
+            None => line.split_whitespace().collect(),
+        };
+        let picked: Vec<&str> = self
+            .ranges
+            .iter()
+            .flat_map(|&(lo, hi)| fields.iter().take(hi).skip(lo - 1).copied())
+            .collect();
+        picked.join(self.delimiter.as_deref().unwrap_or(" "))
+    }
+
+    /// Every line of `text` cut down to the chosen fields, one output line
+    /// per input line so that line numbers still match.
+    pub fn apply(&self, text: &TextSnapshot) -> Vec<u8> {
+        text.map_lines(|line| self.extract(&String::from_utf8_lossy(line)).into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let cols = parse("1,3-4, 6-").unwrap();
+        assert_eq!(cols.ranges, vec![(1, 1), (3, 4), (6, usize::MAX)]);
+        assert_eq!(cols.delimiter, None);
+        assert_eq!(parse("-d, -2").unwrap(), Columns { ranges: vec![(1, 2)], delimiter: Some(",".to_string()) });
+        assert_eq!(parse("-d '\\t' 2").unwrap().delimiter.as_deref(), Some("\t"));
+        assert_eq!(parse("-d\\t 2").unwrap().delimiter.as_deref(), Some("\t"));
+        assert_eq!(parse("-d ' | ' 2").unwrap().delimiter.as_deref(), Some(" | "));
+        assert!(parse("").is_err());
+        assert!(parse("0").is_err());
+        assert!(parse("3-1").is_err());
+        assert!(parse("1,,2").is_err());
+        assert!(parse("-d").is_err());
+    }
+
+    #[test]
+    fn test_extract() {
+        let line = "10.0.0.1 - - [01/Jan] \"GET /\" 200";
+        assert_eq!(parse("1,7").unwrap().extract(line), "10.0.0.1 200");
+        assert_eq!(parse("5-").unwrap().extract("a b c d e f"), "e f");
+        assert_eq!(parse("-d, 3,1,9").unwrap().extract("a,b,c"), "c,a");
+    }
+}
@@ -1,6 +1,16 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use ratatui::style::Color;
 use serde::Deserialize;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::buffer::Limits;
+use crate::config_check::{self, Issue};
+use crate::keymap::{Action, Key};
+use crate::syntax::{Engine, SyntaxHighlighter};
+
+/// The commented example config, written out by `--init-config`.
+const DEFAULT_CONFIG: &str = include_str!("../config.example.toml");
 
 /// Application configuration, loaded from ~/.config/some/config.toml
 /// with CLI flags taking precedence.
@@ -10,21 +20,72 @@ pub struct Config {
     pub general: GeneralConfig,
     pub colors: ColorConfig,
     pub keys: KeysConfig,
+    pub log_levels: LogLevelsConfig,
+    pub statusbar: StatusBarConfig,
+    pub alerts: AlertsConfig,
+    /// `[filetype.<name>]` sections, keyed by extension or syntax name
+    pub filetype: BTreeMap<String, FileTypeConfig>,
+}
+
+/// Settings overridden for one file type. Unset fields keep the `[general]` value.
+#[derive(Debug, Clone, Deserialize, Default)]
+#[serde(default)]
+pub struct FileTypeConfig {
+    pub wrap: Option<bool>,
+    pub tab_width: Option<u8>,
+    pub theme: Option<String>,
+    /// Syntax to highlight with instead of the detected one
+    pub syntax: Option<String>,
+    /// Highlighting backend (`"syntect"` or `"tree-sitter"`)
+    pub highlighter: Option<Engine>,
+    pub line_numbers: Option<bool>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default)]
 pub struct GeneralConfig {
     pub theme: String,
+    /// Paint the view in the theme's background and default foreground
+    pub theme_background: bool,
     pub line_numbers: bool,
     pub wrap: bool,
     pub tab_width: u8,
     pub mouse: bool,
     pub smart_case: bool,
+    /// Whether n/N wrap from the last match back to the first
+    pub wrap_search: bool,
+    /// Compile search patterns with the backtracking engine (lookaround, backrefs)
+    pub fancy_regex: bool,
+    /// Stop a search after this many matches (0 = unlimited)
+    pub max_matches: usize,
+    /// Seconds a status message stays up (0 = until the next one)
+    pub message_timeout: u64,
+    /// Ask to reload the active file when it changes on disk outside follow mode
+    pub reload_prompt: bool,
     /// Bytes threshold above which mmap is used
     pub mmap_threshold: u64,
+    /// Compressed files that decompress to more than this many bytes are
+    /// decompressed into a temporary file instead of memory
+    pub spill_threshold: u64,
+    /// Piped input drops its oldest lines past this many (0 = no limit)
+    pub scrollback_lines: usize,
+    /// Piped input drops its oldest lines past this many bytes (0 = no limit)
+    pub scrollback_bytes: u64,
+    /// Files larger than this many bytes are shown without highlighting
+    /// unless `:syntax on` is given
+    pub highlight_max_size: u64,
+    /// Lines longer than this many bytes are shown without highlighting
+    pub highlight_max_line_len: usize,
+    /// Lines longer than this many bytes are cut off on screen
+    pub max_line_len: usize,
+    /// Shortest run of printable characters `:strings` lists
+    pub strings_min_len: usize,
     /// Optional custom themes directory (default: ~/.config/some/themes/)
     pub themes_dir: Option<PathBuf>,
+    /// Also load bat's themes and syntaxes
+    pub bat_assets: bool,
+    /// Input preprocessor command in LESSOPEN syntax (default: $LESSOPEN)
+    pub preprocessor: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -35,6 +96,46 @@ pub struct ColorConfig {
     pub search_match_fg: String,
     pub search_match_bg: String,
     pub line_number_fg: String,
+    /// Backgrounds for regex capture groups 1, 2, 3, … (cycled)
+    pub search_group_bg: Vec<String>,
+}
+
+/// `ColorConfig` parsed into colors for rendering. Entries that do not
+/// parse keep their default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub status_bar_fg: Color,
+    pub status_bar_bg: Color,
+    pub search_match_fg: Color,
+    pub search_match_bg: Color,
+    pub line_number_fg: Color,
+    pub search_group_bg: Vec<Color>,
+}
+
+impl GeneralConfig {
+    pub fn limits(&self) -> Limits {
+        Limits {
+            mmap_threshold: self.mmap_threshold,
+            spill_threshold: self.spill_threshold,
+            scrollback_lines: self.scrollback_lines,
+            scrollback_bytes: self.scrollback_bytes,
+        }
+    }
+}
+
+impl ColorConfig {
+    pub fn palette(&self) -> Palette {
+        let defaults = ColorConfig::default();
+        let color = |s: &str, default: &str| parse_hex_color(s).or_else(|| parse_hex_color(default)).unwrap_or(Color::Reset);
+        Palette {
+            status_bar_fg: color(&self.status_bar_fg, &defaults.status_bar_fg),
+            status_bar_bg: color(&self.status_bar_bg, &defaults.status_bar_bg),
+            search_match_fg: color(&self.search_match_fg, &defaults.search_match_fg),
+            search_match_bg: color(&self.search_match_bg, &defaults.search_match_bg),
+            line_number_fg: color(&self.line_number_fg, &defaults.line_number_fg),
+            search_group_bg: self.search_group_bg.iter().filter_map(|hex| parse_hex_color(hex)).collect(),
+        }
+    }
 }
 
 /// Optional keybinding overrides. Unset fields keep their hardcoded defaults.
@@ -56,29 +157,191 @@ pub struct KeysConfig {
     pub search_backward: Option<String>,
     pub next_match: Option<String>,
     pub prev_match: Option<String>,
+    pub toggle_search_wrap: Option<String>,
     pub toggle_numbers: Option<String>,
     pub toggle_wrap: Option<String>,
     pub follow_mode: Option<String>,
     pub enter_command: Option<String>,
     pub filter: Option<String>,
     pub visual: Option<String>,
+    pub visual_char: Option<String>,
     pub set_mark: Option<String>,
     pub jump_mark: Option<String>,
     pub scroll_right: Option<String>,
     pub scroll_left: Option<String>,
+    pub expand_hidden: Option<String>,
+    pub collapse_hidden: Option<String>,
+    pub save_view: Option<String>,
+    pub log_level_filter: Option<String>,
+    pub buffer_picker: Option<String>,
+    pub move_buffer_left: Option<String>,
+    pub move_buffer_right: Option<String>,
+    pub alternate_buffer: Option<String>,
+    pub jump_back: Option<String>,
+    pub jump_forward: Option<String>,
+    pub yank_line: Option<String>,
+    pub yank_all: Option<String>,
+    pub center_view: Option<String>,
+    pub record_macro: Option<String>,
+    pub play_macro: Option<String>,
+    pub help: Option<String>,
+    pub open_editor: Option<String>,
+    pub toggle_blame: Option<String>,
+    pub show_commit: Option<String>,
+    pub toggle_deleted: Option<String>,
+    pub reload: Option<String>,
+    /// lesskey source file whose bindings are added to the defaults
+    pub lesskey: Option<String>,
+    /// Bindings read from `lesskey` by `Config::load`
+    #[serde(skip)]
+    pub imported: Vec<(Vec<Key>, Action)>,
+    /// Any other entry binds a key spec to a command line, e.g. `f5 = ":filter ERROR"`
+    #[serde(flatten)]
+    pub commands: BTreeMap<String, String>,
+}
+
+impl KeysConfig {
+    /// Read the bindings of the configured lesskey file, if any.
+    fn import_lesskey(&mut self) -> Result<()> {
+        if let Some(path) = &self.lesskey {
+            self.imported = crate::lesskey::load(&crate::paths::expand_path(path))?;
+        }
+        Ok(())
+    }
+}
+
+/// Regexes recognising each log level, used by the `L` quick filters.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LogLevelsConfig {
+    pub error: String,
+    pub warn: String,
+    pub info: String,
+    pub debug: String,
+    pub trace: String,
+}
+
+impl LogLevelsConfig {
+    /// Combined regex for `level` and every more severe level, e.g. `'w'` → ERROR|WARN.
+    /// Levels are selected by their first letter: e, w, i, d, t.
+    pub fn at_least(&self, level: char) -> Option<(&'static str, String)> {
+        let ordered: [(&'static str, &str); 5] = [
+            ("ERROR", &self.error),
+            ("WARN", &self.warn),
+            ("INFO", &self.info),
+            ("DEBUG", &self.debug),
+            ("TRACE", &self.trace),
+        ];
+        let upto = ordered
+            .iter()
+            .position(|(name, _)| name.starts_with(level.to_ascii_uppercase()))?;
+        let pattern = ordered[..=upto]
+            .iter()
+            .map(|(_, re)| format!("(?:{})", re))
+            .collect::<Vec<_>>()
+            .join("|");
+        Some((ordered[upto].0, pattern))
+    }
+}
+
+/// Watch patterns: what to do when a new line matching one of them comes
+/// in while following.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AlertsConfig {
+    pub patterns: Vec<String>,
+    /// Ring the terminal bell
+    pub bell: bool,
+    /// Flash the status bar
+    pub flash: bool,
+    /// Shell command to run, with the line in `$SOME_ALERT_LINE`
+    pub command: Option<String>,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            patterns: Vec::new(),
+            bell: true,
+            flash: true,
+            command: None,
+        }
+    }
+}
+
+impl AlertsConfig {
+    /// One regex matching any of the patterns, or None if there are none.
+    /// Invalid patterns are left out (`--check-config` reports them).
+    pub fn regex(&self) -> Option<regex::bytes::Regex> {
+        let valid: Vec<String> = self
+            .patterns
+            .iter()
+            .filter(|p| regex::bytes::Regex::new(p).is_ok())
+            .map(|p| format!("(?:{})", p))
+            .collect();
+        if valid.is_empty() {
+            return None;
+        }
+        regex::bytes::Regex::new(&valid.join("|")).ok()
+    }
+}
+
+/// Status bar layout: templates for its left and right ends, with
+/// `{placeholders}` filled in by `statusbar::render`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StatusBarConfig {
+    pub left: String,
+    pub right: String,
+}
+
+impl Default for StatusBarConfig {
+    fn default() -> Self {
+        Self {
+            left: "{name} {branch} {buffer} {mode} {recording} {flags} {filter}".to_string(),
+            right: "{search}  {position} \u{2502} {percent}%".to_string(),
+        }
+    }
+}
+
+impl Default for LogLevelsConfig {
+    fn default() -> Self {
+        Self {
+            error: r"\b(?:FATAL|CRIT(?:ICAL)?|ERR(?:OR)?|PANIC)\b".to_string(),
+            warn: r"\bWARN(?:ING)?\b".to_string(),
+            info: r"\bINFO\b".to_string(),
+            debug: r"\bDEBUG\b".to_string(),
+            trace: r"\bTRACE\b".to_string(),
+        }
+    }
 }
 
 impl Default for GeneralConfig {
     fn default() -> Self {
         Self {
             theme: "base16-ocean.dark".to_string(),
+            theme_background: true,
             line_numbers: false,
             wrap: false,
             tab_width: 4,
             mouse: true,
             smart_case: true,
+            wrap_search: true,
+            fancy_regex: false,
+            max_matches: 1_000_000,
+            message_timeout: 5,
+            reload_prompt: false,
             mmap_threshold: 10 * 1024 * 1024, // 10 MB
+            spill_threshold: 1024 * 1024 * 1024, // 1 GB
+            scrollback_lines: 0,
+            scrollback_bytes: 1024 * 1024 * 1024, // 1 GB
+            highlight_max_size: 16 * 1024 * 1024, // 16 MB
+            highlight_max_line_len: 10_000,
+            max_line_len: 1024 * 1024, // 1 MB
+            strings_min_len: 4,
             themes_dir: None,
+            bat_assets: false,
+            preprocessor: None,
         }
     }
 }
@@ -86,31 +349,77 @@ impl Default for GeneralConfig {
 impl Default for ColorConfig {
     fn default() -> Self {
         Self {
-            status_bar_fg: "#cdd6f4".to_string(),
-            status_bar_bg: "#1e1e2e".to_string(),
-            search_match_fg: "#1e1e2e".to_string(),
-            search_match_bg: "#f9e2af".to_string(),
-            line_number_fg: "#6c7086".to_string(),
+            status_bar_fg: "#c0c5ce".to_string(),
+            status_bar_bg: "#2b303b".to_string(),
+            search_match_fg: "#2b303b".to_string(),
+            search_match_bg: "#ebcb8b".to_string(),
+            line_number_fg: "#65737e".to_string(),
+            search_group_bg: vec![
+                "#a6e3a1".to_string(),
+                "#89b4fa".to_string(),
+                "#f5c2e7".to_string(),
+            ],
         }
     }
 }
 
 impl Config {
-    pub fn load() -> Result<Self> {
-        if let Some(path) = Self::config_path() {
-            if path.exists() {
-                let content = std::fs::read_to_string(&path)?;
-                let config: Config = toml::from_str(&content)?;
-                return Ok(config);
-            }
+    /// Load the config file. Problems do not stop startup: a file that
+    /// cannot be read or parsed gives the defaults, and everything wrong is
+    /// returned as issues to warn about.
+    pub fn load() -> (Self, Vec<Issue>) {
+        let Some(path) = Self::config_path().filter(|p| p.exists()) else {
+            return (Config::default(), Vec::new());
+        };
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => return (Config::default(), vec![Issue { line: None, message: e.to_string() }]),
+        };
+        let mut config: Config = match toml::from_str(&content) {
+            Ok(config) => config,
+            Err(e) => return (Config::default(), vec![config_check::parse_error(&content, &e)]),
+        };
+        let mut issues = config_check::check(&content, &config);
+        if let Err(e) = config.keys.import_lesskey() {
+            let line = config_check::find_line(&content, Some("keys"), "lesskey");
+            issues.push(Issue { line, message: format!("{:#}", e) });
+        }
+        (config, issues)
+    }
+
+    /// The `[filetype.*]` section for a file: the one named after its
+    /// extension (inside any compression extension), else after its detected
+    /// syntax in lowercase (`rust`, `markdown`).
+    pub fn filetype(&self, path: Option<&Path>, highlighter: &SyntaxHighlighter) -> Option<&FileTypeConfig> {
+        let path = path.filter(|_| !self.filetype.is_empty())?;
+        let mut ext = path.extension().and_then(|e| e.to_str());
+        if matches!(ext, Some("gz" | "zst" | "zstd" | "bz2")) {
+            ext = path.file_stem().and_then(|s| Path::new(s).extension()).and_then(|e| e.to_str());
         }
-        Ok(Config::default())
+        ext.and_then(|ext| self.filetype.get(&ext.to_lowercase()))
+            .or_else(|| self.filetype.get(&highlighter.detect_syntax(Some(path)).name.to_lowercase()))
     }
 
     pub fn config_path() -> Option<PathBuf> {
         dirs::config_dir().map(|d| d.join("some").join("config.toml"))
     }
 
+    /// Write the commented default config and create the user themes
+    /// directory next to it (`--init-config`). Never overwrites an existing
+    /// config file. Returns the config path.
+    pub fn init() -> Result<PathBuf> {
+        let path = Self::config_path().context("No config directory on this system")?;
+        if path.exists() {
+            bail!("'{}' already exists", path.display());
+        }
+        let dir = path.parent().unwrap_or(Path::new("."));
+        std::fs::create_dir_all(dir.join("themes"))
+            .with_context(|| format!("Cannot create '{}'", dir.display()))?;
+        std::fs::write(&path, DEFAULT_CONFIG)
+            .with_context(|| format!("Cannot write '{}'", path.display()))?;
+        Ok(path)
+    }
+
     pub fn merge_cli(&mut self, cli: &crate::cli::Cli) {
         if cli.line_numbers {
             self.general.line_numbers = true;
@@ -127,5 +436,22 @@ impl Config {
         if cli.theme != "base16-ocean.dark" {
             self.general.theme = cli.theme.clone();
         }
+        if cli.no_preprocessor {
+            self.general.preprocessor = None;
+        } else if self.general.preprocessor.is_none() {
+            self.general.preprocessor = std::env::var("LESSOPEN").ok().filter(|s| !s.is_empty());
+        }
+    }
+}
+
+/// Parse a `"#rrggbb"` hex string into an RGB color.
+pub fn parse_hex_color(s: &str) -> Option<Color> {
+    let hex = s.trim().strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
     }
+    let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
+    let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
+    let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
+    Some(Color::Rgb(r, g, b))
 }
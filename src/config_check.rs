@@ -0,0 +1,311 @@
+use std::fmt;
+
+use crate::config::{parse_hex_color, Config};
+use crate::keymap::parse_key_sequence;
+use crate::syntax::SyntaxHighlighter;
+
+/// A problem in config.toml, with the line it is on when known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    /// 1-based line number
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+const GENERAL_KEYS: &[&str] = &[
+    "theme",
+    "theme_background",
+    "line_numbers",
+    "wrap",
+    "tab_width",
+    "mouse",
+    "smart_case",
+    "wrap_search",
+    "fancy_regex",
+    "max_matches",
+    "message_timeout",
+    "reload_prompt",
+    "mmap_threshold",
+    "spill_threshold",
+    "scrollback_lines",
+    "scrollback_bytes",
+    "highlight_max_size",
+    "highlight_max_line_len",
+    "max_line_len",
+    "strings_min_len",
+    "themes_dir",
+    "bat_assets",
+    "preprocessor",
+];
+
+const COLOR_KEYS: &[&str] = &[
+    "status_bar_fg",
+    "status_bar_bg",
+    "search_match_fg",
+    "search_match_bg",
+    "line_number_fg",
+    "search_group_bg",
+];
+
+const STATUSBAR_KEYS: &[&str] = &["left", "right"];
+
+const LOG_LEVEL_KEYS: &[&str] = &["error", "warn", "info", "debug", "trace"];
+
+const ALERT_KEYS: &[&str] = &["patterns", "bell", "flash", "command"];
+
+const FILETYPE_KEYS: &[&str] = &["wrap", "tab_width", "theme", "syntax", "highlighter", "line_numbers"];
+
+/// `[keys]` entries naming an action; any other entry binds a key to a command.
+const ACTION_KEYS: &[&str] = &[
+    "quit",
+    "scroll_down",
+    "scroll_up",
+    "half_page_down",
+    "half_page_up",
+    "full_page_down",
+    "full_page_up",
+    "goto_top",
+    "goto_bottom",
+    "prev_buffer",
+    "next_buffer",
+    "search_forward",
+    "search_backward",
+    "next_match",
+    "prev_match",
+    "toggle_search_wrap",
+    "toggle_numbers",
+    "toggle_wrap",
+    "follow_mode",
+    "enter_command",
+    "filter",
+    "visual",
+    "visual_char",
+    "set_mark",
+    "jump_mark",
+    "scroll_right",
+    "scroll_left",
+    "expand_hidden",
+    "collapse_hidden",
+    "save_view",
+    "log_level_filter",
+    "buffer_picker",
+    "move_buffer_left",
+    "move_buffer_right",
+    "alternate_buffer",
+    "jump_back",
+    "jump_forward",
+    "yank_line",
+    "yank_all",
+    "center_view",
+    "record_macro",
+    "play_macro",
+    "help",
+    "open_editor",
+    "toggle_blame",
+    "show_commit",
+    "toggle_deleted",
+    "reload",
+];
+
+/// Turn a TOML error into an issue on the line it points at.
+pub fn parse_error(content: &str, err: &toml::de::Error) -> Issue {
+    Issue {
+        line: err.span().map(|span| content[..span.start].matches('\n').count() + 1),
+        message: err.message().lines().collect::<Vec<_>>().join(", "),
+    }
+}
+
+/// Check config.toml, already parsed into `config`, for unknown sections
+/// and keys, key specs that do not parse, bad colors and log-level regexes.
+pub fn check(content: &str, config: &Config) -> Vec<Issue> {
+    let Ok(table) = content.parse::<toml::Table>() else { return Vec::new() };
+    let mut issues = Vec::new();
+    let mut issue = |section: Option<&str>, key: &str, message: String| {
+        issues.push(Issue { line: find_line(content, section, key), message });
+    };
+
+    for (name, value) in &table {
+        let Some(entries) = value.as_table() else {
+            issue(None, name, format!("unknown key '{}'", name));
+            continue;
+        };
+        let known = match name.as_str() {
+            "general" => GENERAL_KEYS,
+            "colors" => COLOR_KEYS,
+            "log_levels" => LOG_LEVEL_KEYS,
+            "statusbar" => STATUSBAR_KEYS,
+            "alerts" => ALERT_KEYS,
+            "keys" => {
+                check_keys(entries, &mut issue);
+                continue;
+            }
+            "filetype" => {
+                for (filetype, settings) in entries {
+                    let section = format!("filetype.{}", filetype);
+                    let keys = settings.as_table().into_iter().flat_map(|t| t.keys());
+                    for key in keys.filter(|k| !FILETYPE_KEYS.contains(&k.as_str())) {
+                        issue(Some(&section), key, format!("unknown key '{}.{}'", section, key));
+                    }
+                }
+                continue;
+            }
+            _ => {
+                issue(None, name, format!("unknown section [{}]", name));
+                continue;
+            }
+        };
+        for key in entries.keys().filter(|k| !known.contains(&k.as_str())) {
+            issue(Some(name), key, format!("unknown key '{}.{}'", name, key));
+        }
+    }
+
+    let colors = &config.colors;
+    let single = [
+        ("status_bar_fg", &colors.status_bar_fg),
+        ("status_bar_bg", &colors.status_bar_bg),
+        ("search_match_fg", &colors.search_match_fg),
+        ("search_match_bg", &colors.search_match_bg),
+        ("line_number_fg", &colors.line_number_fg),
+    ];
+    let groups = colors.search_group_bg.iter().map(|c| ("search_group_bg", c));
+    for (key, color) in single.into_iter().chain(groups) {
+        if parse_hex_color(color).is_none() {
+            issue(Some("colors"), key, format!("invalid color '{}' for colors.{} (expected \"#rrggbb\")", color, key));
+        }
+    }
+
+    #[cfg(not(feature = "tree-sitter"))]
+    for (filetype, settings) in &config.filetype {
+        if settings.highlighter == Some(crate::syntax::Engine::TreeSitter) {
+            let section = format!("filetype.{}", filetype);
+            issue(Some(&section), "highlighter", format!("{}.highlighter: built without tree-sitter (using syntect)", section));
+        }
+    }
+
+    let statusbar = &config.statusbar;
+    for (key, template) in [("left", &statusbar.left), ("right", &statusbar.right)] {
+        for name in crate::statusbar::unknown_fields(template) {
+            issue(Some("statusbar"), key, format!("unknown placeholder '{{{}}}' in statusbar.{}", name, key));
+        }
+    }
+
+    let levels = &config.log_levels;
+    for (key, re) in LOG_LEVEL_KEYS.iter().zip([&levels.error, &levels.warn, &levels.info, &levels.debug, &levels.trace]) {
+        if let Err(e) = regex::Regex::new(re) {
+            let reason = e.to_string().lines().last().unwrap_or_default().to_string();
+            issue(Some("log_levels"), key, format!("invalid regex for log_levels.{}: {}", key, reason));
+        }
+    }
+
+    for pattern in &config.alerts.patterns {
+        if let Err(e) = regex::Regex::new(pattern) {
+            let reason = e.to_string().lines().last().unwrap_or_default().to_string();
+            issue(Some("alerts"), "patterns", format!("invalid regex '{}' in alerts.patterns: {}", pattern, reason));
+        }
+    }
+    issues.sort_by_key(|issue| issue.line.unwrap_or(usize::MAX));
+    issues
+}
+
+fn check_keys(entries: &toml::Table, issue: &mut impl FnMut(Option<&str>, &str, String)) {
+    for (key, value) in entries {
+        let Some(value) = value.as_str() else { continue };
+        if ACTION_KEYS.contains(&key.as_str()) {
+            if parse_key_sequence(value).is_none() {
+                issue(Some("keys"), key, format!("invalid key '{}' for keys.{}", value, key));
+            }
+        } else if key == "lesskey" {
+            continue;
+        } else if !value.starts_with(':') {
+            // Command bindings look like `f5 = ":filter ERROR"`, so this is a misspelt action
+            issue(Some("keys"), key, format!("unknown action 'keys.{}'", key));
+        } else if parse_key_sequence(key).is_none() {
+            issue(Some("keys"), key, format!("invalid key '{}' for command '{}'", key, value));
+        }
+    }
+}
+
+/// The first issue for the status bar, pointing at `--check-config` when
+/// there are more.
+pub fn summary(issues: &[Issue]) -> Option<String> {
+    let first = issues.first()?;
+    Some(match issues.len() {
+        1 => first.to_string(),
+        n => format!("{} (+{} more, see some --check-config)", first, n - 1),
+    })
+}
+
+/// An issue if `theme` is not one the highlighter knows.
+pub fn check_theme(theme: &str, highlighter: &SyntaxHighlighter) -> Option<Issue> {
+    (!highlighter.has_theme(theme)).then(|| Issue {
+        line: None,
+        message: format!("unknown theme '{}' (using {})", theme, highlighter.theme_name()),
+    })
+}
+
+/// 1-based line of `key` in `[section]` (or before any section).
+pub fn find_line(content: &str, section: Option<&str>, key: &str) -> Option<usize> {
+    let mut current: Option<&str> = None;
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header.split(']').next().unwrap_or_default().trim();
+            if section.is_none() && name == key {
+                return Some(n + 1);
+            }
+            current = Some(name);
+            continue;
+        }
+        let Some((name, _)) = line.split_once('=') else { continue };
+        let name = name.trim().trim_matches(|c| c == '"' || c == '\'');
+        if current == section && name == key {
+            return Some(n + 1);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_reports_lines() {
+        let content = "[general]\ntabwidth = 8\n\n[colors]\nstatus_bar_fg = \"blue\"\n\n[keys]\nquit = \"ctrl+\"\nqiut = \"x\"\nf5 = \":filter ERROR\"\n\n[colours]\n";
+        let config: Config = toml::from_str(content).unwrap();
+        let found: Vec<(Option<usize>, String)> = check(content, &config).into_iter().map(|i| (i.line, i.message)).collect();
+        assert_eq!(
+            found,
+            vec![
+                (Some(2), "unknown key 'general.tabwidth'".to_string()),
+                (Some(5), "invalid color 'blue' for colors.status_bar_fg (expected \"#rrggbb\")".to_string()),
+                (Some(8), "invalid key 'ctrl+' for keys.quit".to_string()),
+                (Some(9), "unknown action 'keys.qiut'".to_string()),
+                (Some(12), "unknown section [colours]".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_example_config_is_clean() {
+        let content = include_str!("../config.example.toml");
+        let config: Config = toml::from_str(content).unwrap();
+        assert_eq!(check(content, &config), Vec::new());
+    }
+
+    #[test]
+    fn test_parse_error_line() {
+        let content = "[general]\nwrap = yes\n";
+        let err = toml::from_str::<Config>(content).unwrap_err();
+        assert_eq!(parse_error(content, &err).line, Some(2));
+    }
+}
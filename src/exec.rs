@@ -0,0 +1,128 @@
+//! `--exec`: run a command every few seconds and show its latest output,
+//! with what changed since the run before highlighted, like `watch -d`.
+
+use anyhow::Result;
+use similar::{Algorithm, ChangeTag, DiffOp, TextDiff};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::process::ExitStatus;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::shell;
+
+/// Byte ranges of each line that changed, by line index.
+pub type Changes = HashMap<usize, Vec<Range<usize>>>;
+
+/// A command run over and over on a background thread.
+pub struct Exec {
+    /// Time between the end of one run and the start of the next
+    pub interval: Duration,
+    /// Exit status of the latest run
+    pub status: Option<ExitStatus>,
+    /// What the latest run changed in the output
+    pub changes: Changes,
+    rx: mpsc::Receiver<Result<(Vec<u8>, ExitStatus)>>,
+}
+
+impl Exec {
+    /// Run `command` through `sh -c` every `interval`, starting one
+    /// interval from now (the first output is the caller's to show). The
+    /// thread stops once the `Exec` is dropped.
+    pub fn spawn(command: &str, interval: Duration) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let cmd = command.to_string();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            if tx.send(shell::run(&cmd, None)).is_err() {
+                break;
+            }
+        });
+        Exec {
+            interval,
+            status: None,
+            changes: Changes::new(),
+            rx,
+        }
+    }
+
+    /// The output of the latest run finished since the last call, if any.
+    pub fn poll(&mut self) -> Option<Result<(Vec<u8>, ExitStatus)>> {
+        let mut latest = None;
+        while let Ok(run) = self.rx.try_recv() {
+            latest = Some(run);
+        }
+        latest
+    }
+
+    /// Status bar flag: the interval, and the exit status if the latest run
+    /// failed, e.g. `every 2s, exit status: 1`.
+    pub fn label(&self) -> String {
+        let every = format!("every {}s", self.interval.as_secs_f64());
+        match self.status {
+            Some(status) if !status.success() => format!("{}, {}", every, status),
+            _ => every,
+        }
+    }
+}
+
+/// What changed from `old` to `new` lines: all of a line that was added,
+/// and the characters that differ in a line that was changed.
+pub fn changes(old: &[&str], new: &[&str]) -> Changes {
+    let mut changes = Changes::new();
+    for op in similar::capture_diff_slices(Algorithm::Myers, old, new) {
+        let (old_index, old_len, new_index, new_len) = match op {
+            DiffOp::Insert { old_index, new_index, new_len } => (old_index, 0, new_index, new_len),
+            DiffOp::Replace { old_index, old_len, new_index, new_len } => (old_index, old_len, new_index, new_len),
+            DiffOp::Equal { .. } | DiffOp::Delete { .. } => continue,
+        };
+        for (k, line) in new[new_index..new_index + new_len].iter().enumerate() {
+            // Lines added past the ones replaced are new throughout
+            let before = if k < old_len { old[old_index + k] } else { "" };
+            changes.insert(new_index + k, changed_chars(before, line));
+        }
+    }
+    changes
+}
+
+/// Byte ranges of `new` that are not in `old`, merged where they touch.
+fn changed_chars(old: &str, new: &str) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut at = 0;
+    for change in TextDiff::from_chars(old, new).iter_all_changes() {
+        let len = change.value().len();
+        match change.tag() {
+            ChangeTag::Delete => continue,
+            ChangeTag::Equal => {}
+            ChangeTag::Insert => match ranges.last_mut() {
+                Some(last) if last.end == at => last.end += len,
+                _ => ranges.push(at..at + len),
+            },
+        }
+        at += len;
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changes() {
+        let old = ["NAME   READY", "web-1  1/1", "web-2  0/1"];
+        let new = ["NAME   READY", "web-1  1/1", "web-2  1/1", "web-3  0/1"];
+        let found = changes(&old, &new);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[&2], vec![7..8]);
+        assert_eq!(found[&3], vec![0..10]);
+        assert!(changes(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_changed_chars() {
+        assert_eq!(changed_chars("up 3 min", "up 14 min"), vec![3..5]);
+        assert!(changed_chars("a", "a").is_empty());
+        assert_eq!(changed_chars("é1", "é2"), vec![2..3]);
+    }
+}
@@ -0,0 +1,289 @@
+use anyhow::{Context, Result};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Span;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::buffer::Buffer;
+use crate::overstrike;
+use crate::print;
+use crate::search::SearchPattern;
+use crate::syntax::{self, StyledSpan, SyntaxHighlighter};
+use crate::tabs;
+use crate::viewer;
+
+/// Write the given lines of `buf` to `path`, one per line, preserving the raw bytes.
+/// Returns the number of lines written.
+pub fn write_lines(buf: &Buffer, lines: impl IntoIterator<Item = usize>, path: &Path) -> Result<usize> {
+    let file = File::create(path).with_context(|| format!("Cannot create '{}'", path.display()))?;
+    let mut out = BufWriter::new(file);
+    let mut written = 0;
+    for i in lines {
+        if let Some(bytes) = buf.line_bytes(i) {
+            out.write_all(bytes)?;
+            out.write_all(b"\n")?;
+            written += 1;
+        }
+    }
+    out.flush().with_context(|| format!("Failed to write '{}'", path.display()))?;
+    Ok(written)
+}
+
+/// Write annotated bookmarks as `name:line: note`, each followed by the
+/// noted line indented for context. Returns the number of notes written.
+pub fn write_notes(buf: &Buffer, notes: &BTreeMap<usize, String>, path: &Path) -> Result<usize> {
+    let file = File::create(path).with_context(|| format!("Cannot create '{}'", path.display()))?;
+    let mut out = BufWriter::new(file);
+    let name = buf.path.as_ref().map_or_else(|| buf.name.clone(), |p| p.display().to_string());
+    for (line, note) in notes {
+        writeln!(out, "{}:{}: {}", name, line + 1, note)?;
+        if let Some(text) = buf.get_line(*line) {
+            writeln!(out, "    {}", text)?;
+        }
+    }
+    out.flush().with_context(|| format!("Failed to write '{}'", path.display()))?;
+    Ok(notes.len())
+}
+
+/// How yanked lines are laid out on the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum YankFormat {
+    /// The lines as they are
+    Plain,
+    /// Each line prefixed with `path:line:`
+    Refs,
+    /// A Markdown code block under a `path:start-end` header
+    Markdown,
+}
+
+/// Join lines `start..=end` of `buf` into clipboard text in the given format.
+pub fn format_lines(buf: &Buffer, (start, end): (usize, usize), format: YankFormat) -> String {
+    let name = buf.path.as_ref().map_or_else(|| buf.name.clone(), |p| p.display().to_string());
+    let lines = (start..=end).filter_map(|i| buf.get_line(i).map(|text| (i, text)));
+    match format {
+        YankFormat::Plain => lines.map(|(_, text)| text).collect::<Vec<_>>().join("\n"),
+        YankFormat::Refs => lines
+            .map(|(i, text)| format!("{}:{}: {}", name, i + 1, text))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        YankFormat::Markdown => {
+            let lang = buf
+                .path
+                .as_ref()
+                .and_then(|p| p.extension())
+                .map_or(String::new(), |e| e.to_string_lossy().to_string());
+            let header = if start == end {
+                format!("`{}:{}`", name, start + 1)
+            } else {
+                format!("`{}:{}-{}`", name, start + 1, end + 1)
+            };
+            let body: Vec<&str> = lines.map(|(_, text)| text).collect();
+            format!("{}\n```{}\n{}\n```", header, lang, body.join("\n"))
+        }
+    }
+}
+
+/// What a styled export (`:export html`) includes besides the text.
+pub struct StyledExport<'a> {
+    pub highlighter: &'a SyntaxHighlighter,
+    pub line_numbers: bool,
+    /// Tabs are expanded to spaces up to the next multiple of this
+    pub tab_width: u8,
+    /// Highlight the matches of this search
+    pub search: Option<&'a SearchPattern>,
+    /// Notes to show after their lines
+    pub notes: Option<&'a BTreeMap<usize, String>>,
+}
+
+/// The given lines of `buf` as styled spans: syntax colors with search
+/// matches on top, the way the viewer draws them.
+fn styled_lines<'a>(
+    buf: &'a Buffer,
+    lines: impl IntoIterator<Item = usize> + 'a,
+    opts: &'a StyledExport,
+) -> impl Iterator<Item = (usize, Vec<Span<'static>>)> + 'a {
+    let highlight = opts.highlighter.highlights(buf) && !buf.is_diff;
+    let mut line_runs = highlight.then(|| opts.highlighter.line_runs(buf));
+    // Runs of line `next - 1`; the lines asked for come in order
+    let mut next = 0;
+    let mut runs = None;
+    let match_style = Style::default().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD);
+    lines.into_iter().filter_map(move |i| {
+        let bytes = buf.line_bytes(i)?;
+        let text = String::from_utf8_lossy(bytes);
+        while next <= i {
+            runs = line_runs.as_mut().and_then(|runs| runs.next());
+            next += 1;
+        }
+        let spans = if buf.is_diff {
+            vec![StyledSpan { text: text.to_string(), style: viewer::diff_line_style(&text) }]
+        } else if let Some(runs) = buf.overstrike_runs(i) {
+            overstrike::spans(&text, runs)
+        } else if let Some(runs) = runs.as_ref().filter(|_| next == i + 1) {
+            syntax::spans(runs, &text)
+        } else {
+            vec![StyledSpan { text: text.to_string(), style: Style::default() }]
+        };
+        let matches: Vec<_> = match opts.search {
+            Some(pattern) => pattern.find_iter(text.as_bytes()).map(|r| (r, match_style)).collect(),
+            None => Vec::new(),
+        };
+        let spans = viewer::merge_highlights(spans, &matches);
+        Some((i, tabs::expand(spans, 0, opts.tab_width as usize)))
+    })
+}
+
+/// Format of a styled export.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    /// A standalone HTML page with inline styles
+    Html,
+    /// Text with ANSI color escapes, for terminals
+    Ansi,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "html" => Some(ExportFormat::Html),
+            "ansi" => Some(ExportFormat::Ansi),
+            _ => None,
+        }
+    }
+}
+
+/// Write the given lines of `buf` to `path` (`-` for stdout) in `format`.
+/// Returns the number of lines written.
+pub fn write_styled(
+    buf: &Buffer,
+    lines: impl IntoIterator<Item = usize>,
+    opts: &StyledExport,
+    format: ExportFormat,
+    path: &Path,
+) -> Result<usize> {
+    let mut out: BufWriter<Box<dyn Write>> = if path == Path::new("-") {
+        BufWriter::new(Box::new(std::io::stdout().lock()))
+    } else {
+        let file = File::create(path).with_context(|| format!("Cannot create '{}'", path.display()))?;
+        BufWriter::new(Box::new(file))
+    };
+    let written = match format {
+        ExportFormat::Html => write_html(&mut out, buf, lines, opts),
+        ExportFormat::Ansi => write_ansi(&mut out, buf, lines, opts),
+    }
+    .and_then(|n| out.flush().map(|_| n));
+    written.with_context(|| format!("Failed to write '{}'", path.display()))
+}
+
+/// Lines as ANSI-colored text, with a dim line-number gutter if enabled.
+fn write_ansi(out: &mut impl Write, buf: &Buffer, lines: impl IntoIterator<Item = usize>, opts: &StyledExport) -> std::io::Result<usize> {
+    let num_width = buf.line_count().max(1).to_string().len();
+    let mut written = 0;
+    for (i, spans) in styled_lines(buf, lines, opts) {
+        if opts.line_numbers {
+            let num = format!("{:>w$} \u{2502} ", i + 1, w = num_width);
+            print::write_span(out, &num, Style::default().fg(Color::DarkGray), true)?;
+        }
+        for span in spans {
+            print::write_span(out, &span.content, span.style, true)?;
+        }
+        if let Some(note) = opts.notes.and_then(|notes| notes.get(&i)) {
+            let style = Style::default().fg(Color::Yellow).add_modifier(Modifier::ITALIC);
+            print::write_span(out, &format!("  \u{270e} {}", note), style, true)?;
+        }
+        writeln!(out)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Lines as a standalone HTML page with inline styles.
+fn write_html(out: &mut impl Write, buf: &Buffer, lines: impl IntoIterator<Item = usize>, opts: &StyledExport) -> std::io::Result<usize> {
+    let name = buf.path.as_ref().map_or_else(|| buf.name.clone(), |p| p.display().to_string());
+    let (bg, fg) = opts.highlighter.theme_colors();
+    let bg = bg.and_then(css_color).unwrap_or_else(|| "#2b303b".to_string());
+    let fg = fg.and_then(css_color).unwrap_or_else(|| "#c0c5ce".to_string());
+    let num_width = buf.line_count().max(1).to_string().len();
+
+    writeln!(out, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>{}</title>", escape_html(&name))?;
+    writeln!(out, "<style>")?;
+    writeln!(out, "body {{ margin: 0; background: {}; color: {}; }}", bg, fg)?;
+    writeln!(out, "pre {{ margin: 0; padding: 1em; font-family: ui-monospace, Menlo, Consolas, monospace; }}")?;
+    writeln!(out, ".ln {{ color: #65737e; user-select: none; }}")?;
+    writeln!(out, ".note {{ color: #ebcb8b; font-style: italic; }}")?;
+    writeln!(out, "</style>\n</head>\n<body>\n<pre>")?;
+    let mut written = 0;
+    for (i, spans) in styled_lines(buf, lines, opts) {
+        if opts.line_numbers {
+            write!(out, "<span class=\"ln\">{:>w$} </span>", i + 1, w = num_width)?;
+        }
+        for span in spans {
+            let css = css_style(span.style);
+            if css.is_empty() {
+                write!(out, "{}", escape_html(&span.content))?;
+            } else {
+                write!(out, "<span style=\"{}\">{}</span>", css, escape_html(&span.content))?;
+            }
+        }
+        if let Some(note) = opts.notes.and_then(|notes| notes.get(&i)) {
+            write!(out, "  <span class=\"note\">\u{270e} {}</span>", escape_html(note))?;
+        }
+        writeln!(out)?;
+        written += 1;
+    }
+    writeln!(out, "</pre>\n</body>\n</html>")?;
+    Ok(written)
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Inline CSS for `style`; empty for the default style.
+fn css_style(style: Style) -> String {
+    let mut css = Vec::new();
+    if let Some(fg) = style.fg.and_then(css_color) {
+        css.push(format!("color:{}", fg));
+    }
+    if let Some(bg) = style.bg.and_then(css_color) {
+        css.push(format!("background:{}", bg));
+    }
+    if style.add_modifier.contains(Modifier::BOLD) {
+        css.push("font-weight:bold".to_string());
+    }
+    if style.add_modifier.contains(Modifier::ITALIC) {
+        css.push("font-style:italic".to_string());
+    }
+    if style.add_modifier.contains(Modifier::UNDERLINED) {
+        css.push("text-decoration:underline".to_string());
+    }
+    css.join(";")
+}
+
+/// CSS color for a terminal color (xterm values for the named ones).
+fn css_color(color: Color) -> Option<String> {
+    let (r, g, b) = match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Black => (0, 0, 0),
+        Color::Red => (205, 0, 0),
+        Color::Green => (0, 205, 0),
+        Color::Yellow => (205, 205, 0),
+        Color::Blue => (0, 0, 238),
+        Color::Magenta => (205, 0, 205),
+        Color::Cyan => (0, 205, 205),
+        Color::Gray => (229, 229, 229),
+        Color::DarkGray => (127, 127, 127),
+        Color::LightRed => (255, 0, 0),
+        Color::LightGreen => (0, 255, 0),
+        Color::LightYellow => (255, 255, 0),
+        Color::LightBlue => (92, 92, 255),
+        Color::LightMagenta => (255, 0, 255),
+        Color::LightCyan => (0, 255, 255),
+        Color::White => (255, 255, 255),
+        Color::Reset | Color::Indexed(_) => return None,
+    };
+    Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+}
@@ -0,0 +1,275 @@
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc;
+
+use crate::buffer::TextSnapshot;
+
+/// Batch of kept line indices sent from the async filter thread.
+pub enum FilterBatch {
+    Progress {
+        indices: Vec<usize>,
+        lines_scanned: usize,
+        total_lines: usize,
+    },
+    Done {
+        indices: Vec<usize>,
+    },
+}
+
+/// How a filter pattern treats letter case. Cycled with Tab while typing a filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseMode {
+    /// Follow `smart_case`: case-insensitive unless the pattern has an uppercase letter
+    #[default]
+    Smart,
+    /// Always case-sensitive
+    Sensitive,
+    /// Always case-insensitive
+    Insensitive,
+}
+
+impl CaseMode {
+    pub fn next(self) -> Self {
+        match self {
+            CaseMode::Smart => CaseMode::Sensitive,
+            CaseMode::Sensitive => CaseMode::Insensitive,
+            CaseMode::Insensitive => CaseMode::Smart,
+        }
+    }
+
+    /// Whether `query` should be matched ignoring case under this mode.
+    pub fn ignore_case(self, query: &str, smart_case: bool) -> bool {
+        match self {
+            CaseMode::Smart => crate::search::smart_case_insensitive(query, smart_case),
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+        }
+    }
+
+    /// Short tag shown in the filter prompt; empty for the default mode.
+    pub fn tag(self) -> &'static str {
+        match self {
+            CaseMode::Smart => "",
+            CaseMode::Sensitive => "[match case] ",
+            CaseMode::Insensitive => "[ignore case] ",
+        }
+    }
+}
+
+/// One row of the viewport: a buffer line, or a placeholder for lines hidden by a filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViewRow {
+    Line(usize),
+    Hidden { start: usize, count: usize },
+    /// Line `index` of the text git says was deleted after line `at`
+    Deleted { at: usize, index: usize },
+}
+
+/// An active line filter over the current buffer.
+#[derive(Debug, Clone)]
+pub struct LineFilter {
+    /// The regex as typed (without the `!` prefix)
+    pub query: String,
+    /// True when matching lines are hidden instead of kept
+    pub invert: bool,
+    /// Display name for canned filters (e.g. "level>=WARN"), shown instead of the regex
+    pub name: Option<String>,
+    /// Case handling the regex was compiled with
+    pub case: CaseMode,
+    /// Indices of the lines that remain visible, in ascending order
+    pub indices: Vec<usize>,
+    /// Display rows: kept lines interleaved with "N lines hidden" placeholders
+    pub rows: Vec<ViewRow>,
+    /// Start lines of hidden gaps the user has temporarily expanded
+    expanded: Vec<usize>,
+    /// Line count of the buffer the filter was built for
+    total_lines: usize,
+}
+
+impl LineFilter {
+    pub fn new(query: String, invert: bool, indices: Vec<usize>, total_lines: usize) -> Self {
+        let mut filter = Self {
+            query,
+            invert,
+            name: None,
+            case: CaseMode::Smart,
+            indices,
+            rows: Vec::new(),
+            expanded: Vec::new(),
+            total_lines,
+        };
+        filter.rebuild_rows();
+        filter
+    }
+
+    /// Build a filter by scanning `snapshot` on the calling thread. Used when
+    /// restoring a session, where the result is needed before the first draw.
+    pub fn scan(query: String, invert: bool, re: &Regex, snapshot: &TextSnapshot) -> Self {
+        let total = snapshot.line_count();
+        let indices = (0..total)
+            .filter(|&i| snapshot.line_bytes(i).is_some_and(|l| re.is_match(l) != invert))
+            .collect();
+        Self::new(query, invert, indices, total)
+    }
+
+    /// Bring the filter up to date with lines appended to the buffer: the
+    /// old last line is matched again, as it may have grown, and so are the
+    /// new ones. Returns false if the buffer shrank instead, when only a
+    /// full scan will do.
+    pub fn extend(&mut self, re: &Regex, snapshot: &TextSnapshot) -> bool {
+        let total = snapshot.line_count();
+        if total < self.total_lines {
+            return false;
+        }
+        let from = self.total_lines.saturating_sub(1);
+        self.indices.truncate(self.indices.partition_point(|&i| i < from));
+        self.indices
+            .extend((from..total).filter(|&i| snapshot.line_bytes(i).is_some_and(|l| re.is_match(l) != self.invert)));
+        self.total_lines = total;
+        self.rebuild_rows();
+        true
+    }
+
+    /// Recompute `rows` from the kept indices and the expanded gaps.
+    fn rebuild_rows(&mut self) {
+        let mut rows = Vec::with_capacity(self.indices.len() + 1);
+        let mut next = 0;
+        for &i in &self.indices {
+            if i > next {
+                self.push_gap(&mut rows, next, i - next);
+            }
+            rows.push(ViewRow::Line(i));
+            next = i + 1;
+        }
+        if self.total_lines > next {
+            self.push_gap(&mut rows, next, self.total_lines - next);
+        }
+        self.rows = rows;
+    }
+
+    fn push_gap(&self, rows: &mut Vec<ViewRow>, start: usize, count: usize) {
+        if self.expanded.contains(&start) {
+            rows.extend((start..start + count).map(ViewRow::Line));
+        } else {
+            rows.push(ViewRow::Hidden { start, count });
+        }
+    }
+
+    /// Reveal the hidden gap starting at `start`.
+    pub fn expand_gap(&mut self, start: usize) {
+        if !self.expanded.contains(&start) {
+            self.expanded.push(start);
+            self.rebuild_rows();
+        }
+    }
+
+    /// Hide all temporarily expanded gaps again. Returns false if nothing was expanded.
+    pub fn collapse_all(&mut self) -> bool {
+        if self.expanded.is_empty() {
+            return false;
+        }
+        self.expanded.clear();
+        self.rebuild_rows();
+        true
+    }
+
+    /// Index into `rows` of the row showing `line` (or the placeholder hiding it).
+    pub fn row_of_line(&self, line: usize) -> usize {
+        let idx = self.rows.partition_point(|row| match *row {
+            ViewRow::Line(i) => i < line,
+            ViewRow::Hidden { start, count } => start + count <= line,
+            ViewRow::Deleted { at, .. } => at < line,
+        });
+        idx.min(self.rows.len().saturating_sub(1))
+    }
+
+    /// True when `line` is shown as its own row (kept, or inside an expanded gap).
+    pub fn shows_line(&self, line: usize) -> bool {
+        self.rows.get(self.row_of_line(line)) == Some(&ViewRow::Line(line))
+    }
+
+    /// The query as shown to the user, with `!` for inverted filters.
+    pub fn label(&self) -> String {
+        if let Some(name) = &self.name {
+            name.clone()
+        } else if self.invert {
+            format!("!{}", self.query)
+        } else {
+            self.query.clone()
+        }
+    }
+}
+
+/// A filter being computed on a background thread. The viewport keeps showing
+/// the previous state until the job finishes and its `LineFilter` is installed.
+pub struct FilterJob {
+    pub query: String,
+    pub invert: bool,
+    /// Display name carried over to the resulting `LineFilter`
+    pub name: Option<String>,
+    /// Case handling carried over to the resulting `LineFilter`
+    pub case: CaseMode,
+    /// Kept line indices received so far
+    pub indices: Vec<usize>,
+    /// (lines scanned, total lines)
+    pub progress: (usize, usize),
+    pub rx: mpsc::Receiver<FilterBatch>,
+}
+
+impl FilterJob {
+    /// Scan `snapshot` on a background thread, keeping lines whose match state
+    /// differs from `invert`. Dropping the job makes the thread stop at its next batch.
+    pub fn spawn(query: String, invert: bool, re: Regex, snapshot: TextSnapshot) -> Self {
+        let total = snapshot.line_count();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut batch = Vec::new();
+            for line_idx in 0..total {
+                let keep = snapshot
+                    .line_bytes(line_idx)
+                    .map(|l| re.is_match(l) != invert)
+                    .unwrap_or(false);
+                if keep {
+                    batch.push(line_idx);
+                }
+                if line_idx % 10_000 == 9_999 {
+                    let sent = tx.send(FilterBatch::Progress {
+                        indices: std::mem::take(&mut batch),
+                        lines_scanned: line_idx + 1,
+                        total_lines: total,
+                    });
+                    if sent.is_err() {
+                        // Receiver dropped: the filter was cancelled
+                        return;
+                    }
+                }
+            }
+            let _ = tx.send(FilterBatch::Done { indices: batch });
+        });
+        Self {
+            query,
+            invert,
+            name: None,
+            case: CaseMode::Smart,
+            indices: Vec::new(),
+            progress: (0, total),
+            rx,
+        }
+    }
+
+    /// The query as shown to the user while the job runs.
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None if self.invert => format!("!{}", self.query),
+            None => self.query.clone(),
+        }
+    }
+
+    /// Percentage of lines scanned so far.
+    pub fn percent(&self) -> u16 {
+        let (scanned, total) = self.progress;
+        (scanned * 100).checked_div(total).unwrap_or(100) as u16
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc;
+
+use crate::buffer::{GitChange, GitChanges};
+
+/// A `:log` buffer: the history of the file at `path`, one commit per
+/// line starting with its short hash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitLog {
+    pub path: PathBuf,
+    /// Line of the commit Enter opens
+    pub selected: usize,
+}
+
+/// Where a repository stands: its branch, how far it is from its
+/// upstream and whether it has uncommitted changes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepoStatus {
+    /// Branch name, or the short commit hash when detached
+    pub branch: String,
+    pub ahead: usize,
+    pub behind: usize,
+    pub dirty: bool,
+}
+
+impl RepoStatus {
+    /// Status bar text: `main`, then `*` if dirty and `↑n`/`↓n` when ahead
+    /// of or behind the upstream.
+    pub fn label(&self) -> String {
+        let mut label = self.branch.clone();
+        if self.dirty {
+            label.push('*');
+        }
+        if self.ahead > 0 || self.behind > 0 {
+            label.push(' ');
+        }
+        if self.ahead > 0 {
+            label.push_str(&format!("\u{2191}{}", self.ahead));
+        }
+        if self.behind > 0 {
+            label.push_str(&format!("\u{2193}{}", self.behind));
+        }
+        label
+    }
+}
+
+/// Parse the output of `git status --porcelain=v2 --branch`: `# branch.*`
+/// headers, then a line per changed or untracked file.
+pub fn parse_status(porcelain: &str) -> RepoStatus {
+    let mut status = RepoStatus::default();
+    let mut oid = "";
+    for line in porcelain.lines() {
+        match line.strip_prefix("# ").and_then(|h| h.split_once(' ')) {
+            Some(("branch.oid", value)) => oid = value,
+            Some(("branch.head", value)) => status.branch = value.to_string(),
+            Some(("branch.ab", value)) => {
+                for n in value.split(' ') {
+                    if let Some(n) = n.strip_prefix('+') {
+                        status.ahead = n.parse().unwrap_or(0);
+                    } else if let Some(n) = n.strip_prefix('-') {
+                        status.behind = n.parse().unwrap_or(0);
+                    }
+                }
+            }
+            Some(_) => {}
+            None => status.dirty |= !line.is_empty() && !line.starts_with('#'),
+        }
+    }
+    if status.branch == "(detached)" {
+        status.branch = oid[..oid.len().min(7)].to_string();
+    }
+    status
+}
+
+/// Run `git status` for the repository holding `path` on a background
+/// thread. Sends None if it is not in one.
+pub fn spawn_status(path: &Path) -> mpsc::Receiver<Option<RepoStatus>> {
+    let (tx, rx) = mpsc::channel();
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        let status = run(&path, &["status", "--porcelain=v2", "--branch", "--untracked-files=no"], None);
+        let _ = tx.send(status.ok().map(|out| parse_status(&String::from_utf8_lossy(&out))));
+    });
+    rx
+}
+
+/// Run `git diff HEAD` on `path` on a background thread, for the gutter.
+pub fn spawn_changes(path: &Path) -> mpsc::Receiver<Result<GitDiff>> {
+    let (tx, rx) = mpsc::channel();
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        let diff = run(&path, &["diff", "HEAD", "--unified=0", "--"], Some(&path));
+        let _ = tx.send(diff.map(|out| parse_changes(&out)));
+    });
+    rx
+}
+
+/// What `git diff HEAD` says about a file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GitDiff {
+    pub changes: GitChanges,
+    /// The lines deleted at each `GitChange::Deleted` marker
+    pub deleted: HashMap<usize, Vec<String>>,
+}
+
+/// Parse `git diff --unified=0` into the change of each line of the new
+/// file; a deletion is marked on the line before it, which also keeps the
+/// deleted text.
+pub fn parse_changes(stdout: &[u8]) -> GitDiff {
+    let mut diff = GitDiff::default();
+    let changes = &mut diff.changes;
+    let text = match std::str::from_utf8(stdout) {
+        Ok(t) => t,
+        Err(_) => return diff,
+    };
+
+    // Marker line of the deletion hunk being read
+    let mut deleting = None;
+    for line in text.lines() {
+        if !line.starts_with("@@") {
+            if let (Some(at), Some(removed)) = (deleting, line.strip_prefix('-')) {
+                diff.deleted.entry(at).or_default().push(removed.to_string());
+            }
+            continue;
+        }
+        deleting = None;
+        // Parse @@ -old[,count] +new[,count] @@
+        // Example: @@ -10,5 +10,7 @@
+        let rest = &line[2..];
+        let end = rest.find("@@").unwrap_or(rest.len());
+        let hunk_header = rest[..end].trim();
+
+        // Split into old/new parts
+        let parts: Vec<&str> = hunk_header.split_whitespace().collect();
+        if parts.len() < 2 {
+            continue;
+        }
+        let new_part = parts.iter().find(|p| p.starts_with('+'));
+        if let Some(new_range) = new_part {
+            let range_str = &new_range[1..]; // strip leading '+'
+            let (start, count) = parse_range(range_str);
+            if count == 0 {
+                // Deletion at line `start`
+                if start > 0 {
+                    changes.entry(start - 1).or_insert(GitChange::Deleted);
+                    deleting = Some(start - 1);
+                }
+            } else {
+                let old_part = parts.iter().find(|p| p.starts_with('-'));
+                let (_, old_count) = old_part
+                    .map(|p| parse_range(&p[1..]))
+                    .unwrap_or((0, 0));
+
+                let tag = if old_count == 0 {
+                    GitChange::Added
+                } else {
+                    GitChange::Modified
+                };
+                for line_idx in start..(start + count) {
+                    if line_idx > 0 {
+                        changes.entry(line_idx - 1).or_insert(tag);
+                    }
+                }
+            }
+        }
+    }
+    diff
+}
+
+fn parse_range(s: &str) -> (usize, usize) {
+    if let Some(comma) = s.find(',') {
+        let start = s[..comma].parse().unwrap_or(1);
+        let count = s[comma + 1..].parse().unwrap_or(1);
+        (start, count)
+    } else {
+        let start = s.parse().unwrap_or(1);
+        (start, 1)
+    }
+}
+
+/// The commits that touched `path`, newest first, one per line:
+/// short hash, date, author and subject. Follows renames.
+pub fn log(path: &Path) -> Result<Vec<u8>> {
+    run(path, &["log", "--follow", "--date=short", "--format=%h %ad %<(16,trunc)%an %s", "--"], Some(path))
+}
+
+/// The content of `path` as of commit `rev`.
+pub fn show_file(path: &Path, rev: &str) -> Result<Vec<u8>> {
+    let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+    // `./` makes the path relative to the file's directory, not the repository root
+    run(path, &["show", &format!("{}:./{}", rev, name)], None)
+}
+
+/// Run git in the directory of `path`, with the file name of `file` as the
+/// last argument if given. Returns its output, or its first line of errors
+/// if it fails.
+pub fn run(path: &Path, args: &[&str], file: Option<&Path>) -> Result<Vec<u8>> {
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut cmd = Command::new("git");
+    cmd.args(args).current_dir(dir);
+    if let Some(file) = file {
+        cmd.arg(file.file_name().unwrap_or(file.as_os_str()));
+    }
+    let output = cmd.output().context("Cannot run git")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("{}", stderr.lines().next().unwrap_or("git failed"));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_status() {
+        let porcelain = "\
+# branch.oid 0123456789abcdef0123456789abcdef01234567
+# branch.head main
+# branch.upstream origin/main
+# branch.ab +2 -0
+1 .M N... 100644 100644 100644 aaaa bbbb src/main.rs
+";
+        let status = parse_status(porcelain);
+        assert_eq!(status, RepoStatus { branch: "main".to_string(), ahead: 2, behind: 0, dirty: true });
+        assert_eq!(status.label(), "main* \u{2191}2");
+
+        let detached = parse_status("# branch.oid 0123456789abcdef\n# branch.head (detached)\n");
+        assert_eq!(detached.label(), "0123456");
+        assert!(!detached.dirty);
+    }
+
+    #[test]
+    fn test_parse_changes() {
+        let diff = b"\
+@@ -3,0 +4,2 @@
++new
++new
+@@ -10 +12 @@
+-old
++changed
+@@ -20,2 +21,0 @@
+-gone
+-gone
+";
+        let GitDiff { changes, deleted } = parse_changes(diff);
+        assert_eq!(changes.get(&3), Some(&GitChange::Added));
+        assert_eq!(changes.get(&4), Some(&GitChange::Added));
+        assert_eq!(changes.get(&11), Some(&GitChange::Modified));
+        assert_eq!(changes.get(&20), Some(&GitChange::Deleted));
+        assert_eq!(changes.len(), 4);
+        assert_eq!(deleted.get(&20), Some(&vec!["gone".to_string(), "gone".to_string()]));
+        assert_eq!(deleted.len(), 1);
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How much of a file is checked when guessing its encoding.
+const ENCODING_SAMPLE: usize = 1 << 20;
+
+/// What `:info` shows about a buffer, worked out when it is loaded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileInfo {
+    /// Size of the file on disk, before decompression or preprocessing
+    pub size: Option<u64>,
+    pub modified: Option<SystemTime>,
+    pub encoding: &'static str,
+    pub line_endings: LineEndings,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LineEndings {
+    /// A single line without a newline, or nothing at all
+    #[default]
+    None,
+    Lf,
+    Crlf,
+    Mixed { crlf: usize, lf: usize },
+}
+
+impl fmt::Display for LineEndings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LineEndings::None => write!(f, "none"),
+            LineEndings::Lf => write!(f, "LF"),
+            LineEndings::Crlf => write!(f, "CRLF"),
+            LineEndings::Mixed { crlf, lf } => write!(f, "mixed ({} CRLF, {} LF)", crlf, lf),
+        }
+    }
+}
+
+impl FileInfo {
+    /// Metadata of the file at `path` (if any), and the encoding and line
+    /// endings of `data` as loaded, whose lines start at `line_offsets`.
+    pub fn detect(path: Option<&Path>, data: &[u8], line_offsets: &[usize]) -> Self {
+        let metadata = path.and_then(|p| std::fs::metadata(p).ok());
+        FileInfo {
+            size: metadata.as_ref().map(|m| m.len()),
+            modified: metadata.and_then(|m| m.modified().ok()),
+            encoding: encoding(data),
+            line_endings: line_endings(data, line_offsets),
+        }
+    }
+}
+
+/// Best guess at the encoding of `data`, from a byte order mark or the
+/// first `ENCODING_SAMPLE` bytes.
+pub fn encoding(data: &[u8]) -> &'static str {
+    if data.starts_with(b"\xef\xbb\xbf") {
+        return "UTF-8 with BOM";
+    }
+    if data.starts_with(b"\xff\xfe") {
+        return "UTF-16LE";
+    }
+    if data.starts_with(b"\xfe\xff") {
+        return "UTF-16BE";
+    }
+    let sample = &data[..data.len().min(ENCODING_SAMPLE)];
+    if sample.contains(&0) {
+        return "binary";
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) if sample.is_ascii() => "ASCII",
+        Ok(_) => "UTF-8",
+        // The sample ends partway through a character
+        Err(e) if e.error_len().is_none() && sample.len() < data.len() => "UTF-8",
+        Err(_) => "8-bit (not UTF-8)",
+    }
+}
+
+/// Which newlines end the lines of `data`.
+pub fn line_endings(data: &[u8], line_offsets: &[usize]) -> LineEndings {
+    let ends = line_offsets.iter().skip(1).copied().chain(data.ends_with(b"\n").then_some(data.len()));
+    let (mut crlf, mut lf) = (0, 0);
+    for end in ends {
+        if end >= 2 && data[end - 2] == b'\r' {
+            crlf += 1;
+        } else {
+            lf += 1;
+        }
+    }
+    match (crlf, lf) {
+        (0, 0) => LineEndings::None,
+        (0, _) => LineEndings::Lf,
+        (_, 0) => LineEndings::Crlf,
+        (crlf, lf) => LineEndings::Mixed { crlf, lf },
+    }
+}
+
+/// `bytes` in binary units: `512 B`, `1.2 KiB`, `4.7 GiB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// `time` as `YYYY-MM-DD HH:MM:SS UTC`.
+pub fn format_time(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+    let (days, rest) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(encoding(b"plain"), "ASCII");
+        assert_eq!(encoding("caf\u{e9}".as_bytes()), "UTF-8");
+        assert_eq!(encoding(b"caf\xe9"), "8-bit (not UTF-8)");
+        assert_eq!(encoding(b"\xef\xbb\xbfhi"), "UTF-8 with BOM");
+        assert_eq!(encoding(b"\xff\xfeh\0i\0"), "UTF-16LE");
+        assert_eq!(encoding(b"a\0b"), "binary");
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let offsets = |data: &[u8]| {
+            let mut offsets = vec![0];
+            offsets.extend(data.iter().enumerate().filter(|&(i, &b)| b == b'\n' && i + 1 < data.len()).map(|(i, _)| i + 1));
+            offsets
+        };
+        let check = |data: &[u8]| line_endings(data, &offsets(data));
+        assert_eq!(check(b"one"), LineEndings::None);
+        assert_eq!(check(b"one\ntwo\n"), LineEndings::Lf);
+        assert_eq!(check(b"one\r\ntwo\r\n"), LineEndings::Crlf);
+        assert_eq!(check(b"one\r\ntwo\nthree\n"), LineEndings::Mixed { crlf: 1, lf: 2 });
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(512), "512 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(UNIX_EPOCH), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_time(UNIX_EPOCH + Duration::from_secs(951_782_400 + 3661)), "2000-02-29 01:01:01 UTC");
+    }
+}
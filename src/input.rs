@@ -1,6 +1,11 @@
-use crate::app::{App, Mode};
-use crate::keymap::Action;
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
+use crate::app::{App, Bookmark, LastVisual, Mode};
+use crate::export::YankFormat;
+use crate::keymap::{Action, KeyLookup};
+use crate::filter::CaseMode;
+use crate::motion;
+use crate::paths::{self, Completion};
+use crate::range;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 
 /// Process a single crossterm event and mutate app state accordingly.
 pub fn handle_event(app: &mut App, event: Event) {
@@ -17,13 +22,29 @@ pub fn handle_event(app: &mut App, event: Event) {
 }
 
 fn handle_key(app: &mut App, key: KeyEvent) {
+    if !app.replaying {
+        if let Some((_, keys)) = app.recording.as_mut() {
+            if app.pending_keys.is_empty() {
+                app.chord_start = keys.len();
+            }
+            keys.push(key);
+        }
+    }
     match &app.mode {
         Mode::Normal => handle_normal_key(app, key),
         Mode::SearchInput { .. } => handle_search_key(app, key),
         Mode::CommandInput { .. } => handle_command_key(app, key),
         Mode::Follow => handle_follow_key(app, key),
         Mode::FilterInput { .. } => handle_filter_key(app, key),
+        Mode::BufferPicker { .. } => handle_picker_key(app, key),
+        Mode::MarksList { .. } => handle_marks_key(app, key),
+        Mode::Help { .. } => handle_help_key(app, key),
+        Mode::MatchList { .. } => handle_match_list_key(app, key),
+        Mode::Info | Mode::Messages => handle_info_key(app, key),
         Mode::Visual { .. } => handle_visual_key(app, key),
+        Mode::PipeInput { .. } => handle_pipe_key(app, key),
+        Mode::VisualChar { .. } => handle_visual_char_key(app, key),
+        Mode::ReloadPrompt => handle_reload_prompt_key(app, key),
     }
 }
 
@@ -36,8 +57,25 @@ fn handle_normal_key(app: &mut App, key: KeyEvent) {
                     app.marks.insert(c, app.top_line);
                     app.status_message = Some(format!("Mark '{}' set", c));
                 }
+                'L' => app.apply_log_level_filter(c),
+                'Q' => {
+                    if c.is_ascii_alphanumeric() {
+                        app.recording = Some((c, Vec::new()));
+                        app.status_message = Some(format!("Recording @{}", c));
+                    } else {
+                        app.status_message = Some(format!("Invalid macro register '{}'", c));
+                    }
+                }
+                '@' => match c {
+                    '@' => match app.last_macro {
+                        Some(reg) => play_macro(app, reg),
+                        None => app.status_message = Some("No macro replayed yet".to_string()),
+                    },
+                    reg => play_macro(app, reg),
+                },
                 '\'' => {
                     if let Some(&line) = app.marks.get(&c) {
+                        app.push_jump();
                         app.goto_line(line);
                         app.status_message = Some(format!("Jumped to mark '{}'", c));
                     } else {
@@ -50,95 +88,188 @@ fn handle_normal_key(app: &mut App, key: KeyEvent) {
         return;
     }
 
-    match app.key_map.get(&key) {
-        Some(Action::Quit) => app.quit = true,
+    // Esc abandons a half-typed chord
+    if key.code == KeyCode::Esc && !app.pending_keys.is_empty() {
+        app.pending_keys.clear();
+        return;
+    }
+
+    // Esc cancels a filter that is still being computed
+    if key.code == KeyCode::Esc && app.is_filtering() {
+        app.cancel_filter();
+        return;
+    }
+
+    // Esc stops the command a `:!` or `|` tab is showing the output of
+    if key.code == KeyCode::Esc && app.buffer().command.is_some() {
+        app.stop_command();
+        return;
+    }
+
+    // In a `:log` buffer, Enter opens the selected revision and D diffs it
+    if app.pending_keys.is_empty() && app.buffer().log.is_some() && (key.modifiers - KeyModifiers::SHIFT).is_empty() {
+        match key.code {
+            KeyCode::Enter => return app.open_log_revision(false),
+            KeyCode::Char('D') => return app.open_log_revision(true),
+            _ => {}
+        }
+    }
+
+    // In a `:sort`, `:uniq`, `:jq` or `:cols` tab, Enter goes back to the line in the
+    // buffer it was made from
+    if app.pending_keys.is_empty() && app.buffer().derived.is_some() && key.code == KeyCode::Enter && key.modifiers.is_empty() {
+        return app.goto_source();
+    }
+
+    app.pending_keys.push((key.code, key.modifiers));
+    match app.key_map.lookup(&app.pending_keys) {
+        KeyLookup::Pending => {}
+        KeyLookup::Action(action) => {
+            app.pending_keys.clear();
+            run_action(app, action);
+        }
+        KeyLookup::NoMatch => {
+            let keys = std::mem::take(&mut app.pending_keys);
+            if let [prefix @ .., _] = keys.as_slice() {
+                if !prefix.is_empty() {
+                    // The chord so far may be bound itself (`g` while `gg` is
+                    // also bound); run it, then treat this key as a fresh one
+                    if let Some(action) = app.key_map.action_for(prefix) {
+                        run_action(app, action);
+                    }
+                    handle_normal_key(app, key);
+                }
+            }
+        }
+    }
+}
+
+fn run_action(app: &mut App, action: Action) {
+    match action {
+        Action::Quit => app.quit = true,
 
-        Some(Action::ScrollDown) => app.scroll_down(1),
-        Some(Action::ScrollUp)   => app.scroll_up(1),
+        Action::ScrollDown => if !app.step_log(1) { app.scroll_down(1) },
+        Action::ScrollUp   => if !app.step_log(-1) { app.scroll_up(1) },
 
-        Some(Action::HalfPageDown) => {
+        Action::HalfPageDown => {
             let half = app.content_height / 2;
             app.scroll_down(half);
         }
-        Some(Action::HalfPageUp) => {
+        Action::HalfPageUp => {
             let half = app.content_height / 2;
             app.scroll_up(half);
         }
-        Some(Action::FullPageDown) => app.scroll_down(app.content_height),
-        Some(Action::FullPageUp)   => app.scroll_up(app.content_height),
+        Action::FullPageDown => app.scroll_down(app.content_height),
+        Action::FullPageUp   => app.scroll_up(app.content_height),
 
-        Some(Action::GotoTop)    => app.goto_top(),
-        Some(Action::GotoBottom) => app.goto_bottom(),
+        Action::GotoTop => {
+            app.push_jump();
+            app.goto_top();
+        }
+        Action::GotoBottom => {
+            app.push_jump();
+            app.goto_bottom();
+        }
+        Action::JumpBack => app.jump_back(),
+        Action::JumpForward => app.jump_forward(),
 
-        Some(Action::PrevBuffer) => app.prev_buffer(),
-        Some(Action::NextBuffer) => app.next_buffer(),
+        Action::PrevBuffer => app.prev_buffer(),
+        Action::NextBuffer => app.next_buffer(),
 
-        Some(Action::SearchForward) => {
+        Action::SearchForward => {
+            app.search.range = None;
             app.mode = Mode::SearchInput { input: String::new(), forward: true };
         }
-        Some(Action::SearchBackward) => {
+        Action::SearchBackward => {
+            app.search.range = None;
             app.mode = Mode::SearchInput { input: String::new(), forward: false };
         }
 
-        Some(Action::NextMatch) => {
-            if app.search.has_pattern() {
-                if app.search.forward { app.search.next_match(); } else { app.search.prev_match(); }
-                if let Some(line) = app.search.current_match_line() {
-                    app.goto_line(line);
-                    app.status_message = Some(format!(
-                        "Match {}/{}",
-                        app.search.current + 1,
-                        app.search.match_count()
-                    ));
-                }
-            }
-        }
-        Some(Action::PrevMatch) => {
-            if app.search.has_pattern() {
-                if app.search.forward { app.search.prev_match(); } else { app.search.next_match(); }
-                if let Some(line) = app.search.current_match_line() {
-                    app.goto_line(line);
-                    app.status_message = Some(format!(
-                        "Match {}/{}",
-                        app.search.current + 1,
-                        app.search.match_count()
-                    ));
-                }
-            }
-        }
+        Action::NextMatch => app.step_match(app.search.forward),
+        Action::PrevMatch => app.step_match(!app.search.forward),
+        Action::ToggleSearchWrap => app.toggle_search_wrap(),
 
-        Some(Action::ToggleNumbers) => app.show_line_numbers = !app.show_line_numbers,
-        Some(Action::ToggleWrap)    => app.wrap_lines = !app.wrap_lines,
+        Action::ToggleNumbers => app.show_line_numbers = !app.show_line_numbers,
+        Action::ToggleWrap    => app.wrap_lines = !app.wrap_lines,
 
-        Some(Action::FollowMode) => {
+        Action::FollowMode => {
             app.mode = Mode::Follow;
-            app.goto_bottom();
+            app.resume_follow();
             app.status_message = Some("Follow mode \u{2014} press q or Esc to exit".to_string());
         }
 
-        Some(Action::EnterCommand) => {
+        Action::EnterCommand => {
             app.mode = Mode::CommandInput { input: String::new() };
         }
-        Some(Action::Filter) => {
-            app.mode = Mode::FilterInput { input: String::new() };
+        Action::LogLevelFilter => {
+            app.pending_key = Some('L');
+            app.status_message = Some(
+                "L \u{2014} e:errors  w:+warnings  i:+info  d:+debug  t:+trace".to_string(),
+            );
+        }
+        Action::AlternateBuffer => app.alternate_buffer(),
+        Action::MoveBufferLeft => app.shift_buffer(false),
+        Action::MoveBufferRight => app.shift_buffer(true),
+        Action::BufferPicker => {
+            app.mode = Mode::BufferPicker { query: String::new(), selected: 0 };
+        }
+        Action::SaveView => {
+            app.mode = Mode::CommandInput { input: "wfilter ".to_string() };
+        }
+        Action::Filter => {
+            app.mode = Mode::FilterInput {
+                input: String::new(),
+                case: CaseMode::default(),
+            };
         }
-        Some(Action::Visual) => {
+        Action::Visual => {
             app.mode = Mode::Visual { anchor: app.top_line, cursor: app.top_line };
         }
+        Action::VisualChar => {
+            let cursor = (app.top_line, app.first_visible_char(app.top_line));
+            app.mode = Mode::VisualChar { anchor: Some(cursor), cursor };
+        }
 
-        Some(Action::SetMark) => {
+        Action::RecordMacro => match app.recording.take() {
+            Some((reg, mut keys)) => {
+                // Drop the keys that stopped the recording, all of them when
+                // that is a chord such as `qq`
+                keys.truncate(app.chord_start);
+                app.status_message = Some(format!("Recorded {} keys into @{}", keys.len(), reg));
+                app.macros.insert(reg, keys);
+            }
+            None => app.pending_key = Some('Q'),
+        },
+        Action::PlayMacro => {
+            app.pending_key = Some('@');
+        }
+        Action::Help => app.mode = Mode::Help { scroll: 0 },
+        Action::OpenEditor => app.request_edit(),
+        Action::ToggleBlame => app.toggle_blame(),
+        Action::ToggleDeleted => app.toggle_deleted(),
+        Action::Reload => app.reload(),
+        Action::ShowCommit => app.show_blame_commit(),
+        Action::YankLine => app.yank_line(),
+        Action::YankAll => app.yank_all(),
+        Action::CenterView => app.center_view(),
+        Action::Command(idx) => {
+            let cmd = app.key_map.command(idx).to_string();
+            execute_command(app, &cmd);
+        }
+        Action::SetMark => {
             app.pending_key = Some('m');
             app.status_message = Some("m \u{2014} press a letter to set mark".to_string());
         }
-        Some(Action::JumpMark) => {
+        Action::JumpMark => {
             app.pending_key = Some('\'');
             app.status_message = Some("' \u{2014} press a letter to jump to mark".to_string());
         }
 
-        Some(Action::ScrollRight) => app.left_col += 4,
-        Some(Action::ScrollLeft)  => app.left_col = app.left_col.saturating_sub(4),
+        Action::ScrollRight => app.left_col += 4,
+        Action::ScrollLeft  => app.left_col = app.left_col.saturating_sub(4),
 
-        None => {}
+        Action::ExpandHidden   => app.expand_hidden(),
+        Action::CollapseHidden => app.collapse_hidden(),
     }
 }
 
@@ -152,12 +283,12 @@ fn handle_search_key(app: &mut App, key: KeyEvent) {
         KeyCode::Enter => {
             app.search.forward = forward;
             app.search.query_string = input;
-            app.mode = Mode::Normal;
+            app.mode = app.mode_after_input();
             app.execute_search();
         }
         KeyCode::Esc => {
             app.search.preview_matches.clear();
-            app.mode = Mode::Normal;
+            app.mode = app.mode_after_input();
             app.status_message = None;
         }
         KeyCode::Backspace => {
@@ -210,13 +341,38 @@ fn handle_search_key(app: &mut App, key: KeyEvent) {
     }
 }
 
+/// Replay the keys recorded in register `reg` as if they were typed.
+/// Macros cannot start other macros, so a macro calling itself cannot loop.
+fn play_macro(app: &mut App, reg: char) {
+    if app.replaying {
+        return;
+    }
+    let Some(keys) = app.macros.get(&reg).cloned() else {
+        app.status_message = Some(format!("Macro @{} is empty", reg));
+        return;
+    };
+    app.last_macro = Some(reg);
+    app.replaying = true;
+    for key in keys {
+        handle_key(app, key);
+        if app.quit {
+            break;
+        }
+    }
+    app.replaying = false;
+}
+
 fn handle_command_key(app: &mut App, key: KeyEvent) {
     let input = match &app.mode {
         Mode::CommandInput { input } => input.clone(),
         _ => return,
     };
+    if key.code != KeyCode::Tab {
+        app.completion = None;
+    }
 
     match key.code {
+        KeyCode::Tab => complete_command(app, input),
         KeyCode::Enter => {
             app.mode = Mode::Normal;
             execute_command(app, &input);
@@ -241,63 +397,311 @@ fn handle_command_key(app: &mut App, key: KeyEvent) {
     }
 }
 
+/// Tab in command mode: complete the path argument of `:e` or the syntax
+/// name of `:syntax`. The first Tab fills in the longest common prefix;
+/// further Tabs cycle through candidates.
+fn complete_command(app: &mut App, input: String) {
+    let Some((name, arg)) = input.split_once(' ') else { return };
+    let complete: fn(&App, &str) -> Vec<String> = match name {
+        "e" | "edit" => |_, arg| paths::complete_path(arg),
+        "syntax" | "syn" => |app, arg| app.highlighter.complete_syntax(arg),
+        _ => return,
+    };
+    let replacement = match app.completion.as_mut() {
+        Some(completion) => completion.cycle().to_string(),
+        None => {
+            let candidates = complete(app, arg.trim_start());
+            match candidates.len() {
+                0 => return,
+                1 => candidates[0].clone(),
+                _ => {
+                    let prefix = paths::common_prefix(&candidates);
+                    app.completion = Some(Completion { candidates, index: None });
+                    prefix
+                }
+            }
+        }
+    };
+    app.mode = Mode::CommandInput { input: format!("{} {}", name, replacement) };
+}
+
 fn handle_follow_key(app: &mut App, key: KeyEvent) {
     match key.code {
         KeyCode::Char('q') | KeyCode::Esc => {
             app.mode = Mode::Normal;
+            app.follow_paused = None;
+            app.pending_keys.clear();
             app.status_message = None;
         }
         KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
             app.quit = true;
         }
-        _ => {}
+        _ => {
+            // Scrolling looks back without leaving follow mode, which pauses
+            // it; G (or F) resumes. Searching and filtering keep following
+            app.pending_keys.push((key.code, key.modifiers));
+            let action = match app.key_map.lookup(&app.pending_keys) {
+                KeyLookup::Pending => return,
+                KeyLookup::Action(action) => action,
+                KeyLookup::NoMatch => {
+                    app.pending_keys.clear();
+                    return;
+                }
+            };
+            app.pending_keys.clear();
+            match action {
+                Action::GotoBottom | Action::FollowMode => app.resume_follow(),
+                Action::ScrollDown
+                | Action::ScrollUp
+                | Action::HalfPageDown
+                | Action::HalfPageUp
+                | Action::FullPageDown
+                | Action::FullPageUp
+                | Action::GotoTop
+                | Action::PrevBuffer
+                | Action::NextBuffer => {
+                    run_action(app, action);
+                    app.update_follow_pause();
+                }
+                // Back to following once the prompt is closed
+                Action::SearchForward | Action::SearchBackward | Action::Filter => {
+                    app.follow_after_input = true;
+                    run_action(app, action);
+                }
+                _ => {}
+            }
+        }
     }
 }
 
 fn handle_filter_key(app: &mut App, key: KeyEvent) {
-    let input = match &app.mode {
-        Mode::FilterInput { input } => input.clone(),
+    let (input, case) = match &app.mode {
+        Mode::FilterInput { input, case } => (input.clone(), *case),
         _ => return,
     };
 
     match key.code {
         KeyCode::Enter => {
-            let query = input;
-            app.mode = Mode::Normal;
-            app.apply_filter(&query);
+            app.mode = app.mode_after_input();
+            // A leading '!' hides matching lines instead of keeping them
+            match input.strip_prefix('!') {
+                Some(query) => app.apply_filter(query, true, case),
+                None => app.apply_filter(&input, false, case),
+            }
         }
         KeyCode::Esc => {
-            app.mode = Mode::Normal;
+            app.mode = app.mode_after_input();
             app.clear_filter();
             app.status_message = None;
         }
+        KeyCode::Tab => {
+            // Cycle smart case -> match case -> ignore case for this filter
+            app.mode = Mode::FilterInput { input, case: case.next() };
+        }
         KeyCode::Backspace => {
             let mut new_input = input;
             new_input.pop();
             app.status_message = Some(format!("&{}", new_input));
-            app.mode = Mode::FilterInput { input: new_input };
+            app.mode = Mode::FilterInput { input: new_input, case };
         }
         KeyCode::Char(c) => {
             let mut new_input = input;
             new_input.push(c);
             app.status_message = Some(format!("&{}", new_input));
-            app.mode = Mode::FilterInput { input: new_input };
+            app.mode = Mode::FilterInput { input: new_input, case };
         }
         _ => {}
     }
 }
 
+fn handle_picker_key(app: &mut App, key: KeyEvent) {
+    let (mut query, selected) = match &app.mode {
+        Mode::BufferPicker { query, selected } => (query.clone(), *selected),
+        _ => return,
+    };
+    let count = app.picker_entries(&query).len();
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+    let selected = match key.code {
+        KeyCode::Enter => {
+            app.mode = Mode::Normal;
+            if let Some(&(idx, ..)) = app.picker_entries(&query).get(selected) {
+                app.switch_buffer(idx);
+            }
+            return;
+        }
+        KeyCode::Esc => {
+            app.mode = Mode::Normal;
+            return;
+        }
+        KeyCode::Up => selected.saturating_sub(1),
+        KeyCode::Char('p') | KeyCode::Char('k') if ctrl => selected.saturating_sub(1),
+        KeyCode::Down | KeyCode::Tab => (selected + 1).min(count.saturating_sub(1)),
+        KeyCode::Char('n') | KeyCode::Char('j') if ctrl => (selected + 1).min(count.saturating_sub(1)),
+        KeyCode::Char('c') if ctrl => {
+            app.quit = true;
+            return;
+        }
+        KeyCode::Backspace => {
+            query.pop();
+            0
+        }
+        KeyCode::Char(c) if !ctrl => {
+            query.push(c);
+            0
+        }
+        _ => selected,
+    };
+    app.mode = Mode::BufferPicker { query, selected };
+}
+
+fn handle_marks_key(app: &mut App, key: KeyEvent) {
+    let selected = match &app.mode {
+        Mode::MarksList { selected } => *selected,
+        _ => return,
+    };
+    let marks = app.bookmarks();
+
+    let selected = match key.code {
+        KeyCode::Enter => {
+            app.mode = Mode::Normal;
+            if let Some(bookmark) = marks.get(selected) {
+                app.push_jump();
+                app.goto_line(bookmark.line());
+                app.status_message = Some(match bookmark {
+                    Bookmark::Mark(c, _) => format!("Jumped to mark '{}'", c),
+                    Bookmark::Note(_, text) => format!("\u{270e} {}", text),
+                });
+            }
+            return;
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.mode = Mode::Normal;
+            return;
+        }
+        KeyCode::Char('d') => {
+            if let Some(bookmark) = marks.get(selected) {
+                app.delete_bookmark(bookmark);
+            }
+            if app.marks.is_empty() && app.notes.is_empty() {
+                app.mode = Mode::Normal;
+                return;
+            }
+            selected.min(marks.len().saturating_sub(2))
+        }
+        KeyCode::Up | KeyCode::Char('k') => selected.saturating_sub(1),
+        KeyCode::Down | KeyCode::Char('j') => (selected + 1).min(marks.len().saturating_sub(1)),
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.quit = true;
+            return;
+        }
+        _ => selected,
+    };
+    app.mode = Mode::MarksList { selected };
+}
+
+fn handle_match_list_key(app: &mut App, key: KeyEvent) {
+    let selected = match &app.mode {
+        Mode::MatchList { selected } => *selected,
+        _ => return,
+    };
+    let lines = app.match_lines();
+    let last = lines.len().saturating_sub(1);
+
+    let selected = match key.code {
+        KeyCode::Enter => {
+            app.mode = Mode::Normal;
+            if let Some(&line) = lines.get(selected) {
+                app.search.jump_to_line(line);
+                app.push_jump();
+                app.goto_line(line);
+                app.status_message = Some(format!("Match {}/{}", app.search.current + 1, app.search.match_count()));
+            }
+            return;
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            app.mode = Mode::Normal;
+            return;
+        }
+        KeyCode::Up | KeyCode::Char('k') => selected.saturating_sub(1),
+        KeyCode::Down | KeyCode::Char('j') => (selected + 1).min(last),
+        KeyCode::PageUp => selected.saturating_sub(app.content_height),
+        KeyCode::PageDown => (selected + app.content_height).min(last),
+        KeyCode::Home | KeyCode::Char('g') => 0,
+        KeyCode::End | KeyCode::Char('G') => last,
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.quit = true;
+            return;
+        }
+        _ => selected,
+    };
+    app.mode = Mode::MatchList { selected };
+}
+
+fn handle_help_key(app: &mut App, key: KeyEvent) {
+    let scroll = match &app.mode {
+        Mode::Help { scroll } => *scroll,
+        _ => return,
+    };
+    // The popup leaves a row above and below and has a border
+    let visible = app.content_height.saturating_sub(2).max(1);
+    let last = app.key_map.bindings().len().saturating_sub(visible);
+    let page = visible;
+
+    let scroll = match key.code {
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('h') | KeyCode::F(1) => {
+            app.mode = Mode::Normal;
+            return;
+        }
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.quit = true;
+            return;
+        }
+        KeyCode::Up | KeyCode::Char('k') => scroll.saturating_sub(1),
+        KeyCode::Down | KeyCode::Char('j') => scroll + 1,
+        KeyCode::PageUp | KeyCode::Char('b') => scroll.saturating_sub(page),
+        KeyCode::PageDown | KeyCode::Char(' ') => scroll + page,
+        KeyCode::Home | KeyCode::Char('g') => 0,
+        KeyCode::End | KeyCode::Char('G') => last,
+        _ => scroll,
+    };
+    app.mode = Mode::Help { scroll: scroll.min(last) };
+}
+
+/// Any key closes the `:info` and `:messages` popups.
+fn handle_info_key(app: &mut App, key: KeyEvent) {
+    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        app.quit = true;
+    }
+    app.mode = Mode::Normal;
+}
+
+fn handle_reload_prompt_key(app: &mut App, key: KeyEvent) {
+    match key.code {
+        KeyCode::Char('y') | KeyCode::Char('Y') => {
+            app.mode = Mode::Normal;
+            app.reload();
+        }
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => app.quit = true,
+        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.mode = Mode::Normal,
+        _ => {}
+    }
+}
+
 fn handle_visual_key(app: &mut App, key: KeyEvent) {
     let (anchor, cursor) = match &app.mode {
         Mode::Visual { anchor, cursor } => (*anchor, *cursor),
         _ => return,
     };
     let total = app.total_lines();
+    app.last_visual = Some(LastVisual::Lines { anchor, cursor });
 
     match key.code {
         KeyCode::Char('j') | KeyCode::Down => {
             let new_cursor = (cursor + 1).min(total.saturating_sub(1));
-            if new_cursor >= app.top_line + app.content_height {
+            if app.filter.is_none() {
+                app.keep_in_view(new_cursor);
+            } else if new_cursor >= app.top_line + app.content_height {
                 app.scroll_down(1);
             }
             app.mode = Mode::Visual { anchor, cursor: new_cursor };
@@ -309,8 +713,33 @@ fn handle_visual_key(app: &mut App, key: KeyEvent) {
             }
             app.mode = Mode::Visual { anchor, cursor: new_cursor };
         }
+        KeyCode::Char('v') => {
+            app.mode = Mode::VisualChar { anchor: None, cursor: (cursor, app.first_visible_char(cursor)) };
+        }
         KeyCode::Char('y') => {
-            app.yank_selection();
+            app.yank_selection(YankFormat::Plain);
+        }
+        KeyCode::Char('Y') => {
+            app.yank_selection(YankFormat::Refs);
+        }
+        KeyCode::Char('M') => {
+            app.yank_selection(YankFormat::Markdown);
+        }
+        KeyCode::Char('|') => {
+            let range = (anchor.min(cursor), anchor.max(cursor));
+            app.mode = Mode::PipeInput { input: String::new(), range };
+        }
+        // Ex commands on the selection, addressed as '<,'>
+        KeyCode::Char('w') => {
+            app.mode = Mode::CommandInput { input: "'<,'>w ".to_string() };
+        }
+        KeyCode::Char(':') => {
+            app.mode = Mode::CommandInput { input: "'<,'>".to_string() };
+        }
+        KeyCode::Char(c @ ('/' | '?')) => {
+            // Search restricted to the selected lines
+            app.search.range = Some((anchor.min(cursor), anchor.max(cursor)));
+            app.mode = Mode::SearchInput { input: String::new(), forward: c == '/' };
         }
         KeyCode::Char('q') | KeyCode::Esc => {
             app.mode = Mode::Normal;
@@ -319,25 +748,249 @@ fn handle_visual_key(app: &mut App, key: KeyEvent) {
     }
 }
 
+fn handle_pipe_key(app: &mut App, key: KeyEvent) {
+    let (mut input, range) = match &app.mode {
+        Mode::PipeInput { input, range } => (input.clone(), *range),
+        _ => return,
+    };
+    match key.code {
+        KeyCode::Enter => {
+            app.mode = Mode::Normal;
+            app.pipe_lines(range, &input);
+        }
+        KeyCode::Esc => {
+            app.mode = Mode::Visual { anchor: range.0, cursor: range.1 };
+        }
+        KeyCode::Backspace => {
+            input.pop();
+            app.mode = Mode::PipeInput { input, range };
+        }
+        KeyCode::Char(c) => {
+            input.push(c);
+            app.mode = Mode::PipeInput { input, range };
+        }
+        _ => {}
+    }
+}
+
+fn handle_visual_char_key(app: &mut App, key: KeyEvent) {
+    let (mut anchor, (line, col)) = match &app.mode {
+        Mode::VisualChar { anchor, cursor } => (*anchor, *cursor),
+        _ => return,
+    };
+    let total = app.total_lines();
+    if let Some(anchor) = anchor {
+        app.last_visual = Some(LastVisual::Chars { anchor, cursor: (line, col) });
+    }
+    let text = app.buffer().get_line(line).unwrap_or("").to_string();
+    let last = text.chars().count().saturating_sub(1);
+
+    let cursor = match key.code {
+        KeyCode::Char('h') | KeyCode::Left => (line, col.saturating_sub(1)),
+        KeyCode::Char('l') | KeyCode::Right => (line, (col + 1).min(last)),
+        KeyCode::Char('0') | KeyCode::Home => (line, 0),
+        KeyCode::Char('$') | KeyCode::End => (line, last),
+        KeyCode::Char('j') | KeyCode::Down => ((line + 1).min(total.saturating_sub(1)), col),
+        KeyCode::Char('k') | KeyCode::Up => (line.saturating_sub(1), col),
+        // Word motions continue onto the next / previous line at the ends
+        KeyCode::Char('w') => match motion::next_word_start(&text, col) {
+            Some(c) => (line, c),
+            None if line + 1 < total => (line + 1, 0),
+            None => (line, last),
+        },
+        KeyCode::Char('b') => match motion::prev_word_start(&text, col) {
+            Some(c) => (line, c),
+            None if line > 0 && col == 0 => {
+                let prev = app.buffer().get_line(line - 1).unwrap_or("");
+                let end = prev.chars().count();
+                (line - 1, motion::prev_word_start(prev, end).unwrap_or(0))
+            }
+            None => (line, 0),
+        },
+        KeyCode::Char('e') => (line, motion::word_end(&text, col).unwrap_or(last)),
+        KeyCode::Char('v') => {
+            anchor = Some((line, col));
+            (line, col)
+        }
+        KeyCode::Char('o') => match anchor {
+            Some(a) => {
+                anchor = Some((line, col));
+                a
+            }
+            None => (line, col),
+        },
+        KeyCode::Char('V') => {
+            app.mode = Mode::Visual { anchor: anchor.map_or(line, |a| a.0), cursor: line };
+            return;
+        }
+        KeyCode::Char('y') => {
+            app.yank_selection(YankFormat::Plain);
+            return;
+        }
+        KeyCode::Char(c @ ('/' | '?')) => {
+            let first = anchor.map_or(line, |a| a.0);
+            app.search.range = Some((first.min(line), first.max(line)));
+            app.mode = Mode::SearchInput { input: String::new(), forward: c == '/' };
+            return;
+        }
+        KeyCode::Char('q') | KeyCode::Esc => {
+            app.mode = Mode::Normal;
+            return;
+        }
+        _ => return,
+    };
+
+    // Keep the column on the new line
+    let len = app.buffer().get_line(cursor.0).map_or(0, |l| l.chars().count());
+    let cursor = (cursor.0, cursor.1.min(len.saturating_sub(1)));
+    app.mode = Mode::VisualChar { anchor, cursor };
+    app.reveal_cursor(cursor);
+}
+
+/// Commands addressed to a line range (`:100,200w path`, `:'<,'>y`). A
+/// range with no command jumps to its last line (`:150`).
+fn execute_range_command(app: &mut App, range: (usize, usize), cmd: &str) {
+    if cmd.is_empty() {
+        app.push_jump();
+        app.goto_line(range.1);
+        return;
+    }
+    let (name, arg) = match cmd.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (cmd, ""),
+    };
+    if let Some(shell) = cmd.strip_prefix('!') {
+        app.shell_command(Some(range), shell);
+        return;
+    }
+    if let Some(query) = global_pattern(cmd) {
+        app.global_search(query, Some(range));
+        return;
+    }
+    match name {
+        "w" | "write" => app.write_range(range, arg),
+        "y" | "yank" => app.yank_range(range),
+        "export" => app.export(Some(range), arg),
+        other => app.status_message = Some(format!("Unknown range command: {}", other)),
+    }
+}
+
+/// A byte offset in decimal or, with a `0x` prefix, hex.
+fn parse_offset(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// The pattern of `:g/regex/` (the closing `/` is optional; `:g` alone
+/// gives an empty pattern).
+fn global_pattern(cmd: &str) -> Option<&str> {
+    let rest = cmd.strip_prefix("global").or_else(|| cmd.strip_prefix('g'))?;
+    if rest.is_empty() {
+        return Some("");
+    }
+    let pattern = rest.strip_prefix('/')?;
+    Some(match pattern.strip_suffix('/') {
+        Some(p) if !p.ends_with('\\') => p,
+        _ => pattern,
+    })
+}
+
 fn handle_mouse(app: &mut App, mouse: MouseEvent) {
     match mouse.kind {
         MouseEventKind::ScrollDown => app.scroll_down(3),
         MouseEventKind::ScrollUp => app.scroll_up(3),
-        _ => {}
+        MouseEventKind::Down(MouseButton::Left) => app.click(mouse.column, mouse.row),
+        _ => return,
+    }
+    if app.mode == Mode::Follow {
+        app.update_follow_pause();
     }
 }
 
 fn execute_command(app: &mut App, cmd: &str) {
-    match cmd.trim() {
+    let cmd = cmd.trim();
+    if let Some(percent) = cmd.strip_suffix('%').and_then(|n| n.parse::<f64>().ok()) {
+        app.goto_percent(percent);
+        return;
+    }
+    let (range, rest) = match range::parse(cmd) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            app.status_message = Some(e);
+            return;
+        }
+    };
+    if let Some(range) = range {
+        match app.resolve_range(range) {
+            Ok(range) => execute_range_command(app, range, rest),
+            Err(e) => app.status_message = Some(e),
+        }
+        return;
+    }
+    if let Some(shell) = cmd.strip_prefix('!') {
+        app.shell_command(None, shell);
+        return;
+    }
+    if let Some(query) = global_pattern(cmd) {
+        app.global_search(query, None);
+        return;
+    }
+    let (name, arg) = match cmd.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (cmd, ""),
+    };
+    match name {
         "q" | "quit" => app.quit = true,
         "n" | "next" => app.next_buffer(),
         "p" | "prev" => app.prev_buffer(),
-        other => {
-            if let Ok(line) = other.parse::<usize>() {
-                app.goto_line(line.saturating_sub(1));
+        "e" | "edit" => app.open_file(arg),
+        "mksession" | "mks" => app.save_session(arg),
+        "note" => app.set_note(arg),
+        "wnotes" => app.write_notes(arg),
+        "marks" | "notes" => {
+            if app.marks.is_empty() && app.notes.is_empty() {
+                app.status_message = Some("No marks or notes set".to_string());
             } else {
-                app.status_message = Some(format!("Unknown command: {}", other));
+                app.mode = Mode::MarksList { selected: 0 };
             }
         }
+        "b#" => app.alternate_buffer(),
+        "b" | "buffer" if arg == "#" => app.alternate_buffer(),
+        "b" | "buffer" => match arg.parse::<usize>() {
+            Ok(n) if (1..=app.buffers.len()).contains(&n) => app.switch_buffer(n - 1),
+            _ => app.status_message = Some(format!("No buffer {}", arg)),
+        },
+        "bmove" | "bm" => match arg.parse::<usize>() {
+            Ok(n) if n >= 1 => app.move_buffer(n - 1),
+            _ => app.status_message = Some("Usage: :bmove <N> (1-based tab position)".to_string()),
+        },
+        "wfilter" => app.write_view(arg),
+        // A leading '!' hides matching lines; no pattern clears the filter
+        "filter" => match arg.strip_prefix('!') {
+            Some(query) => app.apply_filter(query, true, CaseMode::default()),
+            None => app.apply_filter(arg, false, CaseMode::default()),
+        },
+        "y%" => app.yank_all(),
+        "byte" => match parse_offset(arg) {
+            Some(offset) => app.goto_byte(offset),
+            None => app.status_message = Some("Usage: :byte <offset> (decimal or 0x hex)".to_string()),
+        },
+        "set" | "se" => app.set_options(arg),
+        "themes" => app.list_themes(),
+        "info" => app.mode = Mode::Info,
+        "messages" | "mes" => app.mode = Mode::Messages,
+        "blame" => app.toggle_blame(),
+        "log" => app.open_log(),
+        "join" => app.join_buffers(),
+        "jq" => app.jq(arg),
+        "cols" => app.cols(arg),
+        "sort" => app.sort(arg),
+        "uniq" => app.uniq(),
+        "strings" => app.strings(arg),
+        "syntax" | "syn" => app.set_syntax(arg),
+        "export" => app.export(None, arg),
+        other => app.status_message = Some(format!("Unknown command: {}", other)),
     }
 }
@@ -0,0 +1,141 @@
+use serde_json::Value;
+
+use crate::buffer::TextSnapshot;
+
+/// One step of a `:jq` path: `.key` or `[index]`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Key(String),
+    Index(usize),
+}
+
+/// A path into a JSON value, like jq's `.a.b[0]`; no steps is `.`, the
+/// whole value.
+pub type Path = Vec<Step>;
+
+/// Parse comma-separated paths such as `.level,.msg` or `.req.headers[0]`.
+/// Keys with other characters than letters, digits, `_` and `-` are quoted:
+/// `."user id"`.
+pub fn parse(spec: &str) -> Result<Vec<Path>, String> {
+    spec.split(',').map(|path| parse_path(path.trim())).collect()
+}
+
+fn parse_path(s: &str) -> Result<Path, String> {
+    let Some(mut rest) = s.strip_prefix('.') else {
+        return Err(format!("'{}': a path starts with '.'", s));
+    };
+    let mut steps = Vec::new();
+    loop {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').ok_or_else(|| format!("'{}': unclosed quote", s))?;
+            steps.push(Step::Key(quoted[..end].to_string()));
+            rest = &quoted[end + 1..];
+        } else {
+            let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-')).unwrap_or(rest.len());
+            if end > 0 {
+                steps.push(Step::Key(rest[..end].to_string()));
+            }
+            rest = &rest[end..];
+        }
+        while let Some(index) = rest.strip_prefix('[') {
+            let end = index.find(']').ok_or_else(|| format!("'{}': unclosed '['", s))?;
+            let n = index[..end].trim().parse().map_err(|_| format!("'{}': '[{}]' is not an index", s, &index[..end]))?;
+            steps.push(Step::Index(n));
+            rest = &index[end + 1..];
+        }
+        match rest.strip_prefix('.') {
+            Some(after) if !after.is_empty() => rest = after,
+            None if rest.is_empty() => return Ok(steps),
+            _ => return Err(format!("'{}': unexpected '{}'", s, rest)),
+        }
+    }
+}
+
+/// The values at `paths` in the JSON object or array on `line`, separated
+/// by spaces: strings as they are, anything else as compact JSON and
+/// missing values as `null`, as `jq -r` prints them. Control characters in
+/// strings are escaped as in JSON, so that the result stays on one line.
+/// None when the line is not a JSON object or array.
+pub fn extract(line: &[u8], paths: &[Path]) -> Option<String> {
+    let value: Value = serde_json::from_slice(line).ok()?;
+    if !value.is_object() && !value.is_array() {
+        return None;
+    }
+    let fields: Vec<String> = paths
+        .iter()
+        .map(|path| {
+            let found = path.iter().try_fold(&value, |v, step| match step {
+                Step::Key(key) => v.get(key),
+                Step::Index(n) => v.get(n),
+            });
+            match found {
+                Some(Value::String(s)) => escape_controls(s),
+                Some(v) => v.to_string(),
+                None => "null".to_string(),
+            }
+        })
+        .collect();
+    Some(fields.join(" "))
+}
+
+/// `s` with newlines, tabs and other control characters written as JSON
+/// escapes (`\n`, `\t`, `\u001b`).
+fn escape_controls(s: &str) -> String {
+    if !s.contains(char::is_control) {
+        return s.to_string();
+    }
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Every line of `text` with the fields at `paths` picked out, one output
+/// line per input line so that line numbers still match. Lines that are
+/// not JSON are kept as they are.
+pub fn apply(text: &TextSnapshot, paths: &[Path]) -> Vec<u8> {
+    text.map_lines(|line| match extract(line, paths) {
+        Some(fields) => fields.into_bytes(),
+        None => line.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let key = |k: &str| Step::Key(k.to_string());
+        assert_eq!(parse(".level, .msg").unwrap(), vec![vec![key("level")], vec![key("msg")]]);
+        assert_eq!(parse(".req.headers[0]").unwrap(), vec![vec![key("req"), key("headers"), Step::Index(0)]]);
+        assert_eq!(parse(".\"user id\"").unwrap(), vec![vec![key("user id")]]);
+        assert_eq!(parse(".").unwrap(), vec![vec![]]);
+        assert!(parse("level").is_err());
+        assert!(parse(".a[x]").is_err());
+        assert!(parse(".a b").is_err());
+    }
+
+    #[test]
+    fn test_extract() {
+        let paths = parse(".level,.msg,.ctx.ids[1],.missing,.ctx").unwrap();
+        let line = br#"{"level":"info","msg":"started","ctx":{"ids":[1,2]}}"#;
+        assert_eq!(extract(line, &paths).as_deref(), Some(r#"info started 2 null {"ids":[1,2]}"#));
+        assert_eq!(extract(b"plain text", &paths), None);
+        assert_eq!(extract(b"42", &paths), None);
+    }
+
+    #[test]
+    fn test_extract_keeps_one_line() {
+        let paths = parse(".msg,.n").unwrap();
+        let line = br#"{"msg":"panic:\n\tat main\r\u001b[0m","n":1}"#;
+        assert_eq!(extract(line, &paths).as_deref(), Some(r"panic:\n\tat main\r\u001b[0m 1"));
+    }
+}
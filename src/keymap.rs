@@ -1,8 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use crossterm::event::{KeyCode, KeyModifiers};
 use crate::config::KeysConfig;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Action {
     Quit,
     ScrollDown,
@@ -19,23 +19,162 @@ pub enum Action {
     SearchBackward,
     NextMatch,
     PrevMatch,
+    ToggleSearchWrap,
     ToggleNumbers,
     ToggleWrap,
     FollowMode,
     EnterCommand,
     Filter,
     Visual,
+    VisualChar,
     SetMark,
     JumpMark,
     ScrollRight,
     ScrollLeft,
+    ExpandHidden,
+    CollapseHidden,
+    SaveView,
+    LogLevelFilter,
+    BufferPicker,
+    MoveBufferLeft,
+    MoveBufferRight,
+    AlternateBuffer,
+    JumpBack,
+    JumpForward,
+    YankLine,
+    YankAll,
+    CenterView,
+    RecordMacro,
+    PlayMacro,
+    Help,
+    OpenEditor,
+    ToggleBlame,
+    ShowCommit,
+    ToggleDeleted,
+    Reload,
+    /// Run the bound command line (index into `KeyMap::command`)
+    Command(usize),
+}
+
+impl Action {
+    /// One-line description for the help overlay.
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::ScrollDown => "Scroll down one line",
+            Action::ScrollUp => "Scroll up one line",
+            Action::HalfPageDown => "Half page down",
+            Action::HalfPageUp => "Half page up",
+            Action::FullPageDown => "Full page down",
+            Action::FullPageUp => "Full page up",
+            Action::GotoTop => "Go to the top",
+            Action::GotoBottom => "Go to the bottom",
+            Action::PrevBuffer => "Previous file",
+            Action::NextBuffer => "Next file",
+            Action::SearchForward => "Search forward",
+            Action::SearchBackward => "Search backward",
+            Action::NextMatch => "Next match",
+            Action::PrevMatch => "Previous match",
+            Action::ToggleSearchWrap => "Toggle search wrap-around",
+            Action::ToggleNumbers => "Toggle line numbers",
+            Action::ToggleWrap => "Toggle line wrap",
+            Action::FollowMode => "Follow mode (tail -f)",
+            Action::EnterCommand => "Command mode",
+            Action::Filter => "Filter lines",
+            Action::Visual => "Visual line selection",
+            Action::VisualChar => "Visual character selection",
+            Action::SetMark => "Set mark <c>",
+            Action::JumpMark => "Jump to mark <c>",
+            Action::ScrollRight => "Scroll right",
+            Action::ScrollLeft => "Scroll left",
+            Action::ExpandHidden => "Expand hidden lines",
+            Action::CollapseHidden => "Collapse hidden lines",
+            Action::SaveView => "Save filtered lines to a file",
+            Action::LogLevelFilter => "Log level filter <e/w/i/d/t>",
+            Action::BufferPicker => "Buffer picker",
+            Action::MoveBufferLeft => "Move tab left",
+            Action::MoveBufferRight => "Move tab right",
+            Action::AlternateBuffer => "Alternate file",
+            Action::JumpBack => "Jump back",
+            Action::JumpForward => "Jump forward",
+            Action::YankLine => "Copy the top line",
+            Action::YankAll => "Copy the buffer (or filtered lines)",
+            Action::CenterView => "Center the current match",
+            Action::RecordMacro => "Record macro <r> / stop recording",
+            Action::PlayMacro => "Replay macro <r>",
+            Action::Help => "This help",
+            Action::OpenEditor => "Open the file in $EDITOR at the top line",
+            Action::ToggleBlame => "Toggle the git blame column",
+            Action::ShowCommit => "Show the commit that last changed the top line",
+            Action::ToggleDeleted => "Show the lines deleted at a \u{25be} marker in view / hide them",
+            Action::Reload => "Reload the file from disk",
+            Action::Command(_) => "Run a command",
+        }
+    }
+}
+
+/// A single keystroke.
+pub type Key = (KeyCode, KeyModifiers);
+
+/// Prefix tree of key sequences. A node can both run an action and lead on
+/// to longer sequences (e.g. `g` and `gg`); see `KeyMap::lookup`.
+#[derive(Default)]
+struct KeyTrie {
+    action: Option<Action>,
+    children: HashMap<Key, KeyTrie>,
+}
+
+impl KeyTrie {
+    fn insert(&mut self, keys: &[Key], action: Action) {
+        let mut node = self;
+        for &key in keys {
+            node = node.children.entry(normalize(key)).or_default();
+        }
+        node.action = Some(action);
+    }
+
+    fn find(&self, keys: &[Key]) -> Option<&KeyTrie> {
+        keys.iter()
+            .try_fold(self, |node, &key| node.children.get(&normalize(key)))
+    }
+
+    /// Unbind `action` everywhere, pruning branches left without bindings.
+    fn remove_action(&mut self, action: Action) {
+        if self.action == Some(action) {
+            self.action = None;
+        }
+        for child in self.children.values_mut() {
+            child.remove_action(action);
+        }
+        self.children.retain(|_, child| child.action.is_some() || !child.children.is_empty());
+    }
+}
+
+/// Shifted characters (and Shift+Tab, as BackTab) arrive with or without
+/// SHIFT depending on the terminal; the key itself already carries it.
+fn normalize((code, mods): Key) -> Key {
+    match code {
+        KeyCode::Char(_) | KeyCode::BackTab => (code, mods - KeyModifiers::SHIFT),
+        _ => (code, mods),
+    }
+}
+
+/// Outcome of feeding the keys typed so far to the keymap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyLookup {
+    Action(Action),
+    /// The keys are the start of a longer binding; wait for the next one
+    Pending,
+    NoMatch,
 }
 
 pub struct KeyMap {
     /// Config-driven primary bindings (user-overridable)
-    primary: HashMap<(KeyCode, KeyModifiers), Action>,
+    primary: KeyTrie,
     /// Hardcoded secondary aliases (arrows, PageUp/Down, Enter) — never overridden
-    secondary: HashMap<(KeyCode, KeyModifiers), Action>,
+    secondary: HashMap<Key, Action>,
+    /// Command lines bound to keys in `[keys]`, without the leading `:`
+    commands: Vec<String>,
 }
 
 impl KeyMap {
@@ -43,56 +182,133 @@ impl KeyMap {
         let mut km = KeyMap {
             primary: Self::defaults(),
             secondary: Self::aliases(),
+            commands: Vec::new(),
         };
+        // lesskey bindings add to the defaults, as they do in less
+        for (seq, action) in &keys.imported {
+            km.primary.insert(seq, *action);
+        }
         km.apply_overrides(keys);
+        km.bind_commands(keys);
         km
     }
 
-    pub fn get(&self, key: &crossterm::event::KeyEvent) -> Option<Action> {
-        let k = (key.code, key.modifiers);
-        self.primary.get(&k).or_else(|| self.secondary.get(&k)).copied()
+    /// Every active binding as (action, key names), grouped by action in
+    /// `Action` order, for the help overlay.
+    pub fn bindings(&self) -> Vec<(Action, Vec<String>)> {
+        fn walk(node: &KeyTrie, prefix: &mut Vec<Key>, out: &mut BTreeMap<Action, Vec<String>>) {
+            if let Some(action) = node.action {
+                let names: Vec<String> = prefix.iter().map(|&k| key_name(k)).collect();
+                let plain = names.iter().all(|n| n.chars().count() == 1);
+                out.entry(action).or_default().push(names.join(if plain { "" } else { " " }));
+            }
+            for (&key, child) in &node.children {
+                prefix.push(key);
+                walk(child, prefix, out);
+                prefix.pop();
+            }
+        }
+        let mut grouped = BTreeMap::new();
+        walk(&self.primary, &mut Vec::new(), &mut grouped);
+        for (&key, &action) in &self.secondary {
+            grouped.entry(action).or_default().push(key_name(key));
+        }
+        grouped
+            .into_iter()
+            .map(|(action, mut names)| {
+                // Short names first: "j" before "down"
+                names.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+                (action, names)
+            })
+            .collect()
     }
 
-    fn defaults() -> HashMap<(KeyCode, KeyModifiers), Action> {
+    /// The command line bound by `Action::Command(idx)`.
+    pub fn command(&self, idx: usize) -> &str {
+        &self.commands[idx]
+    }
+
+    /// Look up a key sequence. A sequence that is both bound and the prefix
+    /// of a longer binding is `Pending`; the caller falls back to its action
+    /// (see `action_for`) if the next key does not continue the chord.
+    pub fn lookup(&self, keys: &[Key]) -> KeyLookup {
+        match self.primary.find(keys) {
+            Some(node) if !node.children.is_empty() => KeyLookup::Pending,
+            Some(KeyTrie { action: Some(action), .. }) => KeyLookup::Action(*action),
+            _ => match keys {
+                [key] => self.secondary.get(&normalize(*key)).map_or(KeyLookup::NoMatch, |&a| KeyLookup::Action(a)),
+                _ => KeyLookup::NoMatch,
+            },
+        }
+    }
+
+    /// The action bound to exactly `keys`, ignoring longer bindings.
+    pub fn action_for(&self, keys: &[Key]) -> Option<Action> {
+        self.primary.find(keys).and_then(|node| node.action)
+    }
+
+    fn defaults() -> KeyTrie {
         use Action::*;
-        let mut m = HashMap::new();
-        m.insert((KeyCode::Char('q'), KeyModifiers::NONE), Quit);
-        m.insert((KeyCode::Char('j'), KeyModifiers::NONE), ScrollDown);
-        m.insert((KeyCode::Char('k'), KeyModifiers::NONE), ScrollUp);
-        m.insert((KeyCode::Char('d'), KeyModifiers::CONTROL), HalfPageDown);
-        m.insert((KeyCode::Char('d'), KeyModifiers::NONE), HalfPageDown);
-        m.insert((KeyCode::Char('u'), KeyModifiers::CONTROL), HalfPageUp);
-        m.insert((KeyCode::Char('u'), KeyModifiers::NONE), HalfPageUp);
-        m.insert((KeyCode::Char(' '), KeyModifiers::NONE), FullPageDown);
-        m.insert((KeyCode::Char('b'), KeyModifiers::NONE), FullPageUp);
-        m.insert((KeyCode::Char('g'), KeyModifiers::NONE), GotoTop);
-        m.insert((KeyCode::Char('G'), KeyModifiers::NONE), GotoBottom);
-        m.insert((KeyCode::Char('G'), KeyModifiers::SHIFT), GotoBottom);
-        m.insert((KeyCode::Char('['), KeyModifiers::NONE), PrevBuffer);
-        m.insert((KeyCode::Char(']'), KeyModifiers::NONE), NextBuffer);
-        m.insert((KeyCode::Char('/'), KeyModifiers::NONE), SearchForward);
-        m.insert((KeyCode::Char('?'), KeyModifiers::NONE), SearchBackward);
-        m.insert((KeyCode::Char('?'), KeyModifiers::SHIFT), SearchBackward);
-        m.insert((KeyCode::Char('n'), KeyModifiers::NONE), NextMatch);
-        m.insert((KeyCode::Char('N'), KeyModifiers::NONE), PrevMatch);
-        m.insert((KeyCode::Char('N'), KeyModifiers::SHIFT), PrevMatch);
-        m.insert((KeyCode::Char('l'), KeyModifiers::NONE), ToggleNumbers);
-        m.insert((KeyCode::Char('w'), KeyModifiers::NONE), ToggleWrap);
-        m.insert((KeyCode::Char('F'), KeyModifiers::NONE), FollowMode);
-        m.insert((KeyCode::Char('F'), KeyModifiers::SHIFT), FollowMode);
-        m.insert((KeyCode::Char(':'), KeyModifiers::NONE), EnterCommand);
-        m.insert((KeyCode::Char(':'), KeyModifiers::SHIFT), EnterCommand);
-        m.insert((KeyCode::Char('&'), KeyModifiers::NONE), Filter);
-        m.insert((KeyCode::Char('&'), KeyModifiers::SHIFT), Filter);
-        m.insert((KeyCode::Char('v'), KeyModifiers::NONE), Visual);
-        m.insert((KeyCode::Char('m'), KeyModifiers::NONE), SetMark);
-        m.insert((KeyCode::Char('\''), KeyModifiers::NONE), JumpMark);
-        m.insert((KeyCode::Right, KeyModifiers::NONE), ScrollRight);
-        m.insert((KeyCode::Left, KeyModifiers::NONE), ScrollLeft);
+        let mut m = KeyTrie::default();
+        m.insert(&[(KeyCode::Char('q'), KeyModifiers::NONE)], Quit);
+        m.insert(&[(KeyCode::Char('j'), KeyModifiers::NONE)], ScrollDown);
+        m.insert(&[(KeyCode::Char('k'), KeyModifiers::NONE)], ScrollUp);
+        m.insert(&[(KeyCode::Char('d'), KeyModifiers::CONTROL)], HalfPageDown);
+        m.insert(&[(KeyCode::Char('d'), KeyModifiers::NONE)], HalfPageDown);
+        m.insert(&[(KeyCode::Char('u'), KeyModifiers::CONTROL)], HalfPageUp);
+        m.insert(&[(KeyCode::Char('u'), KeyModifiers::NONE)], HalfPageUp);
+        m.insert(&[(KeyCode::Char(' '), KeyModifiers::NONE)], FullPageDown);
+        m.insert(&[(KeyCode::Char('b'), KeyModifiers::NONE)], FullPageUp);
+        m.insert(&[(KeyCode::Char('g'), KeyModifiers::NONE)], GotoTop);
+        m.insert(&[(KeyCode::Char('G'), KeyModifiers::NONE)], GotoBottom);
+        m.insert(&[(KeyCode::Char('['), KeyModifiers::NONE)], PrevBuffer);
+        m.insert(&[(KeyCode::Char(']'), KeyModifiers::NONE)], NextBuffer);
+        m.insert(&[(KeyCode::Char('/'), KeyModifiers::NONE)], SearchForward);
+        m.insert(&[(KeyCode::Char('?'), KeyModifiers::NONE)], SearchBackward);
+        m.insert(&[(KeyCode::Char('n'), KeyModifiers::NONE)], NextMatch);
+        m.insert(&[(KeyCode::Char('N'), KeyModifiers::NONE)], PrevMatch);
+        m.insert(&[(KeyCode::Char('W'), KeyModifiers::NONE)], ToggleSearchWrap);
+        m.insert(&[(KeyCode::Char('l'), KeyModifiers::NONE)], ToggleNumbers);
+        m.insert(&[(KeyCode::Char('w'), KeyModifiers::NONE)], ToggleWrap);
+        m.insert(&[(KeyCode::Char('F'), KeyModifiers::NONE)], FollowMode);
+        m.insert(&[(KeyCode::Char(':'), KeyModifiers::NONE)], EnterCommand);
+        m.insert(&[(KeyCode::Char('&'), KeyModifiers::NONE)], Filter);
+        m.insert(&[(KeyCode::Char('V'), KeyModifiers::NONE)], Visual);
+        m.insert(&[(KeyCode::Char('v'), KeyModifiers::NONE)], VisualChar);
+        m.insert(&[(KeyCode::Char('m'), KeyModifiers::NONE)], SetMark);
+        m.insert(&[(KeyCode::Char('\''), KeyModifiers::NONE)], JumpMark);
+        m.insert(&[(KeyCode::Right, KeyModifiers::NONE)], ScrollRight);
+        m.insert(&[(KeyCode::Left, KeyModifiers::NONE)], ScrollLeft);
+        m.insert(&[(KeyCode::Char('+'), KeyModifiers::NONE)], ExpandHidden);
+        m.insert(&[(KeyCode::Char('-'), KeyModifiers::NONE)], CollapseHidden);
+        m.insert(&[(KeyCode::Char('s'), KeyModifiers::NONE)], SaveView);
+        m.insert(&[(KeyCode::Char('L'), KeyModifiers::NONE)], LogLevelFilter);
+        m.insert(&[(KeyCode::Char('p'), KeyModifiers::CONTROL)], BufferPicker);
+        m.insert(&[(KeyCode::Char('{'), KeyModifiers::NONE)], MoveBufferLeft);
+        m.insert(&[(KeyCode::Char('}'), KeyModifiers::NONE)], MoveBufferRight);
+        // Terminals report Ctrl-^ as Ctrl-6
+        m.insert(&[(KeyCode::Char('^'), KeyModifiers::CONTROL)], AlternateBuffer);
+        m.insert(&[(KeyCode::Char('6'), KeyModifiers::CONTROL)], AlternateBuffer);
+        m.insert(&[(KeyCode::Char('o'), KeyModifiers::CONTROL)], JumpBack);
+        // Terminals send Ctrl-I as Tab
+        m.insert(&[(KeyCode::Tab, KeyModifiers::NONE)], JumpForward);
+        m.insert(&[(KeyCode::Char('i'), KeyModifiers::CONTROL)], JumpForward);
+        m.insert(&[(KeyCode::Char('y'), KeyModifiers::NONE), (KeyCode::Char('y'), KeyModifiers::NONE)], YankLine);
+        m.insert(&[(KeyCode::Char('y'), KeyModifiers::NONE), (KeyCode::Char('G'), KeyModifiers::NONE)], YankAll);
+        m.insert(&[(KeyCode::Char('z'), KeyModifiers::NONE), (KeyCode::Char('z'), KeyModifiers::NONE)], CenterView);
+        m.insert(&[(KeyCode::Char('Q'), KeyModifiers::NONE)], RecordMacro);
+        m.insert(&[(KeyCode::Char('@'), KeyModifiers::NONE)], PlayMacro);
+        m.insert(&[(KeyCode::Char('h'), KeyModifiers::NONE)], Help);
+        m.insert(&[(KeyCode::F(1), KeyModifiers::NONE)], Help);
+        m.insert(&[(KeyCode::Char('e'), KeyModifiers::NONE)], OpenEditor);
+        m.insert(&[(KeyCode::Char('B'), KeyModifiers::NONE)], ToggleBlame);
+        m.insert(&[(KeyCode::Char('K'), KeyModifiers::NONE)], ShowCommit);
+        m.insert(&[(KeyCode::Char('z'), KeyModifiers::NONE), (KeyCode::Char('d'), KeyModifiers::NONE)], ToggleDeleted);
+        m.insert(&[(KeyCode::Char('R'), KeyModifiers::NONE)], Reload);
         m
     }
 
-    fn aliases() -> HashMap<(KeyCode, KeyModifiers), Action> {
+    fn aliases() -> HashMap<Key, Action> {
         use Action::*;
         let mut m = HashMap::new();
         // Arrow keys / page keys / Enter always work regardless of config
@@ -108,6 +324,18 @@ impl KeyMap {
         m
     }
 
+    /// Bind `"<key spec>" = ":<command>"` entries. Values that are not
+    /// commands, and keys that do not parse, are ignored.
+    fn bind_commands(&mut self, keys: &KeysConfig) {
+        for (spec, value) in &keys.commands {
+            let (Some(seq), Some(cmd)) = (parse_key_sequence(spec), value.strip_prefix(':')) else {
+                continue;
+            };
+            self.primary.insert(&seq, Action::Command(self.commands.len()));
+            self.commands.push(cmd.to_string());
+        }
+    }
+
     fn apply_overrides(&mut self, keys: &KeysConfig) {
         let overrides: &[(Option<&String>, Action)] = &[
             (keys.quit.as_ref(), Action::Quit),
@@ -125,58 +353,313 @@ impl KeyMap {
             (keys.search_backward.as_ref(), Action::SearchBackward),
             (keys.next_match.as_ref(), Action::NextMatch),
             (keys.prev_match.as_ref(), Action::PrevMatch),
+            (keys.toggle_search_wrap.as_ref(), Action::ToggleSearchWrap),
             (keys.toggle_numbers.as_ref(), Action::ToggleNumbers),
             (keys.toggle_wrap.as_ref(), Action::ToggleWrap),
             (keys.follow_mode.as_ref(), Action::FollowMode),
             (keys.enter_command.as_ref(), Action::EnterCommand),
             (keys.filter.as_ref(), Action::Filter),
             (keys.visual.as_ref(), Action::Visual),
+            (keys.visual_char.as_ref(), Action::VisualChar),
             (keys.set_mark.as_ref(), Action::SetMark),
             (keys.jump_mark.as_ref(), Action::JumpMark),
             (keys.scroll_right.as_ref(), Action::ScrollRight),
             (keys.scroll_left.as_ref(), Action::ScrollLeft),
+            (keys.expand_hidden.as_ref(), Action::ExpandHidden),
+            (keys.collapse_hidden.as_ref(), Action::CollapseHidden),
+            (keys.save_view.as_ref(), Action::SaveView),
+            (keys.log_level_filter.as_ref(), Action::LogLevelFilter),
+            (keys.buffer_picker.as_ref(), Action::BufferPicker),
+            (keys.move_buffer_left.as_ref(), Action::MoveBufferLeft),
+            (keys.move_buffer_right.as_ref(), Action::MoveBufferRight),
+            (keys.alternate_buffer.as_ref(), Action::AlternateBuffer),
+            (keys.jump_back.as_ref(), Action::JumpBack),
+            (keys.jump_forward.as_ref(), Action::JumpForward),
+            (keys.yank_line.as_ref(), Action::YankLine),
+            (keys.yank_all.as_ref(), Action::YankAll),
+            (keys.center_view.as_ref(), Action::CenterView),
+            (keys.record_macro.as_ref(), Action::RecordMacro),
+            (keys.play_macro.as_ref(), Action::PlayMacro),
+            (keys.help.as_ref(), Action::Help),
+            (keys.open_editor.as_ref(), Action::OpenEditor),
+            (keys.toggle_blame.as_ref(), Action::ToggleBlame),
+            (keys.show_commit.as_ref(), Action::ShowCommit),
+            (keys.toggle_deleted.as_ref(), Action::ToggleDeleted),
+            (keys.reload.as_ref(), Action::Reload),
         ];
 
         for (maybe_spec, action) in overrides {
             if let Some(spec) = maybe_spec {
-                if let Some(key) = parse_key_spec(spec) {
+                if let Some(keys) = parse_key_sequence(spec) {
                     // Remove any existing primary binding for this action
-                    self.primary.retain(|_, v| *v != *action);
-                    self.primary.insert(key, *action);
+                    self.primary.remove_action(*action);
+                    self.primary.insert(&keys, *action);
                 }
             }
         }
     }
 }
 
-pub fn parse_key_spec(s: &str) -> Option<(KeyCode, KeyModifiers)> {
-    // Handle ctrl+ prefix
-    let lower = s.to_lowercase();
-    if let Some(rest) = lower.strip_prefix("ctrl+") {
-        let c = rest.chars().next()?;
-        return Some((KeyCode::Char(c), KeyModifiers::CONTROL));
-    }
-
-    // Named keys (case-insensitive)
-    match lower.as_str() {
-        "space"           => return Some((KeyCode::Char(' '), KeyModifiers::NONE)),
-        "enter" | "return" => return Some((KeyCode::Enter, KeyModifiers::NONE)),
-        "tab"             => return Some((KeyCode::Tab, KeyModifiers::NONE)),
-        "pagedown" | "pgdn" => return Some((KeyCode::PageDown, KeyModifiers::NONE)),
-        "pageup" | "pgup"   => return Some((KeyCode::PageUp, KeyModifiers::NONE)),
-        "home"            => return Some((KeyCode::Home, KeyModifiers::NONE)),
-        "end"             => return Some((KeyCode::End, KeyModifiers::NONE)),
-        "up"              => return Some((KeyCode::Up, KeyModifiers::NONE)),
-        "down"            => return Some((KeyCode::Down, KeyModifiers::NONE)),
-        "left"            => return Some((KeyCode::Left, KeyModifiers::NONE)),
-        "right"           => return Some((KeyCode::Right, KeyModifiers::NONE)),
-        "backspace"       => return Some((KeyCode::Backspace, KeyModifiers::NONE)),
-        "delete" | "del"  => return Some((KeyCode::Delete, KeyModifiers::NONE)),
-        "escape" | "esc"  => return Some((KeyCode::Esc, KeyModifiers::NONE)),
-        _ => {}
-    }
-
-    // Single character: use as-is (preserve case from original string)
-    let c = s.chars().next()?;
-    Some((KeyCode::Char(c), KeyModifiers::NONE))
+/// Parse a binding that may be a chord: space-separated key specs
+/// (`"ctrl+w j"`), or a run of plain characters (`"gg"`, `"zz"`).
+pub fn parse_key_sequence(s: &str) -> Option<Vec<Key>> {
+    // A lone space is the space key
+    if s == " " {
+        return Some(vec![(KeyCode::Char(' '), KeyModifiers::NONE)]);
+    }
+    let mut keys = Vec::new();
+    for token in s.split_whitespace() {
+        match parse_key_spec(token) {
+            Some(key) => keys.push(key),
+            // Tokens that are not a key spec are one key per character
+            None if !token.contains('+') => {
+                keys.extend(token.chars().map(|c| (KeyCode::Char(c), KeyModifiers::NONE)));
+            }
+            None => return None,
+        }
+    }
+    (!keys.is_empty()).then_some(keys)
+}
+
+/// Parse a single key: a character, a key name (`"space"`, `"pgdn"`, `"f5"`),
+/// optionally prefixed by any of `ctrl+`, `alt+` and `shift+` (`"ctrl+alt+x"`).
+pub fn parse_key_spec(s: &str) -> Option<Key> {
+    let mut mods = KeyModifiers::NONE;
+    let mut rest = s;
+    loop {
+        let lower = rest.to_lowercase();
+        let (modifier, len) = if lower.starts_with("ctrl+") {
+            (KeyModifiers::CONTROL, 5)
+        } else if lower.starts_with("alt+") {
+            (KeyModifiers::ALT, 4)
+        } else if lower.starts_with("shift+") {
+            (KeyModifiers::SHIFT, 6)
+        } else {
+            break;
+        };
+        // "ctrl++" binds Ctrl and the plus key
+        if rest.len() == len {
+            break;
+        }
+        mods |= modifier;
+        rest = &rest[len..];
+    }
+
+    let code = match named_key(&rest.to_lowercase()) {
+        Some(code) => code,
+        None => {
+            let mut chars = rest.chars();
+            let c = chars.next()?;
+            if chars.next().is_some() {
+                return None;
+            }
+            KeyCode::Char(c)
+        }
+    };
+
+    Some(match code {
+        // Terminals report Shift+Tab as BackTab
+        KeyCode::Tab if mods.contains(KeyModifiers::SHIFT) => (KeyCode::BackTab, mods - KeyModifiers::SHIFT),
+        // Control combinations arrive lowercase; shift+<c> is just the uppercase char
+        KeyCode::Char(c) if mods.contains(KeyModifiers::CONTROL) => (KeyCode::Char(c.to_ascii_lowercase()), mods),
+        KeyCode::Char(c) if mods.contains(KeyModifiers::SHIFT) => {
+            (KeyCode::Char(c.to_ascii_uppercase()), mods - KeyModifiers::SHIFT)
+        }
+        code => (code, mods),
+    })
+}
+
+/// Display name of a key in key-spec syntax, e.g. "ctrl+p", "space", "G".
+/// Chords join these with spaces, except runs of plain characters ("gg").
+pub fn key_name((code, mods): Key) -> String {
+    let base = match code {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("f{}", n),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::BackTab => "shift+tab".to_string(),
+        KeyCode::PageDown => "pgdn".to_string(),
+        KeyCode::PageUp => "pgup".to_string(),
+        KeyCode::Home => "home".to_string(),
+        KeyCode::End => "end".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        KeyCode::Delete => "delete".to_string(),
+        KeyCode::Insert => "insert".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        other => format!("{:?}", other).to_lowercase(),
+    };
+    let mut name = String::new();
+    if mods.contains(KeyModifiers::CONTROL) {
+        name.push_str("ctrl+");
+    }
+    if mods.contains(KeyModifiers::ALT) {
+        name.push_str("alt+");
+    }
+    if mods.contains(KeyModifiers::SHIFT) {
+        name.push_str("shift+");
+    }
+    name + &base
+}
+
+/// Key names understood in specs (lowercase).
+fn named_key(name: &str) -> Option<KeyCode> {
+    let code = match name {
+        "space"              => KeyCode::Char(' '),
+        "enter" | "return"   => KeyCode::Enter,
+        "tab"                => KeyCode::Tab,
+        "backtab"            => KeyCode::BackTab,
+        "pagedown" | "pgdn"  => KeyCode::PageDown,
+        "pageup" | "pgup"    => KeyCode::PageUp,
+        "home"               => KeyCode::Home,
+        "end"                => KeyCode::End,
+        "up"                 => KeyCode::Up,
+        "down"               => KeyCode::Down,
+        "left"               => KeyCode::Left,
+        "right"              => KeyCode::Right,
+        "backspace"          => KeyCode::Backspace,
+        "delete" | "del"     => KeyCode::Delete,
+        "insert" | "ins"     => KeyCode::Insert,
+        "escape" | "esc"     => KeyCode::Esc,
+        _ => {
+            let n: u8 = name.strip_prefix('f')?.parse().ok()?;
+            return (1..=12).contains(&n).then_some(KeyCode::F(n));
+        }
+    };
+    Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, mods: KeyModifiers) -> Option<Key> {
+        Some((code, mods))
+    }
+
+    #[test]
+    fn test_parse_plain_keys() {
+        assert_eq!(parse_key_spec("e"), key(KeyCode::Char('e'), KeyModifiers::NONE));
+        assert_eq!(parse_key_spec("G"), key(KeyCode::Char('G'), KeyModifiers::NONE));
+        assert_eq!(parse_key_spec("+"), key(KeyCode::Char('+'), KeyModifiers::NONE));
+        assert_eq!(parse_key_spec("space"), key(KeyCode::Char(' '), KeyModifiers::NONE));
+        assert_eq!(parse_key_spec("PgDn"), key(KeyCode::PageDown, KeyModifiers::NONE));
+        assert_eq!(parse_key_spec("nonsense"), None);
+    }
+
+    #[test]
+    fn test_parse_function_keys() {
+        assert_eq!(parse_key_spec("f1"), key(KeyCode::F(1), KeyModifiers::NONE));
+        assert_eq!(parse_key_spec("F12"), key(KeyCode::F(12), KeyModifiers::NONE));
+        assert_eq!(parse_key_spec("f13"), None);
+        assert_eq!(parse_key_spec("ctrl+f5"), key(KeyCode::F(5), KeyModifiers::CONTROL));
+    }
+
+    #[test]
+    fn test_parse_modifiers() {
+        assert_eq!(parse_key_spec("ctrl+D"), key(KeyCode::Char('d'), KeyModifiers::CONTROL));
+        assert_eq!(parse_key_spec("alt+x"), key(KeyCode::Char('x'), KeyModifiers::ALT));
+        assert_eq!(
+            parse_key_spec("ctrl+alt+x"),
+            key(KeyCode::Char('x'), KeyModifiers::CONTROL | KeyModifiers::ALT)
+        );
+        assert_eq!(
+            parse_key_spec("Alt+Ctrl+x"),
+            key(KeyCode::Char('x'), KeyModifiers::CONTROL | KeyModifiers::ALT)
+        );
+        assert_eq!(parse_key_spec("ctrl++"), key(KeyCode::Char('+'), KeyModifiers::CONTROL));
+        assert_eq!(parse_key_spec("ctrl+"), None);
+        assert_eq!(parse_key_spec("alt+nonsense"), None);
+    }
+
+    #[test]
+    fn test_parse_shift() {
+        assert_eq!(parse_key_spec("shift+tab"), key(KeyCode::BackTab, KeyModifiers::NONE));
+        assert_eq!(parse_key_spec("shift+a"), key(KeyCode::Char('A'), KeyModifiers::NONE));
+        assert_eq!(parse_key_spec("shift+up"), key(KeyCode::Up, KeyModifiers::SHIFT));
+        assert_eq!(parse_key_spec("alt+shift+f3"), key(KeyCode::F(3), KeyModifiers::ALT | KeyModifiers::SHIFT));
+    }
+
+    #[test]
+    fn test_parse_sequences() {
+        let g = (KeyCode::Char('g'), KeyModifiers::NONE);
+        assert_eq!(parse_key_sequence("gg"), Some(vec![g, g]));
+        assert_eq!(parse_key_sequence("space"), Some(vec![(KeyCode::Char(' '), KeyModifiers::NONE)]));
+        assert_eq!(parse_key_sequence(" "), Some(vec![(KeyCode::Char(' '), KeyModifiers::NONE)]));
+        assert_eq!(
+            parse_key_sequence("ctrl+w j"),
+            Some(vec![(KeyCode::Char('w'), KeyModifiers::CONTROL), (KeyCode::Char('j'), KeyModifiers::NONE)])
+        );
+        assert_eq!(parse_key_sequence("ctrl+nonsense"), None);
+        assert_eq!(parse_key_sequence(""), None);
+    }
+
+    #[test]
+    fn test_lookup_normalizes_shift() {
+        let km = KeyMap::build(&KeysConfig::default());
+        assert_eq!(km.lookup(&[(KeyCode::Char('G'), KeyModifiers::SHIFT)]), KeyLookup::Action(Action::GotoBottom));
+        assert_eq!(km.lookup(&[(KeyCode::Char('G'), KeyModifiers::NONE)]), KeyLookup::Action(Action::GotoBottom));
+    }
+
+    #[test]
+    fn test_lookup_chords() {
+        let keys = KeysConfig {
+            goto_top: Some("gg".to_string()),
+            next_buffer: Some("alt+n".to_string()),
+            prev_buffer: Some("shift+tab".to_string()),
+            ..KeysConfig::default()
+        };
+        let km = KeyMap::build(&keys);
+        let g = (KeyCode::Char('g'), KeyModifiers::NONE);
+        assert_eq!(km.lookup(&[g]), KeyLookup::Pending);
+        assert_eq!(km.lookup(&[g, g]), KeyLookup::Action(Action::GotoTop));
+        assert_eq!(km.lookup(&[g, (KeyCode::Char('x'), KeyModifiers::NONE)]), KeyLookup::NoMatch);
+        assert_eq!(km.lookup(&[(KeyCode::Char('n'), KeyModifiers::ALT)]), KeyLookup::Action(Action::NextBuffer));
+        assert_eq!(km.lookup(&[(KeyCode::BackTab, KeyModifiers::SHIFT)]), KeyLookup::Action(Action::PrevBuffer));
+        // Rebinding replaces the default key
+        assert_eq!(km.lookup(&[(KeyCode::Char(']'), KeyModifiers::NONE)]), KeyLookup::NoMatch);
+    }
+
+    #[test]
+    fn test_command_bindings() {
+        let keys: KeysConfig = toml::from_str(
+            r#"
+            goto_top = "g"
+            f5 = ":filter ERROR"
+            "ctrl+e" = ":e ~/notes.txt"
+            x = "not a command"
+            "#,
+        )
+        .unwrap();
+        let km = KeyMap::build(&keys);
+        let KeyLookup::Action(Action::Command(idx)) = km.lookup(&[(KeyCode::F(5), KeyModifiers::NONE)]) else {
+            panic!("f5 not bound to a command");
+        };
+        assert_eq!(km.command(idx), "filter ERROR");
+        let KeyLookup::Action(Action::Command(idx)) = km.lookup(&[(KeyCode::Char('e'), KeyModifiers::CONTROL)]) else {
+            panic!("ctrl+e not bound to a command");
+        };
+        assert_eq!(km.command(idx), "e ~/notes.txt");
+        assert_eq!(km.lookup(&[(KeyCode::Char('x'), KeyModifiers::NONE)]), KeyLookup::NoMatch);
+        assert_eq!(km.lookup(&[(KeyCode::Char('g'), KeyModifiers::NONE)]), KeyLookup::Action(Action::GotoTop));
+    }
+
+    #[test]
+    fn test_bindings_reflect_overrides() {
+        let keys = KeysConfig {
+            goto_top: Some("gg".to_string()),
+            help: Some("ctrl+h".to_string()),
+            ..KeysConfig::default()
+        };
+        let bindings = KeyMap::build(&keys).bindings();
+        let keys_for = |action| bindings.iter().find(|(a, _)| *a == action).map(|(_, k)| k.clone());
+        assert_eq!(keys_for(Action::GotoTop), Some(vec!["gg".to_string(), "home".to_string()]));
+        assert_eq!(keys_for(Action::Help), Some(vec!["ctrl+h".to_string()]));
+        assert_eq!(keys_for(Action::FullPageDown), Some(vec!["pgdn".to_string(), "space".to_string()]));
+        assert_eq!(key_name((KeyCode::Char('o'), KeyModifiers::CONTROL)), "ctrl+o");
+    }
 }
@@ -0,0 +1,170 @@
+use anyhow::{Context, Result};
+use crossterm::event::{KeyCode, KeyModifiers};
+use std::path::Path;
+
+use crate::keymap::{Action, Key};
+
+/// Read a lesskey source file (the text format `lesskey` compiles, and that
+/// less 582+ reads directly) and return the bindings it makes that map onto
+/// our actions.
+pub fn load(path: &Path) -> Result<Vec<(Vec<Key>, Action)>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Cannot read lesskey file '{}'", path.display()))?;
+    Ok(parse(&text))
+}
+
+/// Parse the `#command` section of a lesskey file. Lines binding less
+/// commands we have no equivalent for (`shell`, `examine`, …) are skipped,
+/// as are the `#line-edit` and `#env` sections.
+pub fn parse(text: &str) -> Vec<(Vec<Key>, Action)> {
+    let mut in_commands = true;
+    let mut bindings = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            // Section headers; any other line starting with '#' is a comment
+            match line {
+                "#command" => in_commands = true,
+                "#line-edit" | "#env" | "#stop" => in_commands = false,
+                _ => {}
+            }
+            continue;
+        }
+        if !in_commands {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let (Some(keys), Some(command)) = (fields.next(), fields.next()) else { continue };
+        if let (Some(keys), Some(action)) = (parse_keys(keys), less_action(command)) {
+            bindings.push((keys, action));
+        }
+    }
+    bindings
+}
+
+/// Decode a lesskey key string: `^X` is Ctrl-X, `\e` Escape, `\kd` the down
+/// arrow and so on, `\NNN` an octal character; anything else is literal.
+fn parse_keys(s: &str) -> Option<Vec<Key>> {
+    let mut keys = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let key = match c {
+            '^' => match chars.next()? {
+                '[' => (KeyCode::Esc, KeyModifiers::NONE),
+                c => (KeyCode::Char(c.to_ascii_lowercase()), KeyModifiers::CONTROL),
+            },
+            '\\' => match chars.next()? {
+                'b' => (KeyCode::Backspace, KeyModifiers::NONE),
+                'e' => (KeyCode::Esc, KeyModifiers::NONE),
+                'n' | 'r' => (KeyCode::Enter, KeyModifiers::NONE),
+                't' => (KeyCode::Tab, KeyModifiers::NONE),
+                'k' => (special_key(chars.next()?)?, KeyModifiers::NONE),
+                d @ '0'..='7' => {
+                    let mut code = d.to_digit(8)?;
+                    for _ in 0..2 {
+                        match chars.peek().and_then(|c| c.to_digit(8)) {
+                            Some(digit) => {
+                                code = code * 8 + digit;
+                                chars.next();
+                            }
+                            None => break,
+                        }
+                    }
+                    (KeyCode::Char(char::from_u32(code)?), KeyModifiers::NONE)
+                }
+                c => (KeyCode::Char(c), KeyModifiers::NONE),
+            },
+            c => (KeyCode::Char(c), KeyModifiers::NONE),
+        };
+        keys.push(key);
+    }
+    (!keys.is_empty()).then_some(keys)
+}
+
+/// The key named by `\k<c>` in lesskey.
+fn special_key(c: char) -> Option<KeyCode> {
+    Some(match c {
+        'u' => KeyCode::Up,
+        'd' => KeyCode::Down,
+        'r' => KeyCode::Right,
+        'l' => KeyCode::Left,
+        'U' => KeyCode::PageUp,
+        'D' => KeyCode::PageDown,
+        'h' => KeyCode::Home,
+        'e' => KeyCode::End,
+        'x' => KeyCode::Delete,
+        'i' => KeyCode::Insert,
+        _ => return None,
+    })
+}
+
+/// Our equivalent of a less command, if there is one.
+fn less_action(command: &str) -> Option<Action> {
+    Some(match command {
+        "quit" => Action::Quit,
+        "forw-line" | "forw-line-force" => Action::ScrollDown,
+        "back-line" | "back-line-force" => Action::ScrollUp,
+        "forw-scroll" => Action::HalfPageDown,
+        "back-scroll" => Action::HalfPageUp,
+        "forw-screen" | "forw-screen-force" | "forw-window" => Action::FullPageDown,
+        "back-screen" | "back-window" => Action::FullPageUp,
+        "goto-line" => Action::GotoTop,
+        "goto-end" | "goto-end-buffered" => Action::GotoBottom,
+        "forw-search" => Action::SearchForward,
+        "back-search" => Action::SearchBackward,
+        "repeat-search" | "repeat-search-all" => Action::NextMatch,
+        "reverse-search" | "reverse-search-all" => Action::PrevMatch,
+        "next-file" => Action::NextBuffer,
+        "prev-file" => Action::PrevBuffer,
+        "set-mark" | "set-mark-bottom" => Action::SetMark,
+        "goto-mark" => Action::JumpMark,
+        "forw-forever" => Action::FollowMode,
+        "flush-repaint" => Action::Reload,
+        "filter" => Action::Filter,
+        "right-scroll" => Action::ScrollRight,
+        "left-scroll" => Action::ScrollLeft,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(c: char) -> Key {
+        (KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(parse_keys("^E"), Some(vec![(KeyCode::Char('e'), KeyModifiers::CONTROL)]));
+        assert_eq!(parse_keys("\\kd"), Some(vec![(KeyCode::Down, KeyModifiers::NONE)]));
+        assert_eq!(parse_keys("\\e"), Some(vec![(KeyCode::Esc, KeyModifiers::NONE)]));
+        assert_eq!(parse_keys("\\40"), Some(vec![plain(' ')]));
+        assert_eq!(parse_keys("\\^"), Some(vec![plain('^')]));
+        assert_eq!(parse_keys("ZZ"), Some(vec![plain('Z'), plain('Z')]));
+        assert_eq!(parse_keys("\\kz"), None);
+    }
+
+    #[test]
+    fn test_parse_sections() {
+        let text = "\
+            # my bindings\n\
+            #command\n\
+            \\r forw-line\n\
+            ZZ quit\n\
+            ! shell\n\
+            #line-edit\n\
+            ^A home\n\
+            #command\n\
+            \\kU back-screen\n";
+        assert_eq!(
+            parse(text),
+            vec![
+                (vec![(KeyCode::Enter, KeyModifiers::NONE)], Action::ScrollDown),
+                (vec![plain('Z'), plain('Z')], Action::Quit),
+                (vec![(KeyCode::PageUp, KeyModifiers::NONE)], Action::FullPageUp),
+            ]
+        );
+    }
+}
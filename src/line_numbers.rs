@@ -2,20 +2,48 @@ use std::collections::HashMap;
 use ratatui::prelude::*;
 use ratatui::widgets::Paragraph;
 use crate::app::App;
+use crate::filter::ViewRow;
 use crate::buffer::GitChange;
 
 pub fn render(
     frame: &mut Frame,
     app: &App,
     area: Rect,
-    line_indices: &[usize],
+    rows: &[ViewRow],
     git_changes: &HashMap<usize, GitChange>,
 ) {
     let width = app.gutter_width();
-    let style = Style::default().fg(Color::DarkGray);
+    let style = Style::default().fg(app.palette.line_number_fg);
+    let mark_lines: HashMap<usize, char> = app.marks.iter().map(|(&c, &line)| (line, c)).collect();
+    let current_match = app.search.current_match_line();
+
+    let blank = || {
+        Line::from(vec![
+            Span::styled(" ".repeat(width.saturating_sub(2)), style),
+            Span::styled(" \u{2502}", Style::default().fg(Color::Rgb(60, 60, 60))),
+        ])
+    };
 
     let mut lines: Vec<Line> = Vec::new();
-    for &line_idx in line_indices {
+    for row in rows {
+        let line_idx = match *row {
+            ViewRow::Line(i) => i,
+            ViewRow::Hidden { .. } => {
+                lines.push(Line::from(vec![
+                    Span::styled(" ".repeat(width.saturating_sub(2)), style),
+                    Span::styled(" \u{2506}", Style::default().fg(Color::Rgb(60, 60, 60))),
+                ]));
+                continue;
+            }
+            ViewRow::Deleted { .. } => {
+                lines.push(Line::from(vec![
+                    Span::styled(" ".repeat(width.saturating_sub(2)), style),
+                    Span::styled("-\u{2502}", Style::default().fg(Color::Red)),
+                ]));
+                lines.extend((1..app.row_height(*row)).map(|_| blank()));
+                continue;
+            }
+        };
         let num_str = format!("{:>width$}", line_idx + 1, width = width - 2);
 
         let (sep_char, sep_style) = match git_changes.get(&line_idx) {
@@ -27,16 +55,36 @@ pub fn render(
 
         lines.push(Line::from(vec![
             Span::styled(num_str, style),
-            Span::styled(format!(" {}", sep_char), sep_style),
+            indicator(app, line_idx, current_match, &mark_lines),
+            Span::styled(sep_char, sep_style),
         ]));
+        // Rows a wrapped line goes on to have no number
+        lines.extend((1..app.row_height(*row)).map(|_| blank()));
     }
-    for _ in line_indices.len()..area.height as usize {
-        let padding = " ".repeat(width.saturating_sub(2));
-        lines.push(Line::from(vec![
-            Span::styled(padding, style),
-            Span::styled(" \u{2502}", Style::default().fg(Color::Rgb(60, 60, 60))),
-        ]));
+    for _ in lines.len()..area.height as usize {
+        lines.push(blank());
     }
     let paragraph = Paragraph::new(lines);
     frame.render_widget(paragraph, area);
 }
+
+/// One-column navigation hint shown just before the separator, by priority:
+/// the current match, a mark (its letter), a note, or any search match.
+fn indicator(
+    app: &App,
+    line_idx: usize,
+    current_match: Option<usize>,
+    mark_lines: &HashMap<usize, char>,
+) -> Span<'static> {
+    if current_match == Some(line_idx) {
+        Span::styled("\u{25b6}", Style::default().fg(Color::Rgb(255, 220, 0)).add_modifier(Modifier::BOLD))
+    } else if let Some(c) = mark_lines.get(&line_idx) {
+        Span::styled(c.to_string(), Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+    } else if app.notes.contains_key(&line_idx) {
+        Span::styled("\u{270e}", Style::default().fg(Color::Yellow))
+    } else if app.search.line_has_match(line_idx) {
+        Span::styled("\u{2022}", Style::default().fg(Color::Rgb(200, 160, 0)))
+    } else {
+        Span::raw(" ")
+    }
+}
@@ -0,0 +1,123 @@
+//! Line indexing: the byte offset where each line of a buffer starts.
+//! Kept free of the rest of the crate so `benches/index_lines.rs` can
+//! include it.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Inputs at least this large are scanned on several threads.
+const PARALLEL_SIZE: usize = 64 << 20;
+
+/// Bytes scanned between progress updates.
+const PROGRESS_CHUNK: usize = 1 << 20;
+
+/// Byte offset of the start of each line of `data`: 0, then one past every
+/// newline but a final one. Adds the bytes scanned to `indexed` as it goes.
+pub fn index(data: &[u8], indexed: &AtomicU64) -> Vec<usize> {
+    let threads = if data.len() < PARALLEL_SIZE {
+        1
+    } else {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    };
+    index_on(data, indexed, threads)
+}
+
+/// Extend `offsets`, the index of `data[..from]`, to the whole of `data`.
+pub fn extend(offsets: &mut Vec<usize>, data: &[u8], from: usize) {
+    if from >= data.len() {
+        return;
+    }
+    if offsets.is_empty() {
+        offsets.push(0);
+    }
+    // A newline that ended the old data starts a line now that more follows
+    let start = from.saturating_sub(1);
+    scan(&data[start..], start, &AtomicU64::new(0), offsets);
+    if offsets.last() == Some(&data.len()) {
+        offsets.pop();
+    }
+}
+
+/// `index`, splitting `data` into one part per thread.
+fn index_on(data: &[u8], indexed: &AtomicU64, threads: usize) -> Vec<usize> {
+    if data.is_empty() {
+        return vec![];
+    }
+    let mut offsets = vec![0];
+    if threads <= 1 {
+        scan(data, 0, indexed, &mut offsets);
+    } else {
+        let part = data.len().div_ceil(threads);
+        let parts: Vec<Vec<usize>> = std::thread::scope(|s| {
+            let handles: Vec<_> = data
+                .chunks(part)
+                .enumerate()
+                .map(|(i, chunk)| {
+                    s.spawn(move || {
+                        let mut found = Vec::new();
+                        scan(chunk, i * part, indexed, &mut found);
+                        found
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().expect("line indexing thread panicked")).collect()
+        });
+        offsets.reserve(parts.iter().map(Vec::len).sum());
+        for found in parts {
+            offsets.extend_from_slice(&found);
+        }
+    }
+    // A final newline ends the last line rather than starting another
+    if offsets.last() == Some(&data.len()) {
+        offsets.pop();
+    }
+    offsets
+}
+
+/// Push the offset after each newline in `chunk`, which starts at `base`.
+fn scan(chunk: &[u8], base: usize, indexed: &AtomicU64, offsets: &mut Vec<usize>) {
+    for (n, piece) in chunk.chunks(PROGRESS_CHUNK).enumerate() {
+        let start = base + n * PROGRESS_CHUNK;
+        offsets.extend(memchr::memchr_iter(b'\n', piece).map(|i| start + i + 1));
+        indexed.fetch_add(piece.len() as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive(data: &[u8]) -> Vec<usize> {
+        if data.is_empty() {
+            return vec![];
+        }
+        let mut offsets = vec![0];
+        for (i, &byte) in data.iter().enumerate() {
+            if byte == b'\n' && i + 1 < data.len() {
+                offsets.push(i + 1);
+            }
+        }
+        offsets
+    }
+
+    #[test]
+    fn test_index() {
+        let inputs: [&[u8]; 6] = [b"", b"\n", b"one", b"one\ntwo\n", b"\n\nthree\r\nfour", b"a\nbb\nccc\ndddd\neeeee\n"];
+        for data in inputs {
+            for threads in [1, 2, 3, 7] {
+                let indexed = AtomicU64::new(0);
+                assert_eq!(index_on(data, &indexed, threads), naive(data), "{:?} on {} threads", data, threads);
+                assert_eq!(indexed.load(Ordering::Relaxed), data.len() as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn test_extend() {
+        let data = b"\none\ntwo\r\n\nthree";
+        for from in 0..=data.len() {
+            let mut offsets = naive(&data[..from]);
+            extend(&mut offsets, data, from);
+            assert_eq!(offsets, naive(data), "from {}", from);
+        }
+    }
+}
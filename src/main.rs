@@ -1,17 +1,45 @@
 mod app;
+mod bat;
+mod blame;
 mod buffer;
 mod cli;
+mod cols;
 mod config;
+mod config_check;
+mod exec;
+mod export;
+mod filter;
+mod git;
+mod info;
 mod input;
+mod jq;
 mod keymap;
+mod lesskey;
+mod lines;
 mod line_numbers;
+mod modeline;
+mod motion;
+mod options;
+mod overstrike;
+mod paths;
+mod picker;
+mod preprocess;
+mod print;
+mod range;
 mod search;
+mod session;
+mod shell;
+mod sigbus;
+mod sort;
 mod statusbar;
+mod strings;
 mod syntax;
+mod tabs;
+#[cfg(feature = "tree-sitter")]
+mod treesitter;
 mod viewer;
 
 use anyhow::{Context, Result};
-use clap::Parser;
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture},
     execute,
@@ -22,23 +50,102 @@ use ratatui::Terminal;
 use std::io;
 
 fn main() -> Result<()> {
-    let cli_args = cli::Cli::parse();
+    sigbus::install();
+    let cli_args = cli::Cli::parse_with_env();
+
+    if cli_args.init_config {
+        match config::Config::init() {
+            Ok(path) => {
+                println!("{}", path.display());
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("some: {:#}", e);
+                std::process::exit(1);
+            }
+        }
+    }
 
     // Load config and merge CLI flags
-    let mut config = config::Config::load()
-        .context("Failed to load configuration")?;
+    let (mut config, mut config_issues) = config::Config::load();
     config.merge_cli(&cli_args);
 
     // Set up syntax highlighting
-    let syntax_enabled = !cli_args.no_syntax && !cli_args.plain;
-    let highlighter = syntax::SyntaxHighlighter::new(
+    let color = !cli_args.plain && cli_args.use_color(atty::is(atty::Stream::Stdout));
+    let syntax_enabled = !cli_args.no_syntax && color;
+    let mut highlighter = syntax::SyntaxHighlighter::new(
         &config.general.theme,
         syntax_enabled,
         config.general.themes_dir.as_deref(),
+        config.general.bat_assets,
     );
+    highlighter.set_limits(config.general.highlight_max_size, config.general.highlight_max_line_len);
+    // Looking the theme up waits for the themes to load, which a plain view
+    // never needs
+    if syntax_enabled || cli_args.check_config {
+        config_issues.extend(config_check::check_theme(&config.general.theme, &highlighter));
+    }
+    if let Some(language) = cli_args.language.as_deref().filter(|l| highlighter.find_syntax(l).is_none()) {
+        eprintln!("some: unknown language '{}'", language);
+        std::process::exit(1);
+    }
+
+    if cli_args.list_themes {
+        let mut out = io::stdout().lock();
+        print::write_theme_list(&mut out, &highlighter, syntax_enabled)?;
+        return Ok(());
+    }
+
+    if cli_args.check_config {
+        let path = config::Config::config_path().unwrap_or_default();
+        if !path.exists() {
+            println!("{}: no config file (using defaults)", path.display());
+            return Ok(());
+        }
+        if config_issues.is_empty() {
+            println!("{}: OK", path.display());
+            return Ok(());
+        }
+        for issue in &config_issues {
+            match issue.line {
+                Some(line) => println!("{}:{}: {}", path.display(), line, issue.message),
+                None => println!("{}: {}", path.display(), issue.message),
+            }
+        }
+        std::process::exit(1);
+    }
+
+    // A saved session supplies the file list when no files are given
+    let session = match cli_args.session.as_deref() {
+        Some(name) => session::Session::load(name)?,
+        None => None,
+    };
+    let mut files: Vec<std::path::PathBuf> = match &session {
+        Some(s) if cli_args.files.is_empty() => s.buffers.iter().map(|b| b.path.clone()).collect(),
+        _ => cli_args.files.clone(),
+    };
+    // The files a --watch-glob pattern matches already are opened with the rest
+    for pattern in &cli_args.watch_glob {
+        let found: Vec<_> = glob::glob(pattern)
+            .map_err(|e| anyhow::anyhow!("Invalid --watch-glob pattern '{}': {}", pattern, e))?
+            .flatten()
+            .filter(|p| p.is_file() && !files.contains(p))
+            .collect();
+        files.extend(found);
+    }
+
+    let export = match (&cli_args.export_html, &cli_args.export_ansi) {
+        (Some(out), _) => Some((export::ExportFormat::Html, out)),
+        (None, Some(out)) => Some((export::ExportFormat::Ansi, out)),
+        (None, None) => None,
+    };
+    // Large files load in the background when they will be paged; a session
+    // needs their contents to restore its positions, searches and filters,
+    // and --cat to join them
+    let background = export.is_none() && cli_args.paging() != cli::Paging::Never && session.is_none() && !cli_args.cat;
 
     // Load buffers
-    let buffers = if let Some(ref diff_path) = cli_args.diff {
+    let mut buffers = if let Some(ref diff_path) = cli_args.diff {
         // Diff mode: compare first positional file against --diff FILE2
         if cli_args.files.is_empty() {
             eprintln!("some: --diff requires a FILE argument");
@@ -47,18 +154,50 @@ fn main() -> Result<()> {
         let diff_buf = buffer::Buffer::from_diff(&cli_args.files[0], diff_path)
             .with_context(|| format!("Failed to create diff: {} vs {}", cli_args.files[0].display(), diff_path.display()))?;
         vec![diff_buf]
-    } else if cli_args.files.is_empty() {
+    } else if let Some(ref cmd) = cli_args.exec {
+        // The first run is shown right away; the rest are run in the
+        // background while paging
+        let (output, status) = shell::run(cmd, None)?;
+        let mut buf = buffer::Buffer::from_bytes(&format!("[$ {}]", cmd), output);
+        if background {
+            let mut exec = exec::Exec::spawn(cmd, std::time::Duration::from_secs_f64(cli_args.interval.max(0.1)));
+            exec.status = Some(status);
+            buf.exec = Some(exec);
+        }
+        vec![buf]
+    } else if files.is_empty() && !cli_args.watch_glob.is_empty() {
+        // Nothing to show until a file matching the pattern turns up
+        let name = format!("[waiting for {}]", cli_args.watch_glob.join(" "));
+        vec![buffer::Buffer::from_bytes(&name, Vec::new())]
+    } else if files.is_empty() {
         // Read from stdin
         if atty::is(atty::Stream::Stdin) {
             eprintln!("Usage: some [OPTIONS] [FILE]...");
             eprintln!("Try 'some --help' for more information.");
             std::process::exit(1);
         }
-        vec![buffer::Buffer::from_stdin()?]
+        vec![stdin_buffer(background, config.general.limits())?]
     } else {
         let mut bufs = Vec::new();
-        for path in &cli_args.files {
-            match buffer::Buffer::from_file(path, config.general.mmap_threshold) {
+        let mut stdin_read = false;
+        for path in &files {
+            let general = &config.general;
+            // '-' stands for stdin, as for cat and less
+            let loaded = if path.as_os_str() == "-" {
+                if atty::is(atty::Stream::Stdin) {
+                    eprintln!("some: -: standard input is a terminal");
+                    continue;
+                }
+                if std::mem::replace(&mut stdin_read, true) {
+                    continue;
+                }
+                stdin_buffer(background, general.limits())
+            } else if background {
+                buffer::Buffer::open(path, general.limits(), general.preprocessor.as_deref())
+            } else {
+                buffer::Buffer::from_file(path, general.limits(), general.preprocessor.as_deref())
+            };
+            match loaded {
                 Ok(buf) => bufs.push(buf),
                 Err(e) => {
                     eprintln!("some: {}: {}", path.display(), e);
@@ -71,22 +210,99 @@ fn main() -> Result<()> {
         }
         bufs
     };
+    for buf in &mut buffers {
+        if cli_args.man {
+            buf.interpret_overstrike(true);
+        }
+        let ft = config.filetype(buf.path.as_deref(), &highlighter);
+        buf.syntax = cli_args.language.clone().or_else(|| ft.and_then(|ft| ft.syntax.clone()));
+        buf.engine = ft.and_then(|ft| ft.highlighter).unwrap_or_default();
+    }
+    if cli_args.cat && buffers.len() > 1 {
+        let parts: Vec<&buffer::Buffer> = buffers.iter().collect();
+        buffers = vec![app::joined(&parts, &highlighter, config.general.limits())?];
+    }
+
+    if let Some((format, out)) = export {
+        let mut search = search::SearchState::new();
+        if let Some(ref pattern) = cli_args.pattern {
+            search
+                .set_pattern(pattern, config.general.smart_case)
+                .with_context(|| format!("Invalid pattern '{}'", pattern))?;
+        }
+        let buf = &buffers[0];
+        let opts = export::StyledExport {
+            highlighter: &highlighter,
+            line_numbers: config.general.line_numbers,
+            tab_width: tab_width(buf, &config, &highlighter),
+            search: search.pattern.as_ref(),
+            notes: None,
+        };
+        export::write_styled(buf, 0..buf.line_count(), &opts, format, out)?;
+        return Ok(());
+    }
+
+    // --print prints instead of paging; -F only when the content is short
+    let opts = |buf: &buffer::Buffer| print::PrintOptions {
+        line_numbers: config.general.line_numbers,
+        color,
+        tab_width: tab_width(buf, &config, &highlighter),
+    };
+    // Give a pipe a moment to finish, so short output can still be printed
+    if cli_args.paging() == cli::Paging::Auto && !cli_args.follow {
+        for buf in &mut buffers {
+            buf.wait_stream(std::time::Duration::from_millis(100))?;
+        }
+    }
+    match cli_args.paging() {
+        cli::Paging::Never => return print_buffers(&buffers, &highlighter, opts),
+        // A file loading in the background is too large to fit anyway, and
+        // a pipe still open may never end
+        cli::Paging::Auto if !cli_args.follow && buffers.iter().all(|b| b.loading.is_none() && b.stream.is_none()) => {
+            if let Ok((width, height)) = crossterm::terminal::size() {
+                // Leave a row for the shell prompt that follows
+                if print::fits_on_screen(&buffers, opts, width, height.saturating_sub(1)) {
+                    return print_buffers(&buffers, &highlighter, opts);
+                }
+            }
+        }
+        _ => {}
+    }
 
     // Build the application state
     let mut app = app::App::new(buffers, config.clone(), highlighter);
 
-    // Start watching files for follow mode
+    // Start watching files for follow mode, and for new ones to open
     app.start_watching();
+    app.watch_globs(&cli_args.watch_glob)?;
+
+    if let Some(ref s) = session {
+        app.restore_session(s);
+    }
+    app.session_name = cli_args.session.clone();
+
+    // Point out config problems; --check-config lists them all
+    if let Some(summary) = config_check::summary(&config_issues) {
+        app.status_message = Some(format!("config.toml: {}", summary));
+    }
+    app.cli = Some(cli_args.clone());
+    app.color = color;
+    app.watch_config();
 
     // Apply CLI-specific overrides
     if let Some(line) = cli_args.start_line {
-        app.goto_line(line.saturating_sub(1));
+        if app.buffer().loading.is_some() {
+            app.pending_goto = Some(line.saturating_sub(1));
+        } else {
+            app.goto_line(line.saturating_sub(1));
+        }
     }
     if let Some(ref pattern) = cli_args.pattern {
         app.search.query_string = pattern.clone();
         app.execute_search();
     }
-    if cli_args.follow {
+    // A pipe that is still open is followed as it grows, as a file is with -f
+    if cli_args.follow || app.is_streaming() {
         app.mode = app::Mode::Follow;
         app.goto_bottom();
     }
@@ -94,11 +310,64 @@ fn main() -> Result<()> {
     // Enter TUI
     run_tui(&mut app)?;
 
+    if let Some(name) = app.session_name.clone() {
+        if let Err(e) = app.session().save(&name) {
+            eprintln!("some: {:#}", e);
+        }
+    }
+
     Ok(())
 }
 
+/// A buffer for stdin: followed as it arrives when it will be paged, else
+/// read to the end.
+fn stdin_buffer(background: bool, limits: buffer::Limits) -> Result<buffer::Buffer> {
+    if background {
+        Ok(buffer::Buffer::streaming("[stdin]", limits, || Ok(io::stdin())))
+    } else {
+        buffer::Buffer::from_stdin()
+    }
+}
+
+/// Write the buffers to stdout one after another. A closed pipe (e.g. into
+/// `head`) is not an error.
+fn print_buffers(
+    buffers: &[buffer::Buffer],
+    highlighter: &syntax::SyntaxHighlighter,
+    opts: impl Fn(&buffer::Buffer) -> print::PrintOptions,
+) -> Result<()> {
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let result = buffers
+        .iter()
+        .try_for_each(|buf| print::write_buffer(&mut out, buf, highlighter, &opts(buf)))
+        .and_then(|_| io::Write::flush(&mut out));
+    match result {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e).context("Failed to write output"),
+        _ => Ok(()),
+    }
+}
+
+/// Tab width `buf` is printed or exported with, as the viewer picks it: its
+/// modeline's, else its `[filetype.*]` section's, else `[general]`'s.
+fn tab_width(buf: &buffer::Buffer, config: &config::Config, highlighter: &syntax::SyntaxHighlighter) -> u8 {
+    let filetype = config.filetype(buf.path.as_deref(), highlighter).and_then(|ft| ft.tab_width);
+    modeline::find(buf).and_then(|m| m.tab_width).or(filetype).unwrap_or(config.general.tab_width)
+}
+
 /// Set up the terminal, run the event loop, then restore the terminal.
 fn run_tui(app: &mut app::App) -> Result<()> {
+    // Put the terminal back before a panic on this thread is reported, or
+    // the message is lost with the alternate screen and the shell is left
+    // in raw mode
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().name() == Some("main") {
+            let _ = disable_raw_mode();
+            let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture, crossterm::cursor::Show);
+        }
+        default_hook(info);
+    }));
+
     enable_raw_mode().context("Failed to enable raw mode")?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)
@@ -122,6 +391,34 @@ fn run_tui(app: &mut app::App) -> Result<()> {
     result
 }
 
+/// Hand the terminal to $EDITOR, then take it back and reload the buffer in
+/// case the file was changed.
+fn run_editor(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut app::App,
+    path: &std::path::Path,
+    line: usize,
+) -> Result<()> {
+    disable_raw_mode().context("Failed to disable raw mode")?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)
+        .context("Failed to leave alternate screen")?;
+    terminal.show_cursor()?;
+
+    let status = shell::edit(path, line);
+
+    enable_raw_mode().context("Failed to enable raw mode")?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)
+        .context("Failed to enter alternate screen")?;
+    terminal.clear()?;
+
+    match status {
+        Ok(status) if status.success() => app.reload_active_buffer(),
+        Ok(status) => app.status_message = Some(format!("Editor exited with {}", status)),
+        Err(e) => app.status_message = Some(format!("{:#}", e)),
+    }
+    Ok(())
+}
+
 /// Poll-based event loop: render → check file changes → wait for input → repeat.
 fn event_loop(
     terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
@@ -130,33 +427,76 @@ fn event_loop(
     use std::time::Duration;
 
     loop {
-        terminal.draw(|frame| {
-            viewer::render(frame, app);
-        })?;
+        app.check_truncated();
+        // Redraw only when something changed; follow mode keeps redrawing
+        // as the file grows
+        if app.dirty || app.mode == app::Mode::Follow {
+            terminal.draw(|frame| {
+                viewer::render(frame, app);
+            })?;
+            app.dirty = false;
+        }
+        if std::mem::take(&mut app.bell) {
+            // Alerts ring the bell once the line that set them off is shown
+            let out = terminal.backend_mut();
+            io::Write::write_all(out, b"\x07")?;
+            io::Write::flush(out)?;
+        }
+        let busy = app.is_busy();
 
         // Check for file-change events (non-blocking); reload in follow mode
-        let mut got_change = false;
+        let mut changed = Vec::new();
         if let Some(rx) = &app.watcher_rx {
             while let Ok(ev) = rx.try_recv() {
                 if let Ok(ev) = ev {
                     use notify::EventKind;
-                    if matches!(ev.kind, EventKind::Modify(_) | EventKind::Create(_)) {
-                        got_change = true;
+                    if matches!(ev.kind, EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_)) {
+                        changed.extend(ev.paths);
                     }
                 }
             }
         }
-        if got_change && app.mode == app::Mode::Follow {
-            app.reload_active_buffer();
+        if !changed.is_empty() {
+            app.dirty = true;
+            app.files_changed(&changed);
         }
+        app.check_config_changed();
 
-        // Drain async search result batches
+        // Drain async load, search and filter results
+        app.drain_loads();
+        app.drain_streams();
+        app.drain_exec();
         app.drain_search_results();
+        app.drain_filter_results();
+        app.drain_blame_results();
+        app.refresh_repo_status();
+        app.drain_git_changes();
+        app.update_messages();
+        // Background work shows progress as it goes, and its results once
+        // it has finished
+        if busy || app.is_busy() {
+            app.dirty = true;
+        }
 
-        // Poll for terminal events with a short timeout (keeps follow mode responsive)
-        if event::poll(Duration::from_millis(200))? {
+        // Poll for terminal events with a short timeout (keeps follow mode
+        // responsive), shorter while highlighting or git blame catches up
+        // and while a file loads, to turn its spinner, or a pipe produces more
+        let timeout = if app.is_highlighting() || app.is_blaming() {
+            50
+        } else if app.is_loading() || app.is_streaming() {
+            100
+        } else {
+            200
+        };
+        if event::poll(Duration::from_millis(timeout))? {
             let ev = event::read().context("Failed to read terminal event")?;
             input::handle_event(app, ev);
+            app.dirty = true;
+        }
+
+        if let Some((path, line)) = app.edit_request.take() {
+            run_editor(terminal, app, &path, line)?;
+            app.dirty = true;
         }
 
         if app.quit {
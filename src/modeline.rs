@@ -0,0 +1,100 @@
+use crate::buffer::Buffer;
+
+/// Lines at each end of a buffer searched for a modeline, as in vim.
+const MODELINES: usize = 5;
+
+/// Settings from a vim (`# vim: set ft=yaml ts=2:`) or Emacs
+/// (`-*- mode: python; tab-width: 4 -*-`) modeline.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Modeline {
+    /// Filetype or mode name, looked up as a syntax name or extension
+    pub syntax: Option<String>,
+    pub tab_width: Option<u8>,
+}
+
+/// The first modeline in the first or last few lines of `buf`.
+pub fn find(buf: &Buffer) -> Option<Modeline> {
+    if buf.is_binary() || buf.is_diff {
+        return None;
+    }
+    let count = buf.line_count();
+    let head = count.min(MODELINES);
+    let tail = count.saturating_sub(MODELINES).max(head);
+    (0..head).chain(tail..count).filter_map(|i| buf.get_line(i)).find_map(parse)
+}
+
+/// The settings in `line` if it is a modeline that sets any we use.
+pub fn parse(line: &str) -> Option<Modeline> {
+    parse_vim(line).or_else(|| parse_emacs(line))
+}
+
+/// `vim: ft=yaml ts=2` or `vim: set ft=yaml ts=2:`, also with `vi:`/`ex:`.
+fn parse_vim(line: &str) -> Option<Modeline> {
+    let rest = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        line.match_indices(marker)
+            .find(|&(i, _)| line[..i].chars().next_back().is_none_or(char::is_whitespace))
+            .map(|(i, _)| &line[i + marker.len()..])
+    })?;
+    let rest = rest.trim_start();
+    let options: Vec<&str> = match rest.strip_prefix("set ").or_else(|| rest.strip_prefix("se ")) {
+        // The `set` form ends at the next colon
+        Some(set) => set.split(':').next().unwrap_or_default().split_whitespace().collect(),
+        None => rest.split(|c: char| c == ':' || c.is_whitespace()).collect(),
+    };
+    let mut modeline = Modeline::default();
+    for option in options {
+        match option.split_once('=') {
+            Some(("ft" | "filetype" | "syn" | "syntax", value)) if !value.is_empty() => {
+                modeline.syntax = Some(value.to_string());
+            }
+            Some(("ts" | "tabstop", value)) => modeline.tab_width = value.parse().ok().filter(|&n| n > 0),
+            _ => {}
+        }
+    }
+    (modeline != Modeline::default()).then_some(modeline)
+}
+
+/// `-*- mode: python; tab-width: 4 -*-`, or just `-*- python -*-`.
+fn parse_emacs(line: &str) -> Option<Modeline> {
+    let (_, rest) = line.split_once("-*-")?;
+    let (vars, _) = rest.split_once("-*-")?;
+    let mut modeline = Modeline::default();
+    if !vars.contains(':') {
+        modeline.syntax = Some(vars.trim().to_string()).filter(|mode| !mode.is_empty());
+    }
+    for var in vars.split(';') {
+        let Some((key, value)) = var.split_once(':') else { continue };
+        let value = value.trim();
+        match key.trim().to_lowercase().as_str() {
+            "mode" if !value.is_empty() => modeline.syntax = Some(value.to_string()),
+            "tab-width" => modeline.tab_width = value.parse().ok().filter(|&n| n > 0),
+            _ => {}
+        }
+    }
+    (modeline != Modeline::default()).then_some(modeline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modeline(syntax: Option<&str>, tab_width: Option<u8>) -> Option<Modeline> {
+        Some(Modeline { syntax: syntax.map(String::from), tab_width })
+    }
+
+    #[test]
+    fn test_parse_vim() {
+        assert_eq!(parse("# vim: ft=yaml ts=2"), modeline(Some("yaml"), Some(2)));
+        assert_eq!(parse("/* vim: set filetype=c tabstop=8 noet: */"), modeline(Some("c"), Some(8)));
+        assert_eq!(parse("vi:syntax=sh:ts=4"), modeline(Some("sh"), Some(4)));
+        assert_eq!(parse("# vim: set et sw=4:"), None);
+        assert_eq!(parse("envi:ft=yaml index:ft=yaml"), None);
+    }
+
+    #[test]
+    fn test_parse_emacs() {
+        assert_eq!(parse("# -*- mode: python; tab-width: 4 -*-"), modeline(Some("python"), Some(4)));
+        assert_eq!(parse(";; -*- lisp -*-"), modeline(Some("lisp"), None));
+        assert_eq!(parse("# -*- coding: utf-8 -*-"), None);
+    }
+}
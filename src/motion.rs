@@ -0,0 +1,71 @@
+#[derive(PartialEq)]
+enum CharClass {
+    Space,
+    Word,
+    Punct,
+}
+
+fn class(c: char) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Space
+    } else if c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punct
+    }
+}
+
+/// Start of the next word after char column `col`, or None at the end of the line (vim `w`).
+pub fn next_word_start(line: &str, col: usize) -> Option<usize> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut i = col;
+    if let Some(&c) = chars.get(i) {
+        let start = class(c);
+        while i < chars.len() && class(chars[i]) == start && start != CharClass::Space {
+            i += 1;
+        }
+    }
+    while i < chars.len() && class(chars[i]) == CharClass::Space {
+        i += 1;
+    }
+    (i < chars.len()).then_some(i)
+}
+
+/// Start of the word before `col`, or None at the start of the line (vim `b`).
+pub fn prev_word_start(line: &str, col: usize) -> Option<usize> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut i = col.min(chars.len());
+    while i > 0 && class(chars[i - 1]) == CharClass::Space {
+        i -= 1;
+    }
+    if i == 0 {
+        return None;
+    }
+    let word = class(chars[i - 1]);
+    while i > 0 && class(chars[i - 1]) == word {
+        i -= 1;
+    }
+    Some(i)
+}
+
+/// End of the current or next word after `col`, or None if there is none (vim `e`).
+pub fn word_end(line: &str, col: usize) -> Option<usize> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut i = col + 1;
+    while i < chars.len() && class(chars[i]) == CharClass::Space {
+        i += 1;
+    }
+    if i >= chars.len() {
+        return None;
+    }
+    let word = class(chars[i]);
+    while i + 1 < chars.len() && class(chars[i + 1]) == word {
+        i += 1;
+    }
+    Some(i)
+}
+
+/// Byte offset of char column `col` in `line` (the line length past the end).
+pub fn byte_offset(line: &str, col: usize) -> usize {
+    line.char_indices().nth(col).map_or(line.len(), |(b, _)| b)
+}
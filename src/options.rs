@@ -0,0 +1,119 @@
+/// One argument of `:set`, in vim syntax.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SetArg<'a> {
+    /// `:set wrap`
+    On(&'a str),
+    /// `:set nowrap`
+    Off(&'a str),
+    /// `:set wrap!` or `:set invwrap`
+    Toggle(&'a str),
+    /// `:set wrap?`
+    Show(&'a str),
+    /// `:set tabwidth=8`
+    Assign(&'a str, &'a str),
+}
+
+/// A runtime option that `:set` can change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Opt {
+    Number,
+    Wrap,
+    WrapScan,
+    SmartCase,
+    FancyRegex,
+    TabWidth,
+    MaxMatches,
+    Theme,
+}
+
+impl Opt {
+    /// Every option, in the order `:set` with no argument lists them.
+    pub const ALL: [Opt; 8] = [
+        Opt::Number,
+        Opt::Wrap,
+        Opt::WrapScan,
+        Opt::SmartCase,
+        Opt::FancyRegex,
+        Opt::TabWidth,
+        Opt::MaxMatches,
+        Opt::Theme,
+    ];
+
+    /// Look an option up by its name or vim-style abbreviation.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "number" | "nu" | "linenumbers" => Opt::Number,
+            "wrap" => Opt::Wrap,
+            "wrapscan" | "ws" => Opt::WrapScan,
+            "smartcase" | "scs" => Opt::SmartCase,
+            "fancyregex" | "fancy" => Opt::FancyRegex,
+            "tabwidth" | "ts" | "tabstop" => Opt::TabWidth,
+            "maxmatches" | "mm" => Opt::MaxMatches,
+            "theme" => Opt::Theme,
+            _ => return None,
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Opt::Number => "number",
+            Opt::Wrap => "wrap",
+            Opt::WrapScan => "wrapscan",
+            Opt::SmartCase => "smartcase",
+            Opt::FancyRegex => "fancyregex",
+            Opt::TabWidth => "tabwidth",
+            Opt::MaxMatches => "maxmatches",
+            Opt::Theme => "theme",
+        }
+    }
+
+    /// Whether the option is on/off rather than taking a value.
+    pub fn is_bool(self) -> bool {
+        !matches!(self, Opt::TabWidth | Opt::MaxMatches | Opt::Theme)
+    }
+}
+
+/// Split the argument of `:set` into its space-separated settings.
+pub fn parse(arg: &str) -> Vec<SetArg<'_>> {
+    arg.split_whitespace().map(parse_one).collect()
+}
+
+fn parse_one(s: &str) -> SetArg<'_> {
+    if let Some((name, value)) = s.split_once('=') {
+        return SetArg::Assign(name, value);
+    }
+    if let Some(name) = s.strip_suffix('?') {
+        return SetArg::Show(name);
+    }
+    if let Some(name) = s.strip_suffix('!').or_else(|| s.strip_prefix("inv")) {
+        return SetArg::Toggle(name);
+    }
+    // "no" only negates a known option, so "nu" stays "nu"
+    match s.strip_prefix("no") {
+        Some(name) if Opt::from_name(name).is_some() => SetArg::Off(name),
+        _ => SetArg::On(s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_set_args() {
+        assert_eq!(
+            parse("wrap nonumber ws! tabwidth=8 theme? nu invscs"),
+            vec![
+                SetArg::On("wrap"),
+                SetArg::Off("number"),
+                SetArg::Toggle("ws"),
+                SetArg::Assign("tabwidth", "8"),
+                SetArg::Show("theme"),
+                SetArg::On("nu"),
+                SetArg::Toggle("scs"),
+            ]
+        );
+        assert_eq!(Opt::from_name("ts"), Some(Opt::TabWidth));
+        assert_eq!(Opt::from_name("bogus"), None);
+    }
+}
@@ -0,0 +1,130 @@
+use ratatui::style::{Modifier, Style};
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::syntax::StyledSpan;
+
+/// A styled byte range of a line with its overstrikes removed.
+pub type Run = (Range<usize>, Modifier);
+
+/// How much of the input to look at when deciding whether it is a man page.
+const DETECT_LEN: usize = 64 * 1024;
+
+/// True if the start of `data` is text containing backspace overstrikes
+/// (`X\bX` for bold, `_\bX` for underline), as `man` writes to a pager.
+pub fn detect(data: &[u8]) -> bool {
+    let head = &data[..data.len().min(DETECT_LEN)];
+    if head.contains(&0) {
+        return false;
+    }
+    head.windows(3).any(|w| w[1] == 0x08 && (w[0] == w[2] || w[0] == b'_' || w[2] == b'_'))
+}
+
+/// Remove the overstrike sequences from `data`. Returns the plain text and
+/// the bold/underlined runs of each line that had any, keyed by line index.
+pub fn strip(data: &[u8]) -> (Vec<u8>, HashMap<usize, Vec<Run>>) {
+    let mut text = Vec::with_capacity(data.len());
+    let mut runs = HashMap::new();
+    for (i, line) in data.split(|&b| b == b'\n').enumerate() {
+        if i > 0 {
+            text.push(b'\n');
+        }
+        if !line.contains(&0x08) {
+            text.extend_from_slice(line);
+            continue;
+        }
+        let (plain, line_runs) = strip_line(&String::from_utf8_lossy(line));
+        text.extend_from_slice(plain.as_bytes());
+        if !line_runs.is_empty() {
+            runs.insert(i, line_runs);
+        }
+    }
+    (text, runs)
+}
+
+fn strip_line(line: &str) -> (String, Vec<Run>) {
+    let chars: Vec<char> = line.chars().collect();
+    let mut plain = String::with_capacity(line.len());
+    let mut runs: Vec<Run> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let mut c = chars[i];
+        i += 1;
+        // A backspace with nothing to overstrike
+        if c == '\x08' {
+            continue;
+        }
+        let mut modifier = Modifier::empty();
+        while i + 1 < chars.len() && chars[i] == '\x08' {
+            let next = chars[i + 1];
+            if next == c {
+                modifier |= Modifier::BOLD;
+            } else if c == '_' {
+                modifier |= Modifier::UNDERLINED;
+                c = next;
+            } else if next == '_' {
+                modifier |= Modifier::UNDERLINED;
+            } else {
+                // Any other overstrike (e.g. "+\bo" bullets): the last one shows
+                c = next;
+            }
+            i += 2;
+        }
+        let start = plain.len();
+        plain.push(c);
+        if modifier.is_empty() {
+            continue;
+        }
+        match runs.last_mut() {
+            Some((range, m)) if range.end == start && *m == modifier => range.end = plain.len(),
+            _ => runs.push((start..plain.len(), modifier)),
+        }
+    }
+    (plain, runs)
+}
+
+/// Split `text` into spans styled by `runs`.
+pub fn spans(text: &str, runs: &[Run]) -> Vec<StyledSpan> {
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    for (range, modifier) in runs {
+        if range.start < pos || range.end > text.len() {
+            continue;
+        }
+        if range.start > pos {
+            spans.push(StyledSpan { text: text[pos..range.start].to_string(), style: Style::default() });
+        }
+        spans.push(StyledSpan {
+            text: text[range.clone()].to_string(),
+            style: Style::default().add_modifier(*modifier),
+        });
+        pos = range.end;
+    }
+    if pos < text.len() {
+        spans.push(StyledSpan { text: text[pos..].to_string(), style: Style::default() });
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_overstrike() {
+        let data = b"N\x08NA\x08AM\x08ME\x08E\n     _\x08l_\x08s [file]\n";
+        assert!(detect(data));
+        let (text, runs) = strip(data);
+        assert_eq!(text, b"NAME\n     ls [file]\n");
+        assert_eq!(runs[&0], vec![(0..4, Modifier::BOLD)]);
+        assert_eq!(runs[&1], vec![(5..7, Modifier::UNDERLINED)]);
+        assert!(!detect(b"plain text\n"));
+    }
+
+    #[test]
+    fn test_strip_bold_underline_and_utf8() {
+        let (plain, runs) = strip_line("_\x08x\x08x \u{2010}\x08\u{2010}");
+        assert_eq!(plain, "x \u{2010}");
+        assert_eq!(runs, vec![(0..1, Modifier::UNDERLINED | Modifier::BOLD), (2..5, Modifier::BOLD)]);
+    }
+}
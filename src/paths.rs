@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+/// Expand a leading `~/` to the user's home directory.
+pub fn expand_path(path: &str) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest);
+        }
+    }
+    PathBuf::from(path)
+}
+
+/// Tab-completion state for a path argument in command mode.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    /// Candidate completions, as they would appear in the input
+    pub candidates: Vec<String>,
+    /// Candidate currently filled in, or None before the first cycle
+    pub index: Option<usize>,
+}
+
+impl Completion {
+    /// Advance to the next candidate (wrapping) and return it.
+    pub fn cycle(&mut self) -> &str {
+        let next = match self.index {
+            Some(i) => (i + 1) % self.candidates.len(),
+            None => 0,
+        };
+        self.index = Some(next);
+        &self.candidates[next]
+    }
+}
+
+/// Paths that complete `partial`, sorted, with a trailing `/` on directories.
+/// Hidden entries are only offered when the typed name starts with `.`.
+pub fn complete_path(partial: &str) -> Vec<String> {
+    let (dir_part, name_prefix) = match partial.rfind('/') {
+        Some(i) => partial.split_at(i + 1),
+        None => ("", partial),
+    };
+    let dir = if dir_part.is_empty() { PathBuf::from(".") } else { expand_path(dir_part) };
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let mut candidates: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(name_prefix) || (name.starts_with('.') && !name_prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            Some(format!("{}{}{}", dir_part, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// Longest common prefix of `items` (empty when `items` is empty).
+pub fn common_prefix(items: &[String]) -> String {
+    let Some(first) = items.first() else {
+        return String::new();
+    };
+    let mut len = first.len();
+    for item in &items[1..] {
+        len = first
+            .char_indices()
+            .zip(item.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, a), _)| i + a.len_utf8())
+            .min(len);
+    }
+    first[..len].to_string()
+}
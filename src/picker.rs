@@ -0,0 +1,47 @@
+/// A fuzzy match of a query against one candidate string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    /// Higher is better
+    pub score: i64,
+    /// Char indices in the candidate that matched the query, for highlighting
+    pub positions: Vec<usize>,
+}
+
+/// Match `query` as a case-insensitive subsequence of `candidate`.
+/// Consecutive matched chars and matches at word starts (after `/`, `.`, `_`,
+/// `-` or a space) score higher; unmatched leading chars cost a little.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<FuzzyMatch> {
+    let chars: Vec<char> = candidate.chars().collect();
+    let mut positions = Vec::new();
+    let mut score = 0i64;
+    let mut from = 0;
+    for q in query.chars().flat_map(char::to_lowercase) {
+        let offset = chars[from..]
+            .iter()
+            .position(|c| c.to_lowercase().eq(std::iter::once(q)))?;
+        let idx = from + offset;
+        score += 1;
+        if positions.last().is_some_and(|&last| last + 1 == idx) {
+            score += 5;
+        }
+        if idx == 0 || matches!(chars[idx - 1], '/' | '.' | '_' | '-' | ' ') {
+            score += 3;
+        }
+        positions.push(idx);
+        from = idx + 1;
+    }
+    score -= positions.first().copied().unwrap_or(0) as i64 / 4;
+    Some(FuzzyMatch { score, positions })
+}
+
+/// Indices of `candidates` matching `query`, best first. Ties keep their
+/// original order; an empty query matches everything.
+pub fn rank<'a>(query: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<(usize, FuzzyMatch)> {
+    let mut ranked: Vec<(usize, FuzzyMatch)> = candidates
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, c)| fuzzy_match(query, c).map(|m| (i, m)))
+        .collect();
+    ranked.sort_by_key(|(_, m)| std::cmp::Reverse(m.score));
+    ranked
+}
@@ -0,0 +1,90 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+
+/// Run a LESSOPEN-style input preprocessor on `path`. `spec` is a command
+/// with `%s` standing for the file name, in one of less's forms:
+///
+/// - `|cmd %s`: the command's output is shown instead of the file, unless
+///   it prints nothing
+/// - `||cmd %s`: the output is shown even when empty, unless the command
+///   fails (exits nonzero) without printing anything
+/// - `cmd %s`: the command prints the name of a replacement file to show
+///   (nothing means the file itself); `$LESSCLOSE` is run afterwards
+///
+/// Returns None when the file should be read as it is.
+pub fn run(spec: &str, path: &Path) -> Result<Option<Vec<u8>>> {
+    // A '-' after the pipe asks less to preprocess stdin too; we never do
+    let (pipe, keep_empty, cmd) = match spec.strip_prefix("||") {
+        Some(cmd) => (true, true, cmd),
+        None => match spec.strip_prefix('|') {
+            Some(cmd) => (true, false, cmd),
+            None => (false, false, spec),
+        },
+    };
+    let cmd = cmd.strip_prefix('-').unwrap_or(cmd);
+    let (output, status) = shell(cmd, &[path])?;
+
+    if pipe {
+        // A preprocessor that does not handle the file fails silently
+        if output.is_empty() && !status.success() {
+            return Ok(None);
+        }
+        return Ok((keep_empty || !output.is_empty()).then_some(output));
+    }
+    let replacement = PathBuf::from(String::from_utf8_lossy(&output).trim());
+    if replacement.as_os_str().is_empty() {
+        return Ok(None);
+    }
+    let data = std::fs::read(&replacement)
+        .with_context(|| format!("Cannot read preprocessed file '{}'", replacement.display()))?;
+    if let Ok(close) = std::env::var("LESSCLOSE") {
+        let _ = shell(&close, &[path, &replacement]);
+    }
+    Ok(Some(data))
+}
+
+/// Run `cmd` through the shell with each `%s` replaced by the next of
+/// `files`, passed as positional parameters so they need no quoting.
+/// Returns its output and how it exited.
+fn shell(cmd: &str, files: &[&Path]) -> Result<(Vec<u8>, ExitStatus)> {
+    let mut script = String::new();
+    let mut parts = cmd.split("%s");
+    script.push_str(parts.next().unwrap_or_default());
+    for (n, part) in parts.enumerate() {
+        script.push_str(&format!("\"${}\"", (n % files.len().max(1)) + 1));
+        script.push_str(part);
+    }
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(&script)
+        .arg("sh")
+        .args(files)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .with_context(|| format!("Cannot run preprocessor '{}'", cmd))?;
+    Ok((output.stdout, output.status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipe() {
+        let path = Path::new("some file");
+        assert_eq!(run("|echo %s", path).unwrap(), Some(b"some file\n".to_vec()));
+        assert_eq!(run("|true", path).unwrap(), None);
+        assert_eq!(run("||true", path).unwrap(), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_failing_preprocessor() {
+        let path = Path::new("some file");
+        assert_eq!(run("||false %s", path).unwrap(), None);
+        assert_eq!(run("|false", path).unwrap(), None);
+        // Output is shown even when the command fails
+        assert_eq!(run("||echo partial; false", path).unwrap(), Some(b"partial\n".to_vec()));
+    }
+}
@@ -0,0 +1,168 @@
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Span;
+use std::io::{self, Write};
+use unicode_width::UnicodeWidthStr;
+
+use crate::buffer::Buffer;
+use crate::overstrike;
+use crate::syntax::{self, StyledSpan, SyntaxHighlighter};
+use crate::tabs;
+use crate::viewer;
+
+/// Options for writing buffers straight to stdout instead of paging them.
+#[derive(Debug, Clone, Copy)]
+pub struct PrintOptions {
+    pub line_numbers: bool,
+    /// Emit ANSI colors (syntax highlighting, diff colors, gutter)
+    pub color: bool,
+    /// Tabs are expanded to spaces up to the next multiple of this
+    pub tab_width: u8,
+}
+
+/// True if `buffers` fit in a `width` x `height` terminal, counting long
+/// lines as the number of rows they wrap to (`-F`). `opts` gives the
+/// options each buffer is printed with.
+pub fn fits_on_screen(buffers: &[Buffer], opts: impl Fn(&Buffer) -> PrintOptions, width: u16, height: u16) -> bool {
+    let mut rows = 0usize;
+    for buf in buffers {
+        let opts = opts(buf);
+        let text_width = (width as usize).saturating_sub(gutter_width(buf, &opts)).max(1);
+        for i in 0..buf.display_line_count() {
+            let cols = if buf.is_binary() {
+                buf.hex_line(i).width()
+            } else {
+                tabs::text_width(&line_text(buf, i), opts.tab_width as usize)
+            };
+            rows += cols.div_ceil(text_width).max(1);
+            if rows > height as usize {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Write every line of `buf` to `out`: hex dump rows for binary files,
+/// highlighted text otherwise.
+pub fn write_buffer(out: &mut impl Write, buf: &Buffer, highlighter: &SyntaxHighlighter, opts: &PrintOptions) -> io::Result<()> {
+    let gutter = gutter_width(buf, opts);
+    let highlight = opts.color && highlighter.highlights(buf) && !buf.is_binary() && !buf.is_diff;
+    let mut line_runs = highlight.then(|| highlighter.line_runs(buf));
+
+    for i in 0..buf.display_line_count() {
+        let runs = line_runs.as_mut().and_then(|runs| runs.next());
+        if gutter > 0 {
+            let num = format!("{:>w$} \u{2502} ", i + 1, w = gutter - 3);
+            write_span(out, &num, Style::default().fg(Color::DarkGray), opts.color)?;
+        }
+        let spans = if buf.is_binary() {
+            vec![StyledSpan { text: buf.hex_line(i), style: Style::default().fg(Color::Rgb(150, 200, 150)) }]
+        } else if buf.is_diff {
+            let text = line_text(buf, i);
+            let style = viewer::diff_line_style(&text);
+            vec![StyledSpan { text, style }]
+        } else if let Some(runs) = buf.overstrike_runs(i) {
+            overstrike::spans(&line_text(buf, i), runs)
+        } else if let Some(runs) = runs {
+            syntax::spans(&runs, &line_text(buf, i))
+        } else {
+            vec![StyledSpan { text: line_text(buf, i), style: Style::default() }]
+        };
+        let spans = spans.into_iter().map(|s| Span::styled(s.text, s.style)).collect();
+        for span in tabs::expand(spans, 0, opts.tab_width as usize) {
+            write_span(out, &span.content, span.style, opts.color)?;
+        }
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Write the available themes, one per line with where each comes from
+/// (`--list-themes`, `:themes`). `preview` adds a line of sample code
+/// highlighted in each theme.
+pub fn write_theme_list(out: &mut impl Write, highlighter: &SyntaxHighlighter, preview: bool) -> io::Result<()> {
+    let themes = highlighter.themes();
+    let width = themes.iter().map(|(name, _)| name.width()).max().unwrap_or(0);
+    for (name, source) in themes {
+        write!(out, "{:<w$}  {}", name, source.label(), w = width)?;
+        if preview {
+            write!(out, "{:w$}", "", w = 9 - source.label().len())?;
+            for StyledSpan { text, style } in highlighter.preview(name, THEME_SAMPLE) {
+                write_span(out, &text, style, true)?;
+            }
+        }
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+const THEME_SAMPLE: &str = r#" fn main() { let n = 42; println!("hi {n}"); } // done "#;
+
+/// Text of line `i`, with invalid UTF-8 replaced.
+fn line_text(buf: &Buffer, i: usize) -> String {
+    match buf.get_line(i) {
+        Some(line) => line.to_string(),
+        None => String::from_utf8_lossy(buf.line_bytes(i).unwrap_or_default()).into_owned(),
+    }
+}
+
+/// Columns taken by the line-number gutter ("  42 │ "), 0 without one.
+fn gutter_width(buf: &Buffer, opts: &PrintOptions) -> usize {
+    if opts.line_numbers {
+        buf.display_line_count().max(1).to_string().len() + 3
+    } else {
+        0
+    }
+}
+
+/// Write `text` in `style`, or plain when `color` is off.
+pub fn write_span(out: &mut impl Write, text: &str, style: Style, color: bool) -> io::Result<()> {
+    if !color || style == Style::default() {
+        return out.write_all(text.as_bytes());
+    }
+    write!(out, "{}{}\x1b[0m", ansi_prefix(style), text)
+}
+
+/// SGR escape sequence selecting `style`.
+fn ansi_prefix(style: Style) -> String {
+    let mut codes = Vec::new();
+    if style.add_modifier.contains(Modifier::BOLD) {
+        codes.push("1".to_string());
+    }
+    if style.add_modifier.contains(Modifier::ITALIC) {
+        codes.push("3".to_string());
+    }
+    if style.add_modifier.contains(Modifier::UNDERLINED) {
+        codes.push("4".to_string());
+    }
+    codes.extend(style.fg.and_then(|c| color_code(c, 30)));
+    codes.extend(style.bg.and_then(|c| color_code(c, 40)));
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+/// SGR parameters for `color`; `base` is 30 for foreground, 40 for background.
+fn color_code(color: Color, base: u8) -> Option<String> {
+    let basic = |n: u8| Some((base + n).to_string());
+    let bright = |n: u8| Some((base + 60 + n).to_string());
+    match color {
+        Color::Reset => Some((base + 9).to_string()),
+        Color::Black => basic(0),
+        Color::Red => basic(1),
+        Color::Green => basic(2),
+        Color::Yellow => basic(3),
+        Color::Blue => basic(4),
+        Color::Magenta => basic(5),
+        Color::Cyan => basic(6),
+        Color::Gray => basic(7),
+        Color::DarkGray => bright(0),
+        Color::LightRed => bright(1),
+        Color::LightGreen => bright(2),
+        Color::LightYellow => bright(3),
+        Color::LightBlue => bright(4),
+        Color::LightMagenta => bright(5),
+        Color::LightCyan => bright(6),
+        Color::White => bright(7),
+        Color::Rgb(r, g, b) => Some(format!("{};2;{};{};{}", base + 8, r, g, b)),
+        Color::Indexed(i) => Some(format!("{};5;{}", base + 8, i)),
+    }
+}
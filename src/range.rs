@@ -0,0 +1,124 @@
+/// A line address of an ex range: a line number, `.` (the top line in
+/// view), `$` (the last line) or a mark `'a` (`'<`/`'>` for the last visual
+/// selection), followed by any number of `+N`/`-N` offsets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Address {
+    pub base: Base,
+    pub offset: isize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Base {
+    /// 1-based line number
+    Line(usize),
+    Current,
+    Last,
+    Mark(char),
+}
+
+/// The two ends of a range, in the order they were given
+pub type Ends = (Address, Address);
+
+/// Split a leading range off a command line: `10,20w out` gives the two
+/// addresses and `w out`. A single address is both ends; `%` is the whole
+/// file. Commands without a range come back unchanged. A number too large
+/// to be a line is an error.
+pub fn parse(cmd: &str) -> Result<(Option<Ends>, &str), String> {
+    if let Some(rest) = cmd.strip_prefix('%') {
+        let whole = (Address { base: Base::Line(1), offset: 0 }, Address { base: Base::Last, offset: 0 });
+        return Ok((Some(whole), rest.trim_start()));
+    }
+    let Some((start, rest)) = parse_address(cmd)? else { return Ok((None, cmd)) };
+    let end = match rest.strip_prefix(',') {
+        Some(after) => parse_address(after)?,
+        None => None,
+    };
+    Ok(match end {
+        Some((end, rest)) => (Some((start, end)), rest.trim_start()),
+        None => (Some((start, start)), rest.trim_start()),
+    })
+}
+
+fn parse_address(s: &str) -> Result<Option<(Address, &str)>, String> {
+    let Some(first) = s.chars().next() else { return Ok(None) };
+    let (base, mut rest) = match first {
+        '.' => (Base::Current, &s[1..]),
+        '$' => (Base::Last, &s[1..]),
+        '\'' => {
+            let Some(c) = s[1..].chars().next() else { return Ok(None) };
+            (Base::Mark(c), &s[1 + c.len_utf8()..])
+        }
+        // A bare offset counts from the current line
+        '+' | '-' => (Base::Current, s),
+        c if c.is_ascii_digit() => {
+            let (n, rest) = split_number(s)?;
+            (Base::Line(n), rest)
+        }
+        _ => return Ok(None),
+    };
+    let mut offset = 0isize;
+    while let Some(sign) = rest.chars().next().filter(|c| matches!(c, '+' | '-')) {
+        let (n, after) = split_number(&rest[1..])?;
+        // "+" alone means +1
+        let n = if after.len() == rest.len() - 1 { 1 } else { isize::try_from(n).map_err(|_| INVALID)? };
+        offset = offset.checked_add(if sign == '+' { n } else { -n }).ok_or(INVALID)?;
+        rest = after;
+    }
+    Ok(Some((Address { base, offset }, rest)))
+}
+
+const INVALID: &str = "Invalid line number";
+
+/// Leading decimal number of `s` (0 if none) and the text after it; an
+/// error if the number does not fit in a `usize`.
+fn split_number(s: &str) -> Result<(usize, &str), String> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let n = if end == 0 { 0 } else { s[..end].parse().map_err(|_| INVALID)? };
+    Ok((n, &s[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(n: usize) -> Address {
+        Address { base: Base::Line(n), offset: 0 }
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        assert_eq!(parse("100,200w extract.txt").unwrap(), (Some((line(100), line(200))), "w extract.txt"));
+        assert_eq!(parse("10,50y").unwrap(), (Some((line(10), line(50))), "y"));
+        assert_eq!(parse("42").unwrap(), (Some((line(42), line(42))), ""));
+        assert_eq!(parse("%!sort").unwrap(), (Some((line(1), Address { base: Base::Last, offset: 0 })), "!sort"));
+        assert_eq!(
+            parse("'<,'>w out").unwrap(),
+            (
+                Some((Address { base: Base::Mark('<'), offset: 0 }, Address { base: Base::Mark('>'), offset: 0 })),
+                "w out"
+            )
+        );
+        assert_eq!(parse("e file.txt").unwrap(), (None, "e file.txt"));
+    }
+
+    #[test]
+    fn test_parse_offsets() {
+        assert_eq!(
+            parse(".,.+10y").unwrap(),
+            (
+                Some((Address { base: Base::Current, offset: 0 }, Address { base: Base::Current, offset: 10 })),
+                "y"
+            )
+        );
+        assert_eq!(parse("$-2").unwrap(), (Some((Address { base: Base::Last, offset: -2 }, Address { base: Base::Last, offset: -2 })), ""));
+        assert_eq!(parse("+").unwrap(), (Some((Address { base: Base::Current, offset: 1 }, Address { base: Base::Current, offset: 1 })), ""));
+        assert_eq!(parse("'a+1-3").unwrap(), (Some((Address { base: Base::Mark('a'), offset: -2 }, Address { base: Base::Mark('a'), offset: -2 })), ""));
+    }
+
+    #[test]
+    fn test_parse_overflow() {
+        assert_eq!(parse("99999999999999999999999"), Err(INVALID.to_string()));
+        assert_eq!(parse("1,99999999999999999999999y"), Err(INVALID.to_string()));
+        assert_eq!(parse(".+99999999999999999999"), Err(INVALID.to_string()));
+    }
+}
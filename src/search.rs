@@ -1,22 +1,180 @@
 use anyhow::Result;
-use regex::{Regex, RegexBuilder};
+use rayon::prelude::*;
+use regex::bytes::RegexBuilder;
 use std::ops::Range;
 use std::sync::mpsc;
+use std::time::Instant;
+
+use crate::buffer::TextSnapshot;
+
+/// Lines searched by one rayon task of an async search.
+const CHUNK_LINES: usize = 10_000;
 
 /// Batch of matches sent from the async search thread.
 pub enum SearchBatch {
     Progress {
         matches: Vec<(usize, Range<usize>)>,
+        /// Lines scanned so far, out of `total_lines` in the searched range
         lines_scanned: usize,
+        total_lines: usize,
     },
     Done {
         matches: Vec<(usize, Range<usize>)>,
+        /// True when the search stopped early at `max_matches`
+        truncated: bool,
     },
 }
 
+/// Prefix that forces the backtracking engine for a single query, e.g. `(?look)(?<=id=)\d+`.
+pub const FANCY_PREFIX: &str = "(?look)";
+
+/// Smart case: ignore case unless the query contains an uppercase letter.
+pub fn smart_case_insensitive(query: &str, smart_case: bool) -> bool {
+    smart_case && !query.chars().any(|c| c.is_uppercase())
+}
+
+/// A compiled search pattern. The `regex` crate is the default engine; the
+/// backtracking `fancy-regex` engine is used on request for lookaround and
+/// backreferences, which the default engine rejects.
+///
+/// Patterns match against raw line bytes, in the async search and the
+/// synchronous one alike, so the async search can scan a shared buffer in
+/// place and both find the same matches. The fast engine handles invalid UTF-8 directly; the fancy
+/// engine only works on `&str` and skips lines that are not valid UTF-8.
+#[derive(Debug, Clone)]
+pub enum SearchPattern {
+    Fast(regex::bytes::Regex),
+    Fancy(fancy_regex::Regex),
+}
+
+impl SearchPattern {
+    /// Byte ranges of all non-overlapping matches in `line`.
+    /// Backtracking errors (e.g. hitting the backtrack limit) end the iteration.
+    pub fn find_iter<'a>(&'a self, line: &'a [u8]) -> Box<dyn Iterator<Item = Range<usize>> + 'a> {
+        match self {
+            SearchPattern::Fast(re) => Box::new(re.find_iter(line).map(|m| m.range())),
+            SearchPattern::Fancy(re) => match std::str::from_utf8(line) {
+                Ok(text) => Box::new(re.find_iter(text).map_while(|m| m.ok()).map(|m| m.range())),
+                Err(_) => Box::new(std::iter::empty()),
+            },
+        }
+    }
+
+    /// Number of capture groups, including the implicit whole-match group 0.
+    pub fn captures_len(&self) -> usize {
+        match self {
+            SearchPattern::Fast(re) => re.captures_len(),
+            SearchPattern::Fancy(re) => re.captures_len(),
+        }
+    }
+
+    /// Byte ranges of capture groups 1.. for every match in `line`, tagged with the group index.
+    pub fn group_ranges(&self, line: &[u8]) -> Vec<(usize, Range<usize>)> {
+        let mut groups = Vec::new();
+        match self {
+            SearchPattern::Fast(re) => {
+                for caps in re.captures_iter(line) {
+                    for (idx, group) in caps.iter().enumerate().skip(1) {
+                        if let Some(m) = group {
+                            groups.push((idx, m.range()));
+                        }
+                    }
+                }
+            }
+            SearchPattern::Fancy(re) => {
+                let Ok(text) = std::str::from_utf8(line) else {
+                    return groups;
+                };
+                for caps in re.captures_iter(text).map_while(|c| c.ok()) {
+                    for (idx, group) in caps.iter().enumerate().skip(1) {
+                        if let Some(m) = group {
+                            groups.push((idx, m.range()));
+                        }
+                    }
+                }
+            }
+        }
+        groups
+    }
+}
+
+/// Search lines `first..last` of `text` for `pattern` on a background
+/// thread. The lines are searched a window at a time, one `CHUNK_LINES`
+/// chunk per rayon worker, and each window's matches are sent back in line
+/// order, so `n`/`N` can step through the matches found so far. Stops after
+/// `max_matches` (0 = unlimited), or when the receiver is dropped.
+pub fn spawn_search(
+    text: TextSnapshot,
+    pattern: SearchPattern,
+    (first, last): (usize, usize),
+    max_matches: usize,
+) -> mpsc::Receiver<SearchBatch> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let cap = if max_matches > 0 { max_matches } else { usize::MAX };
+        let window = CHUNK_LINES * rayon::current_num_threads();
+        let mut total = 0usize;
+        let mut start = first;
+        while start < last {
+            let end = (start + window).min(last);
+            let chunks: Vec<_> = (start..end)
+                .into_par_iter()
+                .step_by(CHUNK_LINES)
+                .map(|from| search_lines(&text, &pattern, from..(from + CHUNK_LINES).min(end), cap))
+                .collect();
+            let mut matches: Vec<_> = chunks.into_iter().flatten().collect();
+            if total + matches.len() > cap {
+                matches.truncate(cap - total);
+                let _ = tx.send(SearchBatch::Done { matches, truncated: true });
+                return;
+            }
+            total += matches.len();
+            start = end;
+            if start == last {
+                let _ = tx.send(SearchBatch::Done { matches, truncated: false });
+                return;
+            }
+            let progress = SearchBatch::Progress { matches, lines_scanned: start - first, total_lines: last - first };
+            if tx.send(progress).is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(SearchBatch::Done { matches: Vec::new(), truncated: false });
+    });
+    rx
+}
+
+/// Matches of `pattern` in `lines` of `text`, at most `cap` of them.
+fn search_lines(text: &TextSnapshot, pattern: &SearchPattern, lines: Range<usize>, cap: usize) -> Vec<(usize, Range<usize>)> {
+    let mut found = Vec::new();
+    for line_idx in lines {
+        let Some(line) = text.line_bytes(line_idx) else { break };
+        for range in pattern.find_iter(line) {
+            if found.len() >= cap {
+                return found;
+            }
+            found.push((line_idx, range));
+        }
+    }
+    found
+}
+
+/// Result of stepping to the next or previous match.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchStep {
+    /// Moved to an adjacent match without crossing the end of the buffer
+    Moved,
+    /// Crossed the end of the buffer and continued from the other side
+    Wrapped,
+    /// Reached the last match in this direction and wrap-around is disabled
+    HitEnd,
+    /// There are no matches to step through
+    NoMatches,
+}
+
 /// Tracks search state: pattern, all matches, current position.
 pub struct SearchState {
-    pub pattern: Option<Regex>,
+    pub pattern: Option<SearchPattern>,
     pub query_string: String,
     pub matches: Vec<(usize, Range<usize>)>,
     pub current: usize,
@@ -28,6 +186,29 @@ pub struct SearchState {
     pub is_searching: bool,
     /// Receiver for async search results
     pub search_rx: Option<mpsc::Receiver<SearchBatch>>,
+    /// Whether n/N continue from the other end after the last match
+    pub wrap_around: bool,
+    /// Inclusive line range the search is restricted to (set from a visual selection)
+    pub range: Option<(usize, usize)>,
+    /// Compile every pattern with the backtracking engine (config `fancy_regex`)
+    pub fancy: bool,
+    /// Stop collecting matches after this many (0 = unlimited)
+    pub max_matches: usize,
+    /// True when the last search stopped at `max_matches`
+    pub truncated: bool,
+    /// (lines scanned, total lines) reported by the running async search
+    pub progress: (usize, usize),
+    /// When the running async search was started (for rate / ETA)
+    pub started: Option<Instant>,
+    /// Lines of the buffer the matches were found in, so lines appended
+    /// later can be searched on their own
+    pub searched: usize,
+}
+
+impl Default for SearchState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SearchState {
@@ -41,9 +222,25 @@ impl SearchState {
             preview_matches: Vec::new(),
             is_searching: false,
             search_rx: None,
+            wrap_around: true,
+            range: None,
+            fancy: false,
+            max_matches: 0,
+            truncated: false,
+            progress: (0, 0),
+            started: None,
+            searched: 0,
         }
     }
 
+    /// Carry the user's search settings over from `other`, which may have been
+    /// changed at runtime (e.g. wrap-around toggled with `W`).
+    pub fn inherit_settings(&mut self, other: &SearchState) {
+        self.wrap_around = other.wrap_around;
+        self.fancy = other.fancy;
+        self.max_matches = other.max_matches;
+    }
+
     /// Compile a search pattern with smart case.
     pub fn set_pattern(&mut self, query: &str, smart_case: bool) -> Result<()> {
         self.query_string = query.to_string();
@@ -53,11 +250,23 @@ impl SearchState {
             self.preview_matches.clear();
             return Ok(());
         }
-        let case_insensitive = smart_case && !query.chars().any(|c| c.is_uppercase());
-        let regex = RegexBuilder::new(query)
-            .case_insensitive(case_insensitive)
-            .build()?;
-        self.pattern = Some(regex);
+        let (query, fancy) = match query.strip_prefix(FANCY_PREFIX) {
+            Some(rest) => (rest, true),
+            None => (query, self.fancy),
+        };
+        let case_insensitive = smart_case_insensitive(query, smart_case);
+        let pattern = if fancy {
+            let regex = fancy_regex::RegexBuilder::new(query)
+                .case_insensitive(case_insensitive)
+                .build()?;
+            SearchPattern::Fancy(regex)
+        } else {
+            let regex = RegexBuilder::new(query)
+                .case_insensitive(case_insensitive)
+                .build()?;
+            SearchPattern::Fast(regex)
+        };
+        self.pattern = Some(pattern);
         Ok(())
     }
 
@@ -69,15 +278,46 @@ impl SearchState {
             Some(r) => r,
             None => return,
         };
-        for line_idx in 0..buffer.line_count() {
-            if let Some(text) = buffer.get_line(line_idx) {
-                for mat in regex.find_iter(text) {
-                    self.matches.push((line_idx, mat.start()..mat.end()));
+        self.truncated = false;
+        self.searched = buffer.line_count();
+        let (first, last) = self.line_bounds(buffer.line_count());
+        for line_idx in first..last {
+            if let Some(line) = buffer.line_bytes(line_idx) {
+                for range in regex.find_iter(line) {
+                    if self.max_matches > 0 && self.matches.len() >= self.max_matches {
+                        self.truncated = true;
+                        return;
+                    }
+                    self.matches.push((line_idx, range));
                 }
             }
         }
     }
 
+    /// Search the lines appended to `buffer` since the matches were found:
+    /// the old last line again, which may have grown, and the new ones. A
+    /// search still running catches up when it is done.
+    pub fn search_appended(&mut self, buffer: &crate::buffer::Buffer) {
+        let count = buffer.line_count();
+        if self.is_searching || self.truncated || count == self.searched {
+            return;
+        }
+        if count < self.searched {
+            return self.search_buffer(buffer);
+        }
+        let Some(pattern) = &self.pattern else { return };
+        let from = self.searched.saturating_sub(1);
+        let keep = self.matches.partition_point(|(line, _)| *line < from);
+        self.matches.truncate(keep);
+        let (first, last) = self.line_bounds(count);
+        let cap = if self.max_matches > 0 { self.max_matches.saturating_sub(keep) } else { usize::MAX };
+        let found = search_lines(&buffer.snapshot(), pattern, from.max(first)..last, cap);
+        self.truncated = self.max_matches > 0 && found.len() >= cap;
+        self.matches.extend(found);
+        self.current = self.current.min(self.matches.len().saturating_sub(1));
+        self.searched = count;
+    }
+
     /// Search only the currently visible lines, updating `preview_matches`.
     pub fn search_visible_lines(&mut self, buffer: &crate::buffer::Buffer, start: usize, end: usize) {
         self.preview_matches.clear();
@@ -85,29 +325,51 @@ impl SearchState {
             Some(r) => r,
             None => return,
         };
-        let limit = end.min(buffer.line_count());
-        for line_idx in start..limit {
-            if let Some(text) = buffer.get_line(line_idx) {
-                for mat in regex.find_iter(text) {
-                    self.preview_matches.push((line_idx, mat.start()..mat.end()));
+        let (first, last) = self.line_bounds(buffer.line_count());
+        for line_idx in start.max(first)..end.min(last) {
+            if let Some(line) = buffer.line_bytes(line_idx) {
+                for range in regex.find_iter(line) {
+                    self.preview_matches.push((line_idx, range));
                 }
             }
         }
     }
 
-    pub fn next_match(&mut self) {
-        if !self.matches.is_empty() {
-            self.current = (self.current + 1) % self.matches.len();
+    /// Half-open line bounds to search, honoring `range` when set.
+    pub fn line_bounds(&self, line_count: usize) -> (usize, usize) {
+        match self.range {
+            Some((first, last)) => (first.min(line_count), (last + 1).min(line_count)),
+            None => (0, line_count),
         }
     }
 
-    pub fn prev_match(&mut self) {
-        if !self.matches.is_empty() {
-            self.current = if self.current == 0 {
-                self.matches.len() - 1
-            } else {
-                self.current - 1
-            };
+    pub fn next_match(&mut self) -> MatchStep {
+        if self.matches.is_empty() {
+            return MatchStep::NoMatches;
+        }
+        if self.current + 1 < self.matches.len() {
+            self.current += 1;
+            MatchStep::Moved
+        } else if self.wrap_around {
+            self.current = 0;
+            MatchStep::Wrapped
+        } else {
+            MatchStep::HitEnd
+        }
+    }
+
+    pub fn prev_match(&mut self) -> MatchStep {
+        if self.matches.is_empty() {
+            return MatchStep::NoMatches;
+        }
+        if self.current > 0 {
+            self.current -= 1;
+            MatchStep::Moved
+        } else if self.wrap_around {
+            self.current = self.matches.len() - 1;
+            MatchStep::Wrapped
+        } else {
+            MatchStep::HitEnd
         }
     }
 
@@ -121,6 +383,12 @@ impl SearchState {
         self.matches.get(self.current).map(|(line, _)| *line)
     }
 
+    /// True if a committed match lies on `line` (matches are sorted by line).
+    pub fn line_has_match(&self, line: usize) -> bool {
+        let idx = self.matches.partition_point(|(l, _)| *l < line);
+        self.matches.get(idx).is_some_and(|(l, _)| *l == line)
+    }
+
     pub fn matches_on_line(&self, line: usize) -> Vec<Range<usize>> {
         self.matches
             .iter()
@@ -129,6 +397,19 @@ impl SearchState {
             .collect()
     }
 
+    /// Byte ranges of capture groups (1-based group index) within committed matches on `line`.
+    /// Empty when the pattern has no groups or the line has no matches.
+    pub fn group_ranges_on_line(&self, line: usize, text: &str) -> Vec<(usize, Range<usize>)> {
+        let regex = match &self.pattern {
+            Some(r) if r.captures_len() > 1 => r,
+            _ => return Vec::new(),
+        };
+        if !self.matches.iter().any(|(l, _)| *l == line) {
+            return Vec::new();
+        }
+        regex.group_ranges(text.as_bytes())
+    }
+
     pub fn preview_matches_on_line(&self, line: usize) -> Vec<Range<usize>> {
         self.preview_matches
             .iter()
@@ -137,6 +418,27 @@ impl SearchState {
             .collect()
     }
 
+    /// Fraction of the searched range scanned so far (0.0–1.0).
+    pub fn progress_ratio(&self) -> f64 {
+        let (scanned, total) = self.progress;
+        if total == 0 {
+            0.0
+        } else {
+            (scanned as f64 / total as f64).min(1.0)
+        }
+    }
+
+    /// Estimated seconds until the running search finishes, from the scan rate so far.
+    pub fn eta_secs(&self) -> Option<u64> {
+        let elapsed = self.started?.elapsed().as_secs_f64();
+        let (scanned, total) = self.progress;
+        if scanned == 0 || elapsed <= 0.0 {
+            return None;
+        }
+        let rate = scanned as f64 / elapsed;
+        Some((total.saturating_sub(scanned) as f64 / rate).ceil() as u64)
+    }
+
     pub fn match_count(&self) -> usize {
         self.matches.len()
     }
@@ -145,3 +447,30 @@ impl SearchState {
         self.pattern.is_some()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::Buffer;
+
+    #[test]
+    fn test_sync_and_async_agree_on_invalid_utf8() {
+        let buf = Buffer::from_bytes("t", b"abc\xffdef error\nok error\n".to_vec());
+        let mut search = SearchState::new();
+        search.set_pattern("error", false).unwrap();
+        search.search_buffer(&buf);
+        assert_eq!(search.matches, vec![(0, 8..13), (1, 3..8)]);
+
+        let rx = spawn_search(buf.snapshot(), search.pattern.clone().unwrap(), (0, buf.line_count()), 0);
+        let mut found = Vec::new();
+        for batch in rx {
+            match batch {
+                SearchBatch::Progress { matches, .. } | SearchBatch::Done { matches, .. } => found.extend(matches),
+            }
+        }
+        assert_eq!(found, search.matches);
+
+        search.search_visible_lines(&buf, 0, 2);
+        assert_eq!(search.preview_matches, search.matches);
+    }
+}
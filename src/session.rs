@@ -0,0 +1,117 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::filter::CaseMode;
+
+/// A saved set of open files and their view state, stored as TOML in
+/// ~/.config/some/sessions/<name>.toml.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Index into `buffers` of the buffer that was active
+    pub active: usize,
+    pub buffers: Vec<SessionBuffer>,
+}
+
+/// One file of a session with its viewport, filter, search and marks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionBuffer {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub top_line: usize,
+    #[serde(default)]
+    pub left_col: usize,
+    #[serde(default)]
+    pub top_filter_idx: usize,
+    pub filter: Option<SessionFilter>,
+    pub search: Option<SessionSearch>,
+    /// Mark character (as a one-char string) → line
+    #[serde(default)]
+    pub marks: BTreeMap<String, usize>,
+    #[serde(default)]
+    pub notes: Vec<SessionNote>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionNote {
+    pub line: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionFilter {
+    pub query: String,
+    #[serde(default)]
+    pub invert: bool,
+    #[serde(default)]
+    pub case: CaseMode,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSearch {
+    pub query: String,
+    #[serde(default = "default_forward")]
+    pub forward: bool,
+    /// Index of the current match
+    #[serde(default)]
+    pub current: usize,
+}
+
+fn default_forward() -> bool {
+    true
+}
+
+impl Session {
+    /// The file session `name` is kept in, or None without a config directory.
+    /// Names that are empty or could reach outside the sessions directory are
+    /// rejected.
+    pub fn path(name: &str) -> Result<Option<PathBuf>> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+            bail!("Invalid session name '{}'", name);
+        }
+        Ok(dirs::config_dir().map(|d| d.join("some").join("sessions").join(format!("{}.toml", name))))
+    }
+
+    /// Load the session `name`, or None if it has not been saved yet.
+    pub fn load(name: &str) -> Result<Option<Self>> {
+        let Some(path) = Self::path(name)? else { return Ok(None) };
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Cannot read session '{}'", path.display()))?;
+        let session = toml::from_str(&content)
+            .with_context(|| format!("Invalid session file '{}'", path.display()))?;
+        Ok(Some(session))
+    }
+
+    /// Write the session to its file, creating the sessions directory if needed.
+    pub fn save(&self, name: &str) -> Result<PathBuf> {
+        let path = Self::path(name)?.context("No config directory for sessions")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Cannot create '{}'", dir.display()))?;
+        }
+        let content = toml::to_string_pretty(self).context("Cannot serialize session")?;
+        std::fs::write(&path, content)
+            .with_context(|| format!("Cannot write session '{}'", path.display()))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_rejects_unsafe_names() {
+        for name in ["", "../config", "a/b", "a\\b", ".."] {
+            assert!(Session::path(name).is_err(), "{:?}", name);
+        }
+        assert!(Session::path("work").is_ok());
+        assert!(Session::path("release-1.2").is_ok());
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, ExitStatus, Stdio};
+use std::thread::JoinHandle;
+
+/// A command started by `:!` or `|` whose output a buffer reads as it
+/// comes. Kept with the buffer so that it can be killed, and so that its
+/// exit status can be reported once its output ends.
+pub struct Running {
+    pub cmd: String,
+    child: Child,
+    /// Reads its stderr, when that is not merged into stdout
+    stderr: Option<JoinHandle<Vec<u8>>>,
+    killed: bool,
+}
+
+/// Start `cmd` through `sh -c` in the background and return it with its
+/// stdout. `input`, if any, is written to stdin from a thread, so a command
+/// that writes output before it has read all of its input cannot deadlock
+/// against us; otherwise stdin is empty. With `merge_stderr` stderr goes
+/// to stdout, so the output reads as it would in a terminal (`:!`);
+/// otherwise it is kept for `Running::try_finish` to report.
+pub fn start(cmd: &str, input: Option<Vec<u8>>, merge_stderr: bool) -> Result<(Running, ChildStdout)> {
+    let script = if merge_stderr { format!("{{ {}\n}} 2>&1", cmd) } else { cmd.to_string() };
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(script)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(if merge_stderr { Stdio::null() } else { Stdio::piped() });
+    // A group of its own, so that `Running::kill` reaches every process
+    // of a pipeline
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let mut child = command.spawn().with_context(|| format!("Cannot run '{}'", cmd))?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        });
+    }
+    let stderr = child.stderr.take().map(|mut stderr| {
+        std::thread::spawn(move || {
+            let mut text = Vec::new();
+            let _ = stderr.read_to_end(&mut text);
+            text
+        })
+    });
+    let stdout = child.stdout.take().context("Cannot open command stdout")?;
+    Ok((Running { cmd: cmd.to_string(), child, stderr, killed: false }, stdout))
+}
+
+impl Running {
+    /// Stop the command and whatever it started (Esc). Its output stream
+    /// then ends.
+    pub fn kill(&mut self) {
+        #[cfg(unix)]
+        // SAFETY: kill(2) only sends a signal; the group is the child's own
+        unsafe {
+            libc::kill(-(self.child.id() as libc::pid_t), libc::SIGKILL);
+        }
+        let _ = self.child.kill();
+        self.killed = true;
+    }
+
+    /// True once `kill` was called.
+    pub fn is_killed(&self) -> bool {
+        self.killed
+    }
+
+    /// How the command exited along with what it wrote to stderr, once it
+    /// has exited and its stderr has ended; None while either is still
+    /// going. Never blocks, so it can be polled each tick: a command can
+    /// close its stdout and keep running, or leave a child behind that
+    /// holds its stderr open.
+    pub fn try_finish(&mut self) -> Option<(Option<ExitStatus>, String)> {
+        let status = match self.child.try_wait() {
+            Ok(Some(status)) => Some(status),
+            Ok(None) => return None,
+            Err(_) => None,
+        };
+        if self.stderr.as_ref().is_some_and(|reader| !reader.is_finished()) {
+            return None;
+        }
+        let stderr = self.stderr.take().and_then(|reader| reader.join().ok()).unwrap_or_default();
+        Some((status, String::from_utf8_lossy(&stderr).trim().to_string()))
+    }
+}
+
+/// Run `cmd` through `sh -c` for `:!`, with stderr merged into stdout so the
+/// output reads as it would in a terminal. `input`, if any, is fed to stdin;
+/// otherwise stdin is empty. A failing exit status is returned, not an error.
+pub fn run(cmd: &str, input: Option<Vec<u8>>) -> Result<(Vec<u8>, ExitStatus)> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{{ {}\n}} 2>&1", cmd))
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Cannot run '{}'", cmd))?;
+
+    let writer = match (input, child.stdin.take()) {
+        (Some(input), Some(mut stdin)) => Some(std::thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        })),
+        _ => None,
+    };
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("'{}' did not finish", cmd))?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    Ok((output.stdout, output.status))
+}
+
+/// Open `path` at `line` in the user's editor ($VISUAL, then $EDITOR, then
+/// vi) and wait for it to exit. The editor setting may include arguments,
+/// so it goes through the shell; the path is passed separately as `$1` so
+/// it needs no quoting.
+pub fn edit(path: &Path, line: usize) -> Result<ExitStatus> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} +{} \"$1\"", editor, line))
+        .arg("sh")
+        .arg(path)
+        .status()
+        .with_context(|| format!("Cannot run editor '{}'", editor))
+}
+
+/// Start `cmd` through `sh -c` with `env` added to its environment and let
+/// it run on its own. Its output is discarded so it cannot draw over the
+/// viewer; a thread waits for it to exit.
+pub fn spawn(cmd: &str, env: &[(&str, &str)]) -> Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .envs(env.iter().copied())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Cannot run '{}'", cmd))?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! Reading a mapped file past the end another process has truncated it to
+//! raises SIGBUS, which kills the process. `App::check_truncated()` remaps
+//! such a file on the next tick, but a background search, filter or
+//! highlighting job can be reading the old map at that moment, and so can
+//! a draw that comes first. The handler installed here maps a page of
+//! zeros over the page that faulted, so the read gets zeros instead; what
+//! the job made of them is thrown away when the buffer is reloaded.
+//! Faults outside the maps registered here go to the previous handler.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Maps that can be registered at once; any past that are not covered
+const SLOTS: usize = 64;
+
+/// Start address and length of each registered map; 0 marks a free slot
+static STARTS: [AtomicUsize; SLOTS] = [const { AtomicUsize::new(0) }; SLOTS];
+static LENS: [AtomicUsize; SLOTS] = [const { AtomicUsize::new(0) }; SLOTS];
+
+/// Cover the file map `map` until `unregister`.
+pub fn register(map: &[u8]) {
+    if map.is_empty() {
+        return;
+    }
+    let start = map.as_ptr() as usize;
+    for (slot, len) in STARTS.iter().zip(&LENS) {
+        if slot.compare_exchange(0, start, Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+            len.store(map.len(), Ordering::Release);
+            return;
+        }
+    }
+}
+
+/// Stop covering `map`, before it is unmapped.
+pub fn unregister(map: &[u8]) {
+    let start = map.as_ptr() as usize;
+    for (slot, len) in STARTS.iter().zip(&LENS) {
+        if start != 0 && slot.load(Ordering::Acquire) == start {
+            len.store(0, Ordering::Release);
+            slot.store(0, Ordering::Release);
+            return;
+        }
+    }
+}
+
+/// True if `addr` is in a registered map.
+#[cfg_attr(not(any(target_os = "linux", target_os = "android", target_os = "macos")), allow(dead_code))]
+fn is_registered(addr: usize) -> bool {
+    STARTS.iter().zip(&LENS).any(|(slot, len)| {
+        let start = slot.load(Ordering::Acquire);
+        start != 0 && (start..start + len.load(Ordering::Acquire)).contains(&addr)
+    })
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+mod handler {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::OnceLock;
+
+    /// The handler there was before ours: the Rust runtime's, which reports
+    /// stack overflows
+    static PREVIOUS: OnceLock<libc::sigaction> = OnceLock::new();
+    static PAGE_SIZE: AtomicUsize = AtomicUsize::new(4096);
+
+    pub fn install() {
+        // SAFETY: plain libc calls on values set up here
+        unsafe {
+            PAGE_SIZE.store(libc::sysconf(libc::_SC_PAGESIZE).max(1) as usize, Ordering::Relaxed);
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sigbus as *const () as usize;
+            action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+            libc::sigemptyset(&mut action.sa_mask);
+            let mut previous: libc::sigaction = std::mem::zeroed();
+            if libc::sigaction(libc::SIGBUS, &action, &mut previous) == 0 {
+                let _ = PREVIOUS.set(previous);
+            }
+        }
+    }
+
+    extern "C" fn on_sigbus(_: libc::c_int, info: *mut libc::siginfo_t, _: *mut libc::c_void) {
+        // SAFETY: the kernel passes a valid siginfo with SA_SIGINFO
+        let addr = unsafe { (*info).si_addr() } as usize;
+        if super::is_registered(addr) {
+            let page = PAGE_SIZE.load(Ordering::Relaxed);
+            // SAFETY: the page belongs to a registered read-only file map
+            // whose file no longer reaches it; nothing else lives there
+            let zeros = unsafe {
+                libc::mmap(
+                    (addr - addr % page) as *mut libc::c_void,
+                    page,
+                    libc::PROT_READ,
+                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED,
+                    -1,
+                    0,
+                )
+            };
+            if zeros != libc::MAP_FAILED {
+                return;
+            }
+        }
+        // Not ours: put the previous handler back, which gets the fault
+        // when the access is retried
+        // SAFETY: restores an action sigaction gave us
+        unsafe {
+            match PREVIOUS.get() {
+                Some(previous) => libc::sigaction(libc::SIGBUS, previous, std::ptr::null_mut()),
+                None => libc::signal(libc::SIGBUS, libc::SIG_DFL) as libc::c_int,
+            };
+        }
+    }
+}
+
+/// Install the handler. Called once at startup.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+pub fn install() {
+    static INSTALLED: std::sync::Once = std::sync::Once::new();
+    INSTALLED.call_once(handler::install);
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+pub fn install() {}
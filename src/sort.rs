@@ -0,0 +1,120 @@
+use rayon::prelude::*;
+
+use crate::buffer::TextSnapshot;
+
+/// `:sort` flags, as `sort` takes them.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SortOptions {
+    /// `-n`: by the number the line starts with
+    pub numeric: bool,
+    /// `-r`: largest first
+    pub reverse: bool,
+}
+
+/// Parse `:sort` arguments: `-n`, `-r`, or both (`-nr`, `-n -r`).
+pub fn parse(args: &str) -> Result<SortOptions, String> {
+    let mut options = SortOptions::default();
+    for arg in args.split_whitespace() {
+        let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+            return Err(format!("unknown argument '{}'", arg));
+        };
+        for flag in flags.chars() {
+            match flag {
+                'n' => options.numeric = true,
+                'r' => options.reverse = true,
+                _ => return Err(format!("unknown flag '-{}' (use -n or -r)", flag)),
+            }
+        }
+    }
+    Ok(options)
+}
+
+/// The line numbers of `text` in sorted order. Lines that compare equal
+/// keep their order, in reverse too.
+pub fn order(text: &TextSnapshot, options: SortOptions) -> Vec<usize> {
+    let line = |i: usize| text.line_bytes(i).unwrap_or_default();
+    let mut lines: Vec<usize> = (0..text.line_count()).collect();
+    let compare = |&a: &usize, &b: &usize| {
+        let ordering = if options.numeric {
+            number(line(a)).total_cmp(&number(line(b))).then_with(|| line(a).cmp(line(b)))
+        } else {
+            line(a).cmp(line(b))
+        };
+        if options.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    };
+    lines.par_sort_by(compare);
+    lines
+}
+
+/// Runs of identical adjacent lines in `text`, as (first line, length)
+/// (`:uniq`).
+pub fn runs(text: &TextSnapshot) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for i in 0..text.line_count() {
+        match runs.last_mut() {
+            Some((first, count)) if text.line_bytes(*first) == text.line_bytes(i) => *count += 1,
+            _ => runs.push((i, 1)),
+        }
+    }
+    runs
+}
+
+/// The number at the start of `line` after any blanks, as `sort -n` reads
+/// it; 0 when there is none.
+fn number(line: &[u8]) -> f64 {
+    let text = String::from_utf8_lossy(line);
+    let text = text.trim_start();
+    let mut end = 0;
+    let mut seen_dot = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '-' | '+' if i == 0 => {}
+            '.' if !seen_dot => seen_dot = true,
+            c if c.is_ascii_digit() => {}
+            _ => break,
+        }
+        end = i + 1;
+    }
+    text[..end].parse().unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("").unwrap(), SortOptions::default());
+        assert_eq!(parse("-nr").unwrap(), SortOptions { numeric: true, reverse: true });
+        assert_eq!(parse("-r -n").unwrap(), SortOptions { numeric: true, reverse: true });
+        assert!(parse("-k").is_err());
+        assert!(parse("n").is_err());
+    }
+
+    #[test]
+    fn test_order() {
+        let text = crate::buffer::Buffer::from_bytes("t", b"10 b\n9 a\nx\n10 a\n".to_vec()).snapshot();
+        assert_eq!(order(&text, SortOptions::default()), vec![3, 0, 1, 2]);
+        assert_eq!(order(&text, SortOptions { numeric: true, reverse: false }), vec![2, 1, 3, 0]);
+        assert_eq!(order(&text, SortOptions { numeric: true, reverse: true }), vec![0, 3, 1, 2]);
+    }
+
+    #[test]
+    fn test_runs() {
+        let text = crate::buffer::Buffer::from_bytes("t", b"a\na\nb\na\na\na\n".to_vec()).snapshot();
+        assert_eq!(runs(&text), vec![(0, 2), (2, 1), (3, 3)]);
+    }
+
+    #[test]
+    fn test_number() {
+        assert_eq!(number(b"  42 apples"), 42.0);
+        assert_eq!(number(b"-1.5s"), -1.5);
+        assert_eq!(number(b"1.2.3"), 1.2);
+        assert_eq!(number(b"none"), 0.0);
+        assert_eq!(number(b"-"), 0.0);
+    }
+}
@@ -1,6 +1,13 @@
 use ratatui::prelude::*;
 use ratatui::widgets::Paragraph;
 use crate::app::{App, Mode};
+use crate::info;
+
+/// Placeholders the `[statusbar]` templates can use.
+pub const FIELDS: &[&str] = &[
+    "name", "path", "branch", "buffer", "mode", "recording", "flags", "filter", "search", "position", "lines", "total",
+    "percent", "size", "encoding", "eol", "offset", "bytes",
+];
 
 pub fn render(frame: &mut Frame, app: &App, area: Rect) {
     let buf = app.buffer();
@@ -10,45 +17,122 @@ pub fn render(frame: &mut Frame, app: &App, area: Rect) {
     let pct = app.scroll_percentage();
 
     let buffer_indicator = if app.buffers.len() > 1 {
-        format!(" [{}/{}]", app.active_buffer + 1, app.buffers.len())
+        format!("[{}/{}]", app.active_buffer + 1, app.buffers.len())
     } else {
         String::new()
     };
 
     let mode_indicator = match &app.mode {
         Mode::Normal => "",
-        Mode::SearchInput { .. } => " [SEARCH]",
-        Mode::CommandInput { .. } => " [COMMAND]",
-        Mode::Follow => " [FOLLOW]",
-        Mode::FilterInput { .. } => " [FILTER]",
-        Mode::Visual { .. } => " [VISUAL]",
+        Mode::SearchInput { .. } => "[SEARCH]",
+        Mode::CommandInput { .. } => "[COMMAND]",
+        Mode::Follow if app.follow_paused.is_some() => "[FOLLOW PAUSED]",
+        Mode::Follow => "[FOLLOW]",
+        Mode::FilterInput { .. } => "[FILTER]",
+        Mode::BufferPicker { .. } => "[BUFFERS]",
+        Mode::MarksList { .. } => "[MARKS]",
+        Mode::Help { .. } => "[HELP]",
+        Mode::Info => "[INFO]",
+        Mode::Messages => "[MESSAGES]",
+        Mode::MatchList { .. } => "[MATCHES]",
+        Mode::PipeInput { .. } => "[PIPE]",
+        Mode::Visual { .. } | Mode::VisualChar { .. } => "[VISUAL]",
+        Mode::ReloadPrompt => "[RELOAD?]",
+    };
+
+    let hex_indicator = if let Some(loading) = &buf.loading {
+        format!("[{} loading {}]", loading.spinner(), loading.progress())
+    } else if let Some(exec) = &buf.exec {
+        format!("[{}]", exec.label())
+    } else if buf.deleted {
+        "[deleted]".to_string()
+    } else if buf.dropped > 0 {
+        format!("[{} earlier lines dropped]", buf.dropped)
+    } else if buf.is_binary() {
+        "[HEX]".to_string()
+    } else if buf.is_man() {
+        "[MAN]".to_string()
+    } else if let Some(len) = app.truncated_line_len() {
+        format!("[line truncated, {}]", info::human_size(len as u64))
+    } else if app.highlighter.too_large(buf) {
+        "[PLAIN]".to_string()
+    } else {
+        String::new()
     };
 
-    let hex_indicator = if buf.is_binary() { " [HEX]" } else { "" };
+    let recording_indicator = match app.recording {
+        Some((reg, _)) => format!("[REC @{}]", reg),
+        None => String::new(),
+    };
 
-    let filter_indicator = if let Some((ref q, ref idx)) = app.filter {
-        format!(" [~{} {}L]", q, idx.len())
+    let filter_indicator = if let Some(ref job) = app.filter_job {
+        format!("[~{} {}%]", job.label(), job.percent())
+    } else if let Some(ref filter) = app.filter {
+        format!("[~{} {}L]", filter.label(), filter.indices.len())
     } else {
         String::new()
     };
 
-    let left = format!(" {}{}{}{}{} ", buf.name, buffer_indicator, mode_indicator, hex_indicator, filter_indicator);
+    let repo_indicator = match &app.repo_status {
+        Some(status) => format!("[{}]", status.label()),
+        None => String::new(),
+    };
+
+    let searching_indicator = if app.search.is_searching {
+        format!(" [searching {}%]", (app.search.progress_ratio() * 100.0) as u16)
+    } else {
+        String::new()
+    };
 
-    let searching_indicator = if app.search.is_searching { " [searching…]" } else { "" };
+    let range_indicator = match app.search.range {
+        Some((lo, hi)) => format!(" [L{}-{}]", lo + 1, hi + 1),
+        None => String::new(),
+    };
 
     let search_info = if app.search.has_pattern() {
         format!(
-            " {}{} ({} matches){} \u{2502}",
+            "{}{}{} ({}{} matches){}",
             if app.search.forward { "/" } else { "?" },
             app.search.query_string,
+            range_indicator,
             app.search.match_count(),
+            if app.search.truncated { "+" } else { "" },
             searching_indicator,
         )
     } else {
         String::new()
     };
 
-    let right = format!("{}  {}-{}/{} \u{2502} {}% ", search_info, top, bottom, total, pct);
+    let field = |name: &str| -> Option<String> {
+        Some(match name {
+            "name" => buf.name.clone(),
+            "path" => buf.path.as_ref().map_or_else(|| buf.name.clone(), |p| p.display().to_string()),
+            "branch" => repo_indicator.clone(),
+            "buffer" => buffer_indicator.clone(),
+            "mode" => mode_indicator.to_string(),
+            "recording" => recording_indicator.clone(),
+            "flags" => hex_indicator.clone(),
+            "filter" => filter_indicator.clone(),
+            "search" => search_info.clone(),
+            "position" => format!("{}-{}/{}", top, bottom, total),
+            "lines" => format!("{}-{}", top, bottom),
+            "total" => total.to_string(),
+            "percent" => pct.to_string(),
+            "size" => info::human_size(buf.info.size.unwrap_or(buf.byte_len() as u64)),
+            "encoding" => buf.info.encoding.to_string(),
+            "eol" => buf.info.line_endings.to_string(),
+            "offset" => buf.byte_of_row(app.top_view_line()).to_string(),
+            "bytes" => format!(
+                "{} / {}",
+                info::human_size(buf.byte_of_row(app.top_view_line()) as u64),
+                info::human_size(buf.byte_len() as u64)
+            ),
+            _ => return None,
+        })
+    };
+    let format = &app.config.statusbar;
+    let left = format!(" {} ", expand(&format.left, field));
+    let right = format!("{} ", expand(&format.right, field));
 
     let available = area.width as usize;
     let left_len = left.chars().count();
@@ -60,7 +144,80 @@ pub fn render(frame: &mut Frame, app: &App, area: Rect) {
     };
 
     let status = format!("{}{}{}", left, padding, right);
-    let style = Style::default().fg(Color::Rgb(192, 197, 206)).bg(Color::Rgb(43, 48, 59));
+    let style = if app.alert_flashing() {
+        Style::default().fg(Color::White).bg(Color::Red).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(app.palette.status_bar_fg).bg(app.palette.status_bar_bg)
+    };
     let paragraph = Paragraph::new(status).style(style);
     frame.render_widget(paragraph, area);
 }
+
+/// Fill in the `{placeholders}` of a status bar template with `field`. A
+/// placeholder that is empty takes the space before it along, so optional
+/// indicators can be listed one space apart. Unknown placeholders are left
+/// as they are.
+pub fn expand(template: &str, field: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let Some((close, value)) = after.find('}').and_then(|close| Some((close, field(&after[..close])?))) else {
+            out.push('{');
+            rest = after;
+            continue;
+        };
+        if value.is_empty() {
+            if out.ends_with(' ') {
+                out.pop();
+            }
+        } else {
+            out.push_str(&value);
+        }
+        rest = &after[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Placeholders in `template` that are not in `FIELDS`.
+pub fn unknown_fields(template: &str) -> Vec<&str> {
+    let mut unknown = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        rest = &rest[open + 1..];
+        let Some(close) = rest.find('}') else { break };
+        if !FIELDS.contains(&&rest[..close]) {
+            unknown.push(&rest[..close]);
+        }
+        rest = &rest[close + 1..];
+    }
+    unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str) -> Option<String> {
+        match name {
+            "name" => Some("log.txt".to_string()),
+            "buffer" => Some("[2/3]".to_string()),
+            "mode" | "filter" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(expand("{name} {mode} {buffer} {filter}", field), "log.txt [2/3]");
+        assert_eq!(expand("{mode}{name}!", field), "log.txt!");
+        assert_eq!(expand("{nope} {name} {", field), "{nope} log.txt {");
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        assert_eq!(unknown_fields("{name} {nope} {percent}% {"), vec!["nope"]);
+    }
+}
@@ -0,0 +1,72 @@
+/// Runs of at least `min_len` printable characters in `data`, with the
+/// byte offset each starts at, as `strings` finds them. Printable is ASCII
+/// from space to `~` and tab, and any other well-formed UTF-8 character
+/// that is not a control character, so text in other scripts is found too.
+pub fn find(data: &[u8], min_len: usize) -> Vec<(usize, String)> {
+    let mut found = Vec::new();
+    let mut start = 0;
+    let mut chars = 0;
+    let mut i = 0;
+    while i < data.len() {
+        let len = printable_len(&data[i..]);
+        if len == 0 {
+            if chars >= min_len.max(1) {
+                found.push((start, String::from_utf8_lossy(&data[start..i]).into_owned()));
+            }
+            chars = 0;
+            i += 1;
+            start = i;
+        } else {
+            chars += 1;
+            i += len;
+        }
+    }
+    if chars >= min_len.max(1) {
+        found.push((start, String::from_utf8_lossy(&data[start..]).into_owned()));
+    }
+    found
+}
+
+/// Length in bytes of the printable character `data` starts with; 0 if it
+/// does not start with one.
+fn printable_len(data: &[u8]) -> usize {
+    match data[0] {
+        b'\t' | b' '..=b'~' => 1,
+        0x00..=0x7f => 0,
+        lead => {
+            let len = match lead {
+                0xc2..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf4 => 4,
+                _ => return 0,
+            };
+            match data.get(..len).map(std::str::from_utf8) {
+                Some(Ok(s)) if !s.chars().any(char::is_control) => len,
+                _ => 0,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        let data = b"\x00\x01/usr/lib/ld.so\x00ab\x00\xffhello world\n\x7fx";
+        assert_eq!(find(data, 4), vec![(2, "/usr/lib/ld.so".to_string()), (21, "hello world".to_string())]);
+        assert_eq!(find(data, 2).len(), 3);
+        assert_eq!(find(b"tail", 4), vec![(0, "tail".to_string())]);
+    }
+
+    #[test]
+    fn test_utf8() {
+        let data = "\0\0größe\0".as_bytes();
+        assert_eq!(find(data, 5), vec![(2, "größe".to_string())]);
+        // A cut-off character ends the run
+        assert_eq!(find(b"abcd\xc3", 4), vec![(0, "abcd".to_string())]);
+        // U+0085, a C1 control
+        assert_eq!(find(b"ab\xc2\x85cd", 3), vec![]);
+    }
+}
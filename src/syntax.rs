@@ -1,9 +1,22 @@
 use ratatui::style::{Color, Style};
-use std::path::Path;
-use syntect::highlighting::{ThemeSet, Theme};
-use syntect::parsing::{SyntaxReference, SyntaxSet};
+use serde::Deserialize;
+use std::cell::RefMut;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use syntect::highlighting::{HighlightIterator, HighlightState, Highlighter, ThemeSet, Theme};
+use syntect::parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet};
 use syntect::easy::HighlightLines;
 
+use crate::bat;
+use crate::buffer::{Buffer, TextSnapshot};
+use crate::modeline;
+#[cfg(feature = "tree-sitter")]
+use crate::treesitter;
+
 const BUNDLED_THEMES: &[(&str, &[u8])] = &[
     ("Monokai",          include_bytes!("../assets/themes/Monokai.tmTheme")),
     ("Dracula",          include_bytes!("../assets/themes/Dracula.tmTheme")),
@@ -13,21 +26,39 @@ const BUNDLED_THEMES: &[(&str, &[u8])] = &[
 
 /// Manages syntax highlighting using syntect.
 pub struct SyntaxHighlighter {
-    syntax_set: SyntaxSet,
-    theme: Theme,
+    /// Syntaxes and themes, once `loader` has finished
+    assets: OnceLock<Assets>,
+    /// Background thread loading the assets, until they are first needed
+    loader: Mutex<Option<JoinHandle<Assets>>>,
+    /// The theme asked for at startup, looked up on first use
+    initial_theme: String,
+    /// Name of the current theme, and the theme
+    theme: OnceLock<(String, Theme)>,
     enabled: bool,
+    /// Files larger than this are not highlighted unless forced
+    max_size: u64,
+    /// Lines longer than this are never highlighted
+    max_line_len: usize,
 }
 
-/// A styled span of text for rendering.
-#[derive(Debug, Clone)]
-pub struct StyledSpan {
-    pub text: String,
-    pub style: Style,
+/// Syntaxes and themes: syntect's defaults, the bundled themes, and bat's
+/// and the user's additions. Loading them takes long enough to delay the
+/// first frame, so `SyntaxHighlighter::new` does it on another thread.
+struct Assets {
+    syntax_set: Arc<SyntaxSet>,
+    /// Built-in, bundled and user themes, for switching with `:set theme=`
+    theme_set: ThemeSet,
+    /// Names of the themes loaded from bat's directories
+    bat_themes: Vec<String>,
+    /// Names of the themes loaded from the user themes directory
+    user_themes: Vec<String>,
 }
 
-impl SyntaxHighlighter {
-    pub fn new(theme_name: &str, enabled: bool, themes_dir: Option<&Path>) -> Self {
-        let syntax_set = SyntaxSet::load_defaults_newlines();
+impl Assets {
+    fn load(themes_dir: Option<PathBuf>, bat_assets: bool) -> Self {
+        // Lines are highlighted without their newline, which these syntaxes
+        // expect; the `newlines` ones let line comments run on
+        let mut syntax_set = SyntaxSet::load_defaults_nonewlines();
         let mut theme_set = ThemeSet::load_defaults();
 
         // Load bundled themes
@@ -38,34 +69,247 @@ impl SyntaxHighlighter {
             }
         }
 
+        // bat's themes and syntaxes (override bundled themes)
+        let mut bat_themes = Vec::new();
+        if bat_assets {
+            for (name, theme) in bat::themes() {
+                bat_themes.push(name.clone());
+                theme_set.themes.insert(name, theme);
+            }
+            syntax_set = bat::add_syntaxes(syntax_set);
+        }
+
         // Load user themes (override bundled themes with same name)
-        let user_dir = themes_dir
-            .map(|p| p.to_path_buf())
-            .or_else(|| dirs::config_dir().map(|d| d.join("some").join("themes")));
+        let user_dir = themes_dir.or_else(|| dirs::config_dir().map(|d| d.join("some").join("themes")));
 
+        let mut user_themes = Vec::new();
         if let Some(dir) = user_dir {
             if dir.exists() {
                 if let Ok(extra) = ThemeSet::load_from_folder(&dir) {
                     for (name, theme) in extra.themes {
+                        user_themes.push(name.clone());
                         theme_set.themes.insert(name, theme);
                     }
                 }
             }
         }
 
-        let theme = theme_set
-            .themes
-            .get(theme_name)
-            .cloned()
-            .unwrap_or_else(|| {
-                theme_set.themes["base16-ocean.dark"].clone()
-            });
+        Assets { syntax_set: Arc::new(syntax_set), theme_set, bat_themes, user_themes }
+    }
+}
 
+/// Where a theme was loaded from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThemeSource {
+    /// Shipped with syntect
+    Default,
+    /// Compiled into `some`
+    Bundled,
+    /// Loaded from bat's cache or config directory (`general.bat_assets`)
+    Bat,
+    /// A .tmTheme file in the themes directory
+    User,
+}
+
+impl ThemeSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            ThemeSource::Default => "default",
+            ThemeSource::Bundled => "bundled",
+            ThemeSource::Bat => "bat",
+            ThemeSource::User => "user",
+        }
+    }
+}
+
+/// Which backend highlights a file type (`[filetype.*] highlighter`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Engine {
+    /// syntect's TextMate grammars, for every syntax
+    #[default]
+    Syntect,
+    /// tree-sitter grammars for Rust, Python and TypeScript, when built
+    /// with the `tree-sitter` feature; other files fall back to syntect
+    TreeSitter,
+}
+
+/// Colors the lines of a file. Lines are highlighted from the top down so
+/// that block comments and strings opened above a line color it.
+pub trait Backend: Send {
+    /// Runs of each line of `text`, in order.
+    fn lines<'a>(&'a self, text: &'a TextSnapshot) -> Box<dyn Iterator<Item = LineRuns> + 'a>;
+}
+
+/// A styled span of text for rendering.
+#[derive(Debug, Clone)]
+pub struct StyledSpan {
+    pub text: String,
+    pub style: Style,
+}
+
+impl SyntaxHighlighter {
+    /// `bat_assets` adds bat's themes and syntaxes (`general.bat_assets`).
+    pub fn new(theme_name: &str, enabled: bool, themes_dir: Option<&Path>, bat_assets: bool) -> Self {
+        let themes_dir = themes_dir.map(Path::to_path_buf);
+        let loader = std::thread::spawn(move || Assets::load(themes_dir, bat_assets));
         Self {
-            syntax_set,
-            theme,
+            assets: OnceLock::new(),
+            loader: Mutex::new(Some(loader)),
+            initial_theme: theme_name.to_string(),
+            theme: OnceLock::new(),
             enabled,
+            max_size: u64::MAX,
+            max_line_len: usize::MAX,
+        }
+    }
+
+    /// The syntaxes and themes, waiting for the loader thread the first time.
+    fn assets(&self) -> &Assets {
+        self.assets.get_or_init(|| {
+            let loader = self.loader.lock().ok().and_then(|mut loader| loader.take());
+            loader.expect("assets are loaded once").join().expect("Loading syntaxes and themes failed")
+        })
+    }
+
+    fn syntax_set(&self) -> &Arc<SyntaxSet> {
+        &self.assets().syntax_set
+    }
+
+    /// Name of the current theme and the theme, looking up the one asked
+    /// for at startup (or the default if there is none by that name) the
+    /// first time.
+    fn current_theme(&self) -> &(String, Theme) {
+        self.theme.get_or_init(|| {
+            let themes = &self.assets().theme_set.themes;
+            let name = if themes.contains_key(&self.initial_theme) { self.initial_theme.as_str() } else { "base16-ocean.dark" };
+            (name.to_string(), themes[name].clone())
+        })
+    }
+
+    /// Switch to the theme called `name`; false if there is no such theme.
+    pub fn set_theme(&mut self, name: &str) -> bool {
+        match self.assets().theme_set.themes.get(name) {
+            Some(theme) => {
+                self.theme = OnceLock::from((name.to_string(), theme.clone()));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Every theme and where it comes from, sorted by name.
+    pub fn themes(&self) -> Vec<(&str, ThemeSource)> {
+        let assets = self.assets();
+        assets
+            .theme_set
+            .themes
+            .keys()
+            .map(|name| {
+                let source = if assets.user_themes.contains(name) {
+                    ThemeSource::User
+                } else if assets.bat_themes.contains(name) {
+                    ThemeSource::Bat
+                } else if BUNDLED_THEMES.iter().any(|(bundled, _)| bundled == name) {
+                    ThemeSource::Bundled
+                } else {
+                    ThemeSource::Default
+                };
+                (name.as_str(), source)
+            })
+            .collect()
+    }
+
+    /// `sample` highlighted as Rust in theme `name`, on its background.
+    pub fn preview(&self, name: &str, sample: &str) -> Vec<StyledSpan> {
+        let Some(theme) = self.assets().theme_set.themes.get(name) else { return Vec::new() };
+        let syntax_set = self.syntax_set();
+        let syntax = syntax_set.find_syntax_by_extension("rs").unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+        let bg = theme.settings.background.map(|c| Color::Rgb(c.r, c.g, c.b));
+        let mut hl = HighlightLines::new(syntax, theme);
+        match hl.highlight_line(sample, syntax_set) {
+            Ok(ranges) => ranges
+                .into_iter()
+                .map(|(style, text)| {
+                    let style = syntect_to_ratatui_style(&style);
+                    StyledSpan { text: text.to_string(), style: bg.map_or(style, |bg| style.bg(bg)) }
+                })
+                .collect(),
+            Err(_) => vec![StyledSpan { text: sample.to_string(), style: Style::default() }],
+        }
+    }
+
+    /// False with `--no-syntax`, `--plain` or when colors are off.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn has_theme(&self, name: &str) -> bool {
+        self.assets().theme_set.themes.contains_key(name)
+    }
+
+    pub fn theme_name(&self) -> &str {
+        &self.current_theme().0
+    }
+
+    /// Background and default foreground of the theme, if it sets them.
+    pub fn theme_colors(&self) -> (Option<Color>, Option<Color>) {
+        let rgb = |c: syntect::highlighting::Color| Color::Rgb(c.r, c.g, c.b);
+        let settings = &self.current_theme().1.settings;
+        (settings.background.map(rgb), settings.foreground.map(rgb))
+    }
+
+    /// The syntax of `buf`: the one it was set to, else the one named by
+    /// a vim or Emacs modeline, else the one its name suggests, else the
+    /// one its first line suggests (a `#!` line, `<?xml`), which also
+    /// covers stdin.
+    pub fn buffer_syntax(&self, buf: &Buffer) -> &SyntaxReference {
+        if let Some(syntax) = buf.syntax.as_deref().and_then(|name| self.find_syntax(name)) {
+            return syntax;
+        }
+        let modeline = modeline::find(buf).and_then(|m| m.syntax);
+        if let Some(syntax) = modeline.and_then(|name| self.find_syntax(&name)) {
+            return syntax;
+        }
+        let syntax = self.detect_syntax(buf.path.as_deref());
+        if !std::ptr::eq(syntax, self.syntax_set().find_syntax_plain_text()) {
+            return syntax;
+        }
+        buf.get_line(0)
+            .and_then(|line| self.syntax_set().find_syntax_by_first_line(line))
+            .unwrap_or(syntax)
+    }
+
+    /// The name of the syntax all of `bufs` are highlighted with, if they
+    /// agree, for a buffer joining them (`--cat`, `:join`).
+    pub fn common_syntax(&self, bufs: &[&Buffer]) -> Option<String> {
+        if !self.is_enabled() {
+            return None;
         }
+        let mut names = bufs.iter().map(|buf| &self.buffer_syntax(buf).name);
+        let first = names.next()?;
+        names.all(|name| name == first).then(|| first.clone())
+    }
+
+    /// The syntax used for files with extension `name`, or called `name`
+    /// ignoring case (`:syntax`, `--language`).
+    pub fn find_syntax(&self, name: &str) -> Option<&SyntaxReference> {
+        self.syntax_set().find_syntax_by_token(name)
+    }
+
+    /// Names of the syntaxes starting with `prefix`, ignoring case, sorted
+    /// (`:syntax` completion).
+    pub fn complete_syntax(&self, prefix: &str) -> Vec<String> {
+        let prefix = prefix.to_lowercase();
+        let mut names: Vec<String> = self
+            .syntax_set()
+            .syntaxes()
+            .iter()
+            .filter(|s| !s.hidden && s.name.to_lowercase().starts_with(&prefix))
+            .map(|s| s.name.clone())
+            .collect();
+        names.sort_by_key(|name| name.to_lowercase());
+        names
     }
 
     /// Detect the syntax for a file path, falling back to plain text.
@@ -75,7 +319,7 @@ impl SyntaxHighlighter {
             let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
 
             // Try direct extension match first
-            if let Some(syntax) = self.syntax_set.find_syntax_by_extension(ext) {
+            if let Some(syntax) = self.syntax_set().find_syntax_by_extension(ext) {
                 return syntax;
             }
 
@@ -83,7 +327,7 @@ impl SyntaxHighlighter {
             if matches!(ext, "gz" | "zst" | "zstd" | "bz2") {
                 let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
                 if let Some(inner_ext) = Path::new(stem).extension().and_then(|e| e.to_str()) {
-                    if let Some(syntax) = self.syntax_set.find_syntax_by_extension(inner_ext) {
+                    if let Some(syntax) = self.syntax_set().find_syntax_by_extension(inner_ext) {
                         return syntax;
                     }
                 }
@@ -91,55 +335,249 @@ impl SyntaxHighlighter {
 
             // Try by filename
             if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                if let Some(syntax) = self.syntax_set.find_syntax_by_extension(name) {
+                if let Some(syntax) = self.syntax_set().find_syntax_by_extension(name) {
                     return syntax;
                 }
             }
         }
-        self.syntax_set.find_syntax_plain_text()
+        self.syntax_set().find_syntax_plain_text()
     }
 
-    /// Highlight a single line, returning a list of styled spans.
-    /// If syntax highlighting is disabled, returns the line as a single unstyled span.
-    pub fn highlight_line(
-        &self,
-        line: &str,
-        highlighter: &mut HighlightLines,
-    ) -> Vec<StyledSpan> {
-        if !self.enabled {
-            return vec![StyledSpan {
-                text: line.to_string(),
-                style: Style::default(),
-            }];
+    /// The lines of `buf` highlighted so far, starting a background job for
+    /// the rest if none is running. Waits up to `wait` for line `until`.
+    pub fn highlighted_lines<'a>(&self, buf: &'a Buffer, until: usize, wait: Duration) -> RefMut<'a, HighlightCache> {
+        let mut cache = buf.highlight_cache.borrow_mut();
+        let key = Some((self.backend_name(buf), self.theme_name().to_string()));
+        if cache.key != key {
+            *cache = HighlightCache { key, ..Default::default() };
+        }
+        if !cache.is_running() && cache.lines.len() < buf.line_count() {
+            cache.job = Some(spawn_highlight_job(self.backend(buf), buf.snapshot()));
         }
+        cache.drain(until, wait);
+        cache
+    }
 
-        match highlighter.highlight_line(line, &self.syntax_set) {
-            Ok(ranges) => ranges
-                .into_iter()
-                .map(|(style, text)| StyledSpan {
-                    text: text.to_string(),
-                    style: syntect_to_ratatui_style(&style),
-                })
-                .collect(),
-            Err(_) => vec![StyledSpan {
-                text: line.to_string(),
-                style: Style::default(),
-            }],
+    /// Runs of each line of `buf`, in order, highlighted by a background
+    /// job as they are read (`--print`, exports).
+    pub fn line_runs(&self, buf: &Buffer) -> impl Iterator<Item = LineRuns> {
+        spawn_highlight_job(self.backend(buf), buf.snapshot()).into_iter().flat_map(|batch| batch.lines)
+    }
+
+    /// The backend for `buf`: tree-sitter if its file type asks for it and
+    /// there is a grammar for it, else syntect.
+    pub fn backend(&self, buf: &Buffer) -> Box<dyn Backend> {
+        #[cfg(feature = "tree-sitter")]
+        if let Some(language) = self.tree_sitter_language(buf) {
+            return Box::new(treesitter::TreeSitter::new(language, &self.current_theme().1, self.max_line_len));
         }
+        Box::new(Syntect {
+            syntax_set: Arc::clone(self.syntax_set()),
+            syntax: self.buffer_syntax(buf).name.clone(),
+            theme: self.current_theme().1.clone(),
+            max_line_len: self.max_line_len,
+        })
     }
 
-    /// Create a new highlighter instance for a given syntax.
-    pub fn create_highlight_lines<'a>(&'a self, syntax: &'a SyntaxReference) -> HighlightLines<'a> {
-        HighlightLines::new(syntax, &self.theme)
+    /// The syntax `buf` is highlighted as, with the backend if not syntect
+    /// (`:syntax`).
+    pub fn backend_name(&self, buf: &Buffer) -> String {
+        #[cfg(feature = "tree-sitter")]
+        if let Some(language) = self.tree_sitter_language(buf) {
+            return format!("{} (tree-sitter)", language.name());
+        }
+        self.buffer_syntax(buf).name.clone()
     }
 
-    pub fn is_enabled(&self) -> bool {
-        self.enabled
+    #[cfg(feature = "tree-sitter")]
+    fn tree_sitter_language(&self, buf: &Buffer) -> Option<treesitter::Language> {
+        if buf.engine != Engine::TreeSitter {
+            return None;
+        }
+        treesitter::Language::detect(&self.buffer_syntax(buf).name, buf.path.as_deref())
+    }
+
+    /// Set `general.highlight_max_size` and `general.highlight_max_line_len`.
+    pub fn set_limits(&mut self, max_size: u64, max_line_len: usize) {
+        self.max_size = max_size;
+        self.max_line_len = max_line_len;
+    }
+
+    /// Whether `buf` is highlighted: as set by `:syntax on`/`off`, else
+    /// unless it is larger than `highlight_max_size`.
+    pub fn highlights(&self, buf: &Buffer) -> bool {
+        self.enabled && buf.highlight.unwrap_or(buf.byte_len() as u64 <= self.max_size)
+    }
+
+    /// True if `buf` is shown plain only because of its size.
+    pub fn too_large(&self, buf: &Buffer) -> bool {
+        self.enabled && buf.highlight.is_none() && !self.highlights(buf)
+    }
+}
+
+/// Lines per batch sent back by a background highlighting job.
+const BATCH_LINES: usize = 500;
+
+/// Styled runs of one line: (length in bytes, style).
+pub type LineRuns = Vec<(usize, Style)>;
+
+/// Add `len` bytes in `style` to the end of `runs`, joining them to the
+/// last run if it has the same style.
+pub(crate) fn push_run(runs: &mut LineRuns, len: usize, style: Style) {
+    match runs.last_mut() {
+        Some((last_len, last)) if *last == style => *last_len += len,
+        _ => runs.push((len, style)),
+    }
+}
+
+/// `text` split into spans by `runs`. Text past the last run is plain.
+pub fn spans(runs: &[(usize, Style)], text: &str) -> Vec<StyledSpan> {
+    spans_in(runs, text, 0..text.len())
+}
+
+/// The part of `text` in the byte range `range` split into spans by `runs`,
+/// which start at the beginning of `text`. Only that part is copied.
+pub fn spans_in(runs: &[(usize, Style)], text: &str, range: Range<usize>) -> Vec<StyledSpan> {
+    let mut start = 0;
+    let mut spans: Vec<StyledSpan> = Vec::new();
+    let mut push = |from: usize, to: usize, style: Style| {
+        let (from, to) = (from.max(range.start), to.min(range.end));
+        if from < to {
+            spans.push(StyledSpan { text: text.get(from..to).unwrap_or_default().to_string(), style });
+        }
+    };
+    for &(len, style) in runs {
+        let end = (start + len).min(text.len());
+        push(start, end, style);
+        start = end;
+        if start >= range.end {
+            break;
+        }
+    }
+    push(start, text.len(), Style::default());
+    spans
+}
+
+/// A buffer's lines as highlighted so far by a background job, which
+/// parses from the top of the file so that block comments and strings
+/// opened above the viewport are colored correctly.
+#[derive(Default)]
+pub struct HighlightCache {
+    /// Syntax and theme the lines were highlighted with
+    key: Option<(String, String)>,
+    lines: Vec<LineRuns>,
+    job: Option<mpsc::Receiver<HighlightBatch>>,
+}
+
+/// Highlighted lines from a job, starting at line `start`.
+struct HighlightBatch {
+    start: usize,
+    lines: Vec<LineRuns>,
+}
+
+impl HighlightCache {
+    /// Stop the job and forget all but the first `lines` lines, which are
+    /// still shown while a new job highlights the file again.
+    pub fn truncate(&mut self, lines: usize) {
+        self.job = None;
+        self.lines.truncate(lines);
+    }
+
+    /// True while a background job is still highlighting.
+    pub fn is_running(&self) -> bool {
+        self.job.is_some()
+    }
+
+    /// Spans of line `i`, whose text is `text`, once it has been highlighted.
+    /// Text past the end of the runs, kept from before the line grew, is plain.
+    /// Only the part of it in the byte range `range` is returned.
+    pub fn line(&self, i: usize, text: &str, range: Range<usize>) -> Option<Vec<StyledSpan>> {
+        Some(spans_in(self.lines.get(i)?, text, range))
+    }
+
+    /// Take the batches the job has sent, waiting up to `wait` for line
+    /// `until` if it is not ready yet.
+    fn drain(&mut self, until: usize, wait: Duration) {
+        let deadline = Instant::now() + wait;
+        let mut done = self.lines.len();
+        while let Some(rx) = &self.job {
+            let received = if done > until {
+                rx.try_recv().map_err(|e| e == TryRecvError::Disconnected)
+            } else {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                rx.recv_timeout(timeout).map_err(|e| e == RecvTimeoutError::Disconnected)
+            };
+            match received {
+                Ok(batch) => {
+                    // A restarted job overwrites the lines kept from before
+                    let end = batch.start + batch.lines.len();
+                    let kept = self.lines.len().clamp(batch.start, end);
+                    let mut lines = batch.lines.into_iter();
+                    self.lines.splice(batch.start..kept, lines.by_ref().take(kept - batch.start));
+                    self.lines.extend(lines);
+                    done = end;
+                }
+                // Disconnected: the job is done
+                Err(true) => self.job = None,
+                Err(false) => break,
+            }
+        }
+    }
+}
+
+/// Highlight the lines of `text` on a background thread, sending them back
+/// in batches. Dropping the receiver stops the thread at its next batch.
+fn spawn_highlight_job(backend: Box<dyn Backend>, text: TextSnapshot) -> mpsc::Receiver<HighlightBatch> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let total = text.line_count();
+        let mut batch = HighlightBatch { start: 0, lines: Vec::new() };
+        for (i, runs) in backend.lines(&text).enumerate().take(total) {
+            batch.lines.push(runs);
+            if batch.lines.len() == BATCH_LINES || i + 1 == total {
+                let next = HighlightBatch { start: i + 1, lines: Vec::new() };
+                if tx.send(std::mem::replace(&mut batch, next)).is_err() {
+                    // Receiver dropped: the buffer was reloaded or restyled
+                    return;
+                }
+            }
+        }
+    });
+    rx
+}
+
+/// The default backend: syntect with the syntax called `syntax`. Lines
+/// longer than `max_line_len` are left plain.
+struct Syntect {
+    syntax_set: Arc<SyntaxSet>,
+    syntax: String,
+    theme: Theme,
+    max_line_len: usize,
+}
+
+impl Backend for Syntect {
+    fn lines<'a>(&'a self, text: &'a TextSnapshot) -> Box<dyn Iterator<Item = LineRuns> + 'a> {
+        let Some(syntax) = self.syntax_set.find_syntax_by_name(&self.syntax) else {
+            return Box::new((0..text.line_count()).map(|_| Vec::new()));
+        };
+        let highlighter = Highlighter::new(&self.theme);
+        let mut parse = ParseState::new(syntax);
+        let mut state = HighlightState::new(&highlighter, ScopeStack::new());
+        Box::new((0..text.line_count()).map(move |i| {
+            let line = text.line_bytes(i).and_then(|b| std::str::from_utf8(b).ok()).unwrap_or("");
+            let line = if line.len() > self.max_line_len { "" } else { line };
+            let ops = parse.parse_line(line, &self.syntax_set).unwrap_or_default();
+            let mut runs: LineRuns = Vec::new();
+            for (style, piece) in HighlightIterator::new(&mut state, &ops, line, &highlighter) {
+                push_run(&mut runs, piece.len(), syntect_to_ratatui_style(&style));
+            }
+            runs
+        }))
     }
 }
 
 /// Convert a syntect style to a ratatui style.
-fn syntect_to_ratatui_style(style: &syntect::highlighting::Style) -> Style {
+pub(crate) fn syntect_to_ratatui_style(style: &syntect::highlighting::Style) -> Style {
     let fg = Color::Rgb(
         style.foreground.r,
         style.foreground.g,
@@ -147,3 +585,27 @@ fn syntect_to_ratatui_style(style: &syntect::highlighting::Style) -> Style {
     );
     Style::default().fg(fg)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lines reach the parser without their newline, so the syntaxes have
+    /// to be the ones built for that. With the others a `#` comment or a C
+    /// macro never sees its end and runs on into the next line.
+    #[test]
+    fn test_scopes_end_with_the_line() {
+        let syntax_set = Assets::load(None, false).syntax_set;
+        for (ext, text) in [("py", "x = 1  # note\ny = 2"), ("sh", "echo a # note\necho b"), ("c", "#define X 1\nint y;")] {
+            let syntax = syntax_set.find_syntax_by_extension(ext).unwrap();
+            let mut parse = ParseState::new(syntax);
+            let mut stack = ScopeStack::new();
+            for line in text.lines() {
+                for (_, op) in parse.parse_line(line, &syntax_set).unwrap() {
+                    stack.apply(&op).unwrap();
+                }
+            }
+            assert_eq!(stack.len(), 1, "{}: {:?}", ext, stack);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Tab stops. A tab reaches to the next multiple of the tab width, and
+//! lines are drawn, printed and exported with their tabs expanded to
+//! spaces, so columns line up the same in all three.
+
+use ratatui::text::Span;
+use unicode_width::UnicodeWidthChar;
+
+/// Columns `c` takes when it starts at column `col`.
+pub fn width(c: char, col: usize, tab_width: usize) -> usize {
+    match c {
+        '\t' => tab_width - col % tab_width,
+        c => c.width().unwrap_or(0),
+    }
+}
+
+/// `spans` with their tabs expanded to spaces, the first span starting at
+/// column `col`.
+pub fn expand(spans: Vec<Span<'static>>, mut col: usize, tab_width: usize) -> Vec<Span<'static>> {
+    spans
+        .into_iter()
+        .map(|span| {
+            if !span.content.contains('\t') {
+                col += span.content.chars().map(|c| width(c, 0, tab_width)).sum::<usize>();
+                return span;
+            }
+            let mut text = String::with_capacity(span.content.len());
+            for c in span.content.chars() {
+                let w = width(c, col, tab_width);
+                match c {
+                    '\t' => text.extend(std::iter::repeat_n(' ', w)),
+                    c => text.push(c),
+                }
+                col += w;
+            }
+            Span::styled(text, span.style)
+        })
+        .collect()
+}
+
+/// Columns `text` takes when it starts a line.
+pub fn text_width(text: &str, tab_width: usize) -> usize {
+    text.chars().fold(0, |col, c| col + width(c, col, tab_width))
+}
+
+/// Rows `text` takes when it wraps in a window `columns` wide: a character
+/// that doesn't fit on a row starts the next one, and the spaces of a tab
+/// are split like any others.
+pub fn wrapped_rows(text: &str, columns: usize, tab_width: usize) -> usize {
+    let (mut rows, mut used, mut col) = (1, 0, 0);
+    for c in text.chars() {
+        let w = width(c, col, tab_width);
+        col += w;
+        let (pieces, piece) = if c == '\t' { (w, 1) } else { (1, w) };
+        for _ in 0..pieces {
+            if used + piece > columns && used > 0 {
+                rows += 1;
+                used = 0;
+            }
+            used += piece;
+        }
+    }
+    rows
+}
+
+/// Column where character `index` of `text` starts.
+pub fn column(text: &str, index: usize, tab_width: usize) -> usize {
+    text.chars().take(index).fold(0, |col, c| col + width(c, col, tab_width))
+}
+
+/// Index of the character of `text` drawn over column `col`, which may
+/// start before it if it is a tab or wide character; the number of
+/// characters when `col` is past the end.
+pub fn char_at(text: &str, col: usize, tab_width: usize) -> usize {
+    let mut start = 0;
+    for (i, c) in text.chars().enumerate() {
+        start += width(c, start, tab_width);
+        if start > col {
+            return i;
+        }
+    }
+    text.chars().count()
+}
+
+/// Index of the character of `text` drawn over column `x` of row `row`
+/// when it wraps in a window `columns` wide, breaking rows where
+/// `wrapped_rows()` does. Past the end of a row it is the row's last
+/// character, and past the end of the text the number of characters.
+pub fn char_at_wrapped(text: &str, row: usize, x: usize, columns: usize, tab_width: usize) -> usize {
+    let (mut rows, mut used, mut col) = (0, 0, 0);
+    for (i, c) in text.chars().enumerate() {
+        let w = width(c, col, tab_width);
+        col += w;
+        let (pieces, piece) = if c == '\t' { (w, 1) } else { (1, w) };
+        let mut on_row = false;
+        for _ in 0..pieces {
+            if used + piece > columns && used > 0 {
+                if rows == row {
+                    return if on_row { i } else { i - 1 };
+                }
+                rows += 1;
+                used = 0;
+            }
+            if rows == row && used + piece > x {
+                return i;
+            }
+            on_row |= rows == row;
+            used += piece;
+        }
+    }
+    text.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expanded(text: &str, col: usize) -> String {
+        expand(vec![Span::raw(text.to_string())], col, 4).iter().map(|s| s.content.as_ref()).collect()
+    }
+
+    #[test]
+    fn test_tabs_reach_the_next_stop() {
+        assert_eq!(expanded("\tx", 0), "    x");
+        assert_eq!(expanded("ab\tx", 0), "ab  x");
+        assert_eq!(expanded("abcd\tx", 0), "abcd    x");
+        // Where the text starts counts, as for a line scrolled sideways
+        assert_eq!(expanded("\tx", 3), " x");
+    }
+
+    #[test]
+    fn test_stops_carry_across_spans() {
+        let spans = expand(vec![Span::raw("ab"), Span::raw("\tx")], 0, 4);
+        assert_eq!(spans[1].content, "  x");
+    }
+
+    #[test]
+    fn test_wrapped_rows() {
+        assert_eq!(wrapped_rows("", 4, 4), 1);
+        assert_eq!(wrapped_rows("abcd", 4, 4), 1);
+        assert_eq!(wrapped_rows("abcde", 4, 4), 2);
+        assert_eq!(wrapped_rows("ab\tc", 4, 4), 2);
+        // A wide character that doesn't fit goes to the next row whole
+        assert_eq!(wrapped_rows("abc\u{4e2d}", 4, 4), 2);
+        assert_eq!(wrapped_rows("\u{4e2d}", 1, 4), 1);
+    }
+
+    #[test]
+    fn test_columns_and_characters() {
+        let text = "a\t\u{4e2d}b";
+        assert_eq!(column(text, 1, 4), 1);
+        assert_eq!(column(text, 2, 4), 4);
+        assert_eq!(column(text, 3, 4), 6);
+        assert_eq!(char_at(text, 0, 4), 0);
+        assert_eq!(char_at(text, 2, 4), 1);
+        assert_eq!(char_at(text, 5, 4), 2);
+        assert_eq!(char_at(text, 6, 4), 3);
+        assert_eq!(char_at(text, 9, 4), 4);
+    }
+
+    #[test]
+    fn test_characters_of_wrapped_rows() {
+        // Rows of 3: "ab" and the tab's first space, then its second
+        // space and "c"
+        let text = "ab\tc";
+        assert_eq!(char_at_wrapped(text, 0, 1, 3, 4), 1);
+        assert_eq!(char_at_wrapped(text, 0, 2, 3, 4), 2);
+        assert_eq!(char_at_wrapped(text, 1, 0, 3, 4), 2);
+        assert_eq!(char_at_wrapped(text, 1, 1, 3, 4), 3);
+        assert_eq!(char_at_wrapped(text, 1, 2, 3, 4), 4);
+        // A wide character that went to the next row leaves a gap
+        assert_eq!(char_at_wrapped("abc\u{4e2d}", 0, 3, 4, 4), 2);
+        assert_eq!(char_at_wrapped("abc\u{4e2d}", 1, 1, 4, 4), 3);
+    }
+}
@@ -0,0 +1,187 @@
+use ratatui::style::Style;
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::highlighting::{Highlighter, Theme};
+use syntect::parsing::Scope;
+use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent};
+
+use crate::buffer::TextSnapshot;
+use crate::syntax::{self, Backend, LineRuns};
+
+/// tree-sitter capture names we color, with the TextMate scope whose
+/// theme style they take, so both backends share the themes.
+const CAPTURES: &[(&str, &str)] = &[
+    ("attribute", "entity.other.attribute-name"),
+    ("comment", "comment"),
+    ("constant", "constant"),
+    ("constant.builtin", "constant.language"),
+    ("constructor", "entity.name.type"),
+    ("escape", "constant.character.escape"),
+    ("function", "entity.name.function"),
+    ("function.builtin", "support.function"),
+    ("function.macro", "entity.name.function.macro"),
+    ("keyword", "keyword"),
+    ("label", "entity.name.label"),
+    ("number", "constant.numeric"),
+    ("operator", "keyword.operator"),
+    ("property", "variable.other.member"),
+    ("punctuation", "punctuation"),
+    ("string", "string"),
+    ("string.special", "string.regexp"),
+    ("tag", "entity.name.tag"),
+    ("type", "entity.name.type"),
+    ("type.builtin", "storage.type"),
+    ("variable.builtin", "variable.language"),
+    ("variable.parameter", "variable.parameter"),
+];
+
+/// Languages with a bundled tree-sitter grammar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    Rust,
+    Python,
+    TypeScript,
+    Tsx,
+}
+
+impl Language {
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Rust => "Rust",
+            Language::Python => "Python",
+            Language::TypeScript => "TypeScript",
+            Language::Tsx => "TSX",
+        }
+    }
+
+    /// The grammar for a file with syntect syntax `syntax`, else for its
+    /// extension (syntect has no TypeScript syntax of its own).
+    pub fn detect(syntax: &str, path: Option<&Path>) -> Option<Self> {
+        let ext = path.and_then(|p| p.extension()).and_then(|e| e.to_str()).unwrap_or("");
+        match (syntax, ext) {
+            ("Rust", _) | (_, "rs") => Some(Language::Rust),
+            ("Python", _) | (_, "py" | "pyi" | "pyw") => Some(Language::Python),
+            ("TypeScriptReact", _) | (_, "tsx") => Some(Language::Tsx),
+            ("TypeScript", _) | (_, "ts" | "mts" | "cts") => Some(Language::TypeScript),
+            _ => None,
+        }
+    }
+
+    /// The grammar and queries, compiled on first use. None if a query
+    /// does not compile against its grammar.
+    fn config(self) -> Option<&'static HighlightConfiguration> {
+        static CONFIGS: [OnceLock<Option<HighlightConfiguration>>; 4] =
+            [OnceLock::new(), OnceLock::new(), OnceLock::new(), OnceLock::new()];
+        CONFIGS[self as usize]
+            .get_or_init(|| {
+                let typescript_highlights =
+                    format!("{}\n{}", tree_sitter_typescript::HIGHLIGHTS_QUERY, tree_sitter_javascript::HIGHLIGHT_QUERY);
+                let typescript_locals =
+                    format!("{}\n{}", tree_sitter_typescript::LOCALS_QUERY, tree_sitter_javascript::LOCALS_QUERY);
+                let (language, highlights, injections, locals) = match self {
+                    Language::Rust => (
+                        tree_sitter_rust::LANGUAGE,
+                        tree_sitter_rust::HIGHLIGHTS_QUERY,
+                        tree_sitter_rust::INJECTIONS_QUERY,
+                        "",
+                    ),
+                    Language::Python => (tree_sitter_python::LANGUAGE, tree_sitter_python::HIGHLIGHTS_QUERY, "", ""),
+                    Language::TypeScript => (
+                        tree_sitter_typescript::LANGUAGE_TYPESCRIPT,
+                        typescript_highlights.as_str(),
+                        "",
+                        typescript_locals.as_str(),
+                    ),
+                    Language::Tsx => (
+                        tree_sitter_typescript::LANGUAGE_TSX,
+                        typescript_highlights.as_str(),
+                        "",
+                        typescript_locals.as_str(),
+                    ),
+                };
+                let mut config =
+                    HighlightConfiguration::new(language.into(), self.name(), highlights, injections, locals).ok()?;
+                config.configure(&CAPTURES.iter().map(|(name, _)| *name).collect::<Vec<_>>());
+                Some(config)
+            })
+            .as_ref()
+    }
+}
+
+/// Highlights with a tree-sitter grammar, which parses the whole file and
+/// so tells apart what a line-by-line TextMate grammar cannot (types from
+/// modules, macro bodies, nested generics). Lines longer than
+/// `max_line_len` are left plain.
+pub struct TreeSitter {
+    language: Language,
+    /// Theme style of each of `CAPTURES`
+    styles: Vec<Style>,
+    /// Style of text outside any capture
+    default: Style,
+    max_line_len: usize,
+}
+
+impl TreeSitter {
+    pub fn new(language: Language, theme: &Theme, max_line_len: usize) -> Self {
+        let highlighter = Highlighter::new(theme);
+        let style = |scopes: &[Scope]| syntax::syntect_to_ratatui_style(&highlighter.style_for_stack(scopes));
+        let styles = CAPTURES
+            .iter()
+            .map(|(_, scope)| Scope::new(scope).map_or_else(|_| style(&[]), |scope| style(&[scope])))
+            .collect();
+        Self { language, styles, default: style(&[]), max_line_len }
+    }
+
+    /// Runs of each line of `source`, which holds `count` lines joined by
+    /// newlines. None if the grammar fails to highlight it.
+    fn highlight(&self, source: &[u8], count: usize) -> Option<Vec<LineRuns>> {
+        let config = self.language.config()?;
+        let mut highlighter = tree_sitter_highlight::Highlighter::new();
+        let events = highlighter.highlight(config, source, None, |_| None).ok()?;
+        let mut lines: Vec<LineRuns> = Vec::with_capacity(count);
+        let mut line = LineRuns::new();
+        let mut stack: Vec<Style> = Vec::new();
+        for event in events {
+            match event.ok()? {
+                HighlightEvent::HighlightStart(h) => stack.push(self.styles[h.0]),
+                HighlightEvent::HighlightEnd => {
+                    stack.pop();
+                }
+                HighlightEvent::Source { start, end } => {
+                    let style = stack.last().copied().unwrap_or(self.default);
+                    let mut pieces = source[start..end].split(|&b| b == b'\n').peekable();
+                    while let Some(piece) = pieces.next() {
+                        if !piece.is_empty() {
+                            syntax::push_run(&mut line, piece.len(), style);
+                        }
+                        if pieces.peek().is_some() {
+                            lines.push(std::mem::take(&mut line));
+                        }
+                    }
+                }
+            }
+        }
+        lines.push(line);
+        Some(lines)
+    }
+}
+
+impl Backend for TreeSitter {
+    fn lines<'a>(&'a self, text: &'a TextSnapshot) -> Box<dyn Iterator<Item = LineRuns> + 'a> {
+        let count = text.line_count();
+        let mut source = Vec::new();
+        let mut long = Vec::new();
+        for i in 0..count {
+            if i > 0 {
+                source.push(b'\n');
+            }
+            // Like syntect, lines that are not UTF-8 are parsed as empty
+            let line = text.line_bytes(i).filter(|b| std::str::from_utf8(b).is_ok()).unwrap_or_default();
+            source.extend_from_slice(line);
+            long.push(line.len() > self.max_line_len);
+        }
+        let lines = self.highlight(&source, count).unwrap_or_default();
+        let lines = lines.into_iter().chain(std::iter::repeat_with(Vec::new)).take(count);
+        Box::new(lines.zip(long).map(|(runs, long)| if long { Vec::new() } else { runs }))
+    }
+}
@@ -1,16 +1,28 @@
 use ratatui::prelude::*;
-use ratatui::widgets::Paragraph;
+use ratatui::widgets::{Block, Clear, LineGauge, Paragraph};
 
-use crate::app::{App, Mode};
+use crate::app::{App, Bookmark, Mode};
+use crate::blame;
+use crate::filter::ViewRow;
 use crate::line_numbers;
+use crate::motion;
+use crate::overstrike;
+use crate::paths::Completion;
+use crate::keymap::Action;
 use crate::statusbar;
 use crate::syntax::StyledSpan;
+use crate::tabs;
+use unicode_width::UnicodeWidthChar;
+
+/// How long a render waits for background highlighting to reach the lines
+/// on screen before showing them plain.
+const HIGHLIGHT_WAIT: std::time::Duration = std::time::Duration::from_millis(30);
 
 pub fn render(frame: &mut Frame, app: &mut App) {
     let area = frame.area();
     let tab_bar_height: u16 = if app.has_tab_bar() { 1 } else { 0 };
     app.content_height = (area.height as usize).saturating_sub(2 + tab_bar_height as usize);
-    app.content_width = (area.width as usize).saturating_sub(app.gutter_width());
+    app.content_width = (area.width as usize).saturating_sub(app.blame_width() + app.gutter_width());
 
     if app.has_tab_bar() {
         let chunks = Layout::default()
@@ -23,6 +35,7 @@ pub fn render(frame: &mut Frame, app: &mut App) {
             ])
             .split(area);
         render_tab_bar(frame, app, chunks[0]);
+        app.content_area = chunks[1];
         render_content(frame, app, chunks[1]);
         statusbar::render(frame, app, chunks[2]);
         render_input_bar(frame, app, chunks[3]);
@@ -35,10 +48,97 @@ pub fn render(frame: &mut Frame, app: &mut App) {
                 Constraint::Length(1),
             ])
             .split(area);
+        app.content_area = chunks[0];
         render_content(frame, app, chunks[0]);
         statusbar::render(frame, app, chunks[1]);
         render_input_bar(frame, app, chunks[2]);
     }
+
+    match &app.mode {
+        Mode::BufferPicker { query, selected } => render_buffer_picker(frame, app, query, *selected, area),
+        Mode::MarksList { selected } => render_marks_list(frame, app, *selected, area),
+        Mode::Help { scroll } => render_help(frame, app, *scroll, area),
+        Mode::Info => render_info(frame, app, area),
+        Mode::Messages => render_messages(frame, app, area),
+        Mode::MatchList { selected } => render_match_list(frame, app, *selected, area),
+        _ => {}
+    }
+
+    if !app.color {
+        strip_colors(frame.buffer_mut());
+    }
+}
+
+/// Drop every color from `buf` for `--color=never`. Cells drawn on a
+/// background (status bar, search matches, selections) are shown in reverse
+/// video instead so they still stand out.
+fn strip_colors(buf: &mut ratatui::buffer::Buffer) {
+    for cell in &mut buf.content {
+        if cell.bg != Color::Reset {
+            cell.modifier.insert(Modifier::REVERSED);
+        }
+        cell.fg = Color::Reset;
+        cell.bg = Color::Reset;
+    }
+}
+
+/// Rect of at most `width` x `height` centered in `area`.
+fn centered_popup(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// Centered popup listing open buffers that fuzzy-match `query`.
+fn render_buffer_picker(frame: &mut Frame, app: &App, query: &str, selected: usize, area: Rect) {
+    let entries = app.picker_entries(query);
+    let width = area.width.saturating_sub(4).min(70);
+    // Borders + query line + one row per entry
+    let height = (entries.len() as u16 + 3).clamp(4, area.height.saturating_sub(2).max(4));
+    let popup = centered_popup(area, width, height);
+
+    let block = Block::bordered()
+        .title(format!(" Buffers ({}/{}) ", entries.len(), app.buffers.len()))
+        .border_style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(popup);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(block, popup);
+
+    let mut lines = vec![Line::from(vec![
+        Span::styled("> ", Style::default().fg(Color::Cyan)),
+        Span::raw(query.to_string()),
+    ])];
+    // Keep the selection in view when the list is taller than the popup
+    let visible = (inner.height as usize).saturating_sub(1);
+    let skip = (selected + 1).saturating_sub(visible);
+    for (row, (idx, label, m)) in entries.iter().enumerate().skip(skip).take(visible) {
+        let base = if row == selected {
+            Style::default().fg(Color::Black).bg(Color::Cyan)
+        } else {
+            Style::default()
+        };
+        let marker = if *idx == app.active_buffer { "* " } else { "  " };
+        let mut spans = vec![Span::styled(marker, base)];
+        for (ci, ch) in label.chars().enumerate() {
+            let style = if m.positions.contains(&ci) {
+                base.fg(if row == selected { Color::Black } else { Color::Yellow })
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                base
+            };
+            spans.push(Span::styled(ch.to_string(), style));
+        }
+        lines.push(Line::from(spans));
+    }
+    if entries.is_empty() {
+        lines.push(Line::styled("  no matching buffers", Style::default().fg(Color::DarkGray)));
+    }
+    frame.render_widget(Paragraph::new(lines), inner);
 }
 
 fn render_tab_bar(frame: &mut Frame, app: &App, area: Rect) {
@@ -51,7 +151,11 @@ fn render_tab_bar(frame: &mut Frame, app: &App, area: Rect) {
         } else {
             buf.name.clone()
         };
-        let text = format!(" {} ", name);
+        let text = match &buf.loading {
+            Some(loading) => format!(" {} {} {}% ", loading.spinner(), name, loading.percent()),
+            None if buf.deleted => format!(" {} [deleted] ", name),
+            None => format!(" {} ", name),
+        };
         if i == app.active_buffer {
             spans.push(Span::styled(
                 text,
@@ -66,6 +170,9 @@ fn render_tab_bar(frame: &mut Frame, app: &App, area: Rect) {
                 Style::default().fg(Color::DarkGray),
             ));
         }
+        if buf.new_data {
+            spans.push(Span::styled("\u{25cf} ", Style::default().fg(Color::Green)));
+        }
         if i + 1 < app.buffers.len() {
             spans.push(Span::styled(
                 "\u{2502}",
@@ -82,53 +189,88 @@ fn render_tab_bar(frame: &mut Frame, app: &App, area: Rect) {
 fn render_content(frame: &mut Frame, app: &App, area: Rect) {
     let gutter_width = app.gutter_width() as u16;
 
-    let (gutter_area, content_area) = if gutter_width > 0 {
-        let chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Length(gutter_width), Constraint::Min(1)])
-            .split(area);
-        (Some(chunks[0]), chunks[1])
-    } else {
-        (None, area)
-    };
+    // The theme's colors under everything drawn below, which only sets
+    // the colors it needs
+    if app.config.general.theme_background && app.highlighter.is_enabled() {
+        let (bg, fg) = app.highlighter.theme_colors();
+        let base = Style { fg, bg, ..Style::default() };
+        frame.buffer_mut().set_style(area, base);
+    }
 
-    let line_indices = app.active_lines();
+    let blame_width = app.blame_width() as u16;
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(blame_width), Constraint::Length(gutter_width), Constraint::Min(1)])
+        .split(area);
+    let (blame_area, gutter_area, content_area) = (chunks[0], chunks[1], chunks[2]);
 
-    if let Some(gutter) = gutter_area {
-        line_numbers::render(frame, app, gutter, &line_indices, &app.buffer().git_changes);
+    let rows = app.active_rows();
+    let line_indices: Vec<usize> = rows
+        .iter()
+        .filter_map(|row| match *row {
+            ViewRow::Line(i) => Some(i),
+            ViewRow::Hidden { .. } | ViewRow::Deleted { .. } => None,
+        })
+        .collect();
+
+    if blame_width > 0 {
+        render_blame(frame, app, blame_area, &rows);
+    }
+    if gutter_width > 0 {
+        line_numbers::render(frame, app, gutter_area, &rows, &app.buffer().git_changes);
     }
 
-    let search_style = Style::default()
-        .fg(Color::Black)
-        .bg(Color::Yellow)
-        .add_modifier(Modifier::BOLD);
-    let preview_style = Style::default()
-        .fg(Color::Black)
-        .bg(Color::Rgb(200, 160, 60));
+    let styles = MatchStyles {
+        search: Style::default()
+            .fg(app.palette.search_match_fg)
+            .bg(app.palette.search_match_bg)
+            .add_modifier(Modifier::BOLD),
+        preview: Style::default()
+            .fg(Color::Black)
+            .bg(Color::Rgb(200, 160, 60)),
+        groups: app
+            .palette
+            .search_group_bg
+            .iter()
+            .map(|&bg| Style::default().fg(Color::Black).bg(bg).add_modifier(Modifier::BOLD))
+            .collect(),
+        selection: Style::default().fg(Color::White).bg(Color::Blue),
+        cursor: Style::default().add_modifier(Modifier::REVERSED),
+        changed: Style::default().add_modifier(Modifier::REVERSED),
+    };
     let visual_style = Style::default()
         .fg(Color::White)
         .bg(Color::Blue);
 
     let buf = app.buffer();
     let mut lines: Vec<Line> = Vec::new();
+    // Lines are cut to `max_line_len` and then to the columns in view, and
+    // only that part is styled and copied
+    let max_len = app.config.general.max_line_len;
+    // Wrapped lines are drawn whole, so the sideways scroll doesn't apply
+    let tab_width = app.tab_width as usize;
+    let left_col = if app.wrap_lines { 0 } else { app.left_col };
+    let window = |text: &str| match app.wrap_lines {
+        true => (0..text.len(), 0),
+        false => visible_range(text, left_col, content_area.width as usize, tab_width),
+    };
+    let place = |spans: Vec<Span<'static>>, start_col: usize| place(spans, start_col, left_col, tab_width);
+    let line_text = |i: usize| buf.line_prefix(i, max_len).unwrap_or("");
 
     // Binary files: render hex dump
     if buf.is_binary() {
         let hex_style = Style::default().fg(Color::Rgb(150, 200, 150));
         for &i in &line_indices {
-            lines.push(Line::from(Span::styled(buf.hex_line(i), hex_style)));
+            let text = buf.hex_line(i);
+            let (range, col) = window(&text);
+            lines.push(place(vec![Span::styled(text[range].to_string(), hex_style)], col));
         }
     } else if buf.is_diff {
         // Diff buffers: colorize by line prefix
         for &i in &line_indices {
-            let text = buf.get_line(i).unwrap_or("");
-            let style = match text.chars().next() {
-                Some('+') => Style::default().fg(Color::Rgb(100, 220, 100)),
-                Some('-') => Style::default().fg(Color::Rgb(220, 80, 80)),
-                Some('@') => Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
-                _         => Style::default().fg(Color::Rgb(170, 170, 170)),
-            };
-            lines.push(Line::from(Span::styled(text.to_string(), style)));
+            let text = line_text(i);
+            let (range, col) = window(text);
+            lines.push(place(vec![Span::styled(text[range].to_string(), diff_line_style(text))], col));
         }
     } else {
         // Normal text rendering
@@ -138,53 +280,95 @@ fn render_content(frame: &mut Frame, app: &App, area: Rect) {
                 let hi = (*anchor).max(*cursor);
                 Some((lo, hi))
             }
-            _ => None,
+            Mode::PipeInput { range, .. } => Some(*range),
+            // The commit Enter opens in a `:log` buffer
+            _ => buf.log.as_ref().map(|log| (log.selected, log.selected)),
         };
 
-        if app.highlighter.is_enabled() {
-            let syntax = app.highlighter.detect_syntax(buf.path.as_deref());
-            let mut hl = app.highlighter.create_highlight_lines(syntax);
+        if buf.is_man() {
+            // Man pages: bold and underline from the overstrikes instead of syntax
             for &i in &line_indices {
-                let text = buf.get_line(i).unwrap_or("");
+                let text = line_text(i);
+                let (range, col) = window(text);
                 let is_selected = visual_range.map(|(lo, hi)| i >= lo && i <= hi).unwrap_or(false);
                 if is_selected {
-                    lines.push(Line::from(Span::styled(text.to_string(), visual_style)));
+                    lines.push(place(vec![Span::styled(text[range].to_string(), visual_style)], col));
                 } else {
-                    let styled_spans = app.highlighter.highlight_line(text, &mut hl);
-                    let search_ranges = app.search.matches_on_line(i);
-                    let preview_ranges = app.search.preview_matches_on_line(i);
-                    let spans = merge_syntax_search_preview(
-                        styled_spans, &preview_ranges, preview_style,
-                        &search_ranges, search_style,
-                    );
-                    lines.push(Line::from(spans));
+                    let man_spans = overstrike::spans(text, buf.overstrike_runs(i).unwrap_or_default());
+                    let highlights = clip_highlights(line_highlights(app, i, text, &styles), &range);
+                    lines.push(place(merge_highlights(clip_spans(man_spans, &range), &highlights), col));
+                }
+            }
+        } else if app.highlighter.highlights(buf) {
+            let until = line_indices.last().copied().unwrap_or(0);
+            let cache = app.highlighter.highlighted_lines(buf, until, HIGHLIGHT_WAIT);
+            for &i in &line_indices {
+                let text = line_text(i);
+                let (range, col) = window(text);
+                let is_selected = visual_range.map(|(lo, hi)| i >= lo && i <= hi).unwrap_or(false);
+                if is_selected {
+                    lines.push(place(vec![Span::styled(text[range].to_string(), visual_style)], col));
+                } else {
+                    // Plain until the background job gets to this line
+                    let styled_spans = cache.line(i, text, range.clone()).unwrap_or_else(|| {
+                        vec![StyledSpan { text: text[range.clone()].to_string(), style: Style::default() }]
+                    });
+                    let highlights = clip_highlights(line_highlights(app, i, text, &styles), &range);
+                    let spans = merge_highlights(styled_spans, &highlights);
+                    lines.push(place(spans, col));
                 }
             }
         } else {
             for &i in &line_indices {
-                let text = buf.get_line(i).unwrap_or("");
+                let text = line_text(i);
+                let (range, col) = window(text);
                 let is_selected = visual_range.map(|(lo, hi)| i >= lo && i <= hi).unwrap_or(false);
                 if is_selected {
-                    lines.push(Line::from(Span::styled(text.to_string(), visual_style)));
+                    lines.push(place(vec![Span::styled(text[range].to_string(), visual_style)], col));
                 } else {
-                    let search_ranges = app.search.matches_on_line(i);
-                    let preview_ranges = app.search.preview_matches_on_line(i);
+                    let highlights = clip_highlights(line_highlights(app, i, text, &styles), &range);
                     let plain_span = vec![StyledSpan {
-                        text: text.to_string(),
+                        text: text[range].to_string(),
                         style: Style::default(),
                     }];
-                    let spans = merge_syntax_search_preview(
-                        plain_span, &preview_ranges, preview_style,
-                        &search_ranges, search_style,
-                    );
-                    lines.push(Line::from(spans));
+                    let spans = merge_highlights(plain_span, &highlights);
+                    lines.push(place(spans, col));
                 }
             }
         }
     }
 
-    let visible_lines = line_indices.len();
-    for _ in visible_lines..area.height as usize {
+    // Interleave "lines hidden" placeholders and deleted lines between the
+    // rendered lines
+    if line_indices.len() < rows.len() {
+        let mut rendered = lines.into_iter();
+        let deleted_style = Style::default().fg(Color::Rgb(170, 80, 80)).add_modifier(Modifier::DIM);
+        lines = rows
+            .iter()
+            .map(|row| match *row {
+                ViewRow::Line(_) => rendered.next().unwrap_or_default(),
+                ViewRow::Hidden { count, .. } => hidden_placeholder(count),
+                ViewRow::Deleted { at, index } => {
+                    let text = buf.git_deleted.get(&at).and_then(|d| d.get(index)).map_or("", String::as_str);
+                    let (range, col) = window(text);
+                    place(vec![Span::styled(text[range].to_string(), deleted_style)], col)
+                }
+            })
+            .collect();
+    }
+
+    if app.wrap_lines {
+        lines = lines
+            .into_iter()
+            .zip(&rows)
+            .flat_map(|(line, row)| match row {
+                ViewRow::Hidden { .. } => vec![line],
+                _ => wrap(line, content_area.width as usize),
+            })
+            .collect();
+    }
+
+    for _ in lines.len()..area.height as usize {
         lines.push(Line::from(Span::styled(
             "~",
             Style::default().fg(Color::DarkGray),
@@ -195,36 +379,154 @@ fn render_content(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_widget(paragraph, content_area);
 }
 
+/// The `git blame` column: short hash, author and age of each line. Lines
+/// from the same commit as the line above show only a dim bar, so the
+/// changes stand out as blocks.
+fn render_blame(frame: &mut Frame, app: &App, area: Rect, rows: &[ViewRow]) {
+    let Some(blame) = app.buffer().blame.as_ref() else { return };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let width = area.width as usize;
+    let dim = Style::default().fg(Color::Rgb(60, 60, 60));
+    let text_style = Style::default().fg(app.palette.line_number_fg);
+    let hash_style = Style::default().fg(Color::Rgb(200, 160, 0));
+    let mut lines: Vec<Line> = Vec::new();
+    let mut previous = None;
+    for row in rows {
+        let ViewRow::Line(i) = *row else {
+            previous = None;
+            let gap = Line::styled(format!("{:>width$}", "\u{2506}"), dim);
+            lines.extend(std::iter::repeat_n(gap, app.row_height(*row)));
+            continue;
+        };
+        let commit = blame.line(i).map(|c| &c.hash);
+        let repeat = commit.is_some() && commit == previous;
+        previous = commit;
+        match blame.column(i, now).filter(|_| !repeat) {
+            Some(text) => {
+                let (hash, rest) = text.split_at(7);
+                lines.push(Line::from(vec![
+                    Span::styled(hash.to_string(), hash_style),
+                    Span::styled(rest.to_string(), text_style),
+                    Span::styled(" \u{2502}", dim),
+                ]));
+            }
+            None => lines.push(Line::styled(format!("{:>width$}", "\u{2502}"), dim)),
+        }
+        // The rows a wrapped line goes on to are part of the same block
+        for _ in 1..app.row_height(*row) {
+            lines.push(Line::styled(format!("{:>width$}", "\u{2502}"), dim));
+        }
+    }
+    frame.render_widget(Paragraph::new(lines), area);
+}
+
+/// Color of a unified diff line, by its prefix.
+pub fn diff_line_style(text: &str) -> Style {
+    match text.chars().next() {
+        Some('+') => Style::default().fg(Color::Rgb(100, 220, 100)),
+        Some('-') => Style::default().fg(Color::Rgb(220, 80, 80)),
+        Some('@') => Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+        _         => Style::default().fg(Color::Rgb(170, 170, 170)),
+    }
+}
+
+/// Dim interstitial row standing in for a run of lines hidden by the filter.
+fn hidden_placeholder(count: usize) -> Line<'static> {
+    let noun = if count == 1 { "line" } else { "lines" };
+    Line::from(Span::styled(
+        format!("\u{2014} {} {} hidden \u{2014}", format_count(count), noun),
+        Style::default().fg(Color::Rgb(90, 90, 90)).add_modifier(Modifier::ITALIC),
+    ))
+}
+
+/// Format a count with thousands separators, e.g. 1234 → "1,234".
+fn format_count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
 fn render_input_bar(frame: &mut Frame, app: &App, area: Rect) {
+    // While an async search runs, show its progress as a thin gauge
+    if app.mode == Mode::Normal && app.search.is_searching {
+        let gauge = LineGauge::default()
+            .ratio(app.search.progress_ratio())
+            .label(app.status_message.clone().unwrap_or_default())
+            .style(Style::default().fg(Color::DarkGray))
+            .filled_style(Style::default().fg(Color::Yellow))
+            .unfilled_style(Style::default().fg(Color::Rgb(60, 60, 60)));
+        frame.render_widget(gauge, area);
+        return;
+    }
+
     let content = match &app.mode {
         Mode::SearchInput { input, forward } => {
             let prefix = if *forward { "/" } else { "?" };
-            format!("{}{}", prefix, input)
+            match app.search.range {
+                Some((lo, hi)) => format!("[{}-{}] {}{}", lo + 1, hi + 1, prefix, input),
+                None => format!("{}{}", prefix, input),
+            }
         }
-        Mode::CommandInput { input } => format!(":{}", input),
-        Mode::FilterInput { input } => format!("&{}", input),
-        Mode::Follow => "Waiting for data... (press Esc or q to stop)".to_string(),
+        Mode::CommandInput { input } => match &app.completion {
+            Some(completion) => format!(":{}    {}", input, completion_menu(completion)),
+            None => format!(":{}", input),
+        },
+        Mode::FilterInput { input, case } => format!("{}&{}", case.tag(), input),
+        Mode::PipeInput { input, range } => format!("[{}-{}] |{}", range.0 + 1, range.1 + 1, input),
+        Mode::Follow => match app.follow_new_lines() {
+            Some(new) => format!("\u{2014} FOLLOW PAUSED ({} new lines) \u{2014} G to resume, q or Esc to stop", new),
+            None => "Waiting for data... (press Esc or q to stop)".to_string(),
+        },
         Mode::Visual { anchor, cursor } => {
             let lo = anchor.min(cursor);
             let hi = anchor.max(cursor);
             format!(
-                "-- VISUAL -- lines {}-{} ({} selected)  y:yank  Esc:cancel",
+                "-- VISUAL -- lines {}-{} ({} selected)  y:yank  w:write  |:pipe  /:search in selection  Esc:cancel",
                 lo + 1,
                 hi + 1,
                 hi - lo + 1
             )
         }
+        Mode::VisualChar { anchor, cursor } => {
+            let keys = "h/l/w/b/e/0/$:move  y:yank  V:line-wise  Esc:cancel";
+            match anchor {
+                Some(_) => format!("-- VISUAL -- line {}, col {}  {}", cursor.0 + 1, cursor.1 + 1, keys),
+                None => format!("-- VISUAL -- line {}, col {}  v:start selection  {}", cursor.0 + 1, cursor.1 + 1, keys),
+            }
+        }
+        Mode::BufferPicker { .. } => {
+            "Type to filter  \u{2191}/\u{2193}:select  Enter:switch  Esc:cancel".to_string()
+        }
+        Mode::MarksList { .. } => "j/k:select  Enter:jump  d:delete  Esc:close".to_string(),
+        Mode::MatchList { .. } => "j/k:select  Enter:jump  Esc:close".to_string(),
+        Mode::Help { .. } => "j/k:scroll  space/b:page  g/G:top/bottom  Esc:close".to_string(),
+        Mode::Info | Mode::Messages => "Press any key to close".to_string(),
+        Mode::ReloadPrompt => format!("{} changed on disk \u{2014} reload? y/n", app.buffer().name),
         Mode::Normal => app
             .status_message
             .clone()
-            .unwrap_or_else(|| "q:quit  /:search  ?:back-search  &:filter  v:visual  F:follow  ::cmd".to_string()),
+            .unwrap_or_else(|| "q:quit  /:search  ?:back-search  &:filter  v/V:visual  F:follow  ::cmd".to_string()),
     };
 
     let style = match &app.mode {
-        Mode::SearchInput { .. } | Mode::CommandInput { .. } | Mode::FilterInput { .. } => {
+        Mode::SearchInput { .. }
+        | Mode::CommandInput { .. }
+        | Mode::FilterInput { .. }
+        | Mode::PipeInput { .. }
+        | Mode::ReloadPrompt => {
             Style::default().fg(Color::White).bg(Color::DarkGray)
         }
-        Mode::Visual { .. } => Style::default().fg(Color::White).bg(Color::Rgb(40, 40, 80)),
+        Mode::Visual { .. } | Mode::VisualChar { .. } => {
+            Style::default().fg(Color::White).bg(Color::Rgb(40, 40, 80))
+        }
         _ => Style::default().fg(Color::DarkGray),
     };
 
@@ -232,70 +534,437 @@ fn render_input_bar(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_widget(paragraph, area);
 }
 
-/// Merge syntax spans with preview (amber) and committed (bright yellow) search highlights.
-/// Preview ranges are overlaid first; committed matches overwrite on the same byte positions.
-fn merge_syntax_search_preview(
+/// Centered popup listing the marks and notes of the active buffer with a
+/// preview of each line.
+fn render_marks_list(frame: &mut Frame, app: &App, selected: usize, area: Rect) {
+    let marks = app.bookmarks();
+    let width = area.width.saturating_sub(4).min(80);
+    let height = (marks.len() as u16 + 2).clamp(3, area.height.saturating_sub(2).max(3));
+    let popup = centered_popup(area, width, height);
+
+    let block = Block::bordered()
+        .title(" Marks & notes ")
+        .border_style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(popup);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(block, popup);
+
+    let visible = inner.height as usize;
+    let skip = (selected + 1).saturating_sub(visible);
+    let num_width = marks.iter().map(|b| (b.line() + 1).to_string().len()).max().unwrap_or(1);
+    let lines: Vec<Line> = marks
+        .iter()
+        .enumerate()
+        .skip(skip)
+        .take(visible)
+        .map(|(row, bookmark)| {
+            let (mark_style, text_style) = if row == selected {
+                let s = Style::default().fg(Color::Black).bg(Color::Cyan);
+                (s.add_modifier(Modifier::BOLD), s)
+            } else {
+                (Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD), Style::default())
+            };
+            let line = bookmark.line();
+            let preview = app.buffer().get_line(line).unwrap_or_default().trim().replace('\t', " ");
+            let mut spans = vec![
+                Span::styled(
+                    match bookmark {
+                        Bookmark::Mark(c, _) => format!(" {} ", c),
+                        Bookmark::Note(..) => " \u{270e} ".to_string(),
+                    },
+                    mark_style,
+                ),
+                Span::styled(format!("{:>w$}  ", line + 1, w = num_width), text_style.fg(Color::DarkGray)),
+            ];
+            if let Bookmark::Note(_, text) = bookmark {
+                spans.push(Span::styled(format!("{}  ", text), mark_style.add_modifier(Modifier::ITALIC)));
+            }
+            spans.push(Span::styled(format!("{:<w$}", preview, w = inner.width as usize), text_style));
+            Line::from(spans)
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Centered popup listing the lines that match the search (`:g/regex`), with
+/// the matched text highlighted. Fills in as an async search progresses.
+fn render_match_list(frame: &mut Frame, app: &App, selected: usize, area: Rect) {
+    let lines = app.match_lines();
+    let width = area.width.saturating_sub(4).min(100);
+    let height = (lines.len() as u16 + 2).clamp(3, area.height.saturating_sub(2).max(3));
+    let popup = centered_popup(area, width, height);
+
+    let more = if app.search.is_searching { " so far\u{2026}" } else { "" };
+    let block = Block::bordered()
+        .title(format!(" /{} \u{2014} {} lines{} ", app.search.query_string, lines.len(), more))
+        .border_style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(popup);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(block, popup);
+
+    let visible = inner.height as usize;
+    let skip = (selected + 1).saturating_sub(visible);
+    let num_width = lines.last().map_or(1, |l| (l + 1).to_string().len());
+    let buf = app.buffer();
+    let rows: Vec<Line> = lines
+        .iter()
+        .enumerate()
+        .skip(skip)
+        .take(visible)
+        .map(|(row, &line)| {
+            let text_style = if row == selected {
+                Style::default().fg(Color::Black).bg(Color::Cyan)
+            } else {
+                Style::default()
+            };
+            let match_style = text_style.fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD);
+            let text = buf.get_line(line).unwrap_or_default();
+            let mut spans = vec![Span::styled(
+                format!(" {:>w$}  ", line + 1, w = num_width),
+                text_style.fg(Color::DarkGray),
+            )];
+            let mut pos = 0;
+            let first = app.search.matches.partition_point(|(l, _)| *l < line);
+            for (_, range) in app.search.matches[first..].iter().take_while(|(l, _)| *l == line) {
+                if range.start < pos || !text.is_char_boundary(range.start) || !text.is_char_boundary(range.end) {
+                    continue;
+                }
+                spans.push(Span::styled(text[pos..range.start].replace('\t', " "), text_style));
+                spans.push(Span::styled(text[range.clone()].to_string(), match_style));
+                pos = range.end;
+            }
+            spans.push(Span::styled(
+                format!("{:<w$}", text[pos..].replace('\t', " "), w = inner.width as usize),
+                text_style,
+            ));
+            Line::from(spans)
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(rows), inner);
+}
+
+/// Centered popup listing every active key binding (defaults, lesskey
+/// imports and `[keys]` overrides alike) with what it does.
+fn render_help(frame: &mut Frame, app: &App, scroll: usize, area: Rect) {
+    let bindings: Vec<(String, String)> = app
+        .key_map
+        .bindings()
+        .into_iter()
+        .map(|(action, keys)| {
+            let desc = match action {
+                Action::Command(idx) => format!(":{}", app.key_map.command(idx)),
+                _ => action.description().to_string(),
+            };
+            (keys.join(", "), desc)
+        })
+        .collect();
+    let key_width = bindings.iter().map(|(k, _)| k.chars().count()).max().unwrap_or(0).min(30);
+    let width = area.width.saturating_sub(4).min(80);
+    let height = (bindings.len() as u16 + 2).clamp(3, area.height.saturating_sub(2).max(3));
+    let popup = centered_popup(area, width, height);
+
+    let block = Block::bordered()
+        .title(" Key bindings ")
+        .border_style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(popup);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(block, popup);
+
+    let scroll = scroll.min(bindings.len().saturating_sub(inner.height as usize));
+    let lines: Vec<Line> = bindings
+        .iter()
+        .skip(scroll)
+        .take(inner.height as usize)
+        .map(|(keys, desc)| {
+            Line::from(vec![
+                Span::styled(
+                    format!(" {:<w$}  ", keys, w = key_width),
+                    Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(desc.clone()),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Centered popup with the size, dates, encoding and line endings of the
+/// current file (`:info`).
+fn render_info(frame: &mut Frame, app: &App, area: Rect) {
+    let rows = app.info_rows();
+    let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let text_width = rows.iter().map(|(_, value)| value.chars().count()).max().unwrap_or(0);
+    let width = ((label_width + text_width + 6) as u16).min(area.width.saturating_sub(4));
+    let popup = centered_popup(area, width, rows.len() as u16 + 2);
+
+    let block = Block::bordered()
+        .title(" File info ")
+        .border_style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(popup);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(block, popup);
+
+    let lines: Vec<Line> = rows
+        .into_iter()
+        .map(|(label, value)| {
+            Line::from(vec![
+                Span::styled(
+                    format!(" {:<w$}  ", label, w = label_width),
+                    Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(value),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Centered popup with the most recent status messages, newest last, and
+/// how long ago each appeared (`:messages`).
+fn render_messages(frame: &mut Frame, app: &App, area: Rect) {
+    let width = area.width.saturating_sub(4).min(100);
+    let height = (app.messages.len().max(1) as u16 + 2).min(area.height.saturating_sub(2).max(3));
+    let popup = centered_popup(area, width, height);
+
+    let block = Block::bordered()
+        .title(format!(" Messages ({}) ", app.messages.len()))
+        .border_style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(popup);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(block, popup);
+
+    if app.messages.is_empty() {
+        frame.render_widget(Paragraph::new(" No messages yet"), inner);
+        return;
+    }
+    let now = std::time::SystemTime::now();
+    let skip = app.messages.len().saturating_sub(inner.height as usize);
+    let lines: Vec<Line> = app
+        .messages
+        .iter()
+        .skip(skip)
+        .map(|(at, message)| {
+            let age = now.duration_since(*at).map_or(0, |d| d.as_secs() as i64);
+            Line::from(vec![
+                Span::styled(format!(" {:>4}  ", blame::age(age)), Style::default().fg(app.palette.line_number_fg)),
+                Span::raw(message.clone()),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Candidate list shown after the command input while Tab-completing,
+/// with the selected candidate in brackets.
+fn completion_menu(completion: &Completion) -> String {
+    completion
+        .candidates
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            // Show only the last path component, keeping a directory's trailing '/'
+            let name = c.trim_end_matches('/').rsplit('/').next().unwrap_or(c);
+            let name = if c.ends_with('/') { format!("{}/", name) } else { name.to_string() };
+            if completion.index == Some(i) { format!("[{}]", name) } else { name }
+        })
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+/// Styles used to overlay search results on a line.
+struct MatchStyles {
+    /// Incremental preview while typing (amber)
+    preview: Style,
+    /// Committed full-file matches (bright yellow)
+    search: Style,
+    /// Capture groups 1, 2, 3, … (cycled when there are more groups than styles)
+    groups: Vec<Style>,
+    /// Character-wise visual selection
+    selection: Style,
+    /// Character-wise visual cursor
+    cursor: Style,
+    /// Text the latest `--exec` run changed
+    changed: Style,
+}
+
+/// Collect the highlight overlays for line `i` in increasing priority order:
+/// preview matches, committed matches, capture groups within committed matches,
+/// then the character-wise visual selection and cursor.
+fn line_highlights(
+    app: &App,
+    i: usize,
+    text: &str,
+    styles: &MatchStyles,
+) -> Vec<(std::ops::Range<usize>, Style)> {
+    let mut highlights: Vec<(std::ops::Range<usize>, Style)> = Vec::new();
+    if let Some(ranges) = app.buffer().exec.as_ref().and_then(|exec| exec.changes.get(&i)) {
+        highlights.extend(ranges.iter().map(|r| (r.clone(), styles.changed)));
+    }
+    for r in app.search.preview_matches_on_line(i) {
+        highlights.push((r, styles.preview));
+    }
+    for r in app.search.matches_on_line(i) {
+        highlights.push((r, styles.search));
+    }
+    if !styles.groups.is_empty() {
+        for (group, r) in app.search.group_ranges_on_line(i, text) {
+            let style = styles.groups[(group - 1) % styles.groups.len()];
+            highlights.push((r, style));
+        }
+    }
+    if let Mode::VisualChar { anchor, cursor } = app.mode {
+        if let Some((start, end)) = app.char_selection() {
+            if (start.0..=end.0).contains(&i) {
+                let from = if i == start.0 { motion::byte_offset(text, start.1) } else { 0 };
+                let to = if i == end.0 { motion::byte_offset(text, end.1 + 1) } else { text.len() };
+                highlights.push((from..to, styles.selection));
+            }
+        }
+        if anchor.is_none() && i == cursor.0 {
+            let from = motion::byte_offset(text, cursor.1);
+            highlights.push((from..motion::byte_offset(text, cursor.1 + 1), styles.cursor));
+        }
+    }
+    highlights
+}
+
+/// The byte range of `text` in view when it is scrolled `left_col` columns
+/// to the left in a `width` columns wide window, and the column it starts
+/// at. A wide character or tab cut by the left edge is left out, so that
+/// can be a little past `left_col`.
+fn visible_range(text: &str, left_col: usize, width: usize, tab_width: usize) -> (std::ops::Range<usize>, usize) {
+    let mut col = 0;
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        if start.is_none() && col >= left_col {
+            start = Some((i, col));
+        }
+        let w = tabs::width(c, col, tab_width);
+        if let Some((start, start_col)) = start.filter(|_| col + w > left_col + width) {
+            return (start..i, start_col);
+        }
+        col += w;
+    }
+    match start {
+        Some((start, start_col)) => (start..text.len(), start_col),
+        None => (text.len()..text.len(), left_col),
+    }
+}
+
+/// `line`, with its tabs expanded, as rows `columns` wide. Breaks where
+/// `tabs::wrapped_rows()` counts them, which `App::row_height()` uses.
+fn wrap(line: Line<'static>, columns: usize) -> Vec<Line<'static>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut used = 0;
+    for span in line.spans {
+        let mut text = String::new();
+        for c in span.content.chars() {
+            let w = c.width().unwrap_or(0);
+            if used + w > columns && used > 0 {
+                if !text.is_empty() {
+                    row.push(Span::styled(std::mem::take(&mut text), span.style));
+                }
+                rows.push(Line::from(std::mem::take(&mut row)));
+                used = 0;
+            }
+            text.push(c);
+            used += w;
+        }
+        if !text.is_empty() {
+            row.push(Span::styled(text, span.style));
+        }
+    }
+    rows.push(Line::from(row));
+    rows
+}
+
+/// `spans`, the part of a line that starts at column `start_col`, as a row
+/// scrolled `left_col` columns to the left: tabs expanded, and moved right
+/// past whatever was cut by the left edge.
+fn place(spans: Vec<Span<'static>>, start_col: usize, left_col: usize, tab_width: usize) -> Line<'static> {
+    let mut row = Vec::with_capacity(spans.len() + 1);
+    if start_col > left_col {
+        row.push(Span::raw(" ".repeat(start_col - left_col)));
+    }
+    row.extend(tabs::expand(spans, start_col, tab_width));
+    Line::from(row)
+}
+
+/// The parts of `spans`, which cover a whole line, that fall in `range`.
+fn clip_spans(spans: Vec<StyledSpan>, range: &std::ops::Range<usize>) -> Vec<StyledSpan> {
+    let mut start = 0;
+    let mut clipped = Vec::new();
+    for span in spans {
+        let end = start + span.text.len();
+        let (lo, hi) = (start.max(range.start), end.min(range.end));
+        if lo < hi {
+            clipped.push(StyledSpan { text: span.text[lo - start..hi - start].to_string(), style: span.style });
+        }
+        start = end;
+    }
+    clipped
+}
+
+/// Highlight ranges of a whole line moved to the part of it in `range`,
+/// which then starts at 0. Ranges are cut to fit, and dropped if nothing
+/// of them is in view, so match offsets past the end of a line cut off at
+/// `max_line_len` are harmless.
+fn clip_highlights(
+    highlights: Vec<(std::ops::Range<usize>, Style)>,
+    range: &std::ops::Range<usize>,
+) -> Vec<(std::ops::Range<usize>, Style)> {
+    highlights
+        .into_iter()
+        .filter_map(|(r, style)| {
+            let (lo, hi) = (r.start.max(range.start), r.end.min(range.end));
+            (lo < hi).then(|| (lo - range.start..hi - range.start, style))
+        })
+        .collect()
+}
+
+/// Overlay highlight ranges onto syntax spans. Highlights are applied in order,
+/// so later entries win where ranges overlap (e.g. a capture group inside a match).
+pub fn merge_highlights(
     syntax_spans: Vec<StyledSpan>,
-    preview_ranges: &[std::ops::Range<usize>],
-    preview_style: Style,
-    search_ranges: &[std::ops::Range<usize>],
-    search_style: Style,
+    highlights: &[(std::ops::Range<usize>, Style)],
 ) -> Vec<Span<'static>> {
-    // Build a combined set of highlights: preview first, search second (wins on overlap)
-    // We'll process them as two ordered passes merged into a unified overlay.
-    if preview_ranges.is_empty() && search_ranges.is_empty() {
+    if highlights.is_empty() {
         return syntax_spans
             .into_iter()
             .map(|s| Span::styled(s.text, s.style))
             .collect();
     }
 
-    // Build a vec of (start, end, style) sorted by start
-    let mut highlights: Vec<(usize, usize, Style)> = Vec::new();
-    for r in preview_ranges {
-        highlights.push((r.start, r.end, preview_style));
-    }
-    for r in search_ranges {
-        highlights.push((r.start, r.end, search_style));
-    }
-    highlights.sort_by_key(|h| h.0);
-
     let mut result: Vec<Span<'static>> = Vec::new();
     let mut byte_pos: usize = 0;
 
     for span in &syntax_spans {
         let span_start = byte_pos;
         let span_end = byte_pos + span.text.len();
-        let mut local_pos: usize = 0;
 
-        for &(hl_start, hl_end, hl_style) in &highlights {
-            if hl_end <= span_start || hl_start >= span_end {
-                continue;
-            }
-            let hl_local_start = hl_start.saturating_sub(span_start);
-            let hl_local_end = (hl_end - span_start).min(span.text.len());
-            let actual_start = hl_local_start.max(local_pos);
-
-            if hl_local_start > local_pos {
-                let s = &span.text[local_pos..hl_local_start];
-                if !s.is_empty() {
-                    result.push(Span::styled(s.to_string(), span.style));
-                }
-            }
-            if hl_local_end > actual_start {
-                let s = &span.text[actual_start..hl_local_end];
-                if !s.is_empty() {
-                    result.push(Span::styled(s.to_string(), hl_style));
+        // Split the span at every highlight boundary that falls inside it
+        let mut cuts: Vec<usize> = vec![0, span.text.len()];
+        for (r, _) in highlights {
+            for b in [r.start, r.end] {
+                if b > span_start && b < span_end && span.text.is_char_boundary(b - span_start) {
+                    cuts.push(b - span_start);
                 }
             }
-            if hl_local_end > local_pos {
-                local_pos = hl_local_end;
-            }
         }
+        cuts.sort_unstable();
+        cuts.dedup();
 
-        if local_pos < span.text.len() {
-            let s = &span.text[local_pos..];
-            result.push(Span::styled(s.to_string(), span.style));
+        for w in cuts.windows(2) {
+            let (lo, hi) = (w[0], w[1]);
+            let abs = span_start + lo;
+            let style = highlights
+                .iter()
+                .rev()
+                .find(|(r, _)| r.start <= abs && abs < r.end)
+                .map(|(_, style)| *style)
+                .unwrap_or(span.style);
+            result.push(Span::styled(span.text[lo..hi].to_string(), style));
         }
 
         byte_pos = span_end;